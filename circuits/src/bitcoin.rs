@@ -4,6 +4,7 @@ use k256::elliptic_curve::group::GroupEncoding;
 use k256::elliptic_curve::ScalarPrimitive;
 use k256::{AffinePoint, PublicKey, Scalar};
 
+use crate::constants::POW_TARGET_TIMESPAN;
 use crate::double_sha256_hash;
 use crate::env::Environment;
 use crate::sha256_hash;
@@ -48,30 +49,82 @@ pub fn validate_threshold_and_subtract_work(
     old_work.wrapping_sub(&work)
 }
 
+/// Decodes the little endian compact target `bits` into a little endian 256 bit target.
+/// Panics on the encodings Bitcoin Core rejects: negative, zero and overflowing targets.
 pub fn decode_compact_target(bits: [u8; 4]) -> [u8; 32] {
-    let mut bits = bits;
-    bits.reverse();
+    let bits = u32::from_le_bytes(bits);
+    let exponent = (bits >> 24) as usize;
+    let mut mantissa = bits & 0x007f_ffff;
+    if exponent <= 3 {
+        mantissa >>= 8 * (3 - exponent);
+    }
+    assert!(
+        mantissa == 0 || bits & 0x0080_0000 == 0,
+        "Compact target is negative"
+    );
+    assert!(mantissa != 0, "Compact target is zero");
+    assert!(
+        exponent <= 34
+            && (mantissa <= 0xff || exponent <= 33)
+            && (mantissa <= 0xffff || exponent <= 32),
+        "Compact target overflows"
+    );
 
+    // target = mantissa * 256^(exponent - 3), the overflow check above keeps every byte in range
     let mut target = [0u8; 32];
-    let exponent = bits[0] as usize;
-    let value = ((bits[1] as u32) << 16) | ((bits[2] as u32) << 8) | (bits[3] as u32);
-
-    if exponent <= 3 {
-        // If the target size is 3 bytes or less, place the value at the end
-        let start_index = 4 - exponent;
-        for i in 0..exponent {
-            target[31 - i] = (value >> (8 * (start_index + i))) as u8;
-        }
-    } else {
-        // If the target size is more than 3 bytes, place the value at the beginning and shift accordingly
-        for i in 0..3 {
-            target[exponent - 3 + i] = (value >> (8 * i)) as u8;
+    let shift = exponent.saturating_sub(3);
+    for i in 0..3 {
+        let byte = (mantissa >> (8 * i)) as u8;
+        if byte != 0 {
+            target[shift + i] = byte;
         }
     }
 
     target
 }
 
+/// Encodes the little endian 256 bit `target` into little endian compact target bits, rounding
+/// down to the three most significant bytes like Bitcoin Core's `GetCompact`.
+pub fn encode_compact_target(target: [u8; 32]) -> [u8; 4] {
+    let mut size = 32;
+    while size > 0 && target[size - 1] == 0 {
+        size -= 1;
+    }
+    // mantissa = target / 256^(size - 3), small targets are shifted to the left instead
+    let mut mantissa = 0u32;
+    for i in 0..3 {
+        if size + i >= 3 {
+            mantissa |= (target[size + i - 3] as u32) << (8 * i);
+        }
+    }
+    // The top bit of the mantissa is the sign bit, so move it to the next byte
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+    ((size as u32) << 24 | mantissa).to_le_bytes()
+}
+
+/// Calculates the compact target of the first block of a difficulty adjustment interval like
+/// Bitcoin Core's `CalculateNextWorkRequired`. The target of the last interval's `bits` is scaled
+/// by the time from `first_time` to `last_time`, limited to a factor of four in either direction
+/// and to the lowest difficulty `pow_limit_bits`.
+pub fn calculate_next_bits(
+    bits: [u8; 4],
+    first_time: u32,
+    last_time: u32,
+    pow_limit_bits: [u8; 4],
+) -> [u8; 4] {
+    let target_timespan = POW_TARGET_TIMESPAN as i64;
+    let timespan =
+        (last_time as i64 - first_time as i64).clamp(target_timespan / 4, target_timespan * 4);
+    let target = U256::from_le_bytes(decode_compact_target(bits))
+        .wrapping_mul(&U256::from_u64(timespan as u64))
+        .wrapping_div(&U256::from_u32(POW_TARGET_TIMESPAN));
+    let pow_limit = U256::from_le_bytes(decode_compact_target(pow_limit_bits));
+    encode_compact_target(target.min(pow_limit).to_le_bytes())
+}
+
 fn check_hash_valid(hash: [u8; 32], target: [u8; 32]) {
     // for loop from 31 to 0
     for i in (0..32).rev() {
@@ -337,15 +390,25 @@ pub fn read_tx_and_calculate_txid<E: Environment>(
     hasher.finalize().into()
 }
 
+/// Reads the merkle path of the transaction with the given txid, returns the merkle root of the block
 pub fn read_and_verify_bitcoin_merkle_path<E: Environment>(txid: [u8; 32]) -> [u8; 32] {
     let mut hash = txid;
-    let mut index = E::read_u32();
+    let position = E::read_u32();
+    let mut index = position;
     // println!("READ index: {:?}", index);
     let levels = E::read_u32();
+    // Every bit of the position has to be consumed by the path, otherwise the same path would verify for another position
+    assert!(levels <= 32, "Merkle path too deep");
+    assert!(
+        (position as u64) < (1u64 << levels),
+        "Position out of range of the merkle path"
+    );
     // bits of path indicator determines if the next tree node should be read from env or be the copy of last node
     let mut path_indicator = E::read_u32();
     for _ in 0..levels {
         let node = if path_indicator & 1 == 1 {
+            // Only the last node of a level is duplicated, so it can only be a left child
+            assert_eq!(index & 1, 0, "Duplicated node must be a left child");
             hash
         } else {
             E::read_32bytes()
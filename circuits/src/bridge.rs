@@ -2,12 +2,15 @@ use crypto_bigint::U256;
 
 use crate::{
     bitcoin::{
-        read_and_verify_bitcoin_merkle_path, read_preimages_and_calculate_commit_taproot,
-        read_tx_and_calculate_txid, validate_threshold_and_add_work, HeaderWithoutPrevBlockHash,
+        calculate_next_bits, read_and_verify_bitcoin_merkle_path,
+        read_preimages_and_calculate_commit_taproot, read_tx_and_calculate_txid,
+        validate_threshold_and_add_work, HeaderWithoutPrevBlockHash,
     },
     constants::{
         BLOCKHASH_MERKLE_TREE_DEPTH, BRIDGE_AMOUNT_SATS, CLAIM_MERKLE_TREE_DEPTH,
-        MAX_BLOCK_HANDLE_OPS, NUM_ROUNDS, PERIOD_CLAIM_MT_ROOTS, WITHDRAWAL_MERKLE_TREE_DEPTH,
+        DIFFICULTY_ADJUSTMENT_INTERVAL, LC_CHECKPOINT_BLOCKHASH, LC_CHECKPOINT_CHAIN_WORK,
+        LC_CHECKPOINT_HEIGHT, MAX_BLOCK_HANDLE_OPS, PERIOD_CLAIM_MT_ROOTS,
+        PERIOD_END_BLOCK_HEIGHTS, POW_LIMIT_BITS, POW_NO_RETARGETING, WITHDRAWAL_MERKLE_TREE_DEPTH,
     },
     double_sha256_hash,
     env::Environment,
@@ -21,12 +24,20 @@ use crate::{
 /// Assuming starting from blockheight 1,
 /// Returns total work accumulated up to (and including) blockheight N, blockhash at N + 1 - MAX_BLOCK_HANDLE_OPS, blockhash at N + 1
 /// Writing block hashes from blockheight 2 to N + 1 to an incremental merkle tree (regenerated ones)
+/// Advances `block_height` by N, it has to reach `period_end_block_height`, the committed end of the period
 pub fn read_blocks_and_add_to_merkle_tree<E: Environment>(
     start_prev_block_hash: [u8; 32],
+    block_height: &mut u32,
+    period_end_block_height: u32,
     imt: &mut IncrementalMerkleTree<BLOCKHASH_MERKLE_TREE_DEPTH>,
     max_block_handle_ops: u32,
 ) -> (U256, [u8; 32], [u8; 32]) {
     let n = E::read_u32();
+    *block_height += n;
+    assert_eq!(
+        *block_height, period_end_block_height,
+        "Period does not end at its committed block height"
+    );
     // println!("READ n: {:?}", n);
     let mut total_work = U256::ZERO;
     let mut curr_prev_block_hash = start_prev_block_hash;
@@ -160,15 +171,59 @@ pub fn read_withdrawal_proof<E: Environment>(
     imt.add(output_address);
 }
 
+/// Read N
+/// Read the prev blockhash and the header of the checkpoint, then N block headers of the light
+/// client's chain (LC_CHECKPOINT_HEIGHT + 1 to LC_CHECKPOINT_HEIGHT + N, inclusive)
+/// Read the light client's chain work and withdrawal merkle root
+/// Checks that the headers form a chain from `LC_CHECKPOINT_BLOCKHASH` to `lc_blockhash` that
+/// follows the difficulty adjustment rules, that the chain work is LC_CHECKPOINT_CHAIN_WORK plus
+/// the work of these headers and that the light client committed to `withdrawal_mt_root`.
+/// Testnet's minimum difficulty blocks are not supported.
 pub fn read_and_verify_lc_proof<E: Environment>(
     lc_blockhash: [u8; 32],
     withdrawal_mt_root: [u8; 32],
 ) {
-    let read_lc_blockhash = E::read_32bytes();
-    assert_eq!(read_lc_blockhash, lc_blockhash);
+    let n = E::read_u32();
+    // The checkpoint is trusted, it starts a difficulty adjustment interval
+    let checkpoint_prev_block_hash = E::read_32bytes();
+    let checkpoint = read_header_except_prev_blockhash::<E>();
+    let mut block_hash = calculate_next_block_hash(checkpoint_prev_block_hash, checkpoint);
+    assert_eq!(
+        block_hash, LC_CHECKPOINT_BLOCKHASH,
+        "Light client chain does not start at the checkpoint"
+    );
+    let mut chain_work = U256::from_le_slice(&LC_CHECKPOINT_CHAIN_WORK);
+    let mut bits = checkpoint.3.to_le_bytes();
+    let mut interval_start_time = checkpoint.2;
+    let mut prev_time = checkpoint.2;
+    for block_height in LC_CHECKPOINT_HEIGHT + 1..=LC_CHECKPOINT_HEIGHT + n {
+        let header_without_prev_blockhash = read_header_except_prev_blockhash::<E>();
+        let time = header_without_prev_blockhash.2;
+        if block_height.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL) {
+            if !POW_NO_RETARGETING {
+                bits = calculate_next_bits(
+                    bits,
+                    interval_start_time,
+                    prev_time,
+                    POW_LIMIT_BITS.to_le_bytes(),
+                );
+            }
+            interval_start_time = time;
+        }
+        assert_eq!(
+            header_without_prev_blockhash.3.to_le_bytes(),
+            bits,
+            "Light client block does not have the required difficulty"
+        );
+        block_hash = calculate_next_block_hash(block_hash, header_without_prev_blockhash);
+        chain_work = validate_threshold_and_add_work(bits, block_hash, chain_work);
+        prev_time = time;
+    }
+    assert_eq!(block_hash, lc_blockhash);
+    let read_chain_work = U256::from_le_slice(&E::read_32bytes());
+    assert_eq!(read_chain_work, chain_work);
     let read_withdrawal_mt_root = E::read_32bytes();
     assert_eq!(read_withdrawal_mt_root, withdrawal_mt_root);
-    // TODO: Verify the proof
 }
 
 pub fn verify_challenge_proof(_proof: [[u8; 32]; 4]) -> bool {
@@ -205,15 +260,18 @@ pub fn bridge_proof<E: Environment>() {
     // println!("READ last_block_hash: {:?}", cur_block_hash);
 
     let mut lc_blockhash = [0; 32];
+    let mut block_height = 0;
     let mut total_num_withdrawals = 0;
     let mut last_period = 0;
-    for period_count in 0..NUM_ROUNDS {
+    for (period_count, &period_end_block_height) in PERIOD_END_BLOCK_HEIGHTS.iter().enumerate() {
         // println!("Proving for Period: {}", period_count);
 
         let work;
         // println!("ROUND: {:?}", period_count);
         (work, lc_blockhash, cur_block_hash) = read_blocks_and_add_to_merkle_tree::<E>(
             cur_block_hash,
+            &mut block_height,
+            period_end_block_height,
             &mut blockhashes_mt,
             MAX_BLOCK_HANDLE_OPS,
         );
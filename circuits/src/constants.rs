@@ -14,6 +14,31 @@ pub const MAX_BLOCK_HANDLE_OPS: u32 = 3;
 pub const NUM_ROUNDS: usize = 4;
 /// The prev_blockhash of the first block of the bridge (calculation of proof of works starts from here)
 pub const START_PREV_BLOCKHASH: [u8; 32] = [0; 32];
+/// Blockhash of the genesis block of the regtest network the bridge runs on, the difficulty rules
+/// below are those of this network
+pub const LC_GENESIS_BLOCKHASH: [u8; 32] = [
+    0x06, 0x22, 0x6e, 0x46, 0x11, 0x1a, 0x0b, 0x59, 0xca, 0xaf, 0x12, 0x60, 0x43, 0xeb, 0x5b, 0xbf,
+    0x28, 0xc3, 0x4f, 0x3a, 0x5e, 0x33, 0x2a, 0x1f, 0xc7, 0xb2, 0xb7, 0x3c, 0xf1, 0x88, 0x91, 0x0f,
+];
+/// Blockhash of the block light client proofs start from, so a proof only carries the headers
+/// after it. On regtest this is the genesis block.
+pub const LC_CHECKPOINT_BLOCKHASH: [u8; 32] = LC_GENESIS_BLOCKHASH;
+/// Height of LC_CHECKPOINT_BLOCKHASH. It is a multiple of DIFFICULTY_ADJUSTMENT_INTERVAL, so the
+/// difficulty of the blocks after the checkpoint only depends on the headers from it on.
+pub const LC_CHECKPOINT_HEIGHT: u32 = 0;
+/// Accumulated work of the chain up to and including LC_CHECKPOINT_BLOCKHASH, little endian
+pub const LC_CHECKPOINT_CHAIN_WORK: [u8; 32] = [
+    2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+];
+/// Number of blocks between two difficulty adjustments
+pub const DIFFICULTY_ADJUSTMENT_INTERVAL: u32 = 2016;
+const _: () = assert!(LC_CHECKPOINT_HEIGHT.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL));
+/// Expected time between two difficulty adjustments in seconds, two weeks
+pub const POW_TARGET_TIMESPAN: u32 = 14 * 24 * 60 * 60;
+/// Compact target of the lowest difficulty a block can have on regtest
+pub const POW_LIMIT_BITS: u32 = 0x207fffff;
+/// Regtest keeps the difficulty of the previous block instead of adjusting it
+pub const POW_NO_RETARGETING: bool = true;
 /// Merkle tree roots for every period for operator to prove they inscribed correct connector tree preimages
 pub const PERIOD_CLAIM_MT_ROOTS: [[u8; 32]; NUM_ROUNDS] = [
     [
@@ -33,9 +58,22 @@ pub const PERIOD_CLAIM_MT_ROOTS: [[u8; 32]; NUM_ROUNDS] = [
         233, 25, 150, 14, 104, 65, 96, 98, 85, 89, 114, 191,
     ],
 ];
-/// Block heights at which each period ends
+/// Number of blocks in a period
+pub const PERIOD_BLOCK_COUNT: u32 = 50; // 10 mins for 1 block, 6 months = 6*30*24*6 = 25920
+/// Block heights at which each period ends, relative to the first block of the bridge proof
 /// After each period_end_block_height, the corresponding connector source utxo opens after K_DEEP + MAX_BITVM_CHALLENGE_RESPONSE blocks.
-pub const PERIOD_END_BLOCK_HEIGHTS: [u32; NUM_ROUNDS] = [0; NUM_ROUNDS];
+pub const PERIOD_END_BLOCK_HEIGHTS: [u32; NUM_ROUNDS] = period_end_block_heights();
+
+/// Ends of the periods, every period is PERIOD_BLOCK_COUNT blocks long
+const fn period_end_block_heights() -> [u32; NUM_ROUNDS] {
+    let mut heights = [0; NUM_ROUNDS];
+    let mut i = 0;
+    while i < NUM_ROUNDS {
+        heights[i] = (i as u32 + 1) * PERIOD_BLOCK_COUNT;
+        i += 1;
+    }
+    heights
+}
 /// Constant bridge amount in sats
 pub const BRIDGE_AMOUNT_SATS: u64 = 100_000_000;
 /// Empty leaf of a merkle tree
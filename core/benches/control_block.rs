@@ -0,0 +1,56 @@
+use bitcoin::taproot::{LeafVersion, TaprootSpendInfo};
+use clementine_core::constants::CONNECTOR_TREE_OPERATOR_TAKES_AFTER;
+use clementine_core::script_builder::ScriptBuilder;
+use clementine_core::transaction_builder::TransactionBuilder;
+use criterion::{criterion_group, criterion_main, Criterion};
+use secp256k1::rand::rngs::StdRng;
+use secp256k1::rand::{Rng, SeedableRng};
+use secp256k1::{Secp256k1, XOnlyPublicKey};
+
+const DEPTH: u32 = 10;
+
+fn control_block(c: &mut Criterion) {
+    let secp = Secp256k1::new();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let actor_pk = XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1);
+    let transaction_builder = TransactionBuilder::new(vec![actor_pk]);
+    let timelock_script = ScriptBuilder::generate_timelock_script(
+        &actor_pk,
+        CONNECTOR_TREE_OPERATOR_TAKES_AFTER as u32,
+    );
+    // Every node of a depth 10 connector tree
+    let tree_infos = (0..2u32.pow(DEPTH + 1) - 1)
+        .map(|_| {
+            TransactionBuilder::create_connector_tree_node_address(
+                &secp,
+                &actor_pk,
+                &rng.gen(),
+                CONNECTOR_TREE_OPERATOR_TAKES_AFTER,
+            )
+            .unwrap()
+            .1
+        })
+        .collect::<Vec<TaprootSpendInfo>>();
+
+    c.bench_function("control_block_fresh", |b| {
+        b.iter(|| {
+            for tree_info in tree_infos.iter() {
+                tree_info
+                    .control_block(&(timelock_script.clone(), LeafVersion::TapScript))
+                    .unwrap();
+            }
+        })
+    });
+    c.bench_function("control_block_cached", |b| {
+        b.iter(|| {
+            for tree_info in tree_infos.iter() {
+                transaction_builder
+                    .control_block(tree_info, &timelock_script)
+                    .unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, control_block);
+criterion_main!(benches);
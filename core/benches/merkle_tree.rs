@@ -0,0 +1,28 @@
+use clementine_circuits::constants::WITHDRAWAL_MERKLE_TREE_DEPTH;
+use clementine_core::merkle::MerkleTree;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const LEAVES: u32 = 10_000;
+
+fn merkle_tree_add(c: &mut Criterion) {
+    let leaves = (0..LEAVES)
+        .map(|i| {
+            let mut leaf = [0u8; 32];
+            leaf[..4].copy_from_slice(&i.to_be_bytes());
+            leaf
+        })
+        .collect::<Vec<_>>();
+
+    c.bench_function("merkle_tree_add_10k", |b| {
+        b.iter(|| {
+            let mut mt = MerkleTree::<WITHDRAWAL_MERKLE_TREE_DEPTH>::new();
+            for leaf in leaves.iter() {
+                mt.add(*leaf);
+            }
+            mt.root()
+        })
+    });
+}
+
+criterion_group!(benches, merkle_tree_add);
+criterion_main!(benches);
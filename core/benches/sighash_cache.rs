@@ -0,0 +1,75 @@
+use bitcoin::absolute::LockTime;
+use bitcoin::hashes::Hash;
+use bitcoin::sighash::SighashCache;
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Transaction, TxIn, TxOut, Txid};
+use clementine_core::actor::Actor;
+use clementine_core::script_builder::ScriptBuilder;
+use criterion::{criterion_group, criterion_main, Criterion};
+use secp256k1::rand::rngs::StdRng;
+use secp256k1::rand::SeedableRng;
+use secp256k1::Secp256k1;
+
+const INPUTS: u32 = 20;
+
+fn sighash_cache(c: &mut Criterion) {
+    let secp = Secp256k1::new();
+    let mut rng = StdRng::from_seed([0u8; 32]);
+    let actor = Actor::new(secp.generate_keypair(&mut rng).0);
+    let script = ScriptBuilder::generate_timelock_script(&actor.xonly_public_key, 1);
+    // A claim spending 20 taproot outputs
+    let tx = Transaction {
+        version: Version(2),
+        lock_time: LockTime::ZERO,
+        input: (0..INPUTS)
+            .map(|vout| TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::from_byte_array([1u8; 32]),
+                    vout,
+                },
+                ..Default::default()
+            })
+            .collect(),
+        output: vec![TxOut {
+            value: Amount::from_sat(INPUTS as u64 * 1_000),
+            script_pubkey: actor.address.script_pubkey(),
+        }],
+    };
+    let prevouts = vec![
+        TxOut {
+            value: Amount::from_sat(1_000),
+            script_pubkey: actor.address.script_pubkey(),
+        };
+        INPUTS as usize
+    ];
+    let scripts: Vec<ScriptBuf> = vec![script; INPUTS as usize];
+
+    c.bench_function("sighash_cache_fresh", |b| {
+        b.iter(|| {
+            let mut tx = tx.clone();
+            for (i, script) in scripts.iter().enumerate() {
+                actor
+                    .sign_taproot_script_spend_tx(&mut tx, &prevouts, script, i)
+                    .unwrap();
+            }
+        })
+    });
+    c.bench_function("sighash_cache_shared", |b| {
+        b.iter(|| {
+            let mut sighash_cache = SighashCache::new(&tx);
+            for (i, script) in scripts.iter().enumerate() {
+                actor
+                    .sign_taproot_script_spend_tx_with_cache(
+                        &mut sighash_cache,
+                        &prevouts,
+                        script,
+                        i,
+                    )
+                    .unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, sighash_cache);
+criterion_main!(benches);
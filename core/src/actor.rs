@@ -1,16 +1,68 @@
+use std::borrow::Borrow;
+
 use crate::errors::BridgeError;
 use crate::transaction_builder::CreateTxOutputs;
+use crate::{EVMAddress, EVMSignature};
 use bitcoin::sighash::SighashCache;
 use bitcoin::taproot::LeafVersion;
 use bitcoin::{
     hashes::Hash,
     secp256k1::{
-        ecdsa, schnorr, All, Keypair, Message, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey,
+        ecdsa::{self, RecoverableSignature, RecoveryId},
+        schnorr, All, Keypair, Message, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey,
     },
     Address, TapSighash, TapTweakHash,
 };
 
-use bitcoin::{TapLeafHash, TapNodeHash, TxOut};
+use bitcoin::{ScriptBuf, TapLeafHash, TapNodeHash, TxOut, Txid};
+use clementine_circuits::HashType;
+use sha3::{Digest, Keccak256};
+
+/// EVM address of a public key, the last 20 bytes of the keccak256 of its uncompressed form
+pub fn evm_address_from_public_key(public_key: &PublicKey) -> EVMAddress {
+    let hash: [u8; 32] = Keccak256::digest(&public_key.serialize_uncompressed()[1..]).into();
+    hash[12..].try_into().expect("20 bytes")
+}
+
+/// Domain tag of deposit messages, so that a deposit signature can not be replayed as a
+/// signature over anything else the same key signs
+pub const DEPOSIT_DOMAIN_TAG: &[u8] = b"clementine/rollup-deposit/v1";
+
+/// Message signed for a rollup deposit, the rollup has to compute it the same way:
+/// `keccak256(DEPOSIT_DOMAIN_TAG || deposit_txid || evm_address || hash)`, where the txid
+/// is in its internal byte order (the reverse of its usual hex form)
+pub fn deposit_message_hash(
+    deposit_txid: Txid,
+    evm_address: &EVMAddress,
+    hash: &HashType,
+) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(DEPOSIT_DOMAIN_TAG);
+    hasher.update(deposit_txid.to_byte_array());
+    hasher.update(evm_address);
+    hasher.update(hash);
+    hasher.finalize().into()
+}
+
+/// Recovers the EVM address that signed the deposit with Actor::sign_deposit, the same way
+/// ecrecover does. A signature over another message recovers to another address.
+pub fn verify_evm_signature(
+    sig: &EVMSignature,
+    deposit_txid: Txid,
+    evm_address: &EVMAddress,
+    hash: &HashType,
+) -> Result<EVMAddress, BridgeError> {
+    // v is 27 or 28
+    let recovery_id =
+        RecoveryId::from_i32(sig[64] as i32 - 27).map_err(|_| BridgeError::InvalidEVMSignature)?;
+    let signature = RecoverableSignature::from_compact(&sig[..64], recovery_id)
+        .map_err(|_| BridgeError::InvalidEVMSignature)?;
+    let message = Message::from_digest(deposit_message_hash(deposit_txid, evm_address, hash));
+    let public_key = Secp256k1::verification_only()
+        .recover_ecdsa(&message, &signature)
+        .map_err(|_| BridgeError::InvalidEVMSignature)?;
+    Ok(evm_address_from_public_key(&public_key))
+}
 
 #[derive(Debug)]
 pub struct Actor {
@@ -67,6 +119,13 @@ impl Actor {
         )
     }
 
+    pub fn sign_digest(&self, data: [u8; 32]) -> schnorr::Signature {
+        self.secp.sign_schnorr(
+            &Message::from_digest_slice(&data).expect("should be hash"),
+            &self.keypair,
+        )
+    }
+
     pub fn sign_ecdsa(&self, data: [u8; 32]) -> ecdsa::Signature {
         self.secp.sign_ecdsa(
             &Message::from_digest_slice(&data).expect("should be hash"),
@@ -74,6 +133,24 @@ impl Actor {
         )
     }
 
+    /// Signs the deposit of `deposit_txid` to `evm_address` for the rollup, see verify_evm_signature
+    pub fn sign_deposit(
+        &self,
+        deposit_txid: Txid,
+        evm_address: &EVMAddress,
+        hash: &HashType,
+    ) -> EVMSignature {
+        let message = Message::from_digest(deposit_message_hash(deposit_txid, evm_address, hash));
+        let (recovery_id, compact) = self
+            .secp
+            .sign_ecdsa_recoverable(&message, &self.secret_key)
+            .serialize_compact();
+        let mut sig = [0u8; 65];
+        sig[..64].copy_from_slice(&compact);
+        sig[64] = recovery_id.to_i32() as u8 + 27;
+        sig
+    }
+
     pub fn sign_taproot_script_spend_tx(
         &self,
         tx: &mut bitcoin::Transaction,
@@ -81,7 +158,23 @@ impl Actor {
         spend_script: &bitcoin::Script,
         input_index: usize,
     ) -> Result<schnorr::Signature, BridgeError> {
-        let mut sighash_cache = SighashCache::new(tx);
+        self.sign_taproot_script_spend_tx_with_cache(
+            &mut SighashCache::new(tx),
+            prevouts,
+            spend_script,
+            input_index,
+        )
+    }
+
+    /// Same as sign_taproot_script_spend_tx, the hashes of the prevouts and outputs kept in
+    /// `sighash_cache` are reused by every signature over the same tx
+    pub fn sign_taproot_script_spend_tx_with_cache<T: Borrow<bitcoin::Transaction>>(
+        &self,
+        sighash_cache: &mut SighashCache<T>,
+        prevouts: &[TxOut],
+        spend_script: &bitcoin::Script,
+        input_index: usize,
+    ) -> Result<schnorr::Signature, BridgeError> {
         let sig_hash = sighash_cache.taproot_script_spend_signature_hash(
             input_index,
             &bitcoin::sighash::Prevouts::All(prevouts),
@@ -91,6 +184,32 @@ impl Actor {
         Ok(self.sign(sig_hash))
     }
 
+    /// Signs every input of `tx` as a script spend of the script with the same index in
+    /// `scripts`, sharing one sighash cache
+    pub fn sign_all_script_spends(
+        &self,
+        tx: &bitcoin::Transaction,
+        prevouts: &[TxOut],
+        scripts: &[ScriptBuf],
+    ) -> Result<Vec<schnorr::Signature>, BridgeError> {
+        if scripts.len() != tx.input.len() {
+            return Err(BridgeError::ScriptCountMismatch);
+        }
+        let mut sighash_cache = SighashCache::new(tx);
+        scripts
+            .iter()
+            .enumerate()
+            .map(|(input_index, script)| {
+                self.sign_taproot_script_spend_tx_with_cache(
+                    &mut sighash_cache,
+                    prevouts,
+                    script,
+                    input_index,
+                )
+            })
+            .collect()
+    }
+
     pub fn sighash_taproot_script_spend(
         &self,
         tx: &mut CreateTxOutputs,
@@ -112,18 +231,14 @@ impl Actor {
         tx: &mut CreateTxOutputs,
         input_index: usize,
     ) -> Result<schnorr::Signature, BridgeError> {
-        // TODO: if sighash_cache exists in the CreateTxOutputs, use it
-        // else create a new one and save it to the CreateTxOutputs
-
-        let mut sighash_cache: SighashCache<&mut bitcoin::Transaction> =
-            SighashCache::new(&mut tx.tx);
-        let sig_hash = sighash_cache.taproot_script_spend_signature_hash(
+        // Signing several inputs of the same tx should go through
+        // sign_taproot_script_spend_tx_with_cache with one shared cache
+        self.sign_taproot_script_spend_tx_with_cache(
+            &mut SighashCache::new(&tx.tx),
+            &tx.prevouts,
+            &tx.scripts[input_index],
             input_index,
-            &bitcoin::sighash::Prevouts::All(&tx.prevouts),
-            TapLeafHash::from_script(&tx.scripts[input_index], LeafVersion::TapScript),
-            bitcoin::sighash::TapSighashType::Default,
-        )?;
-        Ok(self.sign(sig_hash))
+        )
     }
 
     pub fn sign_taproot_pubkey_spend_tx(
@@ -132,7 +247,21 @@ impl Actor {
         prevouts: &Vec<TxOut>,
         input_index: usize,
     ) -> Result<schnorr::Signature, BridgeError> {
-        let mut sighash_cache = SighashCache::new(tx);
+        self.sign_taproot_pubkey_spend_tx_with_cache(
+            &mut SighashCache::new(tx),
+            prevouts,
+            input_index,
+        )
+    }
+
+    /// Same as sign_taproot_pubkey_spend_tx, reusing `sighash_cache` like
+    /// sign_taproot_script_spend_tx_with_cache
+    pub fn sign_taproot_pubkey_spend_tx_with_cache<T: Borrow<bitcoin::Transaction>>(
+        &self,
+        sighash_cache: &mut SighashCache<T>,
+        prevouts: &[TxOut],
+        input_index: usize,
+    ) -> Result<schnorr::Signature, BridgeError> {
         let sig_hash = sighash_cache.taproot_key_spend_signature_hash(
             input_index,
             &bitcoin::sighash::Prevouts::All(prevouts),
@@ -162,3 +291,249 @@ impl Actor {
     //     Some(true)
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::rand::rngs::StdRng;
+    use secp256k1::rand::SeedableRng;
+
+    #[test]
+    fn test_verify_evm_signature() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([64u8; 32]);
+        let actor = Actor::new(secp.generate_keypair(&mut rng).0);
+        let actor_evm_address = evm_address_from_public_key(&actor.public_key);
+        let deposit_txid = Txid::from_byte_array([65u8; 32]);
+        let evm_address = [66u8; 20];
+        let hash = [67u8; 32];
+
+        let sig = actor.sign_deposit(deposit_txid, &evm_address, &hash);
+        assert!(sig[64] == 27 || sig[64] == 28);
+        assert_eq!(
+            verify_evm_signature(&sig, deposit_txid, &evm_address, &hash),
+            Ok(actor_evm_address)
+        );
+
+        // A signature over another deposit recovers to another address
+        assert_ne!(
+            verify_evm_signature(&sig, deposit_txid, &[68u8; 20], &hash),
+            Ok(actor_evm_address)
+        );
+        assert_ne!(
+            verify_evm_signature(&sig, Txid::from_byte_array([69u8; 32]), &evm_address, &hash),
+            Ok(actor_evm_address)
+        );
+        let mut tampered_sig = sig;
+        tampered_sig[64] = 29;
+        assert_eq!(
+            verify_evm_signature(&tampered_sig, deposit_txid, &evm_address, &hash),
+            Err(BridgeError::InvalidEVMSignature)
+        );
+    }
+
+    #[test]
+    fn test_sign_with_cache() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([68u8; 32]);
+        let actor = Actor::new(secp.generate_keypair(&mut rng).0);
+        let script = bitcoin::ScriptBuf::from_bytes(vec![0x51]);
+        let mut tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: (0..3u32)
+                .map(|vout| bitcoin::TxIn {
+                    previous_output: bitcoin::OutPoint {
+                        txid: Txid::from_byte_array([69u8; 32]),
+                        vout,
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: bitcoin::Amount::from_sat(2_000),
+                script_pubkey: actor.address.script_pubkey(),
+            }],
+        };
+        let prevouts = vec![
+            TxOut {
+                value: bitcoin::Amount::from_sat(1_000),
+                script_pubkey: actor.address.script_pubkey(),
+            };
+            3
+        ];
+
+        let mut sighash_cache = SighashCache::new(&tx);
+        let script_sigs = (0..3)
+            .map(|i| {
+                actor
+                    .sign_taproot_script_spend_tx_with_cache(
+                        &mut sighash_cache,
+                        &prevouts,
+                        &script,
+                        i,
+                    )
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let key_sigs = (0..3)
+            .map(|i| {
+                actor
+                    .sign_taproot_pubkey_spend_tx_with_cache(&mut sighash_cache, &prevouts, i)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        // Signatures are randomized, so they are checked against the sighashes of a fresh cache
+        let tweaked_pk = actor
+            .xonly_public_key
+            .add_tweak(
+                &secp,
+                &TapTweakHash::from_key_and_tweak(actor.xonly_public_key, None).to_scalar(),
+            )
+            .unwrap()
+            .0;
+        for i in 0..3 {
+            let script_sighash = SighashCache::new(&tx)
+                .taproot_script_spend_signature_hash(
+                    i,
+                    &bitcoin::sighash::Prevouts::All(&prevouts),
+                    TapLeafHash::from_script(&script, LeafVersion::TapScript),
+                    bitcoin::sighash::TapSighashType::Default,
+                )
+                .unwrap();
+            secp.verify_schnorr(
+                &script_sigs[i],
+                &Message::from_digest(script_sighash.to_byte_array()),
+                &actor.xonly_public_key,
+            )
+            .unwrap();
+            let key_sighash = SighashCache::new(&tx)
+                .taproot_key_spend_signature_hash(
+                    i,
+                    &bitcoin::sighash::Prevouts::All(&prevouts),
+                    bitcoin::sighash::TapSighashType::Default,
+                )
+                .unwrap();
+            secp.verify_schnorr(
+                &key_sigs[i],
+                &Message::from_digest(key_sighash.to_byte_array()),
+                &tweaked_pk,
+            )
+            .unwrap();
+        }
+
+        // The simple signatures still sign the same sighashes
+        let sig = actor
+            .sign_taproot_script_spend_tx(&mut tx, &prevouts, &script, 1)
+            .unwrap();
+        let script_sighash = SighashCache::new(&tx)
+            .taproot_script_spend_signature_hash(
+                1,
+                &bitcoin::sighash::Prevouts::All(&prevouts),
+                TapLeafHash::from_script(&script, LeafVersion::TapScript),
+                bitcoin::sighash::TapSighashType::Default,
+            )
+            .unwrap();
+        secp.verify_schnorr(
+            &sig,
+            &Message::from_digest(script_sighash.to_byte_array()),
+            &actor.xonly_public_key,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_sign_all_script_spends() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([70u8; 32]);
+        let actor = Actor::new(secp.generate_keypair(&mut rng).0);
+        let scripts = vec![
+            bitcoin::ScriptBuf::from_bytes(vec![0x51]),
+            bitcoin::ScriptBuf::from_bytes(vec![0x52]),
+        ];
+        let mut tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: (0..2u32)
+                .map(|vout| bitcoin::TxIn {
+                    previous_output: bitcoin::OutPoint {
+                        txid: Txid::from_byte_array([71u8; 32]),
+                        vout,
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![TxOut {
+                value: bitcoin::Amount::from_sat(2_000),
+                script_pubkey: actor.address.script_pubkey(),
+            }],
+        };
+        let prevouts = vec![
+            TxOut {
+                value: bitcoin::Amount::from_sat(1_000),
+                script_pubkey: actor.address.script_pubkey(),
+            };
+            2
+        ];
+
+        let sigs = actor
+            .sign_all_script_spends(&tx, &prevouts, &scripts)
+            .unwrap();
+        assert_eq!(sigs.len(), 2);
+        // Signatures are randomized, so each batch signature is checked against the sighash
+        // the individual call signs
+        for (i, script) in scripts.iter().enumerate() {
+            let sig = actor
+                .sign_taproot_script_spend_tx(&mut tx, &prevouts, script, i)
+                .unwrap();
+            let sighash = SighashCache::new(&tx)
+                .taproot_script_spend_signature_hash(
+                    i,
+                    &bitcoin::sighash::Prevouts::All(&prevouts),
+                    TapLeafHash::from_script(script, LeafVersion::TapScript),
+                    bitcoin::sighash::TapSighashType::Default,
+                )
+                .unwrap();
+            let msg = Message::from_digest(sighash.to_byte_array());
+            secp.verify_schnorr(&sig, &msg, &actor.xonly_public_key)
+                .unwrap();
+            secp.verify_schnorr(&sigs[i], &msg, &actor.xonly_public_key)
+                .unwrap();
+        }
+
+        assert!(matches!(
+            actor.sign_all_script_spends(&tx, &prevouts, &scripts[..1]),
+            Err(BridgeError::ScriptCountMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_deposit_message_hash() {
+        let deposit_txid = Txid::from_byte_array([65u8; 32]);
+        assert_eq!(
+            hex::encode(deposit_message_hash(deposit_txid, &[66u8; 20], &[67u8; 32])),
+            "155fbf7212b1a24b4b045bdd8b8719b3e1a767e917e3290d8e4d00e5c8587ee6"
+        );
+        // The tag is part of the message
+        let mut untagged = Keccak256::new();
+        untagged.update([65u8; 32]);
+        untagged.update([66u8; 20]);
+        untagged.update([67u8; 32]);
+        assert_ne!(
+            deposit_message_hash(deposit_txid, &[66u8; 20], &[67u8; 32]),
+            <[u8; 32]>::from(untagged.finalize())
+        );
+    }
+
+    #[test]
+    fn test_evm_address_from_public_key() {
+        // Secret key 1, its address is well known
+        let actor =
+            Actor::new(SecretKey::from_slice(&[[0u8; 31].as_slice(), &[1u8]].concat()).unwrap());
+        assert_eq!(
+            hex::encode(evm_address_from_public_key(&actor.public_key)),
+            "7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Lamport-style bit commitments. A bit is committed to with the hashes of two preimages and
+//! opened by revealing the preimage of the hash the bit selects, so revealing both preimages
+//! of a commitment is an equivocation anyone can prove.
+use bitcoin::opcodes::all::{OP_BOOLOR, OP_DUP, OP_EQUAL, OP_SHA256, OP_SWAP};
+use bitcoin::script::Builder;
+use bitcoin::ScriptBuf;
+use clementine_circuits::{sha256_hash, HashType, PreimageType};
+use secp256k1::rand::RngCore;
+
+/// The preimages of a bit commitment, the first opens 0 and the second opens 1
+pub type BitCommitmentPreimages = (PreimageType, PreimageType);
+
+/// The hashes a bit commitment publishes, in the order of its preimages
+pub type BitCommitmentHashes = (HashType, HashType);
+
+/// Number of bit commitments of a bitVM instance, one per bit of the claim proof merkle root
+/// the operator asserts for its period
+pub const INSTANCE_BIT_COUNT: usize = 256;
+
+/// The hash opening `bit` reveals the preimage of
+pub fn commit(bit: bool, preimage0: PreimageType, preimage1: PreimageType) -> HashType {
+    sha256_hash!(if bit { preimage1 } else { preimage0 })
+}
+
+/// Opens `bit`: the hash it is committed with and the preimage revealing it
+pub fn open(bit: bool, preimages: &BitCommitmentPreimages) -> (HashType, PreimageType) {
+    let preimage = if bit { preimages.1 } else { preimages.0 };
+    (sha256_hash!(preimage), preimage)
+}
+
+/// Bit `index` of `hash`, from the most significant bit of its first byte
+pub fn hash_bit(hash: &HashType, index: usize) -> bool {
+    hash[index / 8] >> (7 - index % 8) & 1 == 1
+}
+
+/// The hashes to publish for `preimages`
+pub fn commitment_hashes(preimages: &BitCommitmentPreimages) -> BitCommitmentHashes {
+    (
+        commit(false, preimages.0, preimages.1),
+        commit(true, preimages.0, preimages.1),
+    )
+}
+
+/// The bit `preimage` opens, None if it opens neither hash
+pub fn verify_opening(hashes: &BitCommitmentHashes, preimage: &PreimageType) -> Option<bool> {
+    let hash = sha256_hash!(preimage);
+    if hash == hashes.0 {
+        Some(false)
+    } else if hash == hashes.1 {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Tapscript that is satisfied by a witness revealing a preimage of either hash
+pub fn opening_script(hashes: &BitCommitmentHashes) -> ScriptBuf {
+    Builder::new()
+        .push_opcode(OP_SHA256)
+        .push_opcode(OP_DUP)
+        .push_slice(hashes.0)
+        .push_opcode(OP_EQUAL)
+        .push_opcode(OP_SWAP)
+        .push_slice(hashes.1)
+        .push_opcode(OP_EQUAL)
+        .push_opcode(OP_BOOLOR)
+        .into_script()
+}
+
+/// Random preimages for `count` bit commitments of a new bitVM instance
+pub fn generate_bit_commitments(
+    rng: &mut impl RngCore,
+    count: usize,
+) -> Vec<BitCommitmentPreimages> {
+    (0..count)
+        .map(|_| {
+            let mut preimages = ([0u8; 32], [0u8; 32]);
+            rng.fill_bytes(&mut preimages.0);
+            rng.fill_bytes(&mut preimages.1);
+            preimages
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction_builder::INTERNAL_KEY;
+    use crate::utils::{handle_taproot_witness, verify_taproot_witness};
+    use bitcoin::absolute::LockTime;
+    use bitcoin::taproot::TaprootBuilder;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Address, Amount, OutPoint, Sequence, Transaction, TxIn, TxOut, Witness};
+    use secp256k1::rand::rngs::StdRng;
+    use secp256k1::rand::SeedableRng;
+    use secp256k1::Secp256k1;
+
+    #[test]
+    fn test_bit_commitment() {
+        let mut rng = StdRng::from_seed([21u8; 32]);
+        let preimages = generate_bit_commitments(&mut rng, 2);
+        assert_eq!(preimages.len(), 2);
+        assert_ne!(preimages[0], preimages[1]);
+        let preimages = preimages[0];
+        let hashes = commitment_hashes(&preimages);
+
+        for bit in [false, true] {
+            let (hash, preimage) = open(bit, &preimages);
+            assert_eq!(hash, commit(bit, preimages.0, preimages.1));
+            assert_eq!(verify_opening(&hashes, &preimage), Some(bit));
+        }
+        assert_eq!(verify_opening(&hashes, &[0u8; 32]), None);
+        // An opening of one commitment does not open another
+        let other = commitment_hashes(&generate_bit_commitments(&mut rng, 1)[0]);
+        assert_eq!(verify_opening(&other, &open(true, &preimages).1), None);
+    }
+
+    #[test]
+    fn test_hash_bit() {
+        let mut hash = [0u8; 32];
+        hash[0] = 0b1000_0001;
+        hash[31] = 0b0000_0001;
+        let set_bits = (0..INSTANCE_BIT_COUNT)
+            .filter(|index| hash_bit(&hash, *index))
+            .collect::<Vec<_>>();
+        assert_eq!(set_bits, vec![0, 7, 255]);
+    }
+
+    #[test]
+    fn test_opening_script() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([22u8; 32]);
+        let preimages = generate_bit_commitments(&mut rng, 1)[0];
+        let script = opening_script(&commitment_hashes(&preimages));
+        let tree_info = TaprootBuilder::new()
+            .add_leaf(0, script.clone())
+            .unwrap()
+            .finalize(&secp, *INTERNAL_KEY)
+            .unwrap();
+        let address = Address::p2tr(
+            &secp,
+            *INTERNAL_KEY,
+            tree_info.merkle_root(),
+            bitcoin::Network::Regtest,
+        );
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: address.script_pubkey(),
+        };
+        let spend = |preimage: PreimageType| {
+            let mut tx = Transaction {
+                version: Version(2),
+                lock_time: LockTime::ZERO,
+                input: vec![TxIn {
+                    previous_output: OutPoint::null(),
+                    sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    script_sig: ScriptBuf::default(),
+                    witness: Witness::new(),
+                }],
+                output: vec![],
+            };
+            handle_taproot_witness(&mut tx, 0, &vec![preimage], &script, &tree_info).unwrap();
+            verify_taproot_witness(&tx, 0, std::slice::from_ref(&prevout))
+        };
+
+        assert!(spend(open(false, &preimages).1).is_ok());
+        assert!(spend(open(true, &preimages).1).is_ok());
+        assert!(spend([7u8; 32]).is_err());
+    }
+}
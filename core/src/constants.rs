@@ -15,9 +15,11 @@ pub const CONNECTOR_TREE_DEPTH: usize = CLAIM_MERKLE_TREE_DEPTH;
 pub const DUST_VALUE: u64 = 1000;
 /// Minimum relay fee for mempool acceptance
 pub const MIN_RELAY_FEE: u64 = 500;
+/// Minimum relay feerate of bitcoind, in sat/vB
+pub const MIN_RELAY_FEERATE: u64 = 1;
 
-/// This is temporary. to be able to set PERIOD_END_BLOCK_HEIGHTS
-pub const PERIOD_BLOCK_COUNT: u32 = 50; // 10 mins for 1 block, 6 months = 6*30*24*6 = 25920
+/// The circuit's period ends are derived from it, so both sides use the same periods
+pub use clementine_circuits::constants::PERIOD_BLOCK_COUNT;
 
 /// For deposits, every user makes a timelock to take the money back if deposit deos not happen,
 /// one reason is to not spam the bridge operator
@@ -6,7 +6,121 @@ use clementine_circuits::env::Environment;
 use secp256k1::hashes::Hash;
 use std::marker::PhantomData;
 
-use crate::{errors::BridgeError, merkle::MerkleTree};
+use crate::{errors::BridgeError, lightclient::LcProof, merkle::MerkleTree};
+
+/// A transaction in the form `read_tx_and_calculate_txid` reads it. The fields are written in this order:
+/// 1. version as i32, number of inputs, number of outputs and lock time as u32s
+/// 2. for every input: the previous txid as 32 bytes, the previous vout and the sequence as u32s,
+///    then the script sig length as u32 followed by the script sig in 32 byte chunks
+/// 3. for every output: the value as u64, then for taproot outputs a 0 as u32 followed by the
+///    32 byte output key, for any other output the script pubkey length as u32 followed by the
+///    script pubkey in 32 byte chunks
+///
+/// The last chunk of a script is padded with zeros. An empty script pubkey can not be represented,
+/// since its length would be read as the taproot marker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxReadSpec {
+    pub version: i32,
+    pub lock_time: u32,
+    pub inputs: Vec<TxInReadSpec>,
+    pub outputs: Vec<TxOutReadSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxInReadSpec {
+    pub prev_txid: [u8; 32],
+    pub prev_vout: u32,
+    pub sequence: u32,
+    pub script_sig: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxOutReadSpec {
+    pub value: u64,
+    pub script_pubkey: TxOutScriptReadSpec,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxOutScriptReadSpec {
+    /// Output key of a taproot output
+    Taproot([u8; 32]),
+    /// Any other script pubkey
+    Script(Vec<u8>),
+}
+
+impl From<&Transaction> for TxReadSpec {
+    fn from(tx: &Transaction) -> Self {
+        let inputs = tx
+            .input
+            .iter()
+            .map(|input| TxInReadSpec {
+                prev_txid: input.previous_output.txid.to_byte_array(),
+                prev_vout: input.previous_output.vout,
+                sequence: input.sequence.0,
+                script_sig: input.script_sig.to_bytes(),
+            })
+            .collect();
+        let outputs = tx
+            .output
+            .iter()
+            .map(|output| {
+                let script_pubkey = output.script_pubkey.as_bytes();
+                TxOutReadSpec {
+                    value: output.value.to_sat(),
+                    script_pubkey: if output.script_pubkey.is_p2tr() {
+                        TxOutScriptReadSpec::Taproot(script_pubkey[2..34].try_into().unwrap())
+                    } else {
+                        TxOutScriptReadSpec::Script(script_pubkey.to_vec())
+                    },
+                }
+            })
+            .collect();
+        Self {
+            version: tx.version.0,
+            lock_time: tx.lock_time.to_consensus_u32(),
+            inputs,
+            outputs,
+        }
+    }
+}
+
+impl TxReadSpec {
+    pub fn write<E: Environment>(&self) {
+        E::write_i32(self.version);
+        E::write_u32(self.inputs.len() as u32);
+        E::write_u32(self.outputs.len() as u32);
+        E::write_u32(self.lock_time);
+        for input in self.inputs.iter() {
+            E::write_32bytes(input.prev_txid);
+            E::write_u32(input.prev_vout);
+            E::write_u32(input.sequence);
+            E::write_u32(input.script_sig.len() as u32);
+            write_chunks::<E>(&input.script_sig);
+        }
+        for output in self.outputs.iter() {
+            E::write_u64(output.value);
+            match &output.script_pubkey {
+                TxOutScriptReadSpec::Taproot(output_key) => {
+                    E::write_u32(0); // 0 for taproot
+                    E::write_32bytes(*output_key);
+                }
+                TxOutScriptReadSpec::Script(script) => {
+                    E::write_u32(script.len() as u32);
+                    write_chunks::<E>(script);
+                }
+            }
+        }
+    }
+}
+
+/// Writes `data` in 32 byte chunks, the last chunk is padded with zeros
+fn write_chunks<E: Environment>(data: &[u8]) {
+    for chunk in data.chunks(32) {
+        let mut padded = [0u8; 32];
+        padded[..chunk.len()].copy_from_slice(chunk);
+        E::write_32bytes(padded);
+    }
+}
 
 pub struct ENVWriter<E: Environment> {
     _marker: PhantomData<E>,
@@ -40,63 +154,7 @@ impl<E: Environment> ENVWriter<E> {
     }
 
     pub fn write_tx_to_env(tx: &Transaction) {
-        E::write_i32(tx.version.0);
-        E::write_u32(tx.input.len() as u32);
-        E::write_u32(tx.output.len() as u32);
-        E::write_u32(tx.lock_time.to_consensus_u32());
-        for input in tx.input.iter() {
-            let prev_txid = input.previous_output.txid.as_byte_array();
-            E::write_32bytes(*prev_txid);
-            E::write_u32(input.previous_output.vout);
-            E::write_u32(input.sequence.0);
-            let script_sig_bytes = input.script_sig.as_bytes();
-            E::write_u32(script_sig_bytes.len() as u32);
-            let chunk_num = script_sig_bytes.len() as u32 / 32;
-            let remainder = script_sig_bytes.len() as u32 % 32;
-            for i in 0..chunk_num {
-                E::write_32bytes(
-                    script_sig_bytes[i as usize * 32..(i + 1) as usize * 32]
-                        .try_into()
-                        .unwrap(),
-                );
-            }
-            if remainder > 0 {
-                let padded = [0u8; 32];
-                let mut padded_bytes = script_sig_bytes[chunk_num as usize * 32..].to_vec();
-                padded_bytes.extend_from_slice(&padded[0..(32 - remainder) as usize]);
-                E::write_32bytes(padded_bytes.try_into().unwrap());
-            }
-        }
-        for output in tx.output.iter() {
-            E::write_u64(output.value.to_sat());
-            let output_script_pk = output.script_pubkey.as_bytes();
-            // tracing::debug!("Output ScriptPubKey len: {:?}", output_script_pk.len());
-            if output_script_pk.len() == 34
-                && output_script_pk[0] == 81u8
-                && output_script_pk[1] == 32u8
-            {
-                E::write_u32(0); // 0 for taproot
-                E::write_32bytes(output_script_pk[2..34].try_into().unwrap());
-            } else {
-                let script_pk_len = output_script_pk.len() as u32;
-                E::write_u32(script_pk_len);
-                let chunk_num = script_pk_len / 32;
-                let remainder = script_pk_len % 32;
-                for i in 0..chunk_num {
-                    E::write_32bytes(
-                        output_script_pk[i as usize * 32..(i + 1) as usize * 32]
-                            .try_into()
-                            .unwrap(),
-                    );
-                }
-                if remainder > 0 {
-                    let padded = [0u8; 32];
-                    let mut padded_bytes = output_script_pk[chunk_num as usize * 32..].to_vec();
-                    padded_bytes.extend_from_slice(&padded[0..(32 - remainder) as usize]);
-                    E::write_32bytes(padded_bytes.try_into().unwrap());
-                }
-            }
-        }
+        TxReadSpec::from(tx).write::<E>();
     }
 
     pub fn write_bitcoin_merkle_path(txid: Txid, block: &Block) -> Result<(), BridgeError> {
@@ -194,6 +252,20 @@ impl<E: Environment> ENVWriter<E> {
         }
     }
 
+    /// Writes a light client proof the way `read_and_verify_lc_proof` reads it, the number of
+    /// headers, the checkpoint with its previous blockhash and the headers, then the chain work
+    /// and the withdrawal root
+    pub fn write_lc_proof(proof: &LcProof) {
+        E::write_u32(proof.block_headers.len() as u32);
+        E::write_32bytes(proof.checkpoint.header.prev_blockhash.to_byte_array());
+        ENVWriter::<E>::write_block_header_without_prev(&proof.checkpoint.header);
+        for header in proof.block_headers.iter() {
+            ENVWriter::<E>::write_block_header_without_prev(header);
+        }
+        E::write_32bytes(proof.chain_work.to_le_bytes());
+        E::write_32bytes(proof.withdrawal_mt_root);
+    }
+
     pub fn write_blocks_and_add_to_merkle_tree<const DEPTH: usize>(
         block_headers: Vec<Header>,
         blockhashes_mt: &mut MerkleTree<DEPTH>,
@@ -245,29 +317,53 @@ mod tests {
     use std::str::FromStr;
 
     use bitcoin::{
-        block::Header,
+        absolute::LockTime,
+        block::{Header, Version},
+        blockdata::constants::genesis_block,
         consensus::{deserialize, serialize},
-        Block, Txid, XOnlyPublicKey,
+        pow::CompactTarget,
+        Amount, Block, BlockHash, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxMerkleNode,
+        TxOut, Txid, Witness, XOnlyPublicKey,
     };
     use clementine_circuits::{
         bitcoin::{
+            calculate_next_bits, decode_compact_target, encode_compact_target,
             read_and_verify_bitcoin_merkle_path, read_preimages_and_calculate_commit_taproot,
             read_tx_and_calculate_txid,
         },
         bridge::{
+            bridge_proof, read_and_verify_lc_proof, read_and_verify_verifiers_challenge_proof,
             read_blocks_and_add_to_merkle_tree, read_blocks_and_calculate_work,
             read_merkle_tree_proof,
         },
+        constants::{
+            BLOCKHASH_MERKLE_TREE_DEPTH, BRIDGE_AMOUNT_SATS, CLAIM_MERKLE_TREE_DEPTH,
+            LC_GENESIS_BLOCKHASH, MAX_BLOCK_HANDLE_OPS, NUM_ROUNDS, PERIOD_CLAIM_MT_ROOTS,
+            PERIOD_END_BLOCK_HEIGHTS, POW_LIMIT_BITS, WITHDRAWAL_MERKLE_TREE_DEPTH,
+        },
+        env::Environment,
         incremental_merkle::IncrementalMerkleTree,
+        PreimageType,
     };
     // use operator_circuit::GUEST_ELF;
 
-    use crypto_bigint::U256;
+    use crypto_bigint::{Encoding, U256};
     use secp256k1::hashes::Hash;
+    use secp256k1::rand::rngs::StdRng;
+    use secp256k1::rand::SeedableRng;
 
     use crate::{
-        env_writer::ENVWriter, errors::BridgeError, merkle::MerkleTree, mock_env::MockEnvironment,
-        transaction_builder::TransactionBuilder, utils::parse_hex_to_btc_tx,
+        constants::CONNECTOR_TREE_DEPTH,
+        env_writer::{ENVWriter, TxReadSpec},
+        errors::BridgeError,
+        extended_rpc::ExtendedRpc,
+        lightclient::{verify_proof, LcCheckpoint, LcProof},
+        merkle::MerkleTree,
+        mock_env::{MemoryEnvironment, MockEnvironment},
+        operator::create_all_rounds_connector_preimages,
+        transaction_builder::TransactionBuilder,
+        utils::{get_claim_proof_tree_leaf, get_claim_reveal_indices, parse_hex_to_btc_tx},
+        verifier::Verifier,
     };
 
     fn test_block_merkle_path(block: Block) -> Result<(), BridgeError> {
@@ -281,6 +377,26 @@ mod tests {
         Ok(())
     }
 
+    /// Writes the merkle path of the transaction at `index` with its position replaced by `position`
+    fn write_merkle_path_with_position(block: &Block, index: usize, position: u32) {
+        MockEnvironment::reset_mock_env();
+        ENVWriter::<MockEnvironment>::write_bitcoin_merkle_path(block.txdata[index].txid(), block)
+            .unwrap();
+        let _ = MockEnvironment::read_u32();
+        let levels = MockEnvironment::read_u32();
+        let path_indicator = MockEnvironment::read_u32();
+        let nodes = (0..levels - path_indicator.count_ones())
+            .map(|_| MockEnvironment::read_32bytes())
+            .collect::<Vec<_>>();
+        MockEnvironment::reset_mock_env();
+        MockEnvironment::write_u32(position);
+        MockEnvironment::write_u32(levels);
+        MockEnvironment::write_u32(path_indicator);
+        for node in nodes {
+            MockEnvironment::write_32bytes(node);
+        }
+    }
+
     #[test]
     fn test_tx() {
         let mut _num = SHARED_STATE.lock().unwrap();
@@ -294,6 +410,23 @@ mod tests {
         assert_eq!(btc_tx_id, Txid::from_byte_array(tx_id));
     }
 
+    #[test]
+    fn test_tx_read_spec() {
+        let segwit_block = include_bytes!("../tests/data/mainnet_block_00000000000000000000edfe523d5e2993781d2305f51218ebfc236a250792d6.raw").to_vec();
+        let block: Block = deserialize(&segwit_block).unwrap();
+
+        for tx in block.txdata.iter().take(50) {
+            let spec = TxReadSpec::from(tx);
+            assert_eq!(spec.inputs.len(), tx.input.len());
+            assert_eq!(spec.outputs.len(), tx.output.len());
+
+            MemoryEnvironment::reset();
+            spec.write::<MemoryEnvironment>();
+            let tx_id = read_tx_and_calculate_txid::<MemoryEnvironment>(None, None);
+            assert_eq!(tx.txid(), Txid::from_byte_array(tx_id));
+        }
+    }
+
     #[test]
     fn test_bitcoin_merkle_path() {
         let mut _num = SHARED_STATE.lock().unwrap();
@@ -317,6 +450,281 @@ mod tests {
         test_block_merkle_path(block4).unwrap();
     }
 
+    #[test]
+    fn test_bitcoin_merkle_path_wrong_position() {
+        let mut _num = SHARED_STATE.lock().unwrap();
+
+        let segwit_block = include_bytes!("../tests/data/testnet_block_000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b.raw").to_vec();
+        let block: Block = deserialize(&segwit_block).unwrap();
+        let expected_merkle_root = block.compute_merkle_root().unwrap().to_byte_array();
+        let last_index = block.txdata.len() - 1;
+        let last_txid = block.txdata[last_index].txid().to_byte_array();
+        let levels = (last_index as u32).ilog2() + 1;
+
+        // The unmodified path verifies for the actual position
+        write_merkle_path_with_position(&block, last_index, last_index as u32);
+        assert_eq!(
+            read_and_verify_bitcoin_merkle_path::<MockEnvironment>(last_txid),
+            expected_merkle_root
+        );
+
+        // A position with bits beyond the path would alias the actual one
+        write_merkle_path_with_position(&block, last_index, last_index as u32 + (1 << levels));
+        assert!(std::panic::catch_unwind(|| {
+            read_and_verify_bitcoin_merkle_path::<MockEnvironment>(last_txid)
+        })
+        .is_err());
+
+        // Every other position either fails or yields another root
+        for position in (0..1 << levels).filter(|&p| p != last_index as u32) {
+            write_merkle_path_with_position(&block, last_index, position);
+            if let Ok(merkle_root) = std::panic::catch_unwind(|| {
+                read_and_verify_bitcoin_merkle_path::<MockEnvironment>(last_txid)
+            }) {
+                assert_ne!(merkle_root, expected_merkle_root);
+            }
+        }
+        MockEnvironment::reset_mock_env();
+    }
+
+    /// Mines a regtest block with `txdata` on top of `prev_blockhash`
+    fn mine_regtest_block(prev_blockhash: BlockHash, time: u32, txdata: Vec<Transaction>) -> Block {
+        let mut block = Block {
+            header: Header {
+                version: Version::ONE,
+                prev_blockhash,
+                merkle_root: TxMerkleNode::all_zeros(),
+                time,
+                bits: CompactTarget::from_consensus(POW_LIMIT_BITS),
+                nonce: 0,
+            },
+            txdata,
+        };
+        if let Some(merkle_root) = block.compute_merkle_root() {
+            block.header.merkle_root = merkle_root;
+        }
+        while block.header.validate_pow(block.header.target()).is_err() {
+            block.header.nonce += 1;
+        }
+        block
+    }
+
+    /// Mines `count` empty regtest blocks on top of `prev_header`, ten minutes apart
+    fn mine_regtest_headers(prev_header: &Header, count: usize) -> Vec<Header> {
+        let mut headers: Vec<Header> = Vec::new();
+        for _ in 0..count {
+            let prev_header = headers.last().unwrap_or(prev_header);
+            let header =
+                mine_regtest_block(prev_header.block_hash(), prev_header.time + 600, vec![]).header;
+            headers.push(header);
+        }
+        headers
+    }
+
+    #[test]
+    fn test_read_and_verify_lc_proof() {
+        let genesis = genesis_block(bitcoin::Network::Regtest).header;
+        assert_eq!(genesis.block_hash().to_byte_array(), LC_GENESIS_BLOCKHASH);
+        let headers = mine_regtest_headers(&genesis, 10);
+        let withdrawal_mt_root = [2u8; 32];
+        let checkpoint = LcCheckpoint::circuit(genesis).unwrap();
+        let proof = LcProof::new(
+            bitcoin::Network::Regtest,
+            checkpoint,
+            headers,
+            withdrawal_mt_root,
+        );
+        let lc_blockhash = proof
+            .block_headers
+            .last()
+            .unwrap()
+            .block_hash()
+            .to_byte_array();
+        assert_eq!(
+            verify_proof(&proof, &checkpoint, lc_blockhash, withdrawal_mt_root),
+            Ok(())
+        );
+
+        let read_proof = |proof: &LcProof, lc_blockhash: [u8; 32]| {
+            MemoryEnvironment::reset();
+            ENVWriter::<MemoryEnvironment>::write_lc_proof(proof);
+            let proof_read = std::panic::catch_unwind(|| {
+                read_and_verify_lc_proof::<MemoryEnvironment>(lc_blockhash, withdrawal_mt_root)
+            })
+            .is_ok();
+            // The whole proof is consumed
+            proof_read && std::panic::catch_unwind(MemoryEnvironment::read_u32).is_err()
+        };
+        assert!(read_proof(&proof, lc_blockhash));
+
+        // Mismatching blockhash or withdrawal merkle root
+        assert!(!read_proof(
+            &proof,
+            proof.block_headers[5].block_hash().to_byte_array()
+        ));
+        let mut wrong_root_proof = proof.clone();
+        wrong_root_proof.withdrawal_mt_root = [3u8; 32];
+        assert!(!read_proof(&wrong_root_proof, lc_blockhash));
+
+        // Wrong chain work
+        let mut wrong_work_proof = proof.clone();
+        wrong_work_proof.chain_work = wrong_work_proof.chain_work + proof.block_headers[0].work();
+        assert!(!read_proof(&wrong_work_proof, lc_blockhash));
+
+        // Not starting at the checkpoint
+        let unanchored_proof = LcProof::new(
+            bitcoin::Network::Regtest,
+            checkpoint,
+            proof.block_headers[1..].to_vec(),
+            withdrawal_mt_root,
+        );
+        assert!(!read_proof(&unanchored_proof, lc_blockhash));
+        let other_checkpoint_proof = LcProof::new(
+            bitcoin::Network::Regtest,
+            LcCheckpoint {
+                height: 1,
+                header: proof.block_headers[0],
+                chain_work: checkpoint.chain_work + proof.block_headers[0].work(),
+            },
+            proof.block_headers[1..].to_vec(),
+            withdrawal_mt_root,
+        );
+        assert_eq!(other_checkpoint_proof.chain_work, proof.chain_work);
+        assert!(!read_proof(&other_checkpoint_proof, lc_blockhash));
+
+        // A header with another difficulty than the required one
+        let mut other_bits_headers = proof.block_headers.clone();
+        other_bits_headers.last_mut().unwrap().bits = CompactTarget::from_consensus(0x1d00ffff);
+        let other_bits_proof = LcProof::new(
+            bitcoin::Network::Regtest,
+            checkpoint,
+            other_bits_headers,
+            withdrawal_mt_root,
+        );
+        let other_bits_blockhash = other_bits_proof
+            .block_headers
+            .last()
+            .unwrap()
+            .block_hash()
+            .to_byte_array();
+        assert_eq!(
+            verify_proof(
+                &other_bits_proof,
+                &checkpoint,
+                other_bits_blockhash,
+                withdrawal_mt_root
+            ),
+            Err(BridgeError::InvalidLcProof)
+        );
+        assert!(!read_proof(&other_bits_proof, other_bits_blockhash));
+    }
+
+    #[test]
+    fn test_calculate_next_bits() {
+        // Bitcoin Core's get_next_work test vectors
+        for (first_time, last_time, bits, next_bits) in [
+            (1261130161u32, 1262152739u32, 0x1d00ffffu32, 0x1d00d86au32),
+            (1231006505, 1233061996, 0x1d00ffff, 0x1d00ffff),
+            (1279008237, 1279297671, 0x1c05a3f4, 0x1c0168fd),
+            (1263163443, 1269211443, 0x1c387f6f, 0x1d00e1fd),
+        ] {
+            assert_eq!(
+                calculate_next_bits(
+                    bits.to_le_bytes(),
+                    first_time,
+                    last_time,
+                    0x1d00ffffu32.to_le_bytes()
+                ),
+                next_bits.to_le_bytes()
+            );
+        }
+
+        // Encoding a decoded target gives back the same bits
+        for bits in [
+            0x1d00ffffu32,
+            0x1c05a3f4,
+            0x17034219,
+            0x207fffff,
+            0x01120000,
+        ] {
+            assert_eq!(
+                encode_compact_target(decode_compact_target(bits.to_le_bytes())),
+                bits.to_le_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_challenge_proof() {
+        let secp = secp256k1::Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[32u8; 32]).unwrap();
+        let verifier = Verifier::new(
+            ExtendedRpc::new(),
+            vec![XOnlyPublicKey::from(sk.public_key(&secp))],
+            sk,
+        )
+        .unwrap();
+
+        let last_finalized_bh = [33u8; 32];
+        let work =
+            U256::from_be_hex("00000000000000000000000000000000000000000000000000000001000100ff");
+        let challenge_proof = verifier.build_challenge_proof(last_finalized_bh, 3, work);
+
+        MemoryEnvironment::load(challenge_proof);
+        assert_eq!(
+            read_and_verify_verifiers_challenge_proof::<MemoryEnvironment>(),
+            (work, last_finalized_bh, 3)
+        );
+        // The whole challenge is consumed
+        assert!(std::panic::catch_unwind(MemoryEnvironment::read_u32).is_err());
+    }
+
+    #[test]
+    fn test_decode_compact_target() {
+        let decode =
+            |bits: u32| std::panic::catch_unwind(|| decode_compact_target(bits.to_le_bytes())).ok();
+        let target_from_be = |mut be_bytes: [u8; 32]| {
+            be_bytes.reverse();
+            be_bytes
+        };
+
+        // Genesis difficulty
+        let mut expected = [0u8; 32];
+        expected[4] = 0xff;
+        expected[5] = 0xff;
+        assert_eq!(decode(0x1d00ffff), Some(target_from_be(expected)));
+        // Small exponents shift the mantissa to the right
+        let mut expected = [0u8; 32];
+        expected[31] = 0x12;
+        assert_eq!(decode(0x01123456), Some(target_from_be(expected)));
+        let mut expected = [0u8; 32];
+        expected[30] = 0x12;
+        expected[31] = 0x34;
+        assert_eq!(decode(0x02123456), Some(target_from_be(expected)));
+        // The largest representable targets
+        let mut expected = [0u8; 32];
+        expected[0] = 0x7f;
+        expected[1] = 0xff;
+        expected[2] = 0xff;
+        assert_eq!(decode(0x207fffff), Some(target_from_be(expected)));
+        let mut expected = [0u8; 32];
+        expected[0] = 0x01;
+        assert_eq!(decode(0x22000001), Some(target_from_be(expected)));
+
+        // Zero targets, including a set sign bit shifted out of a zero mantissa
+        assert_eq!(decode(0x00800000), None);
+        assert_eq!(decode(0x1d000000), None);
+        assert_eq!(decode(0x01003456), None);
+        // Negative targets
+        assert_eq!(decode(0x04923456), None);
+        assert_eq!(decode(0x01fedcba), None);
+        // Overflowing targets
+        assert_eq!(decode(0x21010000), None);
+        assert_eq!(decode(0x22000100), None);
+        assert_eq!(decode(0x23000001), None);
+        assert_eq!(decode(0xff123456), None);
+    }
+
     #[test]
     fn test_all_txids_in_block() {
         let mut _num = SHARED_STATE.lock().unwrap();
@@ -389,8 +797,11 @@ mod tests {
         );
 
         let mut read_imt = IncrementalMerkleTree::<32>::new();
+        let mut block_height = 0;
         let res = read_blocks_and_add_to_merkle_tree::<MockEnvironment>(
             start_block_hash,
+            &mut block_height,
+            headers.len() as u32,
             &mut read_imt,
             4, // MAX_BLOCK_HANDLE_OPS
         );
@@ -439,6 +850,317 @@ mod tests {
         assert_eq!(U256::from(380064701315057048298976312u128), res)
     }
 
+    #[test]
+    fn test_write_and_read_multi_round_blocks() {
+        // Writes the periods of a bridge proof the way the operator does and reads them back in
+        // the order bridge_proof does, test_bridge_proof runs a whole proof
+        let mainnet_blocks_from_832000_to_833096 =
+            include_bytes!("../tests/data/mainnet_blocks_from_832000_to_833096.raw").to_vec();
+        let headers: Vec<Header> = deserialize(&mainnet_blocks_from_832000_to_833096).unwrap();
+        let periods = headers[..30].chunks(10).collect::<Vec<_>>();
+
+        MemoryEnvironment::reset();
+        let start_block_hash = headers[0].prev_blockhash.to_byte_array();
+        MemoryEnvironment::write_32bytes(start_block_hash);
+        let mut write_mt = MerkleTree::<32>::new();
+        for (i, period_headers) in periods.iter().enumerate() {
+            ENVWriter::<MemoryEnvironment>::write_blocks_and_add_to_merkle_tree(
+                period_headers.to_vec(),
+                &mut write_mt,
+            );
+            MemoryEnvironment::write_u32(0); // no withdrawals
+            MemoryEnvironment::write_u32((i == periods.len() - 1) as u32);
+        }
+
+        let mut read_imt = IncrementalMerkleTree::<32>::new();
+        let mut cur_block_hash = MemoryEnvironment::read_32bytes();
+        let mut block_height = 0;
+        let mut num_periods = 0;
+        loop {
+            let (_, lc_block_hash, last_block_hash) =
+                read_blocks_and_add_to_merkle_tree::<MemoryEnvironment>(
+                    cur_block_hash,
+                    &mut block_height,
+                    (num_periods as u32 + 1) * 10,
+                    &mut read_imt,
+                    4, // MAX_BLOCK_HANDLE_OPS
+                );
+            let period_headers = periods[num_periods];
+            assert_eq!(
+                last_block_hash,
+                period_headers.last().unwrap().block_hash().to_byte_array()
+            );
+            assert_eq!(
+                lc_block_hash,
+                period_headers[period_headers.len() - 5]
+                    .block_hash()
+                    .to_byte_array()
+            );
+            cur_block_hash = last_block_hash;
+            num_periods += 1;
+            assert_eq!(MemoryEnvironment::read_u32(), 0);
+            if MemoryEnvironment::read_u32() == 1 {
+                break;
+            }
+        }
+        assert_eq!(num_periods, periods.len());
+        assert_eq!(block_height, 30);
+        assert_eq!(read_imt.root, write_mt.root());
+        // Everything written is read back
+        assert!(std::panic::catch_unwind(MemoryEnvironment::read_u32).is_err());
+    }
+
+    /// A pay to taproot output of `value` to the output key `xonly`
+    fn p2tr_output(value: u64, xonly: [u8; 32]) -> TxOut {
+        let mut script_pubkey = vec![0x51, 0x20];
+        script_pubkey.extend_from_slice(&xonly);
+        TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: ScriptBuf::from_bytes(script_pubkey),
+        }
+    }
+
+    /// A transaction spending `previous_output` to `output`
+    fn spend_tx(previous_output: OutPoint, output: TxOut) -> Transaction {
+        Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![output],
+        }
+    }
+
+    #[test]
+    fn test_bridge_proof() {
+        // Writes a bridge proof the way Operator::prove does, over regtest blocks with a withdrawal
+        // in the first period and the preimage reveal of the second period, which is challenged
+        let challenge_period = 1;
+        let num_withdrawals = 1;
+        let secp = secp256k1::Secp256k1::new();
+        let operator_sk = secp256k1::SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let operator_pk = XOnlyPublicKey::from(operator_sk.public_key(&secp));
+
+        // PERIOD_CLAIM_MT_ROOTS are the claim proof roots of the connector trees of this seed
+        let (connector_tree_preimages, connector_tree_hashes) =
+            create_all_rounds_connector_preimages(
+                CONNECTOR_TREE_DEPTH,
+                NUM_ROUNDS,
+                &mut StdRng::from_seed([0u8; 32]),
+            );
+        let preimages: Vec<PreimageType> =
+            get_claim_reveal_indices(CONNECTOR_TREE_DEPTH, num_withdrawals as u32)
+                .unwrap()
+                .iter()
+                .map(|leaf| connector_tree_preimages[challenge_period][leaf.level][leaf.index])
+                .collect();
+        let mut claim_proof_mt = MerkleTree::<CLAIM_MERKLE_TREE_DEPTH>::new();
+        for i in 0..2usize.pow(CONNECTOR_TREE_DEPTH as u32) {
+            claim_proof_mt.add(
+                get_claim_proof_tree_leaf(
+                    CONNECTOR_TREE_DEPTH,
+                    i,
+                    &connector_tree_hashes[challenge_period],
+                )
+                .unwrap(),
+            );
+        }
+        assert_eq!(
+            claim_proof_mt.root(),
+            PERIOD_CLAIM_MT_ROOTS[challenge_period]
+        );
+
+        MemoryEnvironment::reset();
+        ENVWriter::<MemoryEnvironment>::write_preimages(operator_pk, &preimages);
+        let (commit_taproot_addr, claim_proof_leaf) =
+            read_preimages_and_calculate_commit_taproot::<MemoryEnvironment>();
+
+        let withdrawal_address = [7u8; 32];
+        let withdrawal_tx = spend_tx(
+            OutPoint::null(),
+            p2tr_output(BRIDGE_AMOUNT_SATS, withdrawal_address),
+        );
+        let commit_tx = spend_tx(OutPoint::null(), p2tr_output(10_000, commit_taproot_addr));
+        let reveal_tx = spend_tx(
+            OutPoint {
+                txid: commit_tx.txid(),
+                vout: 0,
+            },
+            p2tr_output(9_000, operator_pk.serialize()),
+        );
+
+        // Two periods, then K_DEEP blocks on top of them
+        let period_ends = &PERIOD_END_BLOCK_HEIGHTS[..=challenge_period];
+        let withdrawal_height = 10;
+        let reveal_height = period_ends[1] as usize - 2;
+        let mut blocks: Vec<Block> = Vec::new();
+        for height in 0..period_ends[1] as usize + 6 {
+            let prev_header = blocks
+                .last()
+                .map_or(genesis_block(bitcoin::Network::Regtest).header, |block| {
+                    block.header
+                });
+            let txdata = if height == withdrawal_height {
+                vec![withdrawal_tx.clone(), commit_tx.clone()]
+            } else if height == reveal_height {
+                vec![reveal_tx.clone(), withdrawal_tx.clone()]
+            } else {
+                Vec::new()
+            };
+            blocks.push(mine_regtest_block(
+                prev_header.block_hash(),
+                prev_header.time + 600,
+                txdata,
+            ));
+        }
+        let headers: Vec<Header> = blocks.iter().map(|block| block.header).collect();
+
+        MemoryEnvironment::reset();
+        MemoryEnvironment::write_32bytes(headers[0].prev_blockhash.to_byte_array());
+        let mut blockhashes_mt = MerkleTree::<BLOCKHASH_MERKLE_TREE_DEPTH>::new();
+        let mut withdrawal_mt = MerkleTree::<WITHDRAWAL_MERKLE_TREE_DEPTH>::new();
+        let mut period_start = 0;
+        for (period, &period_end) in period_ends.iter().enumerate() {
+            ENVWriter::<MemoryEnvironment>::write_blocks_and_add_to_merkle_tree(
+                headers[period_start..period_end as usize].to_vec(),
+                &mut blockhashes_mt,
+            );
+            period_start = period_end as usize;
+            if period == 0 {
+                MemoryEnvironment::write_u32(num_withdrawals as u32);
+                let block = &blocks[withdrawal_height];
+                MemoryEnvironment::write_32bytes(withdrawal_address);
+                ENVWriter::<MemoryEnvironment>::write_tx_to_env(&withdrawal_tx);
+                ENVWriter::<MemoryEnvironment>::write_bitcoin_merkle_path(
+                    withdrawal_tx.txid(),
+                    block,
+                )
+                .unwrap();
+                ENVWriter::<MemoryEnvironment>::write_block_header_without_mt_root(&block.header);
+                ENVWriter::<MemoryEnvironment>::write_merkle_tree_proof(
+                    block.block_hash().to_byte_array(),
+                    None,
+                    &blockhashes_mt,
+                );
+                withdrawal_mt.add(withdrawal_address);
+            } else {
+                MemoryEnvironment::write_u32(0);
+            }
+            MemoryEnvironment::write_u32((period == challenge_period) as u32);
+        }
+
+        // The verifier challenges the last period at the same blockhash with less work
+        let last_blockhash = headers[period_start - 1].block_hash().to_byte_array();
+        for _ in 0..4 {
+            MemoryEnvironment::write_32bytes([0u8; 32]);
+        }
+        MemoryEnvironment::write_32bytes(last_blockhash);
+        MemoryEnvironment::write_32bytes(U256::ONE.to_le_bytes());
+        MemoryEnvironment::write_u32(challenge_period as u32);
+
+        ENVWriter::<MemoryEnvironment>::write_blocks(headers[period_start..].to_vec());
+
+        let lc_height = period_start - 1 - MAX_BLOCK_HANDLE_OPS as usize;
+        ENVWriter::<MemoryEnvironment>::write_lc_proof(&LcProof::new(
+            bitcoin::Network::Regtest,
+            LcCheckpoint::genesis(bitcoin::Network::Regtest),
+            headers[..=lc_height].to_vec(),
+            withdrawal_mt.root(),
+        ));
+
+        ENVWriter::<MemoryEnvironment>::write_preimages(operator_pk, &preimages);
+        ENVWriter::<MemoryEnvironment>::write_tx_to_env(&commit_tx);
+        MemoryEnvironment::write_u32(0);
+        ENVWriter::<MemoryEnvironment>::write_tx_to_env(&reveal_tx);
+        let block = &blocks[reveal_height];
+        ENVWriter::<MemoryEnvironment>::write_bitcoin_merkle_path(reveal_tx.txid(), block).unwrap();
+        ENVWriter::<MemoryEnvironment>::write_block_header_without_mt_root(&block.header);
+        ENVWriter::<MemoryEnvironment>::write_merkle_tree_proof(
+            block.block_hash().to_byte_array(),
+            None,
+            &blockhashes_mt,
+        );
+        ENVWriter::<MemoryEnvironment>::write_merkle_tree_proof(
+            claim_proof_leaf,
+            Some(num_withdrawals as u32),
+            &claim_proof_mt,
+        );
+
+        let proof = MemoryEnvironment::bytes();
+        MemoryEnvironment::load(proof.clone());
+        bridge_proof::<MemoryEnvironment>();
+        // Everything written is read back
+        assert!(std::panic::catch_unwind(MemoryEnvironment::read_u32).is_err());
+
+        // A tampered claim proof path does not lead to the committed claim proof root
+        let mut wrong_claim_proof = proof;
+        let claim_index = wrong_claim_proof.len() - 32 * CLAIM_MERKLE_TREE_DEPTH;
+        wrong_claim_proof[claim_index] ^= 1;
+        MemoryEnvironment::load(wrong_claim_proof);
+        assert!(std::panic::catch_unwind(bridge_proof::<MemoryEnvironment>).is_err());
+    }
+
+    #[test]
+    fn test_read_blocks_not_reaching_period_end() {
+        let mainnet_blocks_from_832000_to_833096 =
+            include_bytes!("../tests/data/mainnet_blocks_from_832000_to_833096.raw").to_vec();
+        let headers: Vec<Header> = deserialize(&mainnet_blocks_from_832000_to_833096).unwrap();
+
+        MemoryEnvironment::reset();
+        let mut write_mt = MerkleTree::<32>::new();
+        ENVWriter::<MemoryEnvironment>::write_blocks_and_add_to_merkle_tree(
+            headers[..10].to_vec(),
+            &mut write_mt,
+        );
+        // The second period stops one block before its committed end
+        ENVWriter::<MemoryEnvironment>::write_blocks_and_add_to_merkle_tree(
+            headers[10..19].to_vec(),
+            &mut write_mt,
+        );
+
+        let mut read_imt = IncrementalMerkleTree::<32>::new();
+        let mut block_height = 0;
+        let (_, _, last_block_hash) = read_blocks_and_add_to_merkle_tree::<MemoryEnvironment>(
+            headers[0].prev_blockhash.to_byte_array(),
+            &mut block_height,
+            10,
+            &mut read_imt,
+            4, // MAX_BLOCK_HANDLE_OPS
+        );
+        assert_eq!(block_height, 10);
+        assert!(std::panic::catch_unwind(move || {
+            read_blocks_and_add_to_merkle_tree::<MemoryEnvironment>(
+                last_block_hash,
+                &mut block_height,
+                20,
+                &mut read_imt,
+                4,
+            )
+        })
+        .is_err());
+
+        // A period can not end past its committed end either
+        MemoryEnvironment::reset();
+        ENVWriter::<MemoryEnvironment>::write_blocks_and_add_to_merkle_tree(
+            headers[..11].to_vec(),
+            &mut MerkleTree::<32>::new(),
+        );
+        assert!(std::panic::catch_unwind(|| {
+            read_blocks_and_add_to_merkle_tree::<MemoryEnvironment>(
+                headers[0].prev_blockhash.to_byte_array(),
+                &mut 0,
+                10,
+                &mut IncrementalMerkleTree::<32>::new(),
+                4,
+            )
+        })
+        .is_err());
+    }
+
     #[test]
     fn test_write_and_read_merkle_tree_proof() {
         let mut _num = SHARED_STATE.lock().unwrap();
@@ -1,5 +1,6 @@
 //! This module defines errors returned by the library.
 use bitcoin::taproot::{TaprootBuilder, TaprootBuilderError};
+use bitcoin::{Amount, OutPoint};
 use core::fmt::Debug;
 use std::array::TryFromSliceError;
 use thiserror::Error;
@@ -75,6 +76,141 @@ pub enum BridgeError {
     /// AlreadyInitialized is returned when the operator is already initialized
     #[error("AlreadyInitialized")]
     AlreadyInitialized,
+    /// InvalidWithdrawalProof is returned when a withdrawal is not included in the trusted
+    /// rollup withdrawals root, or is not the next withdrawal the operator has to pay
+    #[error("InvalidWithdrawalProof")]
+    InvalidWithdrawalProof,
+    /// InvalidEvmAddress is returned when the EVM address of a deposit is all zeros, or a hex EVM address can not be parsed
+    #[error("InvalidEvmAddress")]
+    InvalidEvmAddress,
+    /// InvalidConnectorTreeDepth is returned when the connector tree depth is zero or deeper than the claim merkle tree, or a connector tree has no levels
+    #[error("InvalidConnectorTreeDepth")]
+    InvalidConnectorTreeDepth,
+    /// BaseTxNotFound is returned when the transaction of a UTXO to be spent is not found
+    #[error("BaseTxNotFound")]
+    BaseTxNotFound,
+    /// InvalidConnectorTreeUtxoValue is returned when the value of a UTXO is too small to be a connector tree node
+    #[error("InvalidConnectorTreeUtxoValue")]
+    InvalidConnectorTreeUtxoValue,
+    /// MoveTxValueMismatch is returned when the outputs of a move tx and the fee do not add up to the deposit amount
+    #[error("MoveTxValueMismatch")]
+    MoveTxValueMismatch,
+    /// PreimageNotInTree is returned when the preimage is not in the connector tree level the spent utxo belongs to
+    #[error("PreimageNotInTree")]
+    PreimageNotInTree,
+    /// VerifierEvmAddressesMismatch is returned when the number of verifier evm addresses does not match the number of verifiers
+    #[error("VerifierEvmAddressesMismatch")]
+    VerifierEvmAddressesMismatch,
+    /// InvalidTimelock is returned when a relative timelock can not be represented in BIP68 encoding
+    #[error("InvalidTimelock")]
+    InvalidTimelock,
+    /// InvalidLcProof is returned when a light client proof does not verify against the expected values
+    #[error("InvalidLcProof")]
+    InvalidLcProof,
+    /// UnsupportedNetwork is returned when the node's chain is not the one the circuit is compiled for, another network or a chain without the light client checkpoint
+    #[error("UnsupportedNetwork")]
+    UnsupportedNetwork,
+    /// InsufficientPeriodFunding is returned when the operator's wallet can not pay for a period, contains the shortfall
+    #[error("InsufficientPeriodFunding: {0} short")]
+    InsufficientPeriodFunding(Amount),
+    /// InsufficientInscriptionFunding is returned when the operator's wallet can not fund an inscription commit, contains the shortfall
+    #[error("InsufficientInscriptionFunding: {0} short")]
+    InsufficientInscriptionFunding(Amount),
+    /// ConnectorTreeNotReady is returned when a deposit is made before the connector trees are created
+    #[error("ConnectorTreeNotReady")]
+    ConnectorTreeNotReady,
+    /// InvalidClaimCount is returned when more claims are made than a connector tree has leaves
+    #[error("InvalidClaimCount")]
+    InvalidClaimCount,
+    /// MoveSignatureNotFound is returned when no valid move signature is given for a key of the n-of-n script
+    #[error("MoveSignatureNotFound")]
+    MoveSignatureNotFound,
+    /// RevealNotInPeriod is returned when a preimage reveal transaction is confirmed outside of its period's reveal window
+    #[error("RevealNotInPeriod")]
+    RevealNotInPeriod,
+    /// WitnessElementCountMismatch is returned when the number of witness elements does not match the signatures the script checks
+    #[error("WitnessElementCountMismatch")]
+    WitnessElementCountMismatch,
+    /// MoveTxNotFound is returned when there is no move tx stored for a deposit
+    #[error("MoveTxNotFound")]
+    MoveTxNotFound,
+    /// ConnectorTreeSpendMismatch is returned when a connector tree utxo on chain is not the one the connector tree hashes commit to
+    #[error("ConnectorTreeSpendMismatch")]
+    ConnectorTreeSpendMismatch,
+    /// UnsafeRevealOrdering is returned when revealing the preimages would expose a connector leaf whose claim is not broadcast yet
+    #[error("UnsafeRevealOrdering")]
+    UnsafeRevealOrdering,
+    /// DepositAddressMismatch is returned when the deposit utxo does not pay to the deposit address derived from the verifier set and the return address
+    #[error("DepositAddressMismatch")]
+    DepositAddressMismatch,
+    /// PartialDepositBatch is returned when a move of a deposit batch fails after the moves before
+    /// it are sent, contains the move utxos of those deposits and the error of the failed move
+    #[error("PartialDepositBatch: {} deposits moved, then {1}", .0.len())]
+    PartialDepositBatch(Vec<OutPoint>, Box<BridgeError>),
+    /// TxRejected is returned when bitcoind would not accept a transaction into its mempool, contains the reject reason
+    #[error("TxRejected: {0}")]
+    TxRejected(String),
+    /// InvalidInscription is returned when a reveal tx does not spend an inscription script of 32 byte preimages
+    #[error("InvalidInscription")]
+    InvalidInscription,
+    /// InscriptionTooLarge is returned when an inscription script would exceed MAX_SCRIPT_SIZE
+    #[error("InscriptionTooLarge")]
+    InscriptionTooLarge,
+    /// CommitRevealMismatch is returned when a reveal tx does not spend the inscription output of its commit tx
+    #[error("CommitRevealMismatch")]
+    CommitRevealMismatch,
+    /// InvalidEVMSignature is returned when no public key can be recovered from an EVM signature
+    #[error("InvalidEVMSignature")]
+    InvalidEVMSignature,
+    /// InvalidDepositSignatures is returned when the rollup signatures of a deposit are not
+    /// exactly one signature from each expected signer
+    #[error("InvalidDepositSignatures")]
+    InvalidDepositSignatures,
+    /// InvalidFraudProof is returned when a fraud proof does not open the operator's claim root or does not show any missing reveal
+    #[error("InvalidFraudProof")]
+    InvalidFraudProof,
+    /// InvalidChallengeStep is returned when a challenged step has no bit commitment the operator can open
+    #[error("InvalidChallengeStep")]
+    InvalidChallengeStep,
+    /// ConnectorRootMismatch is returned when a connector tree root utxo does not pay to the address committing to the given connector tree hashes
+    #[error("ConnectorRootMismatch")]
+    ConnectorRootMismatch,
+    /// ScriptCountMismatch is returned when the number of spend scripts given to sign a transaction differs from its number of inputs
+    #[error("ScriptCountMismatch")]
+    ScriptCountMismatch,
+    /// TxOutputNotFound is returned when a transaction does not have the output an outpoint refers to
+    #[error("TxOutputNotFound")]
+    TxOutputNotFound,
+    /// ConnectorLeafNotFound is returned when the operator has no connector tree leaf for a deposit index in the period
+    #[error("ConnectorLeafNotFound")]
+    ConnectorLeafNotFound,
+    /// MissingUserSignature is returned when the witness of a deposit script spend is built without the user's signature it takes
+    #[error("MissingUserSignature")]
+    MissingUserSignature,
+    /// ConfirmationTimeout is returned when a transaction does not get enough confirmations in time
+    #[error("ConfirmationTimeout")]
+    ConfirmationTimeout,
+    /// NotYetSpendable is returned when a claim is made before the timelock of its connector leaf has passed
+    #[error("NotYetSpendable")]
+    NotYetSpendable,
+    /// ClaimIndexOutOfRange is returned when a claimed deposit index is past the recorded deposit data, contains the data that is short
+    #[error("ClaimIndexOutOfRange: {0}")]
+    ClaimIndexOutOfRange(&'static str),
+    /// NotAnAnchor is returned when a child pays for parent tx is asked to spend an output that is not an anchor
+    #[error("NotAnAnchor")]
+    NotAnAnchor,
+    /// InsufficientFee is returned when a transaction pays less than the minimum relay feerate for its size
+    #[error("InsufficientFee")]
+    InsufficientFee,
+    /// InsufficientStartUtxo is returned when the start utxo of a deposit does not exist, is spent, holds less than the deposit amount or too little for the move tx fee
+    #[error("InsufficientStartUtxo")]
+    InsufficientStartUtxo,
+    /// InvalidWitness is returned when a witness does not satisfy the script of the output it spends, contains the reason
+    #[error("InvalidWitness: {0}")]
+    InvalidWitness(String),
+    /// UnsupportedScript is returned when a script uses an opcode verify_taproot_witness does not interpret, so it can not tell whether the spend is valid, contains the opcode
+    #[error("UnsupportedScript: {0}")]
+    UnsupportedScript(String),
 }
 
 impl From<secp256k1::Error> for BridgeError {
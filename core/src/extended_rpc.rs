@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use bitcoin::Address;
 use bitcoin::Amount;
 use bitcoin::OutPoint;
@@ -14,6 +16,12 @@ use crypto_bigint::U256;
 
 use crate::errors::BridgeError;
 
+/// bitcoind's error code for "No such mempool or blockchain transaction"
+pub const RPC_INVALID_ADDRESS_OR_KEY: i32 = -5;
+
+/// How often wait_for_confirmation and wait_for_utxo poll bitcoind
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 #[derive(Debug)]
 pub struct ExtendedRpc {
     pub inner: Client,
@@ -59,26 +67,89 @@ impl ExtendedRpc {
             .ok_or(BridgeError::NoConfirmationData)
     }
 
-    pub fn check_utxo_address_and_amount(
+    /// Returns the height of the block that confirms `txid` and the index of the tx in it,
+    /// which orders transactions as the chain does
+    pub fn get_tx_chain_position(&self, txid: &bitcoin::Txid) -> Result<(u64, usize), BridgeError> {
+        let block_hash = self
+            .inner
+            .get_raw_transaction_info(txid, None)?
+            .blockhash
+            .ok_or(BridgeError::NoConfirmationData)?;
+        let height = self.inner.get_block_header_info(&block_hash)?.height as u64;
+        let tx_index = self
+            .inner
+            .get_block(&block_hash)?
+            .txdata
+            .iter()
+            .position(|tx| tx.txid() == *txid)
+            .ok_or(BridgeError::NoConfirmationData)?;
+        Ok((height, tx_index))
+    }
+
+    /// Polls until `txid` has at least `confirmations` confirmations and returns its
+    /// confirmation count, or fails with ConfirmationTimeout after `timeout`
+    pub fn wait_for_confirmation(
+        &self,
+        txid: bitcoin::Txid,
+        confirmations: u32,
+        timeout: Duration,
+    ) -> Result<u32, BridgeError> {
+        let start = Instant::now();
+        loop {
+            let current = match self.confirmation_blocks(&txid) {
+                Ok(current) => current,
+                // In the mempool
+                Err(BridgeError::NoConfirmationData) => 0,
+                Err(e) => return Err(e),
+            };
+            if current >= confirmations {
+                return Ok(current);
+            }
+            if start.elapsed() >= timeout {
+                return Err(BridgeError::ConfirmationTimeout);
+            }
+            std::thread::sleep(CONFIRMATION_POLL_INTERVAL.min(timeout));
+        }
+    }
+
+    /// Polls until `outpoint` is an unspent output in the chain or the mempool and returns it,
+    /// or fails with ConfirmationTimeout after `timeout`
+    pub fn wait_for_utxo(
         &self,
         outpoint: &OutPoint,
-        address: &ScriptBuf,
-        amount_sats: u64,
-    ) -> Result<bool, BridgeError> {
-        let tx = self.inner.get_raw_transaction(&outpoint.txid, None)?;
-        let current_output = tx.output[outpoint.vout as usize].clone();
-        let expected_output = TxOut {
-            script_pubkey: address.clone(),
-            value: Amount::from_sat(amount_sats),
-        };
-        Ok(expected_output == current_output)
+        timeout: Duration,
+    ) -> Result<TxOut, BridgeError> {
+        let start = Instant::now();
+        loop {
+            if let Some(tx_out) = self.get_utxo(outpoint, true)? {
+                return Ok(tx_out);
+            }
+            if start.elapsed() >= timeout {
+                return Err(BridgeError::ConfirmationTimeout);
+            }
+            std::thread::sleep(CONFIRMATION_POLL_INTERVAL.min(timeout));
+        }
     }
 
-    pub fn is_utxo_spent(&self, _outpoint: &OutPoint) -> Result<bool, BridgeError> {
+    pub fn is_utxo_spent(&self, outpoint: &OutPoint) -> Result<bool, BridgeError> {
+        Ok(self.get_utxo(outpoint, true)?.is_none())
+    }
+
+    /// Returns the output of an unspent outpoint, None if it is spent or does not exist. With
+    /// `include_mempool`, outputs spent in the mempool count as spent and outputs of mempool
+    /// transactions are found.
+    pub fn get_utxo(
+        &self,
+        outpoint: &OutPoint,
+        include_mempool: bool,
+    ) -> Result<Option<TxOut>, BridgeError> {
         let res = self
             .inner
-            .get_tx_out(&_outpoint.txid, _outpoint.vout, Some(true))?;
-        Ok(res.is_none())
+            .get_tx_out(&outpoint.txid, outpoint.vout, Some(include_mempool))?;
+        Ok(res.map(|tx_out| TxOut {
+            value: tx_out.value,
+            script_pubkey: ScriptBuf::from(tx_out.script_pub_key.hex),
+        }))
     }
 
     pub fn generate_dummy_block(&self) -> Result<Vec<bitcoin::BlockHash>, BridgeError> {
@@ -120,6 +191,62 @@ impl ExtendedRpc {
         Ok(OutPoint { txid, vout })
     }
 
+    /// Funds and signs a transaction paying `amount_sats` to `address` from the wallet without
+    /// broadcasting it, returns the transaction and its output paying to `address`. The wallet
+    /// does not lock the inputs, so nothing is spent until the transaction is sent.
+    pub fn create_funded_tx(
+        &self,
+        address: &Address,
+        amount_sats: u64,
+    ) -> Result<(Transaction, OutPoint), BridgeError> {
+        let script_pubkey = address.script_pubkey();
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                value: Amount::from_sat(amount_sats),
+                script_pubkey: script_pubkey.clone(),
+            }],
+        };
+        // Without inputs the serialization would be read as a segwit marker
+        let funded = self.inner.fund_raw_transaction(&tx, None, Some(false))?;
+        let signed = self
+            .inner
+            .sign_raw_transaction_with_wallet(&funded.hex, None, None)?;
+        if !signed.complete {
+            return Err(BridgeError::BitcoinTransactionError);
+        }
+        let tx = signed
+            .transaction()
+            .map_err(|_| BridgeError::BitcoinTransactionError)?;
+        let vout = tx
+            .output
+            .iter()
+            .position(|tx_out| tx_out.script_pubkey == script_pubkey)
+            .ok_or(BridgeError::TxOutputNotFound)?;
+        let txid = tx.txid();
+        Ok((
+            tx,
+            OutPoint {
+                txid,
+                vout: vout as u32,
+            },
+        ))
+    }
+
+    /// Sets bitcoind's clock to `timestamp`, 0 goes back to the system clock. Regtest only
+    pub fn set_mock_time(&self, timestamp: u64) -> Result<(), BridgeError> {
+        self.inner
+            .call::<()>("setmocktime", &[serde_json::json!(timestamp)])?;
+        Ok(())
+    }
+
+    /// Returns the trusted balance of the wallet
+    pub fn get_balance(&self) -> Result<Amount, BridgeError> {
+        Ok(self.inner.get_balance(None, None)?)
+    }
+
     pub fn get_work_at_block(&self, blockheight: u64) -> Result<Work, BridgeError> {
         let block_hash = self.get_block_hash(blockheight)?;
         let block = self.inner.get_block(&block_hash)?;
@@ -140,6 +267,11 @@ impl ExtendedRpc {
         Ok(block_header)
     }
 
+    /// Returns the height of the block with `block_hash`
+    pub fn get_block_height_of(&self, block_hash: &bitcoin::BlockHash) -> Result<u64, BridgeError> {
+        Ok(self.inner.get_block_header_info(block_hash)?.height as u64)
+    }
+
     pub fn calculate_total_work_between_blocks(
         &self,
         start: u64,
@@ -216,6 +348,18 @@ impl ExtendedRpc {
         self.inner.send_raw_transaction(tx)
     }
 
+    /// Checks that the node would accept `tx` into its mempool without broadcasting it,
+    /// a rejection carries bitcoind's reason, e.g. "min relay fee not met"
+    pub fn test_accept(&self, tx: &Transaction) -> Result<(), BridgeError> {
+        let results = self.inner.test_mempool_accept(&[tx])?;
+        match results.into_iter().find(|result| !result.allowed) {
+            Some(result) => Err(BridgeError::TxRejected(
+                result.reject_reason.unwrap_or_default(),
+            )),
+            None => Ok(()),
+        }
+    }
+
     pub fn get_block(
         &self,
         block_hash: &bitcoin::BlockHash,
@@ -230,3 +374,123 @@ impl ExtendedRpc {
         self.inner.get_raw_transaction_info(txid, block_hash)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::Actor;
+    use secp256k1::rand::rngs::StdRng;
+    use secp256k1::rand::SeedableRng;
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_wait_for_confirmation() {
+        let rpc = ExtendedRpc::new();
+        let address = rpc
+            .inner
+            .get_new_address(None, None)
+            .unwrap()
+            .assume_checked();
+        let utxo = rpc.send_to_address(&address, 10_000).unwrap();
+
+        let miner = rpc.clone();
+        let mining = std::thread::spawn(move || {
+            for _ in 0..3 {
+                std::thread::sleep(Duration::from_millis(300));
+                miner.mine_blocks(1).unwrap();
+            }
+        });
+        let confirmations = rpc
+            .wait_for_confirmation(utxo.txid, 3, Duration::from_secs(30))
+            .unwrap();
+        mining.join().unwrap();
+        assert!(confirmations >= 3);
+
+        assert_eq!(
+            rpc.wait_for_confirmation(utxo.txid, confirmations + 100, Duration::from_millis(500)),
+            Err(BridgeError::ConfirmationTimeout)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_wait_for_utxo() {
+        let rpc = ExtendedRpc::new();
+        let address = rpc
+            .inner
+            .get_new_address(None, None)
+            .unwrap()
+            .assume_checked();
+        let utxo = rpc.send_to_address(&address, 10_000).unwrap();
+        // Found in the mempool without a confirmation
+        let tx_out = rpc.wait_for_utxo(&utxo, Duration::from_secs(10)).unwrap();
+        assert_eq!(tx_out.value, Amount::from_sat(10_000));
+
+        let missing = OutPoint {
+            txid: utxo.txid,
+            vout: 100,
+        };
+        let start = Instant::now();
+        assert_eq!(
+            rpc.wait_for_utxo(&missing, Duration::from_millis(500)),
+            Err(BridgeError::ConfirmationTimeout)
+        );
+        assert!(start.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_get_utxo() {
+        let rpc = ExtendedRpc::new();
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let actor = Actor::new(secp.generate_keypair(&mut StdRng::from_seed([5u8; 32])).0);
+        let value = Amount::from_sat(10_000);
+        let utxo = rpc.send_to_address(&actor.address, value.to_sat()).unwrap();
+        let expected = TxOut {
+            value,
+            script_pubkey: actor.address.script_pubkey(),
+        };
+        // Unconfirmed outputs are only found in the mempool
+        assert_eq!(rpc.get_utxo(&utxo, false).unwrap(), None);
+        assert_eq!(rpc.get_utxo(&utxo, true).unwrap(), Some(expected.clone()));
+        rpc.mine_blocks(1).unwrap();
+        assert_eq!(rpc.get_utxo(&utxo, false).unwrap(), Some(expected.clone()));
+        assert_eq!(
+            rpc.get_utxo(
+                &OutPoint {
+                    txid: utxo.txid,
+                    vout: 100
+                },
+                true
+            )
+            .unwrap(),
+            None
+        );
+
+        let mut spend_tx = Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: utxo,
+                script_sig: ScriptBuf::new(),
+                sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: bitcoin::Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: value - Amount::from_sat(1_000),
+                script_pubkey: actor.address.script_pubkey(),
+            }],
+        };
+        let sig = actor
+            .sign_taproot_pubkey_spend_tx(&mut spend_tx, &vec![expected.clone()], 0)
+            .unwrap();
+        spend_tx.input[0].witness.push(sig.as_ref());
+        rpc.send_raw_transaction(&spend_tx).unwrap();
+        // Spent in the mempool, but not in the chain yet
+        assert_eq!(rpc.get_utxo(&utxo, true).unwrap(), None);
+        assert_eq!(rpc.get_utxo(&utxo, false).unwrap(), Some(expected));
+        rpc.mine_blocks(1).unwrap();
+        assert_eq!(rpc.get_utxo(&utxo, false).unwrap(), None);
+        assert!(rpc.is_utxo_spent(&utxo).unwrap());
+    }
+}
@@ -0,0 +1,234 @@
+//! Evidence a verifier collects against the operator.
+use std::collections::HashSet;
+
+use clementine_circuits::constants::CLAIM_MERKLE_TREE_DEPTH;
+use clementine_circuits::{sha256_hash, HashType, PreimageType};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::bit_commitment::{verify_opening, BitCommitmentHashes};
+use crate::errors::BridgeError;
+use crate::merkle::{root_from_path, MerkleTree};
+use crate::utils::get_claim_reveal_indices;
+use crate::{ConnectorTreeLeaf, HashTree};
+
+/// The operator did not reveal every connector tree preimage its claims of a period require,
+/// so leaves of withdrawals it never paid can not be burned and it can claim their deposits.
+///
+/// The proof opens the claim proof leaf of `actual_claims` in the claim root the operator
+/// committed to: the leaf is the hash of `required_hashes`, the connector tree hashes whose
+/// preimages had to be revealed. Some of them have no preimage in `observed_reveals`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FraudProof {
+    pub period: usize,
+    /// Number of withdrawals the operator actually paid in the period
+    pub actual_claims: u32,
+    /// Claim proof merkle root the operator committed to for the period
+    pub claim_root: HashType,
+    /// Hashes of the nodes get_claim_reveal_indices returns for `actual_claims`, in its order
+    pub required_hashes: Vec<HashType>,
+    /// Merkle path of the claim proof leaf at index `actual_claims`
+    pub claim_path: [HashType; CLAIM_MERKLE_TREE_DEPTH],
+    /// Preimages revealed in the period, sorted
+    pub observed_reveals: Vec<PreimageType>,
+    /// Nodes that have to be revealed for `actual_claims` claims but were not
+    pub unrevealed_nodes: Vec<ConnectorTreeLeaf>,
+}
+
+impl FraudProof {
+    /// Builds the proof from the connector tree hashes and claim proof merkle tree of
+    /// `period`, None if `revealed` holds every required preimage
+    pub fn new(
+        period: usize,
+        hash_tree: &HashTree,
+        claim_tree: &MerkleTree<CLAIM_MERKLE_TREE_DEPTH>,
+        revealed: &HashSet<PreimageType>,
+        actual_claims: u32,
+    ) -> Option<Self> {
+        let depth = hash_tree.len().checked_sub(1)?;
+        let required_nodes = get_claim_reveal_indices(depth, actual_claims).ok()?;
+        let revealed_hashes = revealed
+            .iter()
+            .map(|preimage| sha256_hash!(preimage))
+            .collect::<HashSet<_>>();
+        let unrevealed_nodes = required_nodes
+            .iter()
+            .filter(|node| !revealed_hashes.contains(&hash_tree[node.level][node.index]))
+            .copied()
+            .collect::<Vec<_>>();
+        if unrevealed_nodes.is_empty() {
+            return None;
+        }
+        let mut observed_reveals = revealed.iter().copied().collect::<Vec<_>>();
+        observed_reveals.sort_unstable();
+        Some(FraudProof {
+            period,
+            actual_claims,
+            claim_root: claim_tree.root(),
+            required_hashes: required_nodes
+                .iter()
+                .map(|node| hash_tree[node.level][node.index])
+                .collect(),
+            claim_path: claim_tree.path(actual_claims),
+            observed_reveals,
+            unrevealed_nodes,
+        })
+    }
+
+    /// Checks the proof against itself for a connector tree of `depth`: the required hashes
+    /// open `claim_root` at `actual_claims`, and exactly `unrevealed_nodes` of them have no
+    /// observed preimage, at least one. Whether `claim_root` is the operator's commitment is
+    /// up to the caller.
+    pub fn validate(&self, depth: usize) -> Result<(), BridgeError> {
+        let required_nodes = get_claim_reveal_indices(depth, self.actual_claims)
+            .map_err(|_| BridgeError::InvalidFraudProof)?;
+        if required_nodes.len() != self.required_hashes.len() {
+            return Err(BridgeError::InvalidFraudProof);
+        }
+
+        let mut hasher = Sha256::new();
+        for hash in self.required_hashes.iter() {
+            hasher.update(hash);
+        }
+        let claim_leaf: HashType = hasher.finalize().into();
+        if root_from_path(claim_leaf, self.actual_claims, &self.claim_path) != self.claim_root {
+            return Err(BridgeError::InvalidFraudProof);
+        }
+
+        let observed_hashes = self
+            .observed_reveals
+            .iter()
+            .map(|preimage| sha256_hash!(preimage))
+            .collect::<HashSet<_>>();
+        let unrevealed_nodes = required_nodes
+            .into_iter()
+            .zip(self.required_hashes.iter())
+            .filter(|(_, hash)| !observed_hashes.contains(*hash))
+            .map(|(node, _)| node)
+            .collect::<Vec<_>>();
+        if unrevealed_nodes.is_empty() || unrevealed_nodes != self.unrevealed_nodes {
+            return Err(BridgeError::InvalidFraudProof);
+        }
+        Ok(())
+    }
+}
+
+/// The operator's answer to a verifier challenging step `step` of the bitVM instance of a
+/// period: the opening of the step's bit commitment. Step `s` commits to bit `s` of the claim
+/// proof merkle root the operator asserts for the period, see bit_commitment::hash_bit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChallengeResponse {
+    pub period: usize,
+    pub step: u32,
+    pub bit: bool,
+    /// The committed hash the preimage opens
+    pub hash: HashType,
+    pub preimage: PreimageType,
+}
+
+impl ChallengeResponse {
+    /// Whether the response opens `bit` with the commitment of its step in `commitments`, the
+    /// published bit commitment hashes of its period's instance
+    pub fn opens(&self, commitments: &[BitCommitmentHashes]) -> bool {
+        commitments
+            .get(self.step as usize)
+            .and_then(|hashes| verify_opening(hashes, &self.preimage))
+            == Some(self.bit)
+            && sha256_hash!(self.preimage) == self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::get_claim_proof_tree_leaf;
+
+    const DEPTH: usize = 2;
+
+    /// Preimages and hashes of a depth 2 connector tree and its claim proof merkle tree
+    fn create_tree() -> (
+        Vec<Vec<PreimageType>>,
+        HashTree,
+        MerkleTree<CLAIM_MERKLE_TREE_DEPTH>,
+    ) {
+        let preimages = (0..=DEPTH as u8)
+            .map(|level| {
+                (0..1u8 << level)
+                    .map(|index| [level * 4 + index + 1; 32])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let hash_tree = preimages
+            .iter()
+            .map(|level| {
+                level
+                    .iter()
+                    .map(|preimage| sha256_hash!(preimage))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<HashTree>();
+        let mut claim_tree = MerkleTree::new();
+        for claims in 0..1 << DEPTH {
+            claim_tree.add(get_claim_proof_tree_leaf(DEPTH, claims, &hash_tree).unwrap());
+        }
+        (preimages, hash_tree, claim_tree)
+    }
+
+    fn reveal(preimages: &[Vec<PreimageType>], claims: u32) -> HashSet<PreimageType> {
+        get_claim_reveal_indices(DEPTH, claims)
+            .unwrap()
+            .iter()
+            .map(|node| preimages[node.level][node.index])
+            .collect()
+    }
+
+    #[test]
+    fn test_fraud_proof() {
+        let (preimages, hash_tree, claim_tree) = create_tree();
+        assert_eq!(
+            FraudProof::new(0, &hash_tree, &claim_tree, &reveal(&preimages, 1), 1),
+            None
+        );
+
+        // Revealed for 3 claims, paid 1
+        let proof = FraudProof::new(0, &hash_tree, &claim_tree, &reveal(&preimages, 3), 1).unwrap();
+        assert_eq!(proof.claim_root, claim_tree.root());
+        assert_eq!(
+            proof.unrevealed_nodes,
+            vec![
+                ConnectorTreeLeaf { level: 2, index: 1 },
+                ConnectorTreeLeaf { level: 1, index: 1 },
+            ]
+        );
+        assert_eq!(proof.validate(DEPTH), Ok(()));
+
+        let json = serde_json::to_string(&proof).unwrap();
+        let deserialized = serde_json::from_str::<FraudProof>(&json).unwrap();
+        assert_eq!(deserialized, proof);
+        assert_eq!(deserialized.validate(DEPTH), Ok(()));
+
+        // The required hashes do not open the claim root
+        let mut tampered = proof.clone();
+        tampered.required_hashes[0] = [50u8; 32];
+        assert_eq!(
+            tampered.validate(DEPTH),
+            Err(BridgeError::InvalidFraudProof)
+        );
+        // Everything required was observed after all
+        let mut tampered = proof.clone();
+        tampered
+            .observed_reveals
+            .extend(reveal(&preimages, 1).into_iter());
+        assert_eq!(
+            tampered.validate(DEPTH),
+            Err(BridgeError::InvalidFraudProof)
+        );
+        // A claim count the tree can not hold
+        let mut tampered = proof;
+        tampered.actual_claims = 5;
+        assert_eq!(
+            tampered.validate(DEPTH),
+            Err(BridgeError::InvalidFraudProof)
+        );
+    }
+}
@@ -1,29 +1,59 @@
-use bitcoin::{OutPoint, Txid};
+use bitcoin::{OutPoint, Transaction, Txid};
 use clementine_circuits::{HashType, PreimageType};
+use serde::{Deserialize, Serialize};
 
 pub mod actor;
+pub mod bit_commitment;
 pub mod constants;
 pub mod env_writer;
 pub mod errors;
 pub mod extended_rpc;
+pub mod fraud_proof;
+pub mod lightclient;
 pub mod merkle;
 pub mod mock_db;
 pub mod mock_env;
 pub mod operator;
 pub mod script_builder;
+#[cfg(test)]
+mod test_utils;
 pub mod traits;
 pub mod transaction_builder;
 pub mod user;
 pub mod utils;
 pub mod verifier;
 
+/// Prints tracing events to the test harness's stdout, filtered by `RUST_LOG`. Installing a
+/// subscriber twice is a no-op, so every test may call this.
+#[cfg(feature = "stdout-logs")]
+pub fn init_stdout_logs() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_test_writer()
+        .try_init();
+}
+
 pub type ConnectorUTXOTree = Vec<Vec<OutPoint>>;
 pub type HashTree = Vec<Vec<HashType>>;
 pub type PreimageTree = Vec<Vec<PreimageType>>;
 pub type InscriptionTxs = (OutPoint, Txid);
 
+/// Type alias for a deposit utxo and the signed move tx spending it
+pub type DepositMoveTx = (OutPoint, Transaction);
+
+/// A node of a connector tree, identified by its level (the root is level 0) and its index
+/// within that level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectorTreeLeaf {
+    pub level: usize,
+    pub index: usize,
+}
+
 /// Type alias for EVM address
 pub type EVMAddress = [u8; 20];
 
+/// Type alias for a recoverable ECDSA signature in the `r || s || v` form EVM's ecrecover takes
+pub type EVMSignature = [u8; 65];
+
 /// Type alias for withdrawal payment, HashType is taproot script hash
 pub type WithdrawalPayment = (Txid, HashType);
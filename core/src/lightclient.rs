@@ -0,0 +1,383 @@
+use bitcoin::block::Header;
+use bitcoin::blockdata::constants::genesis_block;
+use bitcoin::consensus::Params;
+use bitcoin::hashes::Hash;
+use bitcoin::pow::{CompactTarget, Target, Work};
+use bitcoin::Network;
+use clementine_circuits::constants::{
+    LC_CHECKPOINT_BLOCKHASH, LC_CHECKPOINT_CHAIN_WORK, LC_CHECKPOINT_HEIGHT, LC_GENESIS_BLOCKHASH,
+};
+use crypto_bigint::{Encoding, U256};
+
+use crate::errors::BridgeError;
+
+/// A block light client proofs start from instead of the genesis block, so a proof only carries
+/// the headers after it. Its height is a multiple of the difficulty adjustment interval, so the
+/// difficulty of the blocks after it only depends on the headers from it on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LcCheckpoint {
+    pub height: u32,
+    pub header: Header,
+    /// Accumulated work of the chain up to and including the checkpoint
+    pub chain_work: Work,
+}
+
+impl LcCheckpoint {
+    /// The genesis block of `network`
+    pub fn genesis(network: Network) -> Self {
+        let header = genesis_block(network).header;
+        LcCheckpoint {
+            height: 0,
+            header,
+            chain_work: header.work(),
+        }
+    }
+
+    /// The checkpoint the circuit is compiled with, from `header`, the header of
+    /// LC_CHECKPOINT_BLOCKHASH. Fails with UnsupportedNetwork for the header of another block.
+    pub fn circuit(header: Header) -> Result<Self, BridgeError> {
+        if header.block_hash().to_byte_array() != LC_CHECKPOINT_BLOCKHASH {
+            return Err(BridgeError::UnsupportedNetwork);
+        }
+        Ok(LcCheckpoint {
+            height: LC_CHECKPOINT_HEIGHT,
+            header,
+            chain_work: Work::from_le_bytes(LC_CHECKPOINT_CHAIN_WORK),
+        })
+    }
+}
+
+/// Fails with UnsupportedNetwork unless `network` is the one the circuit is compiled for, the
+/// circuit only follows the difficulty rules of that network
+pub fn check_circuit_network(network: Network) -> Result<(), BridgeError> {
+    if genesis_block(network).block_hash().to_byte_array() != LC_GENESIS_BLOCKHASH {
+        return Err(BridgeError::UnsupportedNetwork);
+    }
+    Ok(())
+}
+
+/// Light client proof of a header chain ending at the light client's blockhash,
+/// together with the withdrawal merkle root the light client committed to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LcProof {
+    /// Network whose difficulty rules the headers follow
+    pub network: Network,
+    /// Block the headers build on
+    pub checkpoint: LcCheckpoint,
+    /// Consecutive block headers starting right after the checkpoint, each one building on the
+    /// previous one
+    pub block_headers: Vec<Header>,
+    /// Accumulated work of the chain, the checkpoint's chain work included
+    pub chain_work: Work,
+    pub withdrawal_mt_root: [u8; 32],
+}
+
+impl LcProof {
+    /// Creates the proof of `block_headers` on top of `checkpoint`, adding up their chain work
+    pub fn new(
+        network: Network,
+        checkpoint: LcCheckpoint,
+        block_headers: Vec<Header>,
+        withdrawal_mt_root: [u8; 32],
+    ) -> Self {
+        let chain_work = block_headers
+            .iter()
+            .fold(checkpoint.chain_work, |work, header| work + header.work());
+        LcProof {
+            network,
+            checkpoint,
+            block_headers,
+            chain_work,
+            withdrawal_mt_root,
+        }
+    }
+}
+
+/// Verifies that the proof starts at the trusted `checkpoint`, and that its headers form a chain
+/// from it that follows the difficulty rules, has valid proof of work, adds up to `chain_work` and
+/// ends at `expected_blockhash`, and that the proof commits to `withdrawal_mt_root`
+pub fn verify_proof(
+    proof: &LcProof,
+    checkpoint: &LcCheckpoint,
+    expected_blockhash: [u8; 32],
+    withdrawal_mt_root: [u8; 32],
+) -> Result<(), BridgeError> {
+    let params = Params::new(proof.network);
+    if proof.checkpoint != *checkpoint
+        || !(checkpoint.height as u64).is_multiple_of(params.difficulty_adjustment_interval())
+    {
+        return Err(BridgeError::InvalidLcProof);
+    }
+    let last_header = proof.block_headers.last().unwrap_or(&checkpoint.header);
+    // ancestors[i] is the header at the checkpoint's height + i. The checkpoint starts a
+    // difficulty adjustment interval, so the position in it is the same as by height.
+    let mut ancestors = vec![checkpoint.header];
+    let mut chain_work = checkpoint.chain_work;
+    for header in proof.block_headers.iter() {
+        let prev = ancestors.last().unwrap();
+        if header.prev_blockhash != prev.block_hash()
+            || header.bits != next_work_required(&params, &ancestors, header)
+        {
+            return Err(BridgeError::InvalidLcProof);
+        }
+        header
+            .validate_pow(header.target())
+            .map_err(|_| BridgeError::InvalidLcProof)?;
+        chain_work = chain_work + header.work();
+        ancestors.push(*header);
+    }
+    if last_header.block_hash().to_byte_array() != expected_blockhash
+        || proof.chain_work != chain_work
+        || proof.withdrawal_mt_root != withdrawal_mt_root
+    {
+        return Err(BridgeError::InvalidLcProof);
+    }
+    Ok(())
+}
+
+/// Returns the bits `header` must have on top of `ancestors`, following Bitcoin Core's
+/// `GetNextWorkRequired`. The first ancestor has to start a difficulty adjustment interval.
+fn next_work_required(params: &Params, ancestors: &[Header], header: &Header) -> CompactTarget {
+    let last = ancestors.last().unwrap();
+    let height = ancestors.len() as u64;
+    let interval = params.difficulty_adjustment_interval();
+    if !height.is_multiple_of(interval) {
+        if params.allow_min_difficulty_blocks {
+            let pow_limit_bits = params.pow_limit.to_compact_lossy();
+            // A block more than twice the target spacing after the last one may use the minimum
+            // difficulty, otherwise the last difficulty not set by this rule applies
+            if header.time as u64 > last.time as u64 + params.pow_target_spacing * 2 {
+                return pow_limit_bits;
+            }
+            let mut i = ancestors.len() - 1;
+            while !(i as u64).is_multiple_of(interval) && ancestors[i].bits == pow_limit_bits {
+                i -= 1;
+            }
+            return ancestors[i].bits;
+        }
+        return last.bits;
+    }
+    if params.no_pow_retargeting {
+        return last.bits;
+    }
+    let first = &ancestors[ancestors.len() - interval as usize];
+    retarget(params, last.bits, first.time, last.time)
+}
+
+/// Scales the target of `last_bits` by the time the last difficulty period took, limited to a
+/// factor of four in either direction and to the network's proof of work limit
+fn retarget(
+    params: &Params,
+    last_bits: CompactTarget,
+    first_time: u32,
+    last_time: u32,
+) -> CompactTarget {
+    let target_timespan = params.pow_target_timespan as i64;
+    let timespan =
+        (last_time as i64 - first_time as i64).clamp(target_timespan / 4, target_timespan * 4);
+    let target = U256::from_le_bytes(Target::from_compact(last_bits).to_le_bytes())
+        .wrapping_mul(&U256::from_u64(timespan as u64))
+        .wrapping_div(&U256::from_u64(target_timespan as u64));
+    Target::from_le_bytes(target.to_le_bytes())
+        .min(params.pow_limit)
+        .to_compact_lossy()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus::deserialize;
+
+    fn create_test_proof() -> LcProof {
+        let mainnet_first_11_blocks =
+            include_bytes!("../tests/data/mainnet_first_11_blocks.raw").to_vec();
+        let block_headers: Vec<Header> = deserialize(&mainnet_first_11_blocks).unwrap();
+        LcProof {
+            network: Network::Bitcoin,
+            checkpoint: LcCheckpoint::genesis(Network::Bitcoin),
+            chain_work: total_work(&block_headers),
+            block_headers,
+            withdrawal_mt_root: [1u8; 32],
+        }
+    }
+
+    fn total_work(block_headers: &[Header]) -> Work {
+        block_headers.iter().fold(
+            genesis_block(Network::Bitcoin).header.work(),
+            |work, header| work + header.work(),
+        )
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let proof = create_test_proof();
+        let checkpoint = LcCheckpoint::genesis(Network::Bitcoin);
+        let blockhash = proof
+            .block_headers
+            .last()
+            .unwrap()
+            .block_hash()
+            .to_byte_array();
+        assert_eq!(
+            verify_proof(&proof, &checkpoint, blockhash, [1u8; 32]),
+            Ok(())
+        );
+
+        // Every block of the first difficulty period has 0x100010001 work
+        let mut chain_work = [0u8; 32];
+        chain_work[24..].copy_from_slice(&(12 * 0x1_0001_0001u64).to_be_bytes());
+        assert_eq!(proof.chain_work, Work::from_be_bytes(chain_work));
+
+        // Wrong blockhash or withdrawal merkle root
+        assert_eq!(
+            verify_proof(
+                &proof,
+                &checkpoint,
+                proof.block_headers[0].block_hash().to_byte_array(),
+                [1u8; 32]
+            ),
+            Err(BridgeError::InvalidLcProof)
+        );
+        assert_eq!(
+            verify_proof(&proof, &checkpoint, blockhash, [2u8; 32]),
+            Err(BridgeError::InvalidLcProof)
+        );
+
+        // Wrong chain work
+        let mut overworked_proof = proof.clone();
+        overworked_proof.chain_work = overworked_proof.chain_work + proof.block_headers[0].work();
+        assert_eq!(
+            verify_proof(&overworked_proof, &checkpoint, blockhash, [1u8; 32]),
+            Err(BridgeError::InvalidLcProof)
+        );
+
+        // A proof from another checkpoint than the trusted one
+        let mut other_checkpoint_proof = proof.clone();
+        other_checkpoint_proof.checkpoint.height = 2016;
+        assert_eq!(
+            verify_proof(&other_checkpoint_proof, &checkpoint, blockhash, [1u8; 32]),
+            Err(BridgeError::InvalidLcProof)
+        );
+
+        // A checkpoint inside a difficulty adjustment interval
+        let inner_checkpoint = LcCheckpoint {
+            height: 5,
+            header: proof.block_headers[4],
+            chain_work: total_work(&proof.block_headers[..5]),
+        };
+        let inner_checkpoint_proof = LcProof::new(
+            Network::Bitcoin,
+            inner_checkpoint,
+            proof.block_headers[5..].to_vec(),
+            [1u8; 32],
+        );
+        assert_eq!(inner_checkpoint_proof.chain_work, proof.chain_work);
+        assert_eq!(
+            verify_proof(
+                &inner_checkpoint_proof,
+                &inner_checkpoint,
+                blockhash,
+                [1u8; 32]
+            ),
+            Err(BridgeError::InvalidLcProof)
+        );
+
+        // Headers that do not build on the checkpoint
+        let mut unanchored_proof = proof.clone();
+        unanchored_proof.block_headers.remove(0);
+        unanchored_proof.chain_work = total_work(&unanchored_proof.block_headers);
+        assert_eq!(
+            verify_proof(&unanchored_proof, &checkpoint, blockhash, [1u8; 32]),
+            Err(BridgeError::InvalidLcProof)
+        );
+
+        // Headers that do not form a chain
+        let mut broken_proof = proof.clone();
+        broken_proof.block_headers.remove(5);
+        assert_eq!(
+            verify_proof(&broken_proof, &checkpoint, blockhash, [1u8; 32]),
+            Err(BridgeError::InvalidLcProof)
+        );
+
+        // Header without enough work
+        let mut weak_proof = proof.clone();
+        weak_proof.block_headers.last_mut().unwrap().nonce += 1;
+        let weak_blockhash = weak_proof
+            .block_headers
+            .last()
+            .unwrap()
+            .block_hash()
+            .to_byte_array();
+        assert_eq!(
+            verify_proof(&weak_proof, &checkpoint, weak_blockhash, [1u8; 32]),
+            Err(BridgeError::InvalidLcProof)
+        );
+
+        // Header that picks its own easier difficulty
+        let mut easy_proof = proof.clone();
+        let easy_header = easy_proof.block_headers.last_mut().unwrap();
+        easy_header.bits = CompactTarget::from_consensus(0x207fffff);
+        while easy_header.validate_pow(easy_header.target()).is_err() {
+            easy_header.nonce += 1;
+        }
+        let easy_blockhash = easy_header.block_hash().to_byte_array();
+        easy_proof.chain_work = total_work(&easy_proof.block_headers);
+        assert_eq!(
+            verify_proof(&easy_proof, &checkpoint, easy_blockhash, [1u8; 32]),
+            Err(BridgeError::InvalidLcProof)
+        );
+    }
+
+    #[test]
+    fn test_circuit_checkpoint() {
+        // The circuit is compiled for regtest, its checkpoint is the genesis block
+        assert_eq!(check_circuit_network(Network::Regtest), Ok(()));
+        assert_eq!(
+            check_circuit_network(Network::Bitcoin),
+            Err(BridgeError::UnsupportedNetwork)
+        );
+        assert_eq!(
+            LcCheckpoint::circuit(genesis_block(Network::Regtest).header),
+            Ok(LcCheckpoint::genesis(Network::Regtest))
+        );
+        assert_eq!(
+            LcCheckpoint::circuit(genesis_block(Network::Bitcoin).header),
+            Err(BridgeError::UnsupportedNetwork)
+        );
+
+        // Without headers the proof is of the checkpoint itself
+        let checkpoint = LcCheckpoint::genesis(Network::Regtest);
+        let proof = LcProof::new(Network::Regtest, checkpoint, Vec::new(), [1u8; 32]);
+        assert_eq!(
+            verify_proof(
+                &proof,
+                &checkpoint,
+                checkpoint.header.block_hash().to_byte_array(),
+                [1u8; 32]
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_retarget() {
+        // Bitcoin Core's get_next_work test vectors
+        let params = Params::new(Network::Bitcoin);
+        for (first_time, last_time, last_bits, bits) in [
+            (1261130161, 1262152739, 0x1d00ffff, 0x1d00d86a),
+            (1231006505, 1233061996, 0x1d00ffff, 0x1d00ffff),
+            (1279008237, 1279297671, 0x1c05a3f4, 0x1c0168fd),
+            (1263163443, 1269211443, 0x1c387f6f, 0x1d00e1fd),
+        ] {
+            assert_eq!(
+                retarget(
+                    &params,
+                    CompactTarget::from_consensus(last_bits),
+                    first_time,
+                    last_time
+                ),
+                CompactTarget::from_consensus(bits)
+            );
+        }
+    }
+}
@@ -1,8 +1,14 @@
 use clementine_circuits::bridge::bridge_proof;
-use clementine_circuits::constants::{MAX_BLOCK_HANDLE_OPS, NUM_ROUNDS};
-use clementine_core::constants::{NUM_USERS, NUM_VERIFIERS, PERIOD_BLOCK_COUNT};
+use clementine_circuits::constants::{
+    MAX_BLOCK_HANDLE_OPS, NUM_ROUNDS, WITHDRAWAL_MERKLE_TREE_DEPTH,
+};
+use clementine_core::constants::{
+    CONNECTOR_TREE_DEPTH, NUM_USERS, NUM_VERIFIERS, PERIOD_BLOCK_COUNT,
+};
 use clementine_core::errors::BridgeError;
+use clementine_core::merkle::MerkleTree;
 use clementine_core::mock_env::MockEnvironment;
+use clementine_core::operator::{DepositRequest, WithdrawalProof};
 use clementine_core::traits::verifier::VerifierConnector;
 use clementine_core::verifier::Verifier;
 use clementine_core::EVMAddress;
@@ -41,11 +47,16 @@ fn test_flow() -> Result<(), BridgeError> {
         verifiers.push(Box::new(verifier) as Box<dyn VerifierConnector>);
     }
 
+    let verifier_evm_addresses: Vec<EVMAddress> =
+        (0..NUM_VERIFIERS).map(|i| [i as u8 + 1; 20]).collect();
+
     let mut operator = Operator::new(
         rpc.clone(),
         all_xonly_pks.clone(),
         all_sks[NUM_VERIFIERS],
         verifiers,
+        verifier_evm_addresses,
+        CONNECTOR_TREE_DEPTH,
     )?;
 
     let users: Vec<_> = (0..NUM_USERS)
@@ -79,27 +90,39 @@ fn test_flow() -> Result<(), BridgeError> {
     // tracing::debug!("connector roots created, verifiers agree");
     // In the end, create BitVM
 
+    // Stands in for the rollup's withdrawals merkle tree, its root is what the light client
+    // would hand to the operator
+    let mut rollup_withdrawals = MerkleTree::<WITHDRAWAL_MERKLE_TREE_DEPTH>::new();
+
     for current_period in 0..NUM_ROUNDS {
         tracing::debug!("Current period: {}", current_period);
-        // every user makes a deposit.
-        for i in 0..NUM_USERS {
-            let user = &users[i];
-            let evm_address: EVMAddress = [0; 20];
-            let (deposit_utxo, deposit_return_address, user_evm_address, user_sig) =
+        // every user makes a deposit, the operator moves them all at once.
+        let mut deposit_requests = Vec::new();
+        for user in users.iter() {
+            let evm_address: EVMAddress = [1; 20];
+            let (start_utxo, return_address, evm_address, user_sig) =
                 user.deposit_tx(evm_address).unwrap();
-            rpc.mine_blocks(6)?;
-            operator.new_deposit(
-                deposit_utxo,
-                &deposit_return_address,
-                &user_evm_address,
+            deposit_requests.push(DepositRequest::new(
+                start_utxo,
+                return_address,
+                evm_address,
                 user_sig,
-            )?;
-            // rpc.mine_blocks(1)?;
+            ));
+            rpc.mine_blocks(6)?;
         }
+        operator.new_deposit_batch(&deposit_requests)?;
 
         // make 3 withdrawals
         for i in 0..3 {
-            operator.new_withdrawal(users[i].signer.address.clone())?;
+            let withdrawal_address = users[i].signer.address.clone();
+            let index = rollup_withdrawals.index;
+            rollup_withdrawals.add(WithdrawalProof::leaf(&withdrawal_address)?);
+            operator.set_rollup_withdrawals_root(rollup_withdrawals.root());
+            let proof = WithdrawalProof {
+                index,
+                path: rollup_withdrawals.path(index),
+            };
+            operator.new_withdrawal(withdrawal_address, proof)?;
             // rpc.mine_blocks(1)?;
         }
 
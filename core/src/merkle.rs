@@ -2,6 +2,26 @@ use clementine_circuits::constants::{EMPTYDATA, ZEROES};
 use clementine_circuits::incremental_merkle::IncrementalMerkleTree;
 use clementine_circuits::{sha256_hash, HashType};
 
+/// Computes the root of a `DEPTH` deep tree from a leaf at `index` and its sibling path,
+/// as returned by MerkleTree::path
+pub fn root_from_path<const DEPTH: usize>(
+    leaf: HashType,
+    index: u32,
+    path: &[HashType; DEPTH],
+) -> HashType {
+    let mut i = index;
+    let mut hash = leaf;
+    for sibling in path.iter() {
+        hash = if i.is_multiple_of(2) {
+            sha256_hash!(hash, *sibling)
+        } else {
+            sha256_hash!(*sibling, hash)
+        };
+        i /= 2;
+    }
+    hash
+}
+
 #[derive(Clone, Debug)]
 pub struct MerkleTree<const DEPTH: usize> {
     data: Vec<Vec<HashType>>,
@@ -28,6 +48,9 @@ impl<const DEPTH: usize> MerkleTree<DEPTH> {
         }
     }
 
+    /// Appends a leaf. Only the nodes on the path from the new leaf to the
+    /// root are written, so an insertion costs `DEPTH + 1` hashes regardless
+    /// of how many leaves the tree already holds.
     pub fn add(&mut self, a: HashType) {
         let mut current_index = self.index;
         let mut current_level_hash = a;
@@ -65,6 +88,8 @@ impl<const DEPTH: usize> MerkleTree<DEPTH> {
         p
     }
 
+    /// Returns the current root. An empty tree has the root of `DEPTH` levels
+    /// of zero leaves, i.e. `ZEROES[DEPTH]`.
     pub fn root(&self) -> HashType {
         if self.data[DEPTH].is_empty() {
             ZEROES[DEPTH]
@@ -73,6 +98,15 @@ impl<const DEPTH: usize> MerkleTree<DEPTH> {
         }
     }
 
+    /// Number of leaves added so far.
+    pub fn len(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index == 0
+    }
+
     /// TODO: Make this more efficient
     pub fn index_of(&self, a: HashType) -> Option<u32> {
         for i in 0..self.index {
@@ -113,7 +147,24 @@ impl<const DEPTH: usize> MerkleTree<DEPTH> {
 #[cfg(test)]
 mod tests {
     use crate::merkle::MerkleTree;
+    use clementine_circuits::constants::ZEROES;
     use clementine_circuits::incremental_merkle::IncrementalMerkleTree;
+    use clementine_circuits::{sha256_hash, HashType};
+
+    /// Builds the root level by level from all leaves, padding with zero subtrees.
+    fn naive_root<const DEPTH: usize>(leaves: &[HashType]) -> HashType {
+        let mut level = leaves.to_vec();
+        for zero in ZEROES.iter().take(DEPTH) {
+            if level.len() % 2 == 1 {
+                level.push(*zero);
+            }
+            level = level
+                .chunks(2)
+                .map(|pair| sha256_hash!(pair[0], pair[1]))
+                .collect();
+        }
+        level.first().copied().unwrap_or(ZEROES[DEPTH])
+    }
 
     #[test]
     fn test_merkle_cross_check() {
@@ -137,4 +188,41 @@ mod tests {
         assert_eq!(mt.root(), contract_insert_1_root);
         assert_eq!(mt.root(), imt.root);
     }
+
+    #[test]
+    fn test_merkle_root_and_len() {
+        let mut mt = MerkleTree::<4>::new();
+        assert!(mt.is_empty());
+        assert_eq!(mt.len(), 0);
+        assert_eq!(mt.root(), ZEROES[4]);
+
+        let mut roots = vec![mt.root()];
+        for i in 0..10u8 {
+            mt.add([i; 32]);
+            assert_eq!(mt.len(), i as usize + 1);
+            assert!(!roots.contains(&mt.root()));
+            roots.push(mt.root());
+        }
+
+        let mut other = MerkleTree::<4>::new();
+        for i in 0..10u8 {
+            assert_eq!(other.root(), roots[i as usize]);
+            other.add([i; 32]);
+        }
+        assert_eq!(other.root(), roots[10]);
+    }
+
+    #[test]
+    fn test_merkle_incremental_matches_naive() {
+        let mut mt = MerkleTree::<8>::new();
+        let mut leaves = Vec::new();
+        assert_eq!(mt.root(), naive_root::<8>(&leaves));
+        for i in 0..100u32 {
+            let mut leaf = [0u8; 32];
+            leaf[..4].copy_from_slice(&i.to_be_bytes());
+            mt.add(leaf);
+            leaves.push(leaf);
+            assert_eq!(mt.root(), naive_root::<8>(&leaves));
+        }
+    }
 }
@@ -1,18 +1,26 @@
+use std::collections::HashMap;
+
+use bitcoin::Txid;
 use clementine_circuits::{
     constants::{CLAIM_MERKLE_TREE_DEPTH, WITHDRAWAL_MERKLE_TREE_DEPTH},
     HashType, PreimageType,
 };
 
 use crate::{
-    merkle::MerkleTree, operator::OperatorClaimSigs, traits::operator_db::OperatorDBConnector,
-    ConnectorUTXOTree, HashTree, InscriptionTxs, PreimageTree, WithdrawalPayment,
+    bit_commitment::BitCommitmentPreimages, merkle::MerkleTree, operator::OperatorClaimSigs,
+    traits::operator_db::OperatorDBConnector, ConnectorUTXOTree, DepositMoveTx, HashTree,
+    InscriptionTxs, PreimageTree, WithdrawalPayment,
 };
 
 #[derive(Debug, Clone)]
 pub struct OperatorMockDB {
+    deposit_index: usize,
+    /// Leaf index in the deposit merkle tree of each deposit txid
+    deposit_leaf_indices: HashMap<Txid, u32>,
     deposit_take_sigs: Vec<OperatorClaimSigs>,
     connector_tree_preimages: Vec<PreimageTree>,
     inscribed_connector_tree_preimages: Vec<Vec<PreimageType>>,
+    bit_commitments: Vec<Vec<BitCommitmentPreimages>>,
     connector_tree_hashes: Vec<HashTree>,
     claim_proof_merkle_trees: Vec<MerkleTree<CLAIM_MERKLE_TREE_DEPTH>>,
     inscription_txs: Vec<InscriptionTxs>,
@@ -21,14 +29,18 @@ pub struct OperatorMockDB {
     connector_tree_utxos: Vec<ConnectorUTXOTree>,
     start_block_height: u64,
     period_relative_block_heights: Vec<u32>,
+    move_txs: Vec<DepositMoveTx>,
 }
 
 impl OperatorMockDB {
     pub fn new() -> Self {
         Self {
+            deposit_index: 0,
+            deposit_leaf_indices: HashMap::new(),
             deposit_take_sigs: Vec::new(),
             // deposit_merkle_tree: MerkleTree::new(),
             inscribed_connector_tree_preimages: Vec::new(),
+            bit_commitments: Vec::new(),
             withdrawals_merkle_tree: MerkleTree::new(),
             withdrawals_payment_txids: Vec::new(),
             inscription_txs: Vec::new(),
@@ -36,7 +48,7 @@ impl OperatorMockDB {
             connector_tree_hashes: Vec::new(),
             claim_proof_merkle_trees: Vec::new(),
             // deposit_utxos: Vec::new(),
-            // move_utxos: Vec::new(),
+            move_txs: Vec::new(),
             connector_tree_utxos: Vec::new(),
             start_block_height: 0,
             period_relative_block_heights: Vec::new(),
@@ -45,15 +57,26 @@ impl OperatorMockDB {
 }
 impl OperatorDBConnector for OperatorMockDB {
     fn get_deposit_index(&self) -> usize {
-        self.deposit_take_sigs.len()
+        self.deposit_index
     }
 
-    // fn get_deposit_take_sigs(&self) -> Vec<OperatorClaimSigs> {
-    //     self.deposit_take_sigs.clone()
-    // }
+    fn get_deposit_take_sigs(&self) -> Vec<OperatorClaimSigs> {
+        self.deposit_take_sigs.clone()
+    }
 
     fn add_deposit_take_sigs(&mut self, deposit_take_sigs: OperatorClaimSigs) {
+        self.deposit_leaf_indices
+            .insert(deposit_take_sigs.deposit_txid, self.deposit_index as u32);
         self.deposit_take_sigs.push(deposit_take_sigs);
+        self.deposit_index += 1;
+    }
+
+    fn get_deposit_leaf_index(&self, deposit_txid: Txid) -> Option<u32> {
+        self.deposit_leaf_indices.get(&deposit_txid).copied()
+    }
+
+    fn set_deposit_take_sigs(&mut self, deposit_take_sigs: Vec<OperatorClaimSigs>) {
+        self.deposit_take_sigs = deposit_take_sigs;
     }
 
     fn get_connector_tree_preimages_level(&self, period: usize, level: usize) -> Vec<PreimageType> {
@@ -164,6 +187,29 @@ impl OperatorDBConnector for OperatorMockDB {
         self.inscribed_connector_tree_preimages[period] = preimages;
     }
     fn get_inscribed_preimages(&self, period: usize) -> Vec<PreimageType> {
-        self.inscribed_connector_tree_preimages[period].clone()
+        self.inscribed_connector_tree_preimages
+            .get(period)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn set_bit_commitments(&mut self, period: usize, bit_commitments: Vec<BitCommitmentPreimages>) {
+        while period >= self.bit_commitments.len() {
+            self.bit_commitments.push(Vec::new());
+        }
+        self.bit_commitments[period] = bit_commitments;
+    }
+    fn get_bit_commitments(&self, period: usize) -> Vec<BitCommitmentPreimages> {
+        self.bit_commitments
+            .get(period)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn add_move_tx(&mut self, move_tx: DepositMoveTx) {
+        self.move_txs.push(move_tx);
+    }
+    fn get_move_txs(&self) -> Vec<DepositMoveTx> {
+        self.move_txs.clone()
     }
 }
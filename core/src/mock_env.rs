@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::sync::RwLock;
 
 use clementine_circuits::env::Environment;
@@ -123,6 +124,86 @@ impl Environment for MockEnvironment {
     }
 }
 
+thread_local! {
+    // Bytes of the environment and the read position in them, every thread has its own buffer
+    static MEMORY: RefCell<(Vec<u8>, usize)> = const { RefCell::new((Vec::new(), 0)) };
+}
+
+/// Environment backed by an in-memory byte buffer, values are little endian encoded.
+/// Unlike MockEnvironment the buffer is thread local, so tests using it can run in parallel.
+pub struct MemoryEnvironment;
+
+impl MemoryEnvironment {
+    /// Clears the buffer and the read position
+    pub fn reset() {
+        Self::load(Vec::new());
+    }
+
+    /// Replaces the buffer with `bytes` and reads from the start of them
+    pub fn load(bytes: Vec<u8>) {
+        MEMORY.with(|memory| *memory.borrow_mut() = (bytes, 0));
+    }
+
+    /// Returns every byte written so far
+    pub fn bytes() -> Vec<u8> {
+        MEMORY.with(|memory| memory.borrow().0.clone())
+    }
+
+    fn read_bytes<const N: usize>() -> [u8; N] {
+        MEMORY.with(|memory| {
+            let (bytes, position) = &mut *memory.borrow_mut();
+            let remaining = bytes.len() - *position;
+            if remaining < N {
+                panic!(
+                    "MemoryEnvironment underrun: reading {} bytes at position {} with {} bytes left",
+                    N, position, remaining
+                );
+            }
+            let result: [u8; N] = bytes[*position..*position + N].try_into().unwrap();
+            *position += N;
+            result
+        })
+    }
+
+    fn write_bytes(data: &[u8]) {
+        MEMORY.with(|memory| memory.borrow_mut().0.extend_from_slice(data));
+    }
+}
+
+impl Environment for MemoryEnvironment {
+    fn read_32bytes() -> [u8; 32] {
+        Self::read_bytes::<32>()
+    }
+
+    fn read_u32() -> u32 {
+        u32::from_le_bytes(Self::read_bytes::<4>())
+    }
+
+    fn read_u64() -> u64 {
+        u64::from_le_bytes(Self::read_bytes::<8>())
+    }
+
+    fn read_i32() -> i32 {
+        i32::from_le_bytes(Self::read_bytes::<4>())
+    }
+
+    fn write_32bytes(data: [u8; 32]) {
+        Self::write_bytes(&data);
+    }
+
+    fn write_u32(data: u32) {
+        Self::write_bytes(&data.to_le_bytes());
+    }
+
+    fn write_u64(data: u64) {
+        Self::write_bytes(&data.to_le_bytes());
+    }
+
+    fn write_i32(data: i32) {
+        Self::write_bytes(&data.to_le_bytes());
+    }
+}
+
 pub struct RealEnvironment;
 
 impl Environment for RealEnvironment {
@@ -156,3 +237,42 @@ impl Environment for RealEnvironment {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env_writer::ENVWriter;
+    use bitcoin::block::{Header, Version};
+    use bitcoin::consensus::deserialize;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{CompactTarget, TxMerkleNode};
+
+    #[test]
+    fn test_memory_environment_header() {
+        let mainnet_first_11_blocks =
+            include_bytes!("../tests/data/mainnet_first_11_blocks.raw").to_vec();
+        let headers: Vec<Header> = deserialize(&mainnet_first_11_blocks).unwrap();
+        let header = headers[1];
+
+        MemoryEnvironment::reset();
+        ENVWriter::<MemoryEnvironment>::write_block_header_without_prev(&header);
+        assert_eq!(MemoryEnvironment::bytes().len(), 4 + 32 + 4 + 4 + 4);
+
+        let read_header = Header {
+            version: Version::from_consensus(MemoryEnvironment::read_i32()),
+            // The header is written without its previous blockhash
+            prev_blockhash: header.prev_blockhash,
+            merkle_root: TxMerkleNode::from_byte_array(MemoryEnvironment::read_32bytes()),
+            time: MemoryEnvironment::read_u32(),
+            bits: CompactTarget::from_consensus(MemoryEnvironment::read_u32()),
+            nonce: MemoryEnvironment::read_u32(),
+        };
+        assert_eq!(read_header, header);
+        assert_eq!(read_header.block_hash(), headers[2].prev_blockhash);
+
+        // Everything is read, so the next read underruns the buffer
+        assert!(std::panic::catch_unwind(MemoryEnvironment::read_u32).is_err());
+        MemoryEnvironment::load(vec![1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(MemoryEnvironment::read_u64(), 1);
+    }
+}
@@ -1,43 +1,57 @@
+use std::sync::Mutex;
+use std::time::Duration;
 use std::vec;
 
 use crate::actor::Actor;
+use crate::bit_commitment::{
+    commitment_hashes, generate_bit_commitments, hash_bit, open, BitCommitmentHashes,
+    INSTANCE_BIT_COUNT,
+};
 use crate::constants::{
-    VerifierChallenge, CONNECTOR_TREE_DEPTH, DUST_VALUE, K_DEEP,
-    MAX_BITVM_CHALLENGE_RESPONSE_BLOCKS, MIN_RELAY_FEE, PERIOD_BLOCK_COUNT,
+    VerifierChallenge, CONNECTOR_TREE_DEPTH, CONNECTOR_TREE_OPERATOR_TAKES_AFTER, DUST_VALUE,
+    K_DEEP, MAX_BITVM_CHALLENGE_RESPONSE_BLOCKS, MIN_RELAY_FEE, MIN_RELAY_FEERATE,
+    PERIOD_BLOCK_COUNT, USER_TAKES_AFTER,
 };
 use crate::env_writer::ENVWriter;
 use crate::errors::BridgeError;
-use crate::extended_rpc::ExtendedRpc;
+use crate::extended_rpc::{ExtendedRpc, RPC_INVALID_ADDRESS_OR_KEY};
+use crate::fraud_proof::{ChallengeResponse, FraudProof};
+use crate::lightclient::{check_circuit_network, LcCheckpoint, LcProof};
 
-use crate::merkle::MerkleTree;
+use crate::merkle::{root_from_path, MerkleTree};
 use crate::mock_db::OperatorMockDB;
-use crate::script_builder::ScriptBuilder;
+use crate::script_builder::{AnchorKind, ScriptBuilder};
 use crate::traits::operator_db::OperatorDBConnector;
 use crate::traits::verifier::VerifierConnector;
-use crate::transaction_builder::TransactionBuilder;
+use crate::transaction_builder::{CreateTxOutputs, TransactionBuilder, INTERNAL_KEY};
 use crate::utils::{
-    calculate_amount, check_deposit_utxo, get_claim_reveal_indices, handle_taproot_witness,
-    handle_taproot_witness_new,
+    calculate_claim_proof_root, check_deposit_utxo, check_evm_address,
+    check_witness_signature_count, get_claim_reveal_indices, handle_taproot_witness_new,
+    is_connector_leaf_revealed,
 };
-use crate::{EVMAddress, WithdrawalPayment};
+use crate::{EVMAddress, HashTree, WithdrawalPayment};
 
 use bitcoin::address::NetworkChecked;
 use bitcoin::block::Header;
 use bitcoin::hashes::Hash;
 
 use bitcoin::{secp256k1, secp256k1::schnorr, Address};
-use bitcoin::{Amount, BlockHash, OutPoint};
+use bitcoin::{Amount, BlockHash, Denomination, OutPoint, Transaction, TxOut, Txid};
 use clementine_circuits::constants::{
-    BLOCKHASH_MERKLE_TREE_DEPTH, BRIDGE_AMOUNT_SATS, CLAIM_MERKLE_TREE_DEPTH, MAX_BLOCK_HANDLE_OPS,
-    NUM_ROUNDS, WITHDRAWAL_MERKLE_TREE_DEPTH,
+    BLOCKHASH_MERKLE_TREE_DEPTH, BRIDGE_AMOUNT_SATS, CLAIM_MERKLE_TREE_DEPTH, LC_CHECKPOINT_HEIGHT,
+    MAX_BLOCK_HANDLE_OPS, NUM_ROUNDS, WITHDRAWAL_MERKLE_TREE_DEPTH,
 };
 use clementine_circuits::env::Environment;
 use clementine_circuits::{sha256_hash, HashType, PreimageType};
 use crypto_bigint::{Encoding, U256};
-use secp256k1::rand::{Rng, RngCore};
+use secp256k1::rand::{thread_rng, Rng, RngCore};
 use secp256k1::{Message, SecretKey, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
+/// How long the reveal of an inscription waits for its commit to reach the mempool
+const INSCRIPTION_COMMIT_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub fn create_connector_tree_preimages_and_hashes(
     depth: usize,
     rng: &mut impl RngCore,
@@ -82,9 +96,205 @@ pub struct DepositPresigns {
     pub operator_claim_sign: Vec<schnorr::Signature>,
 }
 
+/// A deposit as the depositor hands it to the operator, and the operator to the verifiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositRequest {
+    start_utxo: OutPoint,
+    return_address: XOnlyPublicKey,
+    evm_address: EVMAddress,
+    /// The depositor's signature over the move tx
+    user_sig: schnorr::Signature,
+}
+
+impl DepositRequest {
+    pub fn new(
+        start_utxo: OutPoint,
+        return_address: XOnlyPublicKey,
+        evm_address: EVMAddress,
+        user_sig: schnorr::Signature,
+    ) -> Self {
+        Self {
+            start_utxo,
+            return_address,
+            evm_address,
+            user_sig,
+        }
+    }
+
+    /// The deposit utxo, paying BRIDGE_AMOUNT_SATS to the deposit address
+    pub fn start_utxo(&self) -> OutPoint {
+        self.start_utxo
+    }
+
+    /// The key the deposit address lets take the deposit back after USER_TAKES_AFTER blocks
+    pub fn return_address(&self) -> &XOnlyPublicKey {
+        &self.return_address
+    }
+
+    /// The rollup address the deposit is minted to
+    pub fn evm_address(&self) -> &EVMAddress {
+        &self.evm_address
+    }
+
+    pub fn user_sig(&self) -> schnorr::Signature {
+        self.user_sig
+    }
+}
+
+/// Inclusion proof of a Withdrawal event in the rollup's withdrawals merkle tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawalProof {
+    /// Index of the withdrawal in the rollup's withdrawals merkle tree
+    pub index: u32,
+    /// Sibling path from the withdrawal leaf to the root
+    pub path: [HashType; WITHDRAWAL_MERKLE_TREE_DEPTH],
+}
+
+impl WithdrawalProof {
+    /// The leaf the rollup commits to for a withdrawal to `withdrawal_address`, the taproot
+    /// output key of the address
+    pub fn leaf(withdrawal_address: &Address) -> Result<HashType, BridgeError> {
+        let taproot_script = withdrawal_address.script_pubkey();
+        // we are assuming that the withdrawal_address is a taproot address so we get the last 32 bytes
+        let hash: [u8; 34] = taproot_script.as_bytes().try_into()?;
+        let hash: [u8; 32] = hash[2..].try_into()?;
+        Ok(hash)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OperatorClaimSigs {
     pub operator_claim_sigs: Vec<Vec<schnorr::Signature>>,
+    /// Txid of the deposit these presigns are for
+    pub deposit_txid: Txid,
+    /// Block height at which the presigns are collected
+    pub height: u32,
+}
+
+/// A verifier's schnorr key paired with the evm address it is identified with on the rollup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifierInfo {
+    pub xonly_pk: XOnlyPublicKey,
+    pub evm_address: EVMAddress,
+}
+
+/// Phase of the operator within the withdrawal periods, see Operator::advance_period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    /// Deposits and withdrawals of the period are processed
+    Withdrawal(usize),
+    /// The last MAX_BLOCK_HANDLE_OPS blocks of the period, in which the connector tree
+    /// preimages for its withdrawals are revealed
+    PreimageReveal(usize),
+    /// Every period is over, only the proofs for the verifiers' challenges are left
+    Finished,
+}
+
+impl Period {
+    /// Position of the phase in the protocol, later phases compare greater
+    fn position(&self) -> (usize, u8) {
+        match *self {
+            Period::Withdrawal(period) => (period, 0),
+            Period::PreimageReveal(period) => (period, 1),
+            Period::Finished => (NUM_ROUNDS, 0),
+        }
+    }
+}
+
+/// Configures an Operator, see Operator::builder. Besides the keys, verifier_evm_addresses is
+/// required whenever there are verifier keys, one address per verifier. Everything else has a
+/// default: ExtendedRpc::new(), no verifier connectors, CONNECTOR_TREE_DEPTH and the defaults
+/// of Operator::new.
+#[derive(Debug)]
+pub struct OperatorBuilder {
+    all_xonly_pks: Vec<XOnlyPublicKey>,
+    operator_sk: SecretKey,
+    rpc: Option<ExtendedRpc>,
+    verifier_connectors: Vec<Box<dyn VerifierConnector>>,
+    verifier_evm_addresses: Vec<EVMAddress>,
+    connector_tree_depth: usize,
+    connector_tree_operator_takes_after: Option<u32>,
+    anchor_kind: AnchorKind,
+    connector_tree_feerate: u64,
+    inscription_feerate: Option<u64>,
+    dry_run: bool,
+}
+
+impl OperatorBuilder {
+    pub fn rpc(mut self, rpc: ExtendedRpc) -> Self {
+        self.rpc = Some(rpc);
+        self
+    }
+
+    /// Clients of the verifiers, in process ones or remote ones alike
+    pub fn verifier_connectors(
+        mut self,
+        verifier_connectors: Vec<Box<dyn VerifierConnector>>,
+    ) -> Self {
+        self.verifier_connectors = verifier_connectors;
+        self
+    }
+
+    pub fn verifier_evm_addresses(mut self, verifier_evm_addresses: Vec<EVMAddress>) -> Self {
+        self.verifier_evm_addresses = verifier_evm_addresses;
+        self
+    }
+
+    pub fn connector_tree_depth(mut self, connector_tree_depth: usize) -> Self {
+        self.connector_tree_depth = connector_tree_depth;
+        self
+    }
+
+    /// See Operator::with_connector_tree_operator_takes_after
+    pub fn connector_tree_operator_takes_after(mut self, block_count: u32) -> Self {
+        self.connector_tree_operator_takes_after = Some(block_count);
+        self
+    }
+
+    /// See Operator::with_anchor_kind
+    pub fn anchor_kind(mut self, anchor_kind: AnchorKind) -> Self {
+        self.anchor_kind = anchor_kind;
+        self
+    }
+
+    /// See Operator::with_connector_tree_feerate
+    pub fn connector_tree_feerate(mut self, feerate_sat_vb: u64) -> Self {
+        self.connector_tree_feerate = feerate_sat_vb;
+        self
+    }
+
+    pub fn inscription_feerate(mut self, feerate_sat_vb: u64) -> Self {
+        self.inscription_feerate = Some(feerate_sat_vb);
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Creates the operator, with the same checks as Operator::new
+    pub fn build(self) -> Result<Operator, BridgeError> {
+        let mut operator = Operator::new(
+            self.rpc.unwrap_or_default(),
+            self.all_xonly_pks,
+            self.operator_sk,
+            self.verifier_connectors,
+            self.verifier_evm_addresses,
+            self.connector_tree_depth,
+        )?;
+        if let Some(block_count) = self.connector_tree_operator_takes_after {
+            operator = operator.with_connector_tree_operator_takes_after(block_count)?;
+        }
+        operator = operator
+            .with_anchor_kind(self.anchor_kind)
+            .with_connector_tree_feerate(self.connector_tree_feerate);
+        if let Some(feerate_sat_vb) = self.inscription_feerate {
+            operator.inscription_feerate = feerate_sat_vb;
+        }
+        operator.dry_run = self.dry_run;
+        Ok(operator)
+    }
 }
 
 #[derive(Debug)]
@@ -93,7 +303,21 @@ pub struct Operator {
     pub signer: Actor,
     pub transaction_builder: TransactionBuilder,
     pub verifiers_pks: Vec<XOnlyPublicKey>,
+    pub verifier_evm_addresses: Vec<EVMAddress>,
     pub verifier_connector: Vec<Box<dyn VerifierConnector>>,
+    pub connector_tree_depth: usize,
+    /// Blocks after which the operator can spend connector tree nodes, see
+    /// with_connector_tree_operator_takes_after
+    connector_tree_operator_takes_after: u16,
+    /// Build transactions without broadcasting them, see take_dry_run_txs. A dry run leaves
+    /// the operator DB as it was, see db_writer.
+    pub dry_run: bool,
+    dry_run_txs: Mutex<Vec<Transaction>>,
+    current_period: Period,
+    /// Feerate in sat/vB the inscription reveal txs pay, their commits are funded for it
+    pub inscription_feerate: u64,
+    /// Withdrawals root of the rollup, trusted to prove Withdrawal events against
+    rollup_withdrawals_root: Option<HashType>,
     operator_db_connector: Box<dyn OperatorDBConnector>,
 }
 
@@ -103,14 +327,31 @@ impl Operator {
         all_xonly_pks: Vec<XOnlyPublicKey>,
         operator_sk: SecretKey,
         verifiers: Vec<Box<dyn VerifierConnector>>,
+        verifier_evm_addresses: Vec<EVMAddress>,
+        connector_tree_depth: usize,
     ) -> Result<Self, BridgeError> {
-        let num_verifiers = all_xonly_pks.len() - 1;
+        // Every leaf of the connector tree needs a claim proof leaf in the claim merkle tree the
+        // circuit is compiled with, and every level doubles the (DUST_VALUE + MIN_RELAY_FEE)
+        // funding of the root. A shallower tree leaves the rest of the claim merkle tree empty,
+        // see claim_proof_merkle_tree.
+        if connector_tree_depth == 0 || connector_tree_depth > CLAIM_MERKLE_TREE_DEPTH {
+            return Err(BridgeError::InvalidConnectorTreeDepth);
+        }
+
+        let Some(num_verifiers) = all_xonly_pks.len().checked_sub(1) else {
+            return Err(BridgeError::InvalidOperatorKey);
+        };
         let signer = Actor::new(operator_sk); // Operator is the last one
 
         if signer.xonly_public_key != all_xonly_pks[num_verifiers] {
             return Err(BridgeError::InvalidOperatorKey);
         }
 
+        // Every verifier, i.e. every key except the operator's, needs an evm address
+        if verifier_evm_addresses.len() != num_verifiers {
+            return Err(BridgeError::VerifierEvmAddressesMismatch);
+        }
+
         let transaction_builder = TransactionBuilder::new(all_xonly_pks.clone());
         let operator_db_connector = Box::new(OperatorMockDB::new());
 
@@ -120,82 +361,259 @@ impl Operator {
             transaction_builder,
             verifier_connector: verifiers,
             verifiers_pks: all_xonly_pks.clone(),
+            verifier_evm_addresses,
+            connector_tree_depth,
+            connector_tree_operator_takes_after: CONNECTOR_TREE_OPERATOR_TAKES_AFTER,
+            dry_run: false,
+            dry_run_txs: Mutex::new(Vec::new()),
+            current_period: Period::Withdrawal(0),
+            inscription_feerate: MIN_RELAY_FEERATE,
+            rollup_withdrawals_root: None,
             operator_db_connector,
         })
     }
 
+    /// Starts configuring an operator with the keys of the verifiers followed by the
+    /// operator's, and the operator's secret key
+    pub fn builder(all_xonly_pks: Vec<XOnlyPublicKey>, operator_sk: SecretKey) -> OperatorBuilder {
+        OperatorBuilder {
+            all_xonly_pks,
+            operator_sk,
+            rpc: None,
+            verifier_connectors: Vec::new(),
+            verifier_evm_addresses: Vec::new(),
+            connector_tree_depth: CONNECTOR_TREE_DEPTH,
+            connector_tree_operator_takes_after: None,
+            anchor_kind: AnchorKind::default(),
+            connector_tree_feerate: MIN_RELAY_FEERATE,
+            inscription_feerate: None,
+            dry_run: false,
+        }
+    }
+
+    /// Sets the relative timelock, in blocks, after which the operator can spend connector
+    /// tree nodes, instead of CONNECTOR_TREE_OPERATOR_TAKES_AFTER. Every connector tree address
+    /// commits to it, so the verifiers have to use the same delay, see
+    /// Verifier::with_connector_tree_operator_takes_after.
+    pub fn with_connector_tree_operator_takes_after(
+        mut self,
+        block_count: u32,
+    ) -> Result<Self, BridgeError> {
+        self.transaction_builder = self
+            .transaction_builder
+            .with_connector_tree_operator_takes_after(block_count)?;
+        self.connector_tree_operator_takes_after =
+            self.transaction_builder.connector_tree_operator_takes_after;
+        Ok(self)
+    }
+
+    /// Sets the feerate the connector trees are funded for, instead of MIN_RELAY_FEERATE. The
+    /// verifiers rebuild the trees, so they have to use the same feerate, see
+    /// Verifier::with_connector_tree_feerate.
+    pub fn with_connector_tree_feerate(mut self, feerate_sat_vb: u64) -> Self {
+        self.transaction_builder = self
+            .transaction_builder
+            .with_connector_tree_feerate(feerate_sat_vb);
+        self
+    }
+
+    /// Sets the anchor output of the move and claim txs. The verifiers sign the same txs, so
+    /// they have to use the same anchor, see Verifier::with_anchor_kind.
+    pub fn with_anchor_kind(mut self, anchor_kind: AnchorKind) -> Self {
+        self.transaction_builder = self.transaction_builder.with_anchor_kind(anchor_kind);
+        self
+    }
+
+    pub fn connector_tree_operator_takes_after(&self) -> u16 {
+        self.connector_tree_operator_takes_after
+    }
+
+    /// Returns the verifiers' schnorr keys paired with their evm addresses, the operator's own key is excluded
+    pub fn verifiers(&self) -> Vec<VerifierInfo> {
+        self.verifiers_pks
+            .iter()
+            .zip(self.verifier_evm_addresses.iter())
+            .map(|(xonly_pk, evm_address)| VerifierInfo {
+                xonly_pk: *xonly_pk,
+                evm_address: *evm_address,
+            })
+            .collect()
+    }
+
+    /// Returns a BIP-21 payment URI paying BRIDGE_AMOUNT_SATS to the deposit address of the given return address
+    pub fn deposit_payment_uri(
+        &self,
+        return_address: &XOnlyPublicKey,
+    ) -> Result<String, BridgeError> {
+        let (deposit_address, _) = self
+            .transaction_builder
+            .generate_deposit_address(return_address)?;
+        Ok(format!(
+            "bitcoin:{}?amount={}",
+            deposit_address,
+            Amount::from_sat(BRIDGE_AMOUNT_SATS).display_in(Denomination::Bitcoin)
+        ))
+    }
+
+    /// Commits to everything the deposit and bridge addresses are derived from, so operators
+    /// sharing a verifier set can check that they derive identical addresses
+    pub fn address_fingerprint(&self) -> Result<[u8; 32], BridgeError> {
+        let (bridge_address, _) = self.transaction_builder.generate_bridge_address()?;
+        Ok(sha256_hash!(
+            bridge_address.script_pubkey().as_bytes(),
+            INTERNAL_KEY.serialize(),
+            USER_TAKES_AFTER.to_le_bytes()
+        ))
+    }
+
     /// this is a public endpoint that every depositor can call
     /// it will get signatures from all verifiers.
-    /// 1. Check if the deposit utxo is valid and finalized (6 blocks confirmation)
+    /// 0. Check if the evm address is not the zero address and the connector trees are created
+    /// 1. Check if the deposit utxo is funded, valid and finalized (6 blocks confirmation)
     /// 2. Check if the utxo is not already spent
     /// 3. Get signatures from all verifiers 1 move signature, ~150 operator takes signatures
     /// 4. Create a move transaction and return the output utxo
-    pub fn new_deposit(
+    pub fn new_deposit(&mut self, request: &DepositRequest) -> Result<OutPoint, BridgeError> {
+        match self.new_deposit_batch(std::slice::from_ref(request)) {
+            Ok(mut move_utxos) => Ok(move_utxos.remove(0)),
+            // Nothing is moved before the only deposit
+            Err(BridgeError::PartialDepositBatch(_, e)) => Err(*e),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Same as new_deposit for many deposits at once, every verifier is asked for the
+    /// presigns of all the deposits in a single call. Returns the move utxos in the order
+    /// of `requests`. Every deposit is checked before any verifier is contacted.
+    /// The deposits are moved in order. If a move fails, the moves before it are already sent
+    /// and recorded, so PartialDepositBatch returns their move utxos together with the error.
+    /// The deposits from the failed one on are not moved, the presigns of the later ones are
+    /// for deposit indices that are only right if the failed one is moved.
+    pub fn new_deposit_batch(
         &mut self,
-        start_utxo: OutPoint,
-        return_address: &XOnlyPublicKey,
-        evm_address: &EVMAddress,
-        user_sig: schnorr::Signature,
-    ) -> Result<OutPoint, BridgeError> {
-        check_deposit_utxo(
-            &self.rpc,
-            &self.transaction_builder,
-            &start_utxo,
-            return_address,
-            BRIDGE_AMOUNT_SATS,
-        )?;
+        requests: &[DepositRequest],
+    ) -> Result<Vec<OutPoint>, BridgeError> {
+        for request in requests {
+            check_evm_address(&request.evm_address)?;
+        }
+        // Deposits can only be claimed through the connector trees, they have to exist beforehand
+        if self
+            .operator_db_connector
+            .get_connector_tree_utxos()
+            .is_empty()
+        {
+            return Err(BridgeError::ConnectorTreeNotReady);
+        }
+
+        for request in requests {
+            self.check_start_utxo(request)?;
+            check_deposit_utxo(
+                &self.rpc,
+                &self.transaction_builder,
+                &request.start_utxo,
+                &request.return_address,
+                BRIDGE_AMOUNT_SATS,
+            )?;
+        }
 
-        let deposit_index = self.operator_db_connector.get_deposit_index();
-        // tracing::debug!("deposit_index: {:?}", deposit_index);
+        let first_deposit_index = self.operator_db_connector.get_deposit_index();
 
-        let presigns_from_all_verifiers: Result<Vec<_>, BridgeError> = self
+        let presigns_from_all_verifiers = self
             .verifier_connector
             .iter()
             .map(|verifier| {
-                // tracing::debug!("Verifier number {:?} is checking new deposit:", i);
-                // Attempt to get the deposit presigns. If an error occurs, it will be propagated out
-                // of the map, causing the collect call to return a Result::Err, effectively stopping
-                // the iteration and returning the error from your_function_name.
-                let deposit_presigns = verifier
-                    .new_deposit(
-                        start_utxo,
-                        return_address,
-                        deposit_index as u32,
-                        evm_address,
-                        &self.signer.address,
-                    )
+                let presigns = verifier
+                    .new_deposit_batch(requests, first_deposit_index as u32, &self.signer.address)
                     .map_err(|e| {
-                        // Log the error or convert it to BridgeError if necessary
                         tracing::error!("Error getting deposit presigns: {:?}", e);
                         e
                     })?;
-                // tracing::debug!("deposit presigns: {:?}", deposit_presigns);
-                // tracing::debug!("Verifier checked new deposit");
-                Ok(deposit_presigns)
+                if presigns.len() != requests.len() {
+                    return Err(BridgeError::FailedToGetPresigns);
+                }
+                Ok(presigns)
             })
-            .collect(); // This tries to collect into a Result<Vec<DepositPresigns>, BridgeError>
+            .collect::<Result<Vec<_>, BridgeError>>()?;
+
+        let block_height = self.rpc.get_block_height()? as u32;
+        let mut move_utxos = Vec::with_capacity(requests.len());
+        for (i, request) in requests.iter().enumerate() {
+            let presigns = presigns_from_all_verifiers
+                .iter()
+                .map(|verifier_presigns| verifier_presigns[i].clone())
+                .collect::<Vec<_>>();
+            match self.move_deposit(request, first_deposit_index + i, &presigns, block_height) {
+                Ok(move_utxo) => move_utxos.push(move_utxo),
+                Err(e) => return Err(BridgeError::PartialDepositBatch(move_utxos, Box::new(e))),
+            }
+        }
+        Ok(move_utxos)
+    }
+
+    /// Checks that the start utxo of a deposit is unspent, also in the mempool, and holds at
+    /// least BRIDGE_AMOUNT_SATS, and that its move tx pays the relay feerate once signed, so
+    /// that no verifier presigns a move tx that can not confirm
+    fn check_start_utxo(&self, request: &DepositRequest) -> Result<(), BridgeError> {
+        let tx_out = self
+            .rpc
+            .get_utxo(&request.start_utxo, true)?
+            .ok_or(BridgeError::InsufficientStartUtxo)?;
+        if tx_out.value < Amount::from_sat(BRIDGE_AMOUNT_SATS) {
+            return Err(BridgeError::InsufficientStartUtxo);
+        }
+        self.check_move_tx_fee(request)
+            .map_err(|_| BridgeError::InsufficientStartUtxo)
+    }
+
+    /// Checks that the move tx of `request` pays at least MIN_RELAY_FEERATE for its signed size
+    fn check_move_tx_fee(&self, request: &DepositRequest) -> Result<(), BridgeError> {
+        let move_tx = self.transaction_builder.create_move_tx(
+            request.start_utxo,
+            &request.evm_address,
+            &request.return_address,
+        )?;
+        self.transaction_builder.check_fee(&move_tx)
+    }
 
-        // Handle the result of the collect operation
-        let presigns_from_all_verifiers = presigns_from_all_verifiers?;
-        // tracing::debug!("presigns_from_all_verifiers: done");
+    /// Finalizes and sends the move tx of a deposit with the verifiers' presigns, one per
+    /// verifier, and stores the operator claim signatures with the given block height
+    fn move_deposit(
+        &mut self,
+        request: &DepositRequest,
+        deposit_index: usize,
+        presigns_from_all_verifiers: &[DepositPresigns],
+        block_height: u32,
+    ) -> Result<OutPoint, BridgeError> {
+        let DepositRequest {
+            start_utxo,
+            return_address,
+            evm_address,
+            user_sig,
+        } = *request;
 
         // 5. Create a move transaction and return the output utxo, save the utxo as a pending deposit
         let mut move_tx =
             self.transaction_builder
-                .create_move_tx(start_utxo, evm_address, &return_address)?;
+                .create_move_tx(start_utxo, &evm_address, &return_address)?;
+        self.validate_move_value_conservation(&move_tx.tx, Amount::from_sat(BRIDGE_AMOUNT_SATS))?;
 
-        // TODO: Simplify this move_signatures thing, maybe with a macro
-        let mut move_signatures = presigns_from_all_verifiers
+        let verifier_move_sigs = presigns_from_all_verifiers
             .iter()
             .map(|presign| presign.move_sign)
             .collect::<Vec<_>>();
-
         let sig = self
             .signer
             .sign_taproot_script_spend_tx_new(&mut move_tx, 0)?;
-        move_signatures.push(sig);
-        move_signatures.push(user_sig);
-        move_signatures.reverse();
+        let ordered_sigs = self.order_move_signatures(&mut move_tx, &verifier_move_sigs, sig)?;
+        let (operator_sig, verifier_sigs) = ordered_sigs
+            .split_last()
+            .ok_or(BridgeError::MoveSignatureNotFound)?;
+        let move_signatures = TransactionBuilder::build_n_of_n_witness(
+            verifier_sigs,
+            *operator_sig,
+            Some(user_sig),
+            &move_tx.scripts[0],
+        )?;
 
         let mut witness_elements: Vec<&[u8]> = Vec::new();
         for sig in move_signatures.iter() {
@@ -203,8 +621,17 @@ impl Operator {
         }
 
         handle_taproot_witness_new(&mut move_tx, &witness_elements, 0)?;
+        self.transaction_builder.check_fee(&move_tx)?;
+        // Catches a witness the deposit script rejects, e.g. misordered signatures, before bitcoind does
+        self.transaction_builder.verify_script_spend(
+            &move_tx.tx,
+            0,
+            &move_tx.prevouts,
+            &move_tx.scripts[0],
+            &move_tx.taproot_spend_infos[0],
+        )?;
         // tracing::debug!("move_tx: {:?}", move_tx);
-        let rpc_move_txid = self.rpc.send_raw_transaction(&move_tx.tx)?;
+        let rpc_move_txid = self.send_tx(&move_tx.tx)?;
         let move_utxo = OutPoint {
             txid: rpc_move_txid,
             vout: 0,
@@ -214,17 +641,21 @@ impl Operator {
                 .iter()
                 .map(|presign| presign.operator_claim_sign.clone())
                 .collect::<Vec<_>>(),
+            deposit_txid: start_utxo.txid,
+            height: block_height,
         };
-        self.operator_db_connector
-            .add_deposit_take_sigs(operator_claim_sigs);
+        if let Some(db) = self.db_writer() {
+            db.add_move_tx((start_utxo, move_tx.tx.clone()));
+            db.add_deposit_take_sigs(operator_claim_sigs);
+        }
 
         for i in 0..NUM_ROUNDS {
             let connector_utxo = self.operator_db_connector.get_connector_tree_utxo(i)
-                [CONNECTOR_TREE_DEPTH][deposit_index as usize];
+                [self.connector_tree_depth][deposit_index];
             let connector_hash = self.operator_db_connector.get_connector_tree_hash(
                 i,
-                CONNECTOR_TREE_DEPTH,
-                deposit_index as usize,
+                self.connector_tree_depth,
+                deposit_index,
             );
             let mut operator_claim_tx = self.transaction_builder.create_operator_claim_tx(
                 move_utxo,
@@ -263,9 +694,264 @@ impl Operator {
             }
         }
 
+        tracing::info!(
+            deposit_index,
+            deposit_txid = %start_utxo.txid,
+            move_txid = %move_utxo.txid,
+            "Deposit moved to the bridge"
+        );
         Ok(move_utxo)
     }
 
+    /// Matches the move signatures to the keys of the n-of-n script and returns them in
+    /// `verifiers_pks` order, which is the order the script checks them in. The operator's
+    /// signature is the last one. The verifier signatures can be given in any order.
+    pub fn order_move_signatures(
+        &self,
+        move_tx: &mut CreateTxOutputs,
+        verifier_sigs: &[schnorr::Signature],
+        operator_sig: schnorr::Signature,
+    ) -> Result<Vec<schnorr::Signature>, BridgeError> {
+        let sig_hash = self.signer.sighash_taproot_script_spend(move_tx, 0)?;
+        let message = Message::from_digest_slice(sig_hash.as_byte_array()).expect("should be hash");
+        let candidates = verifier_sigs
+            .iter()
+            .chain(std::iter::once(&operator_sig))
+            .collect::<Vec<_>>();
+        self.verifiers_pks
+            .iter()
+            .map(|pk| {
+                candidates
+                    .iter()
+                    .find(|sig| self.signer.secp.verify_schnorr(sig, &message, pk).is_ok())
+                    .map(|sig| **sig)
+                    .ok_or(BridgeError::MoveSignatureNotFound)
+            })
+            .collect()
+    }
+
+    /// Broadcasts a transaction, e.g. one that is built in dry run mode. Transactions the node
+    /// would reject are not sent, the error carries the reject reason instead
+    pub fn broadcast(&self, tx: &Transaction) -> Result<Txid, BridgeError> {
+        self.rpc.test_accept(tx)?;
+        self.rpc.send_raw_transaction(tx).map_err(|e| {
+            tracing::error!("Failed to send raw transaction: {}", e);
+            BridgeError::RpcError
+        })
+    }
+
+    /// Returns the transactions built in dry run mode since the last call, in the order they are built
+    pub fn take_dry_run_txs(&self) -> Vec<Transaction> {
+        std::mem::take(&mut *self.dry_run_txs.lock().expect("dry run txs are poisoned"))
+    }
+
+    /// Broadcasts `tx`, in dry run mode it is kept for take_dry_run_txs instead
+    fn send_tx(&self, tx: &Transaction) -> Result<Txid, BridgeError> {
+        tracing::debug!(txid = %tx.txid(), dry_run = self.dry_run, "Sending transaction");
+        if self.dry_run {
+            self.dry_run_txs
+                .lock()
+                .expect("dry run txs are poisoned")
+                .push(tx.clone());
+            return Ok(tx.txid());
+        }
+        self.broadcast(tx)
+    }
+
+    /// The operator DB to write to, None in dry run mode. Every write goes through it, but the
+    /// connector tree records of initial_setup, which funds the trees from the wallet.
+    fn db_writer(&mut self) -> Option<&mut dyn OperatorDBConnector> {
+        if self.dry_run {
+            None
+        } else {
+            Some(self.operator_db_connector.as_mut())
+        }
+    }
+
+    /// Checks that the move transaction spends the whole deposit, i.e. its outputs
+    /// together with the relay fee add up to exactly the deposit amount
+    pub fn validate_move_value_conservation(
+        &self,
+        move_tx: &Transaction,
+        deposit_amount: Amount,
+    ) -> Result<(), BridgeError> {
+        let total_output = move_tx
+            .output
+            .iter()
+            .try_fold(Amount::ZERO, |acc, txout| acc.checked_add(txout.value))
+            .ok_or(BridgeError::MoveTxValueMismatch)?;
+        if total_output.checked_add(Amount::from_sat(MIN_RELAY_FEE)) != Some(deposit_amount) {
+            return Err(BridgeError::MoveTxValueMismatch);
+        }
+        Ok(())
+    }
+
+    /// Returns the move utxos whose move tx is neither in the chain nor in the mempool anymore,
+    /// e.g. because it got reorged out and conflicted. A move utxo that is spent, e.g. by the
+    /// operator's claim, still has its move tx confirmed and is not returned.
+    pub fn detect_reorged_moves(&self) -> Result<Vec<OutPoint>, BridgeError> {
+        let mut reorged_moves = Vec::new();
+        for (_, move_tx) in self.operator_db_connector.get_move_txs() {
+            let move_txid = move_tx.txid();
+            if self.tx_confirmations(&move_txid)?.is_none() {
+                reorged_moves.push(OutPoint {
+                    txid: move_txid,
+                    vout: 0,
+                });
+            }
+        }
+        Ok(reorged_moves)
+    }
+
+    /// Returns the number of confirmations of `txid`, 0 while it is in the mempool, None if
+    /// the node does not know it
+    fn tx_confirmations(&self, txid: &Txid) -> Result<Option<u32>, BridgeError> {
+        match self.rpc.get_raw_transaction_info(txid, None) {
+            Ok(tx_info) => Ok(Some(tx_info.confirmations.unwrap_or(0))),
+            Err(bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::Error::Rpc(
+                ref rpc_error,
+            ))) if rpc_error.code == RPC_INVALID_ADDRESS_OR_KEY => Ok(None),
+            Err(e) => {
+                tracing::error!("Failed to get tx {}: {}", txid, e);
+                Err(BridgeError::RpcError)
+            }
+        }
+    }
+
+    /// Total value locked in the bridge, the sum of the recorded move utxos that are not
+    /// claimed yet. Move utxos spent in the mempool count as claimed.
+    pub fn total_locked(&self) -> Result<Amount, BridgeError> {
+        let mut total = Amount::ZERO;
+        for (_, move_tx) in self.operator_db_connector.get_move_txs() {
+            let move_utxo = OutPoint {
+                txid: move_tx.txid(),
+                vout: 0,
+            };
+            if let Some(txout) = self.rpc.get_utxo(&move_utxo, true)? {
+                total += txout.value;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Returns the recorded deposit utxos whose tx has less than `min_confirmations`
+    /// confirmations, including the ones that are back in the mempool or gone after a reorg.
+    /// Claims should wait while any deposit is returned.
+    pub fn check_deposit_confirmations(
+        &self,
+        min_confirmations: u32,
+    ) -> Result<Vec<OutPoint>, BridgeError> {
+        let mut unconfirmed_deposits = Vec::new();
+        for (deposit_utxo, _) in self.operator_db_connector.get_move_txs() {
+            let confirmations = self.tx_confirmations(&deposit_utxo.txid)?.unwrap_or(0);
+            if confirmations < min_confirmations {
+                unconfirmed_deposits.push(deposit_utxo);
+            }
+        }
+        Ok(unconfirmed_deposits)
+    }
+
+    /// Broadcasts the stored move tx of `deposit_utxo` again, to re-finalize a reorged deposit
+    pub fn rebroadcast_move(&self, deposit_utxo: OutPoint) -> Result<Txid, BridgeError> {
+        let (_, move_tx) = self
+            .operator_db_connector
+            .get_move_txs()
+            .into_iter()
+            .find(|(utxo, _)| *utxo == deposit_utxo)
+            .ok_or(BridgeError::MoveTxNotFound)?;
+        self.broadcast(&move_tx)
+    }
+
+    /// Pairs every verifier's key with its signature over the operator's claim of `deposit_utxo`
+    /// in `period`, as collected when the deposit is made, so that the claim can be audited
+    pub fn collect_claim_authorizations(
+        &self,
+        deposit_utxo: OutPoint,
+        period: usize,
+    ) -> Vec<(XOnlyPublicKey, schnorr::Signature)> {
+        self.operator_db_connector
+            .get_deposit_take_sigs()
+            .into_iter()
+            .find(|sigs| sigs.deposit_txid == deposit_utxo.txid)
+            .map(|sigs| {
+                self.verifiers_pks
+                    .iter()
+                    .zip(sigs.operator_claim_sigs.iter())
+                    .filter_map(|(pk, verifier_sigs)| {
+                        verifier_sigs.get(period).map(|sig| (*pk, *sig))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Walks the connector tree of `period` from the root down, yielding the level, index,
+    /// utxo, preimage and hash of every node. Yields nothing if the tree is not created yet.
+    pub fn connector_tree_iter(
+        &self,
+        period: usize,
+    ) -> impl Iterator<Item = (usize, usize, OutPoint, PreimageType, HashType)> {
+        let utxo_tree = self
+            .operator_db_connector
+            .get_connector_tree_utxos()
+            .get(period)
+            .cloned()
+            .unwrap_or_default();
+        let mut nodes = Vec::new();
+        for (level, utxos) in utxo_tree.iter().enumerate() {
+            let preimages = self
+                .operator_db_connector
+                .get_connector_tree_preimages_level(period, level);
+            for (index, (utxo, preimage)) in utxos.iter().zip(preimages).enumerate() {
+                let hash = self
+                    .operator_db_connector
+                    .get_connector_tree_hash(period, level, index);
+                nodes.push((level, index, *utxo, preimage, hash));
+            }
+        }
+        nodes.into_iter()
+    }
+
+    /// Builds a merkle tree of deposit txids in the order they are confirmed on chain, by block
+    /// height and then by position in the block, whatever order they are processed in
+    pub fn deposit_merkle_tree_ordered<const DEPTH: usize>(
+        &self,
+        deposit_txids: &[Txid],
+    ) -> Result<MerkleTree<DEPTH>, BridgeError> {
+        let mut positioned_txids = deposit_txids
+            .iter()
+            .map(|txid| Ok((self.rpc.get_tx_chain_position(txid)?, *txid)))
+            .collect::<Result<Vec<_>, BridgeError>>()?;
+        positioned_txids.sort();
+        let mut deposit_merkle_tree = MerkleTree::new();
+        for (_, txid) in positioned_txids {
+            deposit_merkle_tree.add(txid.to_byte_array());
+        }
+        Ok(deposit_merkle_tree)
+    }
+
+    /// Returns the leaf index of the deposit in the deposit merkle tree, which is the order
+    /// the deposit is finalized in. Pruning presigns does not change it.
+    pub fn deposit_leaf_index(&self, deposit_txid: Txid) -> Option<u32> {
+        self.operator_db_connector
+            .get_deposit_leaf_index(deposit_txid)
+    }
+
+    /// Removes the presign sets that are collected more than `max_age` blocks before
+    /// `current_height`, since their UTXOs may have been spent in the meantime.
+    /// Returns the deposit txids of the removed presign sets.
+    pub fn prune_stale_presigns(&mut self, current_height: u32, max_age: u32) -> Vec<Txid> {
+        let (stale, fresh): (Vec<_>, Vec<_>) = self
+            .operator_db_connector
+            .get_deposit_take_sigs()
+            .into_iter()
+            .partition(|sigs| current_height.saturating_sub(sigs.height) > max_age);
+        if let Some(db) = self.db_writer() {
+            db.set_deposit_take_sigs(fresh);
+        }
+        stale.iter().map(|sigs| sigs.deposit_txid).collect()
+    }
+
     /// Returns the current withdrawal
     fn get_current_withdrawal_period(&self) -> Result<usize, BridgeError> {
         let cur_block_height = self.rpc.get_block_count().unwrap();
@@ -284,6 +970,54 @@ impl Operator {
         Err(BridgeError::InvalidPeriod)
     }
 
+    /// The period the operator is in, as of the last advance_period
+    pub fn current_period(&self) -> Period {
+        self.current_period
+    }
+
+    /// Moves the operator to the period `current_block_height` is in, by the period boundaries
+    /// set in initial_setup. Entering the preimage reveal phase of a period inscribes its
+    /// connector tree preimages and commits to the bitVM instance of the next period, unless
+    /// that is already done, so calling this again at the same height does nothing. Deposits are
+    /// moved as they arrive and proofs answer the verifiers' challenges, so the other phases have
+    /// no action of their own.
+    pub fn advance_period(&mut self, current_block_height: u64) -> Result<Period, BridgeError> {
+        let start_block_height = self.operator_db_connector.get_start_block_height();
+        let period_relative_block_heights = self
+            .operator_db_connector
+            .get_period_relative_block_heights();
+        let period = period_relative_block_heights
+            .iter()
+            .take(NUM_ROUNDS)
+            .position(|block_height| {
+                current_block_height < start_block_height + *block_height as u64
+            })
+            .map(|i| {
+                let period_end = start_block_height + period_relative_block_heights[i] as u64;
+                if current_block_height < period_end - MAX_BLOCK_HANDLE_OPS as u64 {
+                    Period::Withdrawal(i)
+                } else {
+                    Period::PreimageReveal(i)
+                }
+            })
+            .unwrap_or(Period::Finished);
+        if period.position() < self.current_period.position() {
+            return Err(BridgeError::InvalidPeriod);
+        }
+
+        if let Period::PreimageReveal(i) = period {
+            if self.operator_db_connector.get_inscription_txs_len() <= i {
+                self.inscribe_period_preimages(i)?;
+            }
+            self.commit_instance(i + 1, &mut thread_rng());
+        }
+        if period != self.current_period {
+            tracing::info!(from = ?self.current_period, to = ?period, "Period advanced");
+            self.current_period = period;
+        }
+        Ok(period)
+    }
+
     fn get_current_preimage_reveal_period(&self) -> Result<usize, BridgeError> {
         let cur_block_height = self.rpc.get_block_count().unwrap();
         tracing::debug!("Cur block height: {:?}", cur_block_height);
@@ -310,118 +1044,502 @@ impl Operator {
         Err(BridgeError::InvalidPeriod)
     }
 
-    // this is called when a Withdrawal event emitted on rollup and its corresponding batch proof is finalized
-    pub fn new_withdrawal(
-        &mut self,
-        withdrawal_address: Address<NetworkChecked>,
-    ) -> Result<(), BridgeError> {
-        let taproot_script = withdrawal_address.script_pubkey();
-        // we are assuming that the withdrawal_address is a taproot address so we get the last 32 bytes
-        let hash: [u8; 34] = taproot_script.as_bytes().try_into()?;
-        let hash: [u8; 32] = hash[2..].try_into()?;
-
-        // 1. Add the address to WithdrawalsMerkleTree
-        self.operator_db_connector
-            .add_to_withdrawals_merkle_tree(hash);
-
-        // self.withdrawals_merkle_tree.add(withdrawal_address.to);
-
-        // 2. Pay to the address and save the txid
-        let txid = self
-            .rpc
-            .send_to_address(&withdrawal_address, 100_000_000)?
-            .txid;
-        // tracing::debug!(
-        //     "operator paid to withdrawal address: {:?}, txid: {:?}",
-        //     withdrawal_address, txid
-        // );
-        let current_withdrawal_period = self.get_current_withdrawal_period()?;
-        self.operator_db_connector.add_to_withdrawals_payment_txids(
-            current_withdrawal_period,
-            (txid, hash) as WithdrawalPayment,
+    /// Returns the weight of a claim transaction that claims the deposits at `indices` at once,
+    /// with the bridge and connector leaf inputs of every deposit spent through their script paths
+    pub fn batched_claim_weight(&self, indices: &[usize]) -> Result<usize, BridgeError> {
+        // Only the sizes matter, so any outpoint and connector hash will do
+        let mut claim_tx = self.transaction_builder.create_operator_claim_tx(
+            OutPoint::null(),
+            OutPoint::null(),
+            &self.signer.address,
+            &self.signer.xonly_public_key,
+            &[0u8; 32],
+        )?;
+        let connector_script = ScriptBuilder::generate_timelock_script(
+            &self.signer.xonly_public_key,
+            self.connector_tree_operator_takes_after as u32,
         );
-        Ok(())
+        let dummy_sig = [0u8; 64];
+        self.transaction_builder.handle_taproot_witness(
+            &mut claim_tx.tx,
+            0,
+            &vec![dummy_sig; self.verifiers_pks.len()],
+            &claim_tx.scripts[0],
+            &claim_tx.taproot_spend_infos[0],
+        )?;
+        self.transaction_builder.handle_taproot_witness(
+            &mut claim_tx.tx,
+            1,
+            &vec![dummy_sig],
+            &connector_script,
+            &claim_tx.taproot_spend_infos[1],
+        )?;
+
+        let claim_inputs = claim_tx.tx.input.clone();
+        claim_tx.tx.input = indices.iter().flat_map(|_| claim_inputs.clone()).collect();
+        Ok(claim_tx.tx.weight().to_wu() as usize)
     }
 
-    pub fn spend_connector_tree_utxo(
-        // TODO: Too big, move some parts to Transaction Builder
+    /// Checks that the preimage reveal transaction is confirmed within the reveal window of
+    /// `period`, i.e. in the last `MAX_BLOCK_HANDLE_OPS` blocks before the period ends
+    pub fn verify_reveal_in_period(
         &self,
+        reveal_txid: Txid,
         period: usize,
-        utxo: OutPoint,
-        preimage: PreimageType,
-        tree_depth: usize,
     ) -> Result<(), BridgeError> {
-        let hash = sha256_hash!(preimage);
-        let (_, tree_info) = TransactionBuilder::create_connector_tree_node_address(
-            &self.signer.secp,
-            &self.signer.xonly_public_key,
-            &hash,
-        )?;
+        let period_end = self
+            .operator_db_connector
+            .get_period_relative_block_heights()
+            .get(period)
+            .map(|block_height| {
+                self.operator_db_connector.get_start_block_height() + *block_height as u64
+            })
+            .ok_or(BridgeError::InvalidPeriod)?;
+        let confirmations = self.rpc.confirmation_blocks(&reveal_txid)?;
+        if confirmations == 0 {
+            return Err(BridgeError::NoConfirmationData);
+        }
+        let confirmation_height = self.rpc.get_block_height()? + 1 - confirmations as u64;
+        if confirmation_height < period_end - MAX_BLOCK_HANDLE_OPS as u64
+            || confirmation_height >= period_end
+        {
+            return Err(BridgeError::RevealNotInPeriod);
+        }
+        Ok(())
+    }
 
-        let base_tx = match self.rpc.get_raw_transaction(&utxo.txid, None) {
-            Ok(txid) => Some(txid),
-            Err(e) => {
-                tracing::error!("Failed to get raw transaction: {}", e);
-                None
-            }
+    /// Returns the connector tree leaf of the deposit `index` in `period`
+    fn get_connector_leaf(&self, period: usize, index: usize) -> Result<OutPoint, BridgeError> {
+        self.operator_db_connector
+            .get_connector_tree_utxo(period)
+            .get(self.connector_tree_depth)
+            .and_then(|leaves| leaves.get(index))
+            .copied()
+            .ok_or(BridgeError::ConnectorLeafNotFound)
+    }
+
+    /// Returns whether the connector leaf of the deposit `index` in the current period is
+    /// confirmed deep enough for its connector_tree_operator_takes_after timelock, i.e.
+    /// whether a claim spending it would be accepted now
+    pub fn can_claim(&self, index: usize) -> Result<bool, BridgeError> {
+        let period = self.get_current_withdrawal_period()?;
+        let connector_leaf = self.get_connector_leaf(period, index)?;
+        let confirmations = match self.rpc.confirmation_blocks(&connector_leaf.txid) {
+            Ok(confirmations) => confirmations,
+            // Still in the mempool
+            Err(BridgeError::NoConfirmationData) => return Ok(false),
+            Err(e) => return Err(e),
         };
-        // tracing::debug!("base_tx: {:?}", base_tx);
+        Ok(confirmations >= self.connector_tree_operator_takes_after as u32)
+    }
 
-        if base_tx.is_none() {
-            return Ok(());
+    /// Claims the deposit `index` in the current period: spends its move utxo with the
+    /// verifiers' presigned claim signatures, and its connector leaf after the timelock.
+    /// Returns the txid of the claim tx.
+    pub fn claim_deposit(&self, index: usize) -> Result<Txid, BridgeError> {
+        let period = self.get_current_withdrawal_period()?;
+        let (move_tx, claim_sigs) = self.get_deposit_claim_data(index)?;
+        let connector_utxo = *self
+            .operator_db_connector
+            .get_connector_tree_utxos()
+            .get(period)
+            .and_then(|utxo_tree| utxo_tree.get(self.connector_tree_depth))
+            .and_then(|leaves| leaves.get(index))
+            .ok_or(BridgeError::ClaimIndexOutOfRange("connector tree leaves"))?;
+        if !self.can_claim(index)? {
+            return Err(BridgeError::NotYetSpendable);
         }
-        let depth = u32::ilog2(
-            ((base_tx.unwrap().output[utxo.vout as usize].value.to_sat() + MIN_RELAY_FEE)
-                / (DUST_VALUE + MIN_RELAY_FEE)) as u32,
+        let move_utxo = OutPoint {
+            txid: move_tx.txid(),
+            vout: 0,
+        };
+        let connector_hash = self.operator_db_connector.get_connector_tree_hash(
+            period,
+            self.connector_tree_depth,
+            index,
         );
-        // tracing::debug!("depth: {:?}", depth);
-        let level = tree_depth - depth as usize;
+        let mut claim_tx = self.transaction_builder.create_operator_claim_tx(
+            move_utxo,
+            connector_utxo,
+            &self.signer.address,
+            &self.signer.xonly_public_key,
+            &connector_hash,
+        )?;
+
+        let verifier_sigs = claim_sigs
+            .operator_claim_sigs
+            .iter()
+            .map(|sigs| sigs.get(period).copied())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(BridgeError::FailedToGetPresigns)?;
+        // Both inputs are signed before the witnesses are set
+        let [operator_sig, connector_sig]: [schnorr::Signature; 2] = self
+            .signer
+            .sign_all_script_spends(&claim_tx.tx, &claim_tx.prevouts, &claim_tx.scripts)?
+            .try_into()
+            .map_err(|_| BridgeError::ScriptCountMismatch)?;
+        let claim_signatures = TransactionBuilder::build_n_of_n_witness(
+            &verifier_sigs,
+            operator_sig,
+            None,
+            &claim_tx.scripts[0],
+        )?;
+        let mut witness_elements: Vec<&[u8]> = Vec::new();
+        for sig in claim_signatures.iter() {
+            witness_elements.push(sig.as_ref());
+        }
+        // The bridge input spends the same leaf in every claim, its control block is cached
+        self.transaction_builder.handle_taproot_witness(
+            &mut claim_tx.tx,
+            0,
+            &witness_elements,
+            &claim_tx.scripts[0],
+            &claim_tx.taproot_spend_infos[0],
+        )?;
+        self.transaction_builder.handle_taproot_witness(
+            &mut claim_tx.tx,
+            1,
+            &vec![connector_sig.as_ref()],
+            &claim_tx.scripts[1],
+            &claim_tx.taproot_spend_infos[1],
+        )?;
+        self.transaction_builder.check_fee(&claim_tx)?;
+        for index in 0..claim_tx.tx.input.len() {
+            self.transaction_builder.verify_script_spend(
+                &claim_tx.tx,
+                index,
+                &claim_tx.prevouts,
+                &claim_tx.scripts[index],
+                &claim_tx.taproot_spend_infos[index],
+            )?;
+        }
+
+        let txid = self.send_tx(&claim_tx.tx)?;
+        tracing::info!(index, period, txid = %txid, "Deposit claimed");
+        Ok(txid)
+    }
+
+    /// The move tx of deposit `index` and the verifiers' claim presigns of its deposit. The
+    /// presigns are found by the deposit txid, pruning them leaves the move txs in place.
+    fn get_deposit_claim_data(
+        &self,
+        index: usize,
+    ) -> Result<(Transaction, OperatorClaimSigs), BridgeError> {
+        let (deposit_utxo, move_tx) = self
+            .operator_db_connector
+            .get_move_txs()
+            .into_iter()
+            .nth(index)
+            .ok_or(BridgeError::ClaimIndexOutOfRange("move txs"))?;
+        let claim_sigs = self
+            .operator_db_connector
+            .get_deposit_take_sigs()
+            .into_iter()
+            .find(|sigs| sigs.deposit_txid == deposit_utxo.txid)
+            .ok_or(BridgeError::FailedToGetPresigns)?;
+        Ok((move_tx, claim_sigs))
+    }
+
+    /// Bumps the fee of `parent` to `feerate_sat_vb` with a child spending its anyone can spend
+    /// output at `anchor_vout` and the operator's `resource_utxo` worth `resource_value`. The rest
+    /// of the resource utxo goes back to the operator's address.
+    pub fn bump_fee(
+        &self,
+        parent: &CreateTxOutputs,
+        anchor_vout: u32,
+        resource_utxo: OutPoint,
+        resource_value: Amount,
+        feerate_sat_vb: u64,
+    ) -> Result<Txid, BridgeError> {
+        let mut child_tx = self.transaction_builder.create_child_pays_for_parent(
+            parent,
+            anchor_vout,
+            resource_utxo,
+            TxOut {
+                value: resource_value,
+                script_pubkey: self.signer.address.script_pubkey(),
+            },
+            &self.signer.address,
+            feerate_sat_vb,
+        )?;
+        let sig =
+            self.signer
+                .sign_taproot_pubkey_spend_tx(&mut child_tx.tx, &child_tx.prevouts, 1)?;
+        child_tx.tx.input[1].witness.push(sig.as_ref());
+        let txid = self.send_tx(&child_tx.tx)?;
+        tracing::info!(parent_txid = %parent.tx.txid(), txid = %txid, "Parent fee bumped");
+        Ok(txid)
+    }
+
+    /// Returns the first block height at which the connector leaf of the deposit `index`
+    /// in the current period can be spent, i.e. its confirmation height plus the CSV delay
+    pub fn earliest_claim_height(&self, index: usize) -> Result<u32, BridgeError> {
+        let period = self.get_current_withdrawal_period()?;
+        let connector_leaf = self.get_connector_leaf(period, index)?;
+        let confirmations = self.rpc.confirmation_blocks(&connector_leaf.txid)?;
+        if confirmations == 0 {
+            return Err(BridgeError::NoConfirmationData);
+        }
+        let confirmation_height = self.rpc.get_block_height()? as u32 + 1 - confirmations;
+        Ok(confirmation_height + self.connector_tree_operator_takes_after as u32)
+    }
+
+    /// Sets the withdrawals root of the rollup that new_withdrawal checks Withdrawal events
+    /// against, it has to come from a source the operator trusts, e.g. the light client
+    pub fn set_rollup_withdrawals_root(&mut self, root: HashType) {
+        self.rollup_withdrawals_root = Some(root);
+    }
+
+    /// Checks that `hash` is the next withdrawal to pay, i.e. it sits at the operator's
+    /// withdrawals merkle tree index in the trusted rollup withdrawals root
+    fn check_withdrawal_proof(
+        &self,
+        hash: HashType,
+        proof: &WithdrawalProof,
+    ) -> Result<(), BridgeError> {
+        let root = self
+            .rollup_withdrawals_root
+            .ok_or(BridgeError::InvalidWithdrawalProof)?;
+        // Paying any other index would either pay a withdrawal twice or break the order the
+        // operator's withdrawals merkle tree has to follow
+        if proof.index
+            != self
+                .operator_db_connector
+                .get_withdrawals_merkle_tree_index()
+        {
+            return Err(BridgeError::InvalidWithdrawalProof);
+        }
+        if root_from_path(hash, proof.index, &proof.path) != root {
+            return Err(BridgeError::InvalidWithdrawalProof);
+        }
+        Ok(())
+    }
+
+    // this is called when a Withdrawal event emitted on rollup and its corresponding batch proof is finalized
+    /// Pays a Withdrawal event of the rollup after checking its inclusion proof against the
+    /// trusted rollup withdrawals root, see set_rollup_withdrawals_root. Returns the txid of the
+    /// payment, in dry run mode the payment is only built, see take_dry_run_txs.
+    pub fn new_withdrawal(
+        &mut self,
+        withdrawal_address: Address<NetworkChecked>,
+        proof: WithdrawalProof,
+    ) -> Result<Txid, BridgeError> {
+        let hash = WithdrawalProof::leaf(&withdrawal_address)?;
+        self.check_withdrawal_proof(hash, &proof)?;
+        let current_withdrawal_period = self.get_current_withdrawal_period()?;
+
+        // 1. Pay to the address
+        let (payment_tx, _) = self
+            .rpc
+            .create_funded_tx(&withdrawal_address, BRIDGE_AMOUNT_SATS)?;
+        let txid = self.send_tx(&payment_tx)?;
+
+        // 2. Add the address to WithdrawalsMerkleTree and save the txid
+        if let Some(db) = self.db_writer() {
+            db.add_to_withdrawals_merkle_tree(hash);
+            db.add_to_withdrawals_payment_txids(
+                current_withdrawal_period,
+                (txid, hash) as WithdrawalPayment,
+            );
+        }
+        Ok(txid)
+    }
+
+    /// Checks that the operator's wallet can pay for a whole period: the withdrawals, the
+    /// connector tree of the period and the inscription of its preimages, all with their fees
+    pub fn preflight_period_funding(
+        &self,
+        expected_withdrawals: u32,
+        withdrawal_amount: Amount,
+    ) -> Result<(), BridgeError> {
+        let withdrawals_amount =
+            (withdrawal_amount + Amount::from_sat(MIN_RELAY_FEE)) * expected_withdrawals as u64;
+        let connector_tree_amount = self
+            .transaction_builder
+            .connector_tree_node_amount(self.connector_tree_depth)?
+            + Amount::from_sat(MIN_RELAY_FEE);
+        let inscription_amount = Amount::from_sat(DUST_VALUE * 2 + MIN_RELAY_FEE);
+        let required_amount = withdrawals_amount + connector_tree_amount + inscription_amount;
+
+        let balance = self.rpc.get_balance()?;
+        if balance < required_amount {
+            return Err(BridgeError::InsufficientPeriodFunding(
+                required_amount - balance,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Funding the root of a connector tree needs at `feerate_sat_vb`, see
+    /// TransactionBuilder::connector_tree_funding
+    pub fn connector_tree_funding(&self, feerate_sat_vb: u64) -> Result<Amount, BridgeError> {
+        self.transaction_builder
+            .connector_tree_funding(self.connector_tree_depth, feerate_sat_vb)
+    }
+
+    /// Locates the connector tree node holding `utxo_value` and revealing `preimage` in the given
+    /// period, returns the depth of the node together with the hashes of its two children
+    fn get_connector_tree_node_children(
+        &self,
+        period: usize,
+        utxo_value: Amount,
+        preimage: &PreimageType,
+    ) -> Result<(usize, (HashType, HashType)), BridgeError> {
+        if period >= NUM_ROUNDS {
+            return Err(BridgeError::InvalidPeriod);
+        }
+        // Every node holds exactly the funding of the levels below it. Leaves have no children
+        // to spend into and nothing can be above the root.
+        let mut depth = 1;
+        while self.transaction_builder.connector_tree_node_amount(depth)? != utxo_value {
+            if depth == self.connector_tree_depth {
+                return Err(BridgeError::InvalidConnectorTreeUtxoValue);
+            }
+            depth += 1;
+        }
+        let level = self.connector_tree_depth - depth;
         //find the index of preimage in the connector_tree_preimages[level as usize]
         let index = self
             .operator_db_connector
             .get_connector_tree_preimages_level(period, level)
             .iter()
-            .position(|x| *x == preimage)
-            .ok_or(BridgeError::PreimageNotFound)?;
+            .position(|x| x == preimage)
+            .ok_or(BridgeError::PreimageNotInTree)?;
         let hashes = (
             self.operator_db_connector
                 .get_connector_tree_hash(period, level + 1, 2 * index),
             self.operator_db_connector
                 .get_connector_tree_hash(period, level + 1, 2 * index + 1),
         );
+        Ok((depth, hashes))
+    }
+
+    pub fn spend_connector_tree_utxo(
+        &self,
+        period: usize,
+        utxo: OutPoint,
+        preimage: PreimageType,
+    ) -> Result<Txid, BridgeError> {
+        let tx = self.create_connector_tree_spend_tx(period, utxo, preimage)?;
+        let txid = self.send_tx(&tx)?;
+        tracing::info!(period, utxo = %utxo, txid = %txid, "Connector tree utxo spent");
+        Ok(txid)
+    }
+
+    /// Waits for the CSV delay of the connector tree utxo to pass, for at most `timeout`,
+    /// then spends it. Spending it before would be rejected by bitcoind.
+    pub fn spend_matured_connector_tree_utxo(
+        &self,
+        period: usize,
+        utxo: OutPoint,
+        preimage: PreimageType,
+        timeout: Duration,
+    ) -> Result<Txid, BridgeError> {
+        self.rpc.wait_for_confirmation(
+            utxo.txid,
+            self.connector_tree_operator_takes_after as u32,
+            timeout,
+        )?;
+        self.spend_connector_tree_utxo(period, utxo, preimage)
+    }
+
+    /// Spends every utxo of a connector tree level, `preimages` are in the same order as the
+    /// utxos of the level. All spends are built before any of them is broadcast.
+    pub fn spend_connector_tree_level(
+        &self,
+        period: usize,
+        level: usize,
+        preimages: &[PreimageType],
+    ) -> Result<Vec<Txid>, BridgeError> {
+        let utxos = self
+            .operator_db_connector
+            .get_connector_tree_utxo(period)
+            .get(level)
+            .cloned()
+            .ok_or(BridgeError::TxInputNotFound)?;
+        if utxos.len() != preimages.len() {
+            return Err(BridgeError::PreimageNotFound);
+        }
+        let txs = utxos
+            .iter()
+            .zip(preimages.iter())
+            .map(|(utxo, preimage)| self.create_connector_tree_spend_tx(period, *utxo, *preimage))
+            .collect::<Result<Vec<_>, BridgeError>>()?;
+        txs.iter().map(|tx| self.send_tx(tx)).collect()
+    }
+
+    /// Fetches a transaction from the node, BaseTxNotFound if the node does not know it
+    fn get_base_tx(&self, txid: &Txid) -> Result<Transaction, BridgeError> {
+        self.rpc.get_raw_transaction(txid, None).map_err(|e| {
+            tracing::error!("Failed to get raw transaction: {}", e);
+            match e {
+                bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::Error::Rpc(
+                    ref rpc_error,
+                )) if rpc_error.code == RPC_INVALID_ADDRESS_OR_KEY => BridgeError::BaseTxNotFound,
+                _ => BridgeError::RpcError,
+            }
+        })
+    }
+
+    fn create_connector_tree_spend_tx(
+        // TODO: Too big, move some parts to Transaction Builder
+        &self,
+        period: usize,
+        utxo: OutPoint,
+        preimage: PreimageType,
+    ) -> Result<Transaction, BridgeError> {
+        let hash = sha256_hash!(preimage);
+        let (_, tree_info) = TransactionBuilder::create_connector_tree_node_address(
+            &self.signer.secp,
+            &self.signer.xonly_public_key,
+            &hash,
+            self.connector_tree_operator_takes_after,
+        )?;
+
+        let base_tx = self.get_base_tx(&utxo.txid)?;
+        // tracing::debug!("base_tx: {:?}", base_tx);
+
+        let utxo_txout = base_tx
+            .output
+            .get(utxo.vout as usize)
+            .cloned()
+            .ok_or(BridgeError::TxOutputNotFound)?;
+        let (depth, hashes) =
+            self.get_connector_tree_node_children(period, utxo_txout.value, &preimage)?;
 
-        let utxo_tx = self.rpc.get_raw_transaction(&utxo.txid, None)?;
-        // tracing::debug!("utxo_tx: {:?}", utxo_tx);
-        // tracing::debug!("utxo_txid: {:?}", utxo_tx.txid());
-        let timelock_script =
-            ScriptBuilder::generate_timelock_script(&self.signer.xonly_public_key, 1);
+        let timelock_script = ScriptBuilder::generate_timelock_script(
+            &self.signer.xonly_public_key,
+            self.connector_tree_operator_takes_after as u32,
+        );
 
         let (first_address, _) = TransactionBuilder::create_connector_tree_node_address(
             &self.signer.secp,
             &self.signer.xonly_public_key,
             &hashes.0,
+            self.connector_tree_operator_takes_after,
         )?;
 
         let (second_address, _) = TransactionBuilder::create_connector_tree_node_address(
             &self.signer.secp,
             &self.signer.xonly_public_key,
             &hashes.1,
+            self.connector_tree_operator_takes_after,
         )?;
 
         let mut tx = TransactionBuilder::create_connector_tree_tx(
             &utxo,
-            depth as usize - 1,
+            self.transaction_builder
+                .connector_tree_node_amount(depth - 1)?,
             first_address,
             second_address,
-        );
+            self.connector_tree_operator_takes_after,
+        )?;
         // tracing::debug!("created spend tx: {:?}", tx);
 
-        let sig = self.signer.sign_taproot_script_spend_tx(
-            &mut tx,
-            &vec![utxo_tx.output[utxo.vout as usize].clone()],
-            &timelock_script,
-            0,
-        )?;
+        let prevouts = vec![utxo_txout];
+        let sig =
+            self.signer
+                .sign_taproot_script_spend_tx(&mut tx, &prevouts, &timelock_script, 0)?;
         // let spend_control_block = tree_info
         //     .control_block(&(timelock_script.clone(), LeafVersion::TapScript))
         //     .expect("Cannot create control block");
@@ -434,36 +1552,288 @@ impl Operator {
         let mut witness_elements: Vec<&[u8]> = Vec::new();
         witness_elements.push(sig.as_ref());
 
-        handle_taproot_witness(&mut tx, 0, &witness_elements, &timelock_script, &tree_info)?;
+        check_witness_signature_count(&witness_elements, &timelock_script)?;
+        self.transaction_builder.handle_taproot_witness(
+            &mut tx,
+            0,
+            &witness_elements,
+            &timelock_script,
+            &tree_info,
+        )?;
+        self.transaction_builder.verify_script_spend(
+            &tx,
+            0,
+            &prevouts,
+            &timelock_script,
+            &tree_info,
+        )?;
+        Ok(tx)
+    }
+
+    /// Connector tree hashes of `period`, level by level from the root
+    fn get_connector_tree_hashes(&self, period: usize) -> HashTree {
+        (0..=self.connector_tree_depth)
+            .map(|level| {
+                (0..2usize.pow(level as u32))
+                    .map(|index| {
+                        self.operator_db_connector
+                            .get_connector_tree_hash(period, level, index)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<HashTree>()
+    }
 
-        // tracing::debug!("bytes_connector_tree_tx length: {:?}", bytes_connector_tree_tx.len());
-        // let hex_utxo_tx = hex::encode(bytes_utxo_tx.clone());
-        let _spending_txid = match self.rpc.send_raw_transaction(&tx) {
-            Ok(txid) => Some(txid),
-            Err(e) => {
-                tracing::error!("Failed to send raw transaction: {}", e);
-                None
+    /// Claim proof merkle roots of every period, computed from the operator's connector tree
+    /// hashes. These are the PERIODS_CLAIM_MT_ROOTS the bridge proof is checked against.
+    pub fn compute_period_claim_roots(&self) -> Result<Vec<[u8; 32]>, BridgeError> {
+        (0..NUM_ROUNDS)
+            .map(|period| {
+                calculate_claim_proof_root(
+                    self.connector_tree_depth,
+                    &self.get_connector_tree_hashes(period),
+                )
+            })
+            .collect()
+    }
+
+    /// Rebuilds the connector tree utxos of `period` from its root utxo, e.g. after a restart.
+    /// The tree is derived from the connector tree hashes and checked against the chain: the root
+    /// has to pay to the root address and every spent node has to be spent by its tree transaction.
+    pub fn rebuild_connector_tree_utxos(
+        &mut self,
+        period: usize,
+        root_utxo: OutPoint,
+    ) -> Result<(), BridgeError> {
+        if period >= NUM_ROUNDS {
+            return Err(BridgeError::InvalidPeriod);
+        }
+        let connector_tree_hashes = self.get_connector_tree_hashes(period);
+
+        let (root_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &self.signer.secp,
+            &self.signer.xonly_public_key,
+            &connector_tree_hashes[0][0],
+            self.connector_tree_operator_takes_after,
+        )?;
+        let root_tx = self.get_base_tx(&root_utxo.txid)?;
+        if root_tx
+            .output
+            .get(root_utxo.vout as usize)
+            .map(|txout| txout.script_pubkey != root_address.script_pubkey())
+            .unwrap_or(true)
+        {
+            return Err(BridgeError::ConnectorTreeSpendMismatch);
+        }
+
+        let utxo_tree = self.transaction_builder.create_connector_binary_tree(
+            period,
+            &self.signer.xonly_public_key,
+            &root_utxo,
+            self.connector_tree_depth,
+            connector_tree_hashes,
+        )?;
+        // Walk down the spent part of the tree, every child pair is created by one transaction
+        let mut spent = vec![root_utxo];
+        for levels in utxo_tree.windows(2) {
+            let mut spent_children = Vec::new();
+            for (parent, children) in levels[0].iter().zip(levels[1].chunks(2)) {
+                if !spent.contains(parent) || !self.rpc.is_utxo_spent(parent)? {
+                    continue;
+                }
+                let spend_tx = match self.get_base_tx(&children[0].txid) {
+                    Err(BridgeError::BaseTxNotFound) => {
+                        return Err(BridgeError::ConnectorTreeSpendMismatch)
+                    }
+                    result => result?,
+                };
+                if spend_tx.input[0].previous_output != *parent {
+                    return Err(BridgeError::ConnectorTreeSpendMismatch);
+                }
+                spent_children.extend_from_slice(children);
             }
-        };
-        // tracing::debug!("operator_spending_txid: {:?}", spending_txid);
+            spent = spent_children;
+        }
+
+        let mut utxo_trees = self.operator_db_connector.get_connector_tree_utxos();
+        if utxo_trees.len() <= period {
+            utxo_trees.resize(period + 1, Vec::new());
+        }
+        utxo_trees[period] = utxo_tree;
+        if let Some(db) = self.db_writer() {
+            db.set_connector_tree_utxos(utxo_trees);
+        }
         Ok(())
     }
 
+    /// Spends every connector tree node of the last inscribed period that is confirmed and not yet spent,
+    /// so spending the tree can be interrupted at any point and continued later. Nodes whose parent is
+    /// spent by this call are left for the next one, since their timelock needs them to be confirmed.
+    /// Returns the txids of the spending transactions
+    pub fn resume_connector_spends(&self) -> Result<Vec<Txid>, BridgeError> {
+        let period = self
+            .operator_db_connector
+            .get_inscription_txs_len()
+            .checked_sub(1)
+            .ok_or(BridgeError::InvalidPeriod)?;
+        let utxo_tree = self.operator_db_connector.get_connector_tree_utxo(period);
+
+        let mut spending_txids = Vec::new();
+        // Leaves are claimed one by one, only the inner nodes are spent here
+        for (level, utxos) in utxo_tree.iter().enumerate().take(self.connector_tree_depth) {
+            for (index, utxo) in utxos.iter().enumerate() {
+                if spending_txids.contains(&utxo.txid) || self.rpc.is_utxo_spent(utxo)? {
+                    continue;
+                }
+                let preimage = self
+                    .operator_db_connector
+                    .get_connector_tree_preimages(period, level, index);
+                spending_txids.push(self.spend_connector_tree_utxo(period, *utxo, preimage)?);
+            }
+        }
+        Ok(spending_txids)
+    }
+
+    /// Returns the claim proof merkle root of the given period, which should match
+    /// PERIOD_CLAIM_MT_ROOTS in the circuit, together with the operator's signature over it
+    pub fn sign_period_claim_root(
+        &self,
+        period: usize,
+    ) -> Result<(HashType, schnorr::Signature), BridgeError> {
+        if period >= NUM_ROUNDS {
+            return Err(BridgeError::InvalidPeriod);
+        }
+        let root = self
+            .operator_db_connector
+            .get_claim_proof_merkle_tree(period)
+            .root();
+        let sig = self.signer.sign_digest(root);
+        Ok((root, sig))
+    }
+
+    /// Generates the bit commitments of the bitVM instance of `period`, unless they are already
+    /// generated
+    fn commit_instance(&mut self, period: usize, rng: &mut impl RngCore) {
+        if period >= NUM_ROUNDS
+            || !self
+                .operator_db_connector
+                .get_bit_commitments(period)
+                .is_empty()
+        {
+            return;
+        }
+        let bit_commitments = generate_bit_commitments(rng, INSTANCE_BIT_COUNT);
+        if let Some(db) = self.db_writer() {
+            db.set_bit_commitments(period, bit_commitments);
+        }
+    }
+
+    /// The hashes of the bit commitments of the bitVM instance of `period`, which the operator
+    /// publishes. Empty until the instance is committed to: the first one in initial_setup,
+    /// every next one once the preimage reveal phase of the period before it starts.
+    pub fn bit_commitment_hashes(&self, period: usize) -> Vec<BitCommitmentHashes> {
+        self.operator_db_connector
+            .get_bit_commitments(period)
+            .iter()
+            .map(commitment_hashes)
+            .collect()
+    }
+
+    /// Checks a verifier's fraud proof against the claim root this operator committed to for
+    /// its period and the preimages it inscribed in it. Ok means the proof holds: the operator
+    /// did not reveal the preimages its actual claims require.
+    pub fn challenge_received(&self, proof: &FraudProof) -> Result<(), BridgeError> {
+        if proof.period >= NUM_ROUNDS {
+            return Err(BridgeError::InvalidPeriod);
+        }
+        let root = self
+            .operator_db_connector
+            .get_claim_proof_merkle_tree(proof.period)
+            .root();
+        if proof.claim_root != root {
+            return Err(BridgeError::InvalidFraudProof);
+        }
+        // A verifier leaving out reveals could make any reveal look incomplete
+        let mut inscribed = self
+            .operator_db_connector
+            .get_inscribed_preimages(proof.period);
+        inscribed.sort_unstable();
+        inscribed.dedup();
+        if proof.observed_reveals != inscribed {
+            return Err(BridgeError::InvalidFraudProof);
+        }
+        proof.validate(self.connector_tree_depth)
+    }
+
+    /// Answers a verifier's challenge of `step` of the bitVM instance of `period` by opening the
+    /// step's bit commitment with bit `step` of the period's claim proof merkle root. A step the
+    /// operator has not committed to can not be answered.
+    pub fn respond_to_challenge(
+        &self,
+        period: usize,
+        step: u32,
+    ) -> Result<ChallengeResponse, BridgeError> {
+        if period >= NUM_ROUNDS {
+            return Err(BridgeError::InvalidPeriod);
+        }
+        let preimages = self
+            .operator_db_connector
+            .get_bit_commitments(period)
+            .get(step as usize)
+            .copied()
+            .ok_or(BridgeError::InvalidChallengeStep)?;
+        let claim_root = self
+            .operator_db_connector
+            .get_claim_proof_merkle_tree(period)
+            .root();
+        let bit = hash_bit(&claim_root, step as usize);
+        let (hash, preimage) = open(bit, &preimages);
+        Ok(ChallengeResponse {
+            period,
+            step,
+            bit,
+            hash,
+            preimage,
+        })
+    }
+
     fn get_num_withdrawals_for_period(&self, _period: usize) -> u32 {
         self.operator_db_connector
             .get_withdrawals_merkle_tree_index() // TODO: This is not correct, we should have a cutoff
     }
 
+    /// Checks that the preimages of the current reveal period can be revealed without letting
+    /// anyone burn the connector leaf of deposit `index` first: either the leaf is not below a
+    /// revealed node, or the claim spending it is already broadcast
+    pub fn claim_before_reveal_check(&self, index: usize) -> Result<(), BridgeError> {
+        let period = self.get_current_preimage_reveal_period()?;
+        let number_of_funds_claim = self.get_num_withdrawals_for_period(period);
+        if !is_connector_leaf_revealed(self.connector_tree_depth, number_of_funds_claim, index)? {
+            return Ok(());
+        }
+        let connector_leaf = self.get_connector_leaf(period, index)?;
+        // The leaf is only spent by the claim, in the mempool or in a block
+        if !self.rpc.is_utxo_spent(&connector_leaf)? {
+            return Err(BridgeError::UnsafeRevealOrdering);
+        }
+        Ok(())
+    }
+
     /// This is called internally when every withdrawal for the current period is satisfied
     /// Double checks if all withdrawals are satisfied
     /// Checks that we are in the correct period, and withdrawal period has end for the given period
     /// inscribe the connector tree preimages to the blockchain
-    pub fn inscribe_connector_tree_preimages(
-        &mut self,
-    ) -> Result<(Vec<[u8; 32]>, Address), BridgeError> {
+    /// Returns the commit and reveal txids of the inscription
+    pub fn inscribe_connector_tree_preimages(&mut self) -> Result<(Txid, Txid), BridgeError> {
         tracing::debug!("inscribe_connector_tree_preimages");
         let period = self.get_current_preimage_reveal_period()?;
         tracing::debug!("period: {:?}", period);
+        self.inscribe_period_preimages(period)
+    }
+
+    /// Inscribes the connector tree preimages revealed for the withdrawals of `period`, see
+    /// inscribe_preimages. Nothing is recorded unless both the commit and reveal are sent.
+    fn inscribe_period_preimages(&mut self, period: usize) -> Result<(Txid, Txid), BridgeError> {
         if self.operator_db_connector.get_inscription_txs_len() != period {
             tracing::debug!(
                 "self.operator_db_connector.get_inscription_txs_len(): {:?}",
@@ -474,34 +1844,88 @@ impl Operator {
         let number_of_funds_claim = self.get_num_withdrawals_for_period(period);
         tracing::debug!("number_of_funds_claim: {:?}", number_of_funds_claim);
 
-        let indices = get_claim_reveal_indices(CONNECTOR_TREE_DEPTH, number_of_funds_claim);
+        let indices = get_claim_reveal_indices(self.connector_tree_depth, number_of_funds_claim)?;
         tracing::debug!("indices for preimages: {:?}", indices);
 
         let preimages_to_be_revealed = indices
             .iter()
-            .map(|(depth, index)| {
+            .map(|leaf| {
                 self.operator_db_connector
-                    .get_connector_tree_preimages(period, *depth, *index)
+                    .get_connector_tree_preimages(period, leaf.level, leaf.index)
             })
             .collect::<Vec<_>>();
 
-        // tracing::debug!("preimages_to_be_revealed: {:?}", preimages_to_be_revealed);
+        let (commit_utxo, reveal_txid) = self.send_inscription(&preimages_to_be_revealed)?;
+        tracing::info!(
+            period,
+            commit_txid = %commit_utxo.txid,
+            reveal_txid = %reveal_txid,
+            "Connector tree preimages inscribed"
+        );
 
-        let (commit_address, _commit_tree_info, _inscribe_preimage_script) =
-            self.transaction_builder.create_inscription_commit_address(
-                &self.signer.xonly_public_key,
-                &preimages_to_be_revealed,
-            )?;
+        if let Some(db) = self.db_writer() {
+            db.add_to_inscription_txs((commit_utxo, reveal_txid));
+            db.add_inscribed_preimages(period, preimages_to_be_revealed);
+        }
 
-        // tracing::debug!("script_pubkey: {:?}", commit_address.script_pubkey());
+        Ok((commit_utxo.txid, reveal_txid))
+    }
 
-        let commit_utxo = self.rpc.send_to_address(&commit_address, DUST_VALUE * 2)?;
+    /// Inscribes `preimages` with a commit and a reveal tx and returns their txids. The reveal
+    /// is only broadcast once the commit is in the mempool.
+    pub fn inscribe_preimages(
+        &self,
+        preimages: &[PreimageType],
+    ) -> Result<(Txid, Txid), BridgeError> {
+        let (commit_utxo, reveal_txid) = self.send_inscription(preimages)?;
+        Ok((commit_utxo.txid, reveal_txid))
+    }
+
+    /// Sends the commit and reveal txs of an inscription of `preimages`, returns the commit utxo
+    /// the reveal spends and the reveal txid. In dry run mode both are only built, the commit is
+    /// funded from the wallet without spending from it.
+    fn send_inscription(
+        &self,
+        preimages: &[PreimageType],
+    ) -> Result<(OutPoint, Txid), BridgeError> {
+        let preimages = preimages.to_vec();
+        let (commit_address, _commit_tree_info, _inscribe_preimage_script) = self
+            .transaction_builder
+            .create_inscription_commit_address(&self.signer.xonly_public_key, &preimages)?;
+
+        // tracing::debug!("script_pubkey: {:?}", commit_address.script_pubkey());
 
-        let mut reveal_tx = self.transaction_builder.create_inscription_reveal_tx(
-            commit_utxo,
+        let commit_value = self.transaction_builder.inscription_commit_value(
             &self.signer.xonly_public_key,
-            &preimages_to_be_revealed,
+            &preimages,
+            self.inscription_feerate,
         )?;
+        let balance = self.rpc.get_balance()?;
+        if balance < commit_value {
+            return Err(BridgeError::InsufficientInscriptionFunding(
+                commit_value - balance,
+            ));
+        }
+        let (commit_tx, commit_utxo) = self
+            .rpc
+            .create_funded_tx(&commit_address, commit_value.to_sat())?;
+        self.send_tx(&commit_tx).map_err(|e| {
+            tracing::error!("Inscription commit rejected: {}", e);
+            e
+        })?;
+        if !self.dry_run {
+            self.rpc
+                .wait_for_utxo(&commit_utxo, INSCRIPTION_COMMIT_TIMEOUT)?;
+        }
+
+        let mut reveal_tx = self
+            .transaction_builder
+            .create_inscription_reveal_tx_with_commit_value(
+                commit_utxo,
+                &self.signer.xonly_public_key,
+                &preimages,
+                commit_value,
+            )?;
 
         let sig = self
             .signer
@@ -509,15 +1933,18 @@ impl Operator {
 
         handle_taproot_witness_new(&mut reveal_tx, &vec![sig.as_ref()], 0)?;
 
-        let reveal_txid = self.rpc.send_raw_transaction(&reveal_tx.tx)?;
-
-        self.operator_db_connector
-            .add_to_inscription_txs((commit_utxo, reveal_txid));
-
-        self.operator_db_connector
-            .add_inscribed_preimages(period, preimages_to_be_revealed.clone());
+        self.transaction_builder
+            .verify_commit_reveal(&commit_tx, &reveal_tx.tx)?;
+        self.transaction_builder.verify_script_spend(
+            &reveal_tx.tx,
+            0,
+            &reveal_tx.prevouts,
+            &reveal_tx.scripts[0],
+            &reveal_tx.taproot_spend_infos[0],
+        )?;
 
-        Ok((preimages_to_be_revealed, commit_address))
+        let reveal_txid = self.send_tx(&reveal_tx.tx)?;
+        Ok((commit_utxo, reveal_txid))
     }
 
     /// Helper function for operator to write blocks to env
@@ -604,16 +2031,49 @@ impl Operator {
         Ok(())
     }
 
+    /// Writes the header chain from the circuit's light client checkpoint to `lc_blockhash` as the
+    /// light client's proof. Fails with UnsupportedNetwork if the node follows another network or
+    /// a chain without the checkpoint.
     fn write_lc_proof<E: Environment>(
         &self,
         lc_blockhash: BlockHash,
         withdrawal_mt_root: [u8; 32],
-    ) {
-        E::write_32bytes(lc_blockhash.to_byte_array());
-        E::write_32bytes(withdrawal_mt_root);
-    }
-
-    fn write_verifiers_challenge_proof<E: Environment>(
+    ) -> Result<(), BridgeError> {
+        let network = self
+            .rpc
+            .get_blockchain_info()
+            .map_err(|_| BridgeError::RpcError)?
+            .chain;
+        check_circuit_network(network)?;
+        let checkpoint = LcCheckpoint::circuit(
+            self.rpc
+                .get_block_header(&self.rpc.get_block_hash(LC_CHECKPOINT_HEIGHT as u64)?)?,
+        )?;
+        let lc_height = self.rpc.get_block_height_of(&lc_blockhash)? as u32;
+        if lc_height < checkpoint.height {
+            return Err(BridgeError::InvalidLcProof);
+        }
+        let mut block_headers = Vec::with_capacity((lc_height - checkpoint.height) as usize);
+        let mut blockhash = lc_blockhash;
+        for _ in checkpoint.height..lc_height {
+            let header = self.rpc.get_block_header(&blockhash)?;
+            blockhash = header.prev_blockhash;
+            block_headers.push(header);
+        }
+        if blockhash != checkpoint.header.block_hash() {
+            return Err(BridgeError::UnsupportedNetwork);
+        }
+        block_headers.reverse();
+        ENVWriter::<E>::write_lc_proof(&LcProof::new(
+            network,
+            checkpoint,
+            block_headers,
+            withdrawal_mt_root,
+        ));
+        Ok(())
+    }
+
+    fn write_verifiers_challenge_proof<E: Environment>(
         proof: [[u8; 32]; 4],
         challenge: VerifierChallenge,
     ) -> Result<(), BridgeError> {
@@ -736,7 +2196,7 @@ impl Operator {
         ENVWriter::<E>::write_blocks(k_deep_blocks.clone());
         tracing::debug!("WROTE k_deep_blocks: {:?}", k_deep_blocks);
 
-        self.write_lc_proof::<E>(lc_blockhash, withdrawal_mt.root());
+        self.write_lc_proof::<E>(lc_blockhash, withdrawal_mt.root())?;
         tracing::debug!("WROTE LC PROOF");
 
         let preimages: Vec<PreimageType> = self
@@ -874,19 +2334,18 @@ impl Operator {
             .set_period_relative_block_heights(period_relative_block_heights.clone());
 
         let (connector_tree_preimages, connector_tree_hashes) =
-            create_all_rounds_connector_preimages(CONNECTOR_TREE_DEPTH, NUM_ROUNDS, rng);
+            create_all_rounds_connector_preimages(self.connector_tree_depth, NUM_ROUNDS, rng);
         self.operator_db_connector
             .set_connector_tree_preimages(connector_tree_preimages);
         self.operator_db_connector
             .set_connector_tree_hashes(connector_tree_hashes.clone());
+        self.commit_instance(0, rng);
 
-        let single_tree_amount = calculate_amount(
-            CONNECTOR_TREE_DEPTH,
-            Amount::from_sat(DUST_VALUE),
-            Amount::from_sat(MIN_RELAY_FEE),
-        );
+        let single_tree_amount = self
+            .transaction_builder
+            .connector_tree_node_amount(self.connector_tree_depth)?;
         let total_amount =
-            Amount::from_sat((MIN_RELAY_FEE + single_tree_amount.to_sat()) * NUM_ROUNDS as u64);
+            (single_tree_amount + Amount::from_sat(MIN_RELAY_FEE)) * NUM_ROUNDS as u64;
         // tracing::debug!("total_amount: {:?}", total_amount);
         let (connector_tree_source_address, _) = self
             .transaction_builder
@@ -943,3 +2402,2741 @@ impl Operator {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::CONFIRMATION_BLOCK_COUNT;
+    use crate::script_builder::MAX_INSCRIPTION_ELEMENTS;
+    use crate::test_utils::MoveTxFixture;
+    use crate::user::User;
+    use crate::utils::{
+        calculate_amount, calculate_claim_proof_root, claim_proof_merkle_tree,
+        script_signature_count,
+    };
+    use crate::verifier::Verifier;
+    use bitcoincore_rpc::RpcApi;
+    use clementine_circuits::constants::ZEROES;
+    use secp256k1::rand::rngs::StdRng;
+    use secp256k1::rand::SeedableRng;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    /// Records the message and `txid` field of every info event
+    #[derive(Clone, Default)]
+    struct InfoEvents(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for InfoEvents {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+            struct Visitor(String, String);
+            impl Visit for Visitor {
+                fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                    match field.name() {
+                        "message" => self.0 = format!("{:?}", value),
+                        "txid" => self.1 = format!("{:?}", value),
+                        _ => {}
+                    }
+                }
+            }
+            if *event.metadata().level() != tracing::Level::INFO {
+                return;
+            }
+            let mut visitor = Visitor(String::new(), String::new());
+            event.record(&mut visitor);
+            self.0.lock().unwrap().push((visitor.0, visitor.1));
+        }
+    }
+
+    #[test]
+    fn test_compute_period_claim_roots() {
+        let mut rng = StdRng::from_seed([82u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+
+        // The roots are those of the claim merkle trees the circuit checks against, where no
+        // claims reveal the root of the connector tree
+        let roots = operator.compute_period_claim_roots().unwrap();
+        assert_eq!(roots.len(), NUM_ROUNDS);
+        for (period, root) in roots.iter().enumerate() {
+            let claim_tree = operator
+                .operator_db_connector
+                .get_claim_proof_merkle_tree(period);
+            assert_eq!(*root, claim_tree.root());
+            assert_eq!(
+                root_from_path(sha256_hash!(hashes[period][0][0]), 0, &claim_tree.path(0)),
+                *root
+            );
+        }
+
+        // A single level connector tree fills the first two leaves of the claim merkle tree,
+        // the rest of it is empty
+        let mut operator = create_test_operator_with_depth(&mut rng, 1).unwrap();
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let expected_roots = hashes
+            .iter()
+            .map(|tree| {
+                // No claims reveal the root, a single claim reveals the second leaf
+                let no_claims_leaf = sha256_hash!(tree[0][0]);
+                let one_claim_leaf = sha256_hash!(tree[1][1]);
+                ZEROES[1..CLAIM_MERKLE_TREE_DEPTH].iter().fold(
+                    sha256_hash!(no_claims_leaf, one_claim_leaf),
+                    |node, empty| sha256_hash!(node, *empty),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            operator.compute_period_claim_roots().unwrap(),
+            expected_roots
+        );
+        for (period, root) in expected_roots.iter().enumerate() {
+            assert_eq!(
+                operator
+                    .operator_db_connector
+                    .get_claim_proof_merkle_tree(period)
+                    .root(),
+                *root
+            );
+        }
+    }
+
+    #[test]
+    fn test_advance_period() {
+        let mut rng = StdRng::from_seed([83u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let start_block_height = 100;
+        operator
+            .operator_db_connector
+            .set_start_block_height(start_block_height);
+        operator
+            .operator_db_connector
+            .set_period_relative_block_heights(
+                (0..NUM_ROUNDS as u32 + 1)
+                    .map(|i| PERIOD_BLOCK_COUNT * (i + 1))
+                    .collect(),
+            );
+        // The preimages of every period are already inscribed, so no reveal needs a node
+        for i in 0..NUM_ROUNDS as u8 {
+            operator.operator_db_connector.add_to_inscription_txs((
+                OutPoint {
+                    txid: Txid::from_byte_array([i; 32]),
+                    vout: 0,
+                },
+                Txid::from_byte_array([i + 100; 32]),
+            ));
+        }
+        assert_eq!(operator.current_period(), Period::Withdrawal(0));
+        assert!(operator.bit_commitment_hashes(1).is_empty());
+
+        for period in 0..NUM_ROUNDS {
+            let period_end = start_block_height + PERIOD_BLOCK_COUNT as u64 * (period as u64 + 1);
+            let reveal_start = period_end - MAX_BLOCK_HANDLE_OPS as u64;
+            for height in [reveal_start - 1, reveal_start - 1] {
+                assert_eq!(
+                    operator.advance_period(height).unwrap(),
+                    Period::Withdrawal(period)
+                );
+            }
+            for height in [reveal_start, period_end - 1] {
+                assert_eq!(
+                    operator.advance_period(height).unwrap(),
+                    Period::PreimageReveal(period)
+                );
+            }
+            assert_eq!(operator.current_period(), Period::PreimageReveal(period));
+            // The next instance is committed to once, the reveal above did it already
+            if period + 1 < NUM_ROUNDS {
+                let hashes = operator.bit_commitment_hashes(period + 1);
+                assert_eq!(hashes.len(), INSTANCE_BIT_COUNT);
+                operator.advance_period(period_end - 1).unwrap();
+                assert_eq!(operator.bit_commitment_hashes(period + 1), hashes);
+            }
+        }
+        assert!(operator.bit_commitment_hashes(0).is_empty());
+        let last_period_end = start_block_height + PERIOD_BLOCK_COUNT as u64 * NUM_ROUNDS as u64;
+        assert_eq!(
+            operator.advance_period(last_period_end).unwrap(),
+            Period::Finished
+        );
+        assert_eq!(
+            operator.advance_period(last_period_end + 1000).unwrap(),
+            Period::Finished
+        );
+
+        // Periods never go back, e.g. after a reorg
+        assert_eq!(
+            operator.advance_period(start_block_height),
+            Err(BridgeError::InvalidPeriod)
+        );
+        assert_eq!(operator.current_period(), Period::Finished);
+    }
+
+    #[test]
+    fn test_connector_tree_operator_takes_after() {
+        let mut rng = StdRng::from_seed([52u8; 32]);
+        let operator = create_test_operator(&mut rng);
+        assert_eq!(
+            operator.connector_tree_operator_takes_after(),
+            CONNECTOR_TREE_OPERATOR_TAKES_AFTER
+        );
+        let operator = operator
+            .with_connector_tree_operator_takes_after(144)
+            .unwrap();
+        assert_eq!(operator.connector_tree_operator_takes_after(), 144);
+
+        let claim_tx = operator
+            .transaction_builder
+            .create_operator_claim_tx(
+                OutPoint::null(),
+                OutPoint::null(),
+                &operator.signer.address,
+                &operator.signer.xonly_public_key,
+                &[53u8; 32],
+            )
+            .unwrap();
+        assert_eq!(
+            claim_tx.scripts[1].instructions().next().unwrap().unwrap(),
+            bitcoin::script::Instruction::PushBytes(
+                <&bitcoin::script::PushBytes>::try_from(&[144u8, 0][..]).unwrap()
+            )
+        );
+        assert_eq!(
+            claim_tx.tx.input[1].sequence,
+            bitcoin::Sequence::from_height(144)
+        );
+        // The connector leaf the claim spends commits to the same delay
+        let (leaf_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &operator.signer.secp,
+            &operator.signer.xonly_public_key,
+            &[53u8; 32],
+            144,
+        )
+        .unwrap();
+        assert_eq!(
+            claim_tx.prevouts[1].script_pubkey,
+            leaf_address.script_pubkey()
+        );
+
+        for block_count in [0, u16::MAX as u32 + 1] {
+            assert_eq!(
+                create_test_operator(&mut rng)
+                    .with_connector_tree_operator_takes_after(block_count)
+                    .unwrap_err(),
+                BridgeError::InvalidTimelock
+            );
+        }
+    }
+
+    fn create_test_operator(rng: &mut StdRng) -> Operator {
+        create_test_operator_with_depth(rng, CONNECTOR_TREE_DEPTH).unwrap()
+    }
+
+    fn create_test_operator_with_depth(
+        rng: &mut StdRng,
+        connector_tree_depth: usize,
+    ) -> Result<Operator, BridgeError> {
+        #[cfg(feature = "stdout-logs")]
+        crate::init_stdout_logs();
+        let secp = secp256k1::Secp256k1::new();
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        Operator::new(
+            ExtendedRpc::new(),
+            all_xonly_pks,
+            all_sks[2],
+            Vec::new(),
+            vec![[1u8; 20], [2u8; 20]],
+            connector_tree_depth,
+        )
+    }
+
+    /// Spends an output of `value` paying to the operator's address back to it, minus `fee`
+    fn create_key_spend_tx(
+        operator: &Operator,
+        utxo: OutPoint,
+        value: Amount,
+        fee: u64,
+    ) -> Transaction {
+        let mut tx = Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: utxo,
+                script_sig: bitcoin::ScriptBuf::new(),
+                sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: bitcoin::Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: value - Amount::from_sat(fee),
+                script_pubkey: operator.signer.address.script_pubkey(),
+            }],
+        };
+        let prevouts = vec![TxOut {
+            value,
+            script_pubkey: operator.signer.address.script_pubkey(),
+        }];
+        let sig = operator
+            .signer
+            .sign_taproot_pubkey_spend_tx(&mut tx, &prevouts, 0)
+            .unwrap();
+        tx.input[0].witness.push(sig.as_ref());
+        tx
+    }
+
+    fn setup_connector_trees(operator: &mut Operator, rng: &mut StdRng) -> Vec<HashTree> {
+        let (preimages, hashes) =
+            create_all_rounds_connector_preimages(operator.connector_tree_depth, NUM_ROUNDS, rng);
+        let claim_proof_merkle_trees = hashes
+            .iter()
+            .map(|tree_hashes| {
+                claim_proof_merkle_tree(operator.connector_tree_depth, tree_hashes).unwrap()
+            })
+            .collect::<Vec<_>>();
+        operator
+            .operator_db_connector
+            .set_connector_tree_preimages(preimages);
+        operator
+            .operator_db_connector
+            .set_connector_tree_hashes(hashes.clone());
+        operator
+            .operator_db_connector
+            .set_claim_proof_merkle_trees(claim_proof_merkle_trees);
+        hashes
+    }
+
+    #[test]
+    fn test_sign_period_claim_root() {
+        let mut rng = StdRng::from_seed([1u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+
+        for (period, tree_hashes) in hashes.iter().enumerate() {
+            let (root, sig) = operator.sign_period_claim_root(period).unwrap();
+            assert_eq!(
+                root,
+                calculate_claim_proof_root(operator.connector_tree_depth, tree_hashes).unwrap()
+            );
+            operator
+                .signer
+                .secp
+                .verify_schnorr(
+                    &sig,
+                    &Message::from_digest_slice(&root).unwrap(),
+                    &operator.signer.xonly_public_key,
+                )
+                .unwrap();
+        }
+
+        assert_eq!(
+            operator.sign_period_claim_root(NUM_ROUNDS),
+            Err(BridgeError::InvalidPeriod)
+        );
+    }
+
+    #[test]
+    fn test_challenge_received() {
+        let mut rng = StdRng::from_seed([1u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let depth = operator.connector_tree_depth;
+        let period = 1;
+
+        // The operator revealed what 3 claims require but paid 1 withdrawal
+        let revealed = get_claim_reveal_indices(depth, 3)
+            .unwrap()
+            .iter()
+            .map(|node| {
+                operator
+                    .operator_db_connector
+                    .get_connector_tree_preimages(period, node.level, node.index)
+            })
+            .collect::<HashSet<_>>();
+        operator
+            .operator_db_connector
+            .add_inscribed_preimages(period, revealed.iter().copied().collect());
+        let claim_tree = operator
+            .operator_db_connector
+            .get_claim_proof_merkle_tree(period);
+        let proof = FraudProof::new(period, &hashes[period], &claim_tree, &revealed, 1).unwrap();
+        assert_eq!(operator.challenge_received(&proof), Ok(()));
+
+        // Reveals the operator did not inscribe
+        let mut extra_reveal = revealed.clone();
+        extra_reveal.insert([0u8; 32]);
+        let proof_with_extra =
+            FraudProof::new(period, &hashes[period], &claim_tree, &extra_reveal, 1).unwrap();
+        assert_eq!(
+            operator.challenge_received(&proof_with_extra),
+            Err(BridgeError::InvalidFraudProof)
+        );
+
+        // A proof against another period's root
+        let mut wrong_period = proof.clone();
+        wrong_period.period = 0;
+        assert_eq!(
+            operator.challenge_received(&wrong_period),
+            Err(BridgeError::InvalidFraudProof)
+        );
+        let mut wrong_period = proof;
+        wrong_period.period = NUM_ROUNDS;
+        assert_eq!(
+            operator.challenge_received(&wrong_period),
+            Err(BridgeError::InvalidPeriod)
+        );
+    }
+
+    #[test]
+    fn test_challenge_received_omitted_reveal() {
+        let mut rng = StdRng::from_seed([2u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let depth = operator.connector_tree_depth;
+        let period = 2;
+
+        // The operator revealed exactly what its 3 claims require
+        let required_nodes = get_claim_reveal_indices(depth, 3).unwrap();
+        let inscribed = required_nodes
+            .iter()
+            .map(|node| {
+                operator
+                    .operator_db_connector
+                    .get_connector_tree_preimages(period, node.level, node.index)
+            })
+            .collect::<Vec<_>>();
+        operator
+            .operator_db_connector
+            .add_inscribed_preimages(period, inscribed.clone());
+        let claim_tree = operator
+            .operator_db_connector
+            .get_claim_proof_merkle_tree(period);
+        let complete = inscribed.iter().copied().collect::<HashSet<_>>();
+        assert_eq!(
+            FraudProof::new(period, &hashes[period], &claim_tree, &complete, 3),
+            None
+        );
+
+        // A verifier leaving one of them out gets a proof that is consistent on its own
+        let omitted = inscribed[1..].iter().copied().collect::<HashSet<_>>();
+        let proof = FraudProof::new(period, &hashes[period], &claim_tree, &omitted, 3).unwrap();
+        assert_eq!(proof.unrevealed_nodes, vec![required_nodes[0]]);
+        assert_eq!(proof.validate(depth), Ok(()));
+        assert_eq!(
+            operator.challenge_received(&proof),
+            Err(BridgeError::InvalidFraudProof)
+        );
+    }
+
+    #[test]
+    fn test_respond_to_challenge() {
+        let mut rng = StdRng::from_seed([1u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        setup_connector_trees(&mut operator, &mut rng);
+        operator.commit_instance(2, &mut rng);
+        let commitments = operator.bit_commitment_hashes(2);
+        let claim_root = operator
+            .operator_db_connector
+            .get_claim_proof_merkle_tree(2)
+            .root();
+
+        for step in [0, 7, INSTANCE_BIT_COUNT as u32 - 1] {
+            let response = operator.respond_to_challenge(2, step).unwrap();
+            assert_eq!(response.bit, hash_bit(&claim_root, step as usize));
+            let hashes = commitments[step as usize];
+            assert_eq!(
+                response.hash,
+                if response.bit { hashes.1 } else { hashes.0 }
+            );
+            assert!(response.opens(&commitments));
+        }
+
+        // An opening does not open the other bit, another step or another instance
+        let response = operator.respond_to_challenge(2, 0).unwrap();
+        let flipped = ChallengeResponse {
+            bit: !response.bit,
+            ..response.clone()
+        };
+        assert!(!flipped.opens(&commitments));
+        let other_step = ChallengeResponse {
+            step: 1,
+            ..response.clone()
+        };
+        assert!(!other_step.opens(&commitments));
+        operator.commit_instance(1, &mut rng);
+        assert!(!response.opens(&operator.bit_commitment_hashes(1)));
+
+        // Steps outside the instance and instances not committed to have nothing to open
+        assert_eq!(
+            operator.respond_to_challenge(2, INSTANCE_BIT_COUNT as u32),
+            Err(BridgeError::InvalidChallengeStep)
+        );
+        assert_eq!(
+            operator.respond_to_challenge(3, 0),
+            Err(BridgeError::InvalidChallengeStep)
+        );
+        assert_eq!(
+            operator.respond_to_challenge(NUM_ROUNDS, 0),
+            Err(BridgeError::InvalidPeriod)
+        );
+    }
+
+    #[test]
+    fn test_operator_builder() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([3u8; 32]);
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        let builder = || {
+            Operator::builder(all_xonly_pks.clone(), all_sks[2])
+                .verifier_evm_addresses(vec![[1u8; 20], [2u8; 20]])
+        };
+
+        let operator = builder().build().unwrap();
+        assert_eq!(operator.connector_tree_depth, CONNECTOR_TREE_DEPTH);
+        assert_eq!(
+            operator.connector_tree_operator_takes_after(),
+            CONNECTOR_TREE_OPERATOR_TAKES_AFTER
+        );
+        assert!(!operator.dry_run);
+
+        let operator = builder()
+            .connector_tree_depth(2)
+            .connector_tree_operator_takes_after(7)
+            .anchor_kind(AnchorKind::P2aEphemeral)
+            .inscription_feerate(12)
+            .dry_run(true)
+            .build()
+            .unwrap();
+        assert_eq!(operator.connector_tree_depth, 2);
+        assert_eq!(operator.connector_tree_operator_takes_after(), 7);
+        assert_eq!(
+            operator
+                .transaction_builder
+                .connector_tree_operator_takes_after,
+            7
+        );
+        assert_eq!(
+            operator.transaction_builder.anchor_kind,
+            AnchorKind::P2aEphemeral
+        );
+        assert_eq!(operator.inscription_feerate, 12);
+        assert!(operator.dry_run);
+
+        assert_eq!(
+            builder().connector_tree_depth(0).build().unwrap_err(),
+            BridgeError::InvalidConnectorTreeDepth
+        );
+        assert_eq!(
+            builder()
+                .connector_tree_operator_takes_after(0)
+                .build()
+                .unwrap_err(),
+            BridgeError::InvalidTimelock
+        );
+        assert_eq!(
+            Operator::builder(all_xonly_pks.clone(), all_sks[2])
+                .build()
+                .unwrap_err(),
+            BridgeError::VerifierEvmAddressesMismatch
+        );
+
+        // Without verifier keys no evm address is needed, so the defaults alone build
+        let operator = Operator::builder(vec![all_xonly_pks[2]], all_sks[2])
+            .build()
+            .unwrap();
+        assert_eq!(operator.connector_tree_depth, CONNECTOR_TREE_DEPTH);
+        assert!(operator.verifier_evm_addresses.is_empty());
+        assert!(operator.verifier_connector.is_empty());
+        assert_eq!(operator.inscription_feerate, MIN_RELAY_FEERATE);
+        assert!(!operator.dry_run);
+    }
+
+    #[test]
+    fn test_connector_tree_depth() {
+        let mut rng = StdRng::from_seed([2u8; 32]);
+        let mut operator = create_test_operator_with_depth(&mut rng, 4).unwrap();
+        assert_eq!(operator.connector_tree_depth, 4);
+
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        assert_eq!(hashes.len(), NUM_ROUNDS);
+        for tree_hashes in hashes.iter() {
+            assert_eq!(tree_hashes.len(), 5);
+            for (level, level_hashes) in tree_hashes.iter().enumerate() {
+                assert_eq!(level_hashes.len(), 2usize.pow(level as u32));
+            }
+        }
+
+        for depth in 1..=CLAIM_MERKLE_TREE_DEPTH {
+            let operator = create_test_operator_with_depth(&mut rng, depth).unwrap();
+            assert_eq!(operator.connector_tree_depth, depth);
+        }
+        for depth in [0, CLAIM_MERKLE_TREE_DEPTH + 1] {
+            assert_eq!(
+                create_test_operator_with_depth(&mut rng, depth).unwrap_err(),
+                BridgeError::InvalidConnectorTreeDepth
+            );
+        }
+
+        // Without keys there is no operator key
+        assert_eq!(
+            Operator::new(
+                ExtendedRpc::new(),
+                Vec::new(),
+                SecretKey::new(&mut rng),
+                Vec::new(),
+                Vec::new(),
+                CONNECTOR_TREE_DEPTH,
+            )
+            .unwrap_err(),
+            BridgeError::InvalidOperatorKey
+        );
+    }
+
+    #[test]
+    fn test_prune_stale_presigns() {
+        let mut rng = StdRng::from_seed([3u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let deposit_txid = Txid::from_byte_array([7u8; 32]);
+        operator
+            .operator_db_connector
+            .add_deposit_take_sigs(OperatorClaimSigs {
+                operator_claim_sigs: Vec::new(),
+                deposit_txid,
+                height: 100,
+            });
+
+        assert!(operator.prune_stale_presigns(105, 10).is_empty());
+        assert!(operator.prune_stale_presigns(110, 10).is_empty());
+        assert_eq!(
+            operator.operator_db_connector.get_deposit_take_sigs().len(),
+            1
+        );
+
+        assert_eq!(operator.prune_stale_presigns(111, 10), vec![deposit_txid]);
+        assert!(operator
+            .operator_db_connector
+            .get_deposit_take_sigs()
+            .is_empty());
+        // Pruning does not free the connector tree leaf of the deposit
+        assert_eq!(operator.operator_db_connector.get_deposit_index(), 1);
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_spend_connector_tree_utxo() {
+        let mut rng = StdRng::from_seed([4u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let root_preimage = operator
+            .operator_db_connector
+            .get_connector_tree_preimages(0, 0, 0);
+
+        let (root_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &operator.signer.secp,
+            &operator.signer.xonly_public_key,
+            &hashes[0][0][0],
+            operator.connector_tree_operator_takes_after,
+        )
+        .unwrap();
+        let root_amount = operator.connector_tree_funding(MIN_RELAY_FEERATE).unwrap();
+        let root_utxo = operator
+            .rpc
+            .send_to_address(&root_address, root_amount.to_sat())
+            .unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+
+        // An output the root tx does not have
+        let missing_utxo = OutPoint {
+            txid: root_utxo.txid,
+            vout: 1000,
+        };
+        assert_eq!(
+            operator.spend_connector_tree_utxo(0, missing_utxo, root_preimage),
+            Err(BridgeError::TxOutputNotFound)
+        );
+
+        let txid = operator
+            .spend_connector_tree_utxo(0, root_utxo, root_preimage)
+            .unwrap();
+        let spending_tx = operator.rpc.get_raw_transaction(&txid, None).unwrap();
+        assert_eq!(spending_tx.input[0].previous_output, root_utxo);
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_spend_connector_tree_level() {
+        let mut rng = StdRng::from_seed([32u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+
+        let (root_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &operator.signer.secp,
+            &operator.signer.xonly_public_key,
+            &hashes[0][0][0],
+            operator.connector_tree_operator_takes_after,
+        )
+        .unwrap();
+        let root_amount = operator.connector_tree_funding(MIN_RELAY_FEERATE).unwrap();
+        let root_utxo = operator
+            .rpc
+            .send_to_address(&root_address, root_amount.to_sat())
+            .unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+        let root_preimage = operator
+            .operator_db_connector
+            .get_connector_tree_preimages(0, 0, 0);
+        let root_spend_txid = operator
+            .spend_connector_tree_utxo(0, root_utxo, root_preimage)
+            .unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+
+        let mut utxo_tree = vec![Vec::new(); operator.connector_tree_depth + 1];
+        utxo_tree[0].push(root_utxo);
+        for vout in 0..2 {
+            utxo_tree[1].push(OutPoint {
+                txid: root_spend_txid,
+                vout,
+            });
+        }
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(vec![utxo_tree.clone()]);
+
+        // The serial path builds the same transactions one by one
+        let preimages = operator
+            .operator_db_connector
+            .get_connector_tree_preimages_level(0, 1);
+        let serial_txids = utxo_tree[1]
+            .iter()
+            .zip(preimages.iter())
+            .map(|(utxo, preimage)| {
+                operator
+                    .create_connector_tree_spend_tx(0, *utxo, *preimage)
+                    .unwrap()
+                    .txid()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            operator.spend_connector_tree_level(0, 1, &preimages[..1]),
+            Err(BridgeError::PreimageNotFound)
+        );
+        assert_eq!(
+            operator
+                .spend_connector_tree_level(0, 1, &preimages)
+                .unwrap(),
+            serial_txids
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_rebuild_connector_tree_utxos() {
+        let mut rng = StdRng::from_seed([34u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+
+        let (root_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &operator.signer.secp,
+            &operator.signer.xonly_public_key,
+            &hashes[0][0][0],
+            operator.connector_tree_operator_takes_after,
+        )
+        .unwrap();
+        let root_amount = operator.connector_tree_funding(MIN_RELAY_FEERATE).unwrap();
+        let root_utxo = operator
+            .rpc
+            .send_to_address(&root_address, root_amount.to_sat())
+            .unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+        let expected_tree = operator
+            .transaction_builder
+            .create_connector_binary_tree(
+                0,
+                &operator.signer.xonly_public_key,
+                &root_utxo,
+                operator.connector_tree_depth,
+                hashes[0].clone(),
+            )
+            .unwrap();
+
+        // Spend the root and both nodes of the first level
+        for (level, utxos) in expected_tree.iter().enumerate().take(2) {
+            for (index, utxo) in utxos.iter().enumerate() {
+                let preimage = operator
+                    .operator_db_connector
+                    .get_connector_tree_preimages(0, level, index);
+                operator
+                    .spend_connector_tree_utxo(0, *utxo, preimage)
+                    .unwrap();
+            }
+            operator.rpc.mine_blocks(1).unwrap();
+        }
+
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(Vec::new());
+        operator.rebuild_connector_tree_utxos(0, root_utxo).unwrap();
+        assert_eq!(
+            operator.operator_db_connector.get_connector_tree_utxo(0),
+            expected_tree
+        );
+
+        // A root utxo that does not pay to the root address
+        assert_eq!(
+            operator.rebuild_connector_tree_utxos(0, expected_tree[1][0]),
+            Err(BridgeError::ConnectorTreeSpendMismatch)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_dry_run_spend_connector_tree_utxo() {
+        let mut rng = StdRng::from_seed([37u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let root_preimage = operator
+            .operator_db_connector
+            .get_connector_tree_preimages(0, 0, 0);
+
+        let (root_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &operator.signer.secp,
+            &operator.signer.xonly_public_key,
+            &hashes[0][0][0],
+            operator.connector_tree_operator_takes_after,
+        )
+        .unwrap();
+        let root_amount = operator.connector_tree_funding(MIN_RELAY_FEERATE).unwrap();
+        let root_utxo = operator
+            .rpc
+            .send_to_address(&root_address, root_amount.to_sat())
+            .unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+
+        operator.dry_run = true;
+        let txid = operator
+            .spend_connector_tree_utxo(0, root_utxo, root_preimage)
+            .unwrap();
+        assert!(!operator.rpc.is_utxo_spent(&root_utxo).unwrap());
+        let dry_run_txs = operator.take_dry_run_txs();
+        assert_eq!(dry_run_txs.len(), 1);
+        assert_eq!(dry_run_txs[0].txid(), txid);
+        assert!(operator.take_dry_run_txs().is_empty());
+
+        assert_eq!(operator.rpc.test_accept(&dry_run_txs[0]), Ok(()));
+        assert_eq!(operator.broadcast(&dry_run_txs[0]).unwrap(), txid);
+        assert!(operator.rpc.is_utxo_spent(&root_utxo).unwrap());
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_spend_connector_tree_utxo_base_tx_not_found() {
+        let mut rng = StdRng::from_seed([5u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        setup_connector_trees(&mut operator, &mut rng);
+        let root_preimage = operator
+            .operator_db_connector
+            .get_connector_tree_preimages(0, 0, 0);
+
+        let fabricated_utxo = OutPoint {
+            txid: Txid::from_byte_array([9u8; 32]),
+            vout: 0,
+        };
+        assert_eq!(
+            operator.spend_connector_tree_utxo(0, fabricated_utxo, root_preimage),
+            Err(BridgeError::BaseTxNotFound)
+        );
+    }
+
+    #[test]
+    fn test_validate_move_value_conservation() {
+        let mut rng = StdRng::from_seed([6u8; 32]);
+        let operator = create_test_operator(&mut rng);
+        let deposit_utxo = OutPoint {
+            txid: Txid::from_byte_array([7u8; 32]),
+            vout: 0,
+        };
+        let move_tx = operator
+            .transaction_builder
+            .create_move_tx(deposit_utxo, &[1u8; 20], &operator.signer.xonly_public_key)
+            .unwrap()
+            .tx;
+        let deposit_amount = Amount::from_sat(BRIDGE_AMOUNT_SATS);
+        assert!(operator
+            .validate_move_value_conservation(&move_tx, deposit_amount)
+            .is_ok());
+
+        let mut leaking_move_tx = move_tx.clone();
+        leaking_move_tx.output[0].value -= Amount::from_sat(1);
+        assert_eq!(
+            operator.validate_move_value_conservation(&leaking_move_tx, deposit_amount),
+            Err(BridgeError::MoveTxValueMismatch)
+        );
+    }
+
+    #[test]
+    fn test_get_connector_tree_node_children() {
+        let mut rng = StdRng::from_seed([8u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let depth = operator.connector_tree_depth;
+        let root_amount = operator.connector_tree_funding(MIN_RELAY_FEERATE).unwrap();
+        let root_preimage = operator
+            .operator_db_connector
+            .get_connector_tree_preimages(0, 0, 0);
+
+        assert_eq!(
+            operator.get_connector_tree_node_children(0, root_amount, &root_preimage),
+            Ok((depth, (hashes[0][1][0], hashes[0][1][1])))
+        );
+
+        // A preimage of another period does not belong to this tree
+        let foreign_preimage = operator
+            .operator_db_connector
+            .get_connector_tree_preimages(1, 0, 0);
+        assert_eq!(
+            operator.get_connector_tree_node_children(0, root_amount, &foreign_preimage),
+            Err(BridgeError::PreimageNotInTree)
+        );
+
+        // Leaves can not be spent into further connector tree nodes
+        let leaf_preimage = operator
+            .operator_db_connector
+            .get_connector_tree_preimages(0, depth, 0);
+        assert_eq!(
+            operator.get_connector_tree_node_children(
+                0,
+                Amount::from_sat(DUST_VALUE),
+                &leaf_preimage
+            ),
+            Err(BridgeError::InvalidConnectorTreeUtxoValue)
+        );
+        assert_eq!(
+            operator.get_connector_tree_node_children(0, Amount::ZERO, &leaf_preimage),
+            Err(BridgeError::InvalidConnectorTreeUtxoValue)
+        );
+    }
+
+    #[test]
+    fn test_verifiers() {
+        let mut rng = StdRng::from_seed([9u8; 32]);
+        let operator = create_test_operator(&mut rng);
+        let verifiers = operator.verifiers();
+        assert_eq!(verifiers.len(), operator.verifier_evm_addresses.len());
+        for (i, verifier) in verifiers.iter().enumerate() {
+            assert_eq!(verifier.xonly_pk, operator.verifiers_pks[i]);
+            assert_eq!(verifier.evm_address, operator.verifier_evm_addresses[i]);
+        }
+
+        let secp = secp256k1::Secp256k1::new();
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        assert_eq!(
+            Operator::new(
+                ExtendedRpc::new(),
+                all_xonly_pks,
+                all_sks[2],
+                Vec::new(),
+                vec![[1u8; 20]],
+                CONNECTOR_TREE_DEPTH,
+            )
+            .err(),
+            Some(BridgeError::VerifierEvmAddressesMismatch)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_earliest_claim_height() {
+        let mut rng = StdRng::from_seed([10u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let depth = operator.connector_tree_depth;
+
+        let (leaf_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &operator.signer.secp,
+            &operator.signer.xonly_public_key,
+            &hashes[0][depth][0],
+            operator.connector_tree_operator_takes_after,
+        )
+        .unwrap();
+        let leaf_utxo = operator
+            .rpc
+            .send_to_address(&leaf_address, DUST_VALUE)
+            .unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+        let confirmation_height = operator.rpc.get_block_height().unwrap();
+        operator.rpc.mine_blocks(2).unwrap();
+
+        // Only the leaf of the first deposit is needed, the rest of the tree is never looked at
+        let mut utxo_tree = vec![Vec::new(); depth + 1];
+        utxo_tree[depth].push(leaf_utxo);
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(vec![utxo_tree]);
+        operator
+            .operator_db_connector
+            .set_start_block_height(confirmation_height);
+        operator
+            .operator_db_connector
+            .set_period_relative_block_heights(vec![PERIOD_BLOCK_COUNT]);
+
+        assert_eq!(
+            operator.earliest_claim_height(0).unwrap(),
+            confirmation_height as u32 + CONNECTOR_TREE_OPERATOR_TAKES_AFTER as u32
+        );
+        assert_eq!(
+            operator.earliest_claim_height(1),
+            Err(BridgeError::ConnectorLeafNotFound)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_can_claim() {
+        let mut rng = StdRng::from_seed([76u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let depth = operator.connector_tree_depth;
+
+        let (leaf_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &operator.signer.secp,
+            &operator.signer.xonly_public_key,
+            &hashes[0][depth][0],
+            operator.connector_tree_operator_takes_after,
+        )
+        .unwrap();
+        let leaf_utxo = operator
+            .rpc
+            .send_to_address(&leaf_address, DUST_VALUE)
+            .unwrap();
+        let mut utxo_tree = vec![Vec::new(); depth + 1];
+        utxo_tree[depth].push(leaf_utxo);
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(vec![utxo_tree]);
+        operator
+            .operator_db_connector
+            .set_start_block_height(operator.rpc.get_block_height().unwrap());
+        operator
+            .operator_db_connector
+            .set_period_relative_block_heights(vec![PERIOD_BLOCK_COUNT]);
+
+        // Too early, the leaf is not even confirmed
+        assert_eq!(operator.can_claim(0), Ok(false));
+        operator
+            .rpc
+            .mine_blocks(CONNECTOR_TREE_OPERATOR_TAKES_AFTER as u64)
+            .unwrap();
+        assert_eq!(operator.can_claim(0), Ok(true));
+        assert_eq!(
+            operator.can_claim(1),
+            Err(BridgeError::ConnectorLeafNotFound)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_claim_deposit() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([77u8; 32]);
+        let actors = (0..4)
+            .map(|_| Actor::new(secp.generate_keypair(&mut rng).0))
+            .collect::<Vec<_>>();
+        let mut operator = Operator::new(
+            ExtendedRpc::new(),
+            actors.iter().map(|actor| actor.xonly_public_key).collect(),
+            actors[3].secret_key,
+            Vec::new(),
+            vec![[1u8; 20], [2u8; 20], [3u8; 20]],
+            CONNECTOR_TREE_DEPTH,
+        )
+        .unwrap();
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let depth = operator.connector_tree_depth;
+
+        // A single tx stands in for the move tx (output 0) and the connector tree (output 1)
+        let (bridge_address, _) = operator
+            .transaction_builder
+            .generate_bridge_address()
+            .unwrap();
+        let (leaf_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &secp,
+            &operator.signer.xonly_public_key,
+            &hashes[0][depth][0],
+            operator.connector_tree_operator_takes_after,
+        )
+        .unwrap();
+        let bridge_value = Amount::from_sat(BRIDGE_AMOUNT_SATS - MIN_RELAY_FEE)
+            - ScriptBuilder::anyone_can_spend_txout().value;
+        let funding_value = bridge_value + Amount::from_sat(DUST_VALUE + MIN_RELAY_FEE);
+        let funding_utxo = operator
+            .rpc
+            .send_to_address(&operator.signer.address, funding_value.to_sat())
+            .unwrap();
+        let mut move_tx =
+            create_key_spend_tx(&operator, funding_utxo, funding_value, MIN_RELAY_FEE);
+        move_tx.output = vec![
+            TxOut {
+                value: bridge_value,
+                script_pubkey: bridge_address.script_pubkey(),
+            },
+            TxOut {
+                value: Amount::from_sat(DUST_VALUE),
+                script_pubkey: leaf_address.script_pubkey(),
+            },
+        ];
+        move_tx.input[0].witness = bitcoin::Witness::new();
+        let sig = operator
+            .signer
+            .sign_taproot_pubkey_spend_tx(
+                &mut move_tx,
+                &vec![TxOut {
+                    value: funding_value,
+                    script_pubkey: operator.signer.address.script_pubkey(),
+                }],
+                0,
+            )
+            .unwrap();
+        move_tx.input[0].witness.push(sig.as_ref());
+        let move_txid = operator.rpc.send_raw_transaction(&move_tx).unwrap();
+        let move_utxo = OutPoint {
+            txid: move_txid,
+            vout: 0,
+        };
+        let leaf_utxo = OutPoint {
+            txid: move_txid,
+            vout: 1,
+        };
+
+        let mut utxo_tree = vec![Vec::new(); depth + 1];
+        utxo_tree[depth].push(leaf_utxo);
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(vec![utxo_tree]);
+        operator
+            .operator_db_connector
+            .set_start_block_height(operator.rpc.get_block_height().unwrap());
+        operator
+            .operator_db_connector
+            .set_period_relative_block_heights(vec![PERIOD_BLOCK_COUNT]);
+        operator
+            .operator_db_connector
+            .add_move_tx((funding_utxo, move_tx));
+
+        // The verifiers presign the claim of the first period
+        let mut claim_tx = operator
+            .transaction_builder
+            .create_operator_claim_tx(
+                move_utxo,
+                leaf_utxo,
+                &operator.signer.address,
+                &operator.signer.xonly_public_key,
+                &hashes[0][depth][0],
+            )
+            .unwrap();
+        let operator_claim_sigs = actors[..3]
+            .iter()
+            .map(|actor| {
+                vec![actor
+                    .sign_taproot_script_spend_tx_new(&mut claim_tx, 0)
+                    .unwrap()]
+            })
+            .collect::<Vec<_>>();
+        operator
+            .operator_db_connector
+            .add_deposit_take_sigs(OperatorClaimSigs {
+                operator_claim_sigs,
+                deposit_txid: funding_utxo.txid,
+                height: 0,
+            });
+
+        // The connector leaf is not confirmed yet
+        assert_eq!(operator.claim_deposit(0), Err(BridgeError::NotYetSpendable));
+        operator
+            .rpc
+            .mine_blocks(CONNECTOR_TREE_OPERATOR_TAKES_AFTER as u64)
+            .unwrap();
+
+        let info_events = InfoEvents::default();
+        let claim_txid = tracing::subscriber::with_default(
+            tracing_subscriber::registry().with(info_events.clone()),
+            || operator.claim_deposit(0).unwrap(),
+        );
+        assert!(info_events
+            .0
+            .lock()
+            .unwrap()
+            .contains(&("Deposit claimed".to_string(), claim_txid.to_string())));
+        assert_eq!(claim_txid, claim_tx.tx.txid());
+        operator.rpc.mine_blocks(1).unwrap();
+        let mined_claim_tx = operator
+            .rpc
+            .get_raw_transaction_info(&claim_txid, None)
+            .unwrap();
+        assert!(mined_claim_tx.blockhash.is_some());
+        assert_eq!(
+            mined_claim_tx.transaction().unwrap().input[0].previous_output,
+            move_utxo
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_claim_deposit_index_out_of_range() {
+        let mut rng = StdRng::from_seed([78u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        operator
+            .operator_db_connector
+            .set_start_block_height(operator.rpc.get_block_height().unwrap());
+        operator
+            .operator_db_connector
+            .set_period_relative_block_heights(vec![PERIOD_BLOCK_COUNT]);
+        assert_eq!(
+            operator.claim_deposit(0),
+            Err(BridgeError::ClaimIndexOutOfRange("move txs"))
+        );
+
+        // One deposit is recorded, but its connector leaf is not
+        let deposit_utxo = OutPoint {
+            txid: Txid::from_byte_array([79u8; 32]),
+            vout: 0,
+        };
+        operator
+            .operator_db_connector
+            .add_deposit_take_sigs(OperatorClaimSigs {
+                operator_claim_sigs: Vec::new(),
+                deposit_txid: deposit_utxo.txid,
+                height: 0,
+            });
+        let move_tx = create_key_spend_tx(
+            &operator,
+            deposit_utxo,
+            Amount::from_sat(BRIDGE_AMOUNT_SATS),
+            MIN_RELAY_FEE,
+        );
+        operator
+            .operator_db_connector
+            .add_move_tx((deposit_utxo, move_tx));
+        assert_eq!(
+            operator.claim_deposit(0),
+            Err(BridgeError::ClaimIndexOutOfRange("connector tree leaves"))
+        );
+
+        // Past the last recorded deposit
+        let mut utxo_tree = vec![Vec::new(); operator.connector_tree_depth + 1];
+        utxo_tree[operator.connector_tree_depth].push(OutPoint {
+            txid: Txid::from_byte_array([80u8; 32]),
+            vout: 0,
+        });
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(vec![utxo_tree]);
+        assert_eq!(
+            operator.claim_deposit(1),
+            Err(BridgeError::ClaimIndexOutOfRange("move txs"))
+        );
+    }
+
+    #[test]
+    fn test_get_deposit_claim_data() {
+        let mut rng = StdRng::from_seed([86u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let deposit_utxos = (0..3u8)
+            .map(|i| OutPoint {
+                txid: Txid::from_byte_array([i + 1; 32]),
+                vout: 0,
+            })
+            .collect::<Vec<_>>();
+        for (i, deposit_utxo) in deposit_utxos.iter().enumerate() {
+            let move_tx = create_key_spend_tx(
+                &operator,
+                *deposit_utxo,
+                Amount::from_sat(BRIDGE_AMOUNT_SATS),
+                MIN_RELAY_FEE,
+            );
+            operator
+                .operator_db_connector
+                .add_move_tx((*deposit_utxo, move_tx));
+            operator
+                .operator_db_connector
+                .add_deposit_take_sigs(OperatorClaimSigs {
+                    operator_claim_sigs: Vec::new(),
+                    deposit_txid: deposit_utxo.txid,
+                    height: 100 + i as u32 * 10,
+                });
+        }
+        let move_txids = operator
+            .operator_db_connector
+            .get_move_txs()
+            .iter()
+            .map(|(_, move_tx)| move_tx.txid())
+            .collect::<Vec<_>>();
+
+        // Claimed out of order, every deposit gets its own presigns
+        for index in [2, 0, 1] {
+            let (move_tx, claim_sigs) = operator.get_deposit_claim_data(index).unwrap();
+            assert_eq!(move_tx.txid(), move_txids[index]);
+            assert_eq!(claim_sigs.deposit_txid, deposit_utxos[index].txid);
+        }
+
+        // Pruning the first deposit's presigns does not shift the others onto its index
+        assert_eq!(
+            operator.prune_stale_presigns(115, 10),
+            vec![deposit_utxos[0].txid]
+        );
+        assert_eq!(
+            operator.get_deposit_claim_data(0).map(|_| ()),
+            Err(BridgeError::FailedToGetPresigns)
+        );
+        for index in [2, 1] {
+            let (move_tx, claim_sigs) = operator.get_deposit_claim_data(index).unwrap();
+            assert_eq!(move_tx.txid(), move_txids[index]);
+            assert_eq!(claim_sigs.deposit_txid, deposit_utxos[index].txid);
+        }
+        assert_eq!(
+            operator.get_deposit_claim_data(3).map(|_| ()),
+            Err(BridgeError::ClaimIndexOutOfRange("move txs"))
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_bump_fee_returns_change() {
+        let mut rng = StdRng::from_seed([81u8; 32]);
+        let operator = create_test_operator(&mut rng);
+        let anchor_txout = ScriptBuilder::anyone_can_spend_txout();
+
+        // A parent paying just the relay fee, with an anyone can spend output to bump it
+        let funding_value = Amount::from_sat(100_000);
+        let funding_utxo = operator
+            .rpc
+            .send_to_address(&operator.signer.address, funding_value.to_sat())
+            .unwrap();
+        let mut parent_tx = create_key_spend_tx(
+            &operator,
+            funding_utxo,
+            funding_value,
+            MIN_RELAY_FEE + anchor_txout.value.to_sat(),
+        );
+        parent_tx.output.push(anchor_txout.clone());
+        parent_tx.input[0].witness = bitcoin::Witness::new();
+        let parent_prevouts = vec![TxOut {
+            value: funding_value,
+            script_pubkey: operator.signer.address.script_pubkey(),
+        }];
+        let sig = operator
+            .signer
+            .sign_taproot_pubkey_spend_tx(&mut parent_tx, &parent_prevouts, 0)
+            .unwrap();
+        parent_tx.input[0].witness.push(sig.as_ref());
+        operator.rpc.send_raw_transaction(&parent_tx).unwrap();
+        let parent = CreateTxOutputs {
+            tx: parent_tx,
+            prevouts: parent_prevouts,
+            scripts: vec![],
+            taproot_spend_infos: vec![],
+        };
+
+        // The resource utxo holds far more than the package needs
+        let resource_value = Amount::from_sat(BRIDGE_AMOUNT_SATS);
+        let resource_utxo = operator
+            .rpc
+            .send_to_address(&operator.signer.address, resource_value.to_sat())
+            .unwrap();
+        let child_txid = operator
+            .bump_fee(&parent, 1, resource_utxo, resource_value, 20)
+            .unwrap();
+
+        let child_tx = operator.rpc.get_raw_transaction(&child_txid, None).unwrap();
+        assert_eq!(child_tx.output.len(), 1);
+        assert_eq!(
+            child_tx.output[0].script_pubkey,
+            operator.signer.address.script_pubkey()
+        );
+        let child_fee = anchor_txout.value + resource_value - child_tx.output[0].value;
+        assert!(child_fee < Amount::from_sat(20 * 1_000));
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_verify_reveal_in_period() {
+        let mut rng = StdRng::from_seed([25u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+
+        let reveal_utxo = operator
+            .rpc
+            .send_to_address(&operator.signer.address, DUST_VALUE)
+            .unwrap();
+        assert_eq!(
+            operator.verify_reveal_in_period(reveal_utxo.txid, 0),
+            Err(BridgeError::InvalidPeriod)
+        );
+
+        operator.rpc.mine_blocks(1).unwrap();
+        let confirmation_height = operator.rpc.get_block_height().unwrap();
+        operator.rpc.mine_blocks(2).unwrap();
+
+        // The reveal window of the first period starts at the confirmation height,
+        // the second period's window starts PERIOD_BLOCK_COUNT blocks later
+        operator.operator_db_connector.set_start_block_height(
+            confirmation_height + MAX_BLOCK_HANDLE_OPS as u64 - PERIOD_BLOCK_COUNT as u64,
+        );
+        operator
+            .operator_db_connector
+            .set_period_relative_block_heights(vec![PERIOD_BLOCK_COUNT, 2 * PERIOD_BLOCK_COUNT]);
+
+        assert_eq!(
+            operator.verify_reveal_in_period(reveal_utxo.txid, 0),
+            Ok(())
+        );
+        assert_eq!(
+            operator.verify_reveal_in_period(reveal_utxo.txid, 1),
+            Err(BridgeError::RevealNotInPeriod)
+        );
+        assert_eq!(
+            operator.verify_reveal_in_period(reveal_utxo.txid, 2),
+            Err(BridgeError::InvalidPeriod)
+        );
+    }
+
+    #[test]
+    fn test_connector_tree_funding() {
+        let mut rng = StdRng::from_seed([28u8; 32]);
+        let operator = create_test_operator(&mut rng);
+        let depth = operator.connector_tree_depth as u32;
+
+        let funding = |feerate| operator.connector_tree_funding(feerate).unwrap();
+        assert_eq!(funding(0), Amount::from_sat(DUST_VALUE) * 2u64.pow(depth));
+        // Fees grow linearly with the feerate, for 2^depth - 1 node txs and 2^depth leaf spends
+        let fee_per_sat_vb = funding(1) - funding(0);
+        assert_eq!(funding(25) - funding(0), fee_per_sat_vb * 25);
+        assert!(fee_per_sat_vb.to_sat() > (2u64.pow(depth + 1) - 1) * (41 + 64 / 4));
+        // The flat relay fee of the connector tree txs is enough at the minimum relay feerate
+        assert!(
+            funding(MIN_RELAY_FEERATE)
+                <= calculate_amount(
+                    operator.connector_tree_depth,
+                    Amount::from_sat(DUST_VALUE),
+                    Amount::from_sat(MIN_RELAY_FEE)
+                )
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_connector_tree_high_feerate() {
+        let mut rng = StdRng::from_seed([64u8; 32]);
+        let mut operator = create_test_operator(&mut rng)
+            .with_connector_tree_operator_takes_after(2)
+            .unwrap()
+            .with_connector_tree_feerate(50);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+
+        let (root_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &operator.signer.secp,
+            &operator.signer.xonly_public_key,
+            &hashes[0][0][0],
+            operator.connector_tree_operator_takes_after,
+        )
+        .unwrap();
+        let root_amount = operator.connector_tree_funding(50).unwrap();
+        let root_utxo = operator
+            .rpc
+            .send_to_address(&root_address, root_amount.to_sat())
+            .unwrap();
+        let utxo_tree = operator
+            .transaction_builder
+            .create_connector_binary_tree(
+                0,
+                &operator.signer.xonly_public_key,
+                &root_utxo,
+                operator.connector_tree_depth,
+                hashes[0].clone(),
+            )
+            .unwrap();
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(vec![utxo_tree.clone()]);
+
+        // Every level is spent once its parents are deep enough for the delay
+        for level in 0..operator.connector_tree_depth {
+            operator
+                .rpc
+                .mine_blocks(operator.connector_tree_operator_takes_after as u64)
+                .unwrap();
+            let preimages = operator
+                .operator_db_connector
+                .get_connector_tree_preimages_level(0, level);
+            let txids = operator
+                .spend_connector_tree_level(0, level, &preimages)
+                .unwrap();
+            operator.rpc.mine_blocks(1).unwrap();
+            for (txid, children) in txids.iter().zip(utxo_tree[level + 1].chunks(2)) {
+                assert_eq!(*txid, children[0].txid);
+                assert!(operator.rpc.confirmation_blocks(txid).unwrap() >= 1);
+            }
+        }
+        // The leaves are funded for their own spends
+        for leaf in utxo_tree[operator.connector_tree_depth].iter() {
+            let leaf_txout = operator.rpc.get_utxo(leaf, false).unwrap().unwrap();
+            assert_eq!(
+                leaf_txout.value,
+                operator
+                    .transaction_builder
+                    .connector_tree_node_amount(0)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_batched_claim_weight() {
+        let mut rng = StdRng::from_seed([27u8; 32]);
+        let operator = create_test_operator(&mut rng);
+
+        let single_claim_weight = operator.batched_claim_weight(&[0]).unwrap();
+        let claim_input_weight =
+            operator.batched_claim_weight(&[0, 1]).unwrap() - single_claim_weight;
+        // Two script path spends with signatures, scripts and control blocks
+        assert!(claim_input_weight > 2 * (41 * 4 + 64 + 33));
+        for count in 1..=8 {
+            let indices = (0..count).collect::<Vec<_>>();
+            assert_eq!(
+                operator.batched_claim_weight(&indices).unwrap(),
+                single_claim_weight + (count - 1) * claim_input_weight
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_claim_before_reveal_check() {
+        let mut rng = StdRng::from_seed([35u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let depth = operator.connector_tree_depth;
+
+        // A wallet utxo of the operator stands in for the connector leaf of the first deposit
+        let leaf_value = Amount::from_sat(DUST_VALUE * 10);
+        let leaf_utxo = operator
+            .rpc
+            .send_to_address(&operator.signer.address, leaf_value.to_sat())
+            .unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+        let mut utxo_tree = vec![Vec::new(); depth + 1];
+        utxo_tree[depth].push(leaf_utxo);
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(vec![utxo_tree]);
+        // The current block is in the reveal window of the first period
+        let cur_block_height = operator.rpc.get_block_height().unwrap();
+        operator
+            .operator_db_connector
+            .set_start_block_height(cur_block_height + 1 - PERIOD_BLOCK_COUNT as u64);
+        operator
+            .operator_db_connector
+            .set_period_relative_block_heights(vec![PERIOD_BLOCK_COUNT]);
+
+        // Without withdrawals the root preimage is revealed, so the claim has to come first
+        assert_eq!(
+            operator.claim_before_reveal_check(0),
+            Err(BridgeError::UnsafeRevealOrdering)
+        );
+
+        let claim_tx = create_key_spend_tx(&operator, leaf_utxo, leaf_value, MIN_RELAY_FEE);
+        operator.rpc.send_raw_transaction(&claim_tx).unwrap();
+
+        // The claim is in the mempool, revealing is safe now
+        assert_eq!(operator.claim_before_reveal_check(0), Ok(()));
+    }
+
+    #[test]
+    fn test_deposit_payment_uri() {
+        let mut rng = StdRng::from_seed([13u8; 32]);
+        let operator = create_test_operator(&mut rng);
+        let secp = secp256k1::Secp256k1::new();
+        let (_, return_pk) = secp.generate_keypair(&mut rng);
+        let return_address = XOnlyPublicKey::from(return_pk);
+
+        let (deposit_address, _) = operator
+            .transaction_builder
+            .generate_deposit_address(&return_address)
+            .unwrap();
+        assert_eq!(
+            operator.deposit_payment_uri(&return_address).unwrap(),
+            format!("bitcoin:{}?amount=1", deposit_address)
+        );
+    }
+
+    #[test]
+    fn test_address_fingerprint() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([14u8; 32]);
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        let create_operator = |all_xonly_pks: Vec<XOnlyPublicKey>| {
+            Operator::new(
+                ExtendedRpc::new(),
+                all_xonly_pks,
+                all_sks[2],
+                Vec::new(),
+                vec![[1u8; 20], [2u8; 20]],
+                CONNECTOR_TREE_DEPTH,
+            )
+            .unwrap()
+        };
+
+        let first_operator = create_operator(all_xonly_pks.clone());
+        let second_operator = create_operator(all_xonly_pks.clone());
+        assert_eq!(
+            first_operator.address_fingerprint().unwrap(),
+            second_operator.address_fingerprint().unwrap()
+        );
+
+        let mut other_xonly_pks = all_xonly_pks.clone();
+        let (_, other_pk) = secp.generate_keypair(&mut rng);
+        other_xonly_pks[0] = XOnlyPublicKey::from(other_pk);
+        let other_operator = create_operator(other_xonly_pks);
+        assert_ne!(
+            first_operator.address_fingerprint().unwrap(),
+            other_operator.address_fingerprint().unwrap()
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_resume_connector_spends() {
+        let mut rng = StdRng::from_seed([15u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let depth = operator.connector_tree_depth;
+
+        let (root_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &operator.signer.secp,
+            &operator.signer.xonly_public_key,
+            &hashes[0][0][0],
+            operator.connector_tree_operator_takes_after,
+        )
+        .unwrap();
+        let root_amount = operator.connector_tree_funding(MIN_RELAY_FEERATE).unwrap();
+        let root_utxo = operator
+            .rpc
+            .send_to_address(&root_address, root_amount.to_sat())
+            .unwrap();
+        let utxo_tree = operator
+            .transaction_builder
+            .create_connector_binary_tree(
+                0,
+                &operator.signer.xonly_public_key,
+                &root_utxo,
+                depth,
+                hashes[0].clone(),
+            )
+            .unwrap();
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(vec![utxo_tree.clone()]);
+        operator
+            .operator_db_connector
+            .add_to_inscription_txs((root_utxo, root_utxo.txid));
+        operator.rpc.mine_blocks(1).unwrap();
+
+        // Spend the root and only one of its children, as if the operator went offline in between
+        operator
+            .spend_connector_tree_utxo(
+                0,
+                root_utxo,
+                operator
+                    .operator_db_connector
+                    .get_connector_tree_preimages(0, 0, 0),
+            )
+            .unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+        operator
+            .spend_connector_tree_utxo(
+                0,
+                utxo_tree[1][0],
+                operator
+                    .operator_db_connector
+                    .get_connector_tree_preimages(0, 1, 0),
+            )
+            .unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+
+        let mut num_spent = 2;
+        loop {
+            let spending_txids = operator.resume_connector_spends().unwrap();
+            if spending_txids.is_empty() {
+                break;
+            }
+            num_spent += spending_txids.len();
+            operator.rpc.mine_blocks(1).unwrap();
+        }
+        // Every inner node is spent exactly once and every leaf is left unspent
+        assert_eq!(num_spent, 2_usize.pow(depth as u32) - 1);
+        for leaf in utxo_tree[depth].iter() {
+            assert!(!operator.rpc.is_utxo_spent(leaf).unwrap());
+        }
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_preflight_period_funding() {
+        let mut rng = StdRng::from_seed([16u8; 32]);
+        let operator = create_test_operator(&mut rng);
+        let balance = operator.rpc.get_balance().unwrap();
+
+        assert_eq!(
+            operator.preflight_period_funding(0, Amount::from_sat(BRIDGE_AMOUNT_SATS)),
+            Ok(())
+        );
+        // A single withdrawal worth the whole balance can not be covered together with the fees
+        let connector_tree_amount = operator.connector_tree_funding(MIN_RELAY_FEERATE).unwrap()
+            + Amount::from_sat(MIN_RELAY_FEE);
+        let expected_shortfall = Amount::from_sat(MIN_RELAY_FEE)
+            + connector_tree_amount
+            + Amount::from_sat(DUST_VALUE * 2 + MIN_RELAY_FEE);
+        assert_eq!(
+            operator.preflight_period_funding(1, balance),
+            Err(BridgeError::InsufficientPeriodFunding(expected_shortfall))
+        );
+    }
+
+    #[test]
+    fn test_collect_claim_authorizations() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([40u8; 32]);
+        let actors = (0..4)
+            .map(|_| Actor::new(secp.generate_keypair(&mut rng).0))
+            .collect::<Vec<_>>();
+        let all_xonly_pks = actors
+            .iter()
+            .map(|actor| actor.xonly_public_key)
+            .collect::<Vec<_>>();
+        let mut operator = Operator::new(
+            ExtendedRpc::new(),
+            all_xonly_pks,
+            actors[3].secret_key,
+            Vec::new(),
+            vec![[1u8; 20], [2u8; 20], [3u8; 20]],
+            CONNECTOR_TREE_DEPTH,
+        )
+        .unwrap();
+        let deposit_utxo = OutPoint {
+            txid: Txid::from_byte_array([41u8; 32]),
+            vout: 0,
+        };
+        let move_utxo = OutPoint {
+            txid: Txid::from_byte_array([42u8; 32]),
+            vout: 0,
+        };
+        let connector_utxos = [[43u8; 32], [44u8; 32]].map(|txid| OutPoint {
+            txid: Txid::from_byte_array(txid),
+            vout: 0,
+        });
+        let mut claim_txs = connector_utxos
+            .iter()
+            .map(|connector_utxo| {
+                operator
+                    .transaction_builder
+                    .create_operator_claim_tx(
+                        move_utxo,
+                        *connector_utxo,
+                        &operator.signer.address,
+                        &operator.signer.xonly_public_key,
+                        &[45u8; 32],
+                    )
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        // Every verifier signs the claim of both periods
+        let operator_claim_sigs = actors[..3]
+            .iter()
+            .map(|actor| {
+                claim_txs
+                    .iter_mut()
+                    .map(|claim_tx| actor.sign_taproot_script_spend_tx_new(claim_tx, 0).unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        operator
+            .operator_db_connector
+            .add_deposit_take_sigs(OperatorClaimSigs {
+                operator_claim_sigs,
+                deposit_txid: deposit_utxo.txid,
+                height: 100,
+            });
+
+        for (period, claim_tx) in claim_txs.iter_mut().enumerate() {
+            let authorizations = operator.collect_claim_authorizations(deposit_utxo, period);
+            assert_eq!(authorizations.len(), 3);
+            let sighash = operator
+                .signer
+                .sighash_taproot_script_spend(claim_tx, 0)
+                .unwrap();
+            let message = Message::from_digest_slice(sighash.as_byte_array()).unwrap();
+            for (i, (pk, sig)) in authorizations.iter().enumerate() {
+                assert_eq!(*pk, actors[i].xonly_public_key);
+                assert!(secp.verify_schnorr(sig, &message, pk).is_ok());
+            }
+        }
+        assert!(operator
+            .collect_claim_authorizations(move_utxo, 0)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_deposit_leaf_index() {
+        let mut rng = StdRng::from_seed([46u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let deposit_txids = [[47u8; 32], [48u8; 32], [49u8; 32]].map(Txid::from_byte_array);
+        for (i, deposit_txid) in deposit_txids.iter().enumerate() {
+            operator
+                .operator_db_connector
+                .add_deposit_take_sigs(OperatorClaimSigs {
+                    operator_claim_sigs: Vec::new(),
+                    deposit_txid: *deposit_txid,
+                    height: 100 + i as u32,
+                });
+        }
+        // Pruning the first deposit's presigns keeps the positions of the others
+        assert_eq!(
+            operator.prune_stale_presigns(105, 4),
+            vec![deposit_txids[0]]
+        );
+
+        for (i, deposit_txid) in deposit_txids.iter().enumerate() {
+            assert_eq!(operator.deposit_leaf_index(*deposit_txid), Some(i as u32));
+        }
+        assert_eq!(
+            operator.deposit_leaf_index(Txid::from_byte_array([50u8; 32])),
+            None
+        );
+    }
+
+    #[test]
+    fn test_connector_tree_iter() {
+        let mut rng = StdRng::from_seed([54u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        assert_eq!(operator.connector_tree_iter(0).count(), 0);
+
+        let hashes = setup_connector_trees(&mut operator, &mut rng);
+        let utxo_tree = operator
+            .transaction_builder
+            .create_connector_binary_tree(
+                0,
+                &operator.signer.xonly_public_key,
+                &OutPoint {
+                    txid: Txid::from_byte_array([55u8; 32]),
+                    vout: 0,
+                },
+                operator.connector_tree_depth,
+                hashes[0].clone(),
+            )
+            .unwrap();
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(vec![utxo_tree.clone()]);
+
+        let nodes = operator.connector_tree_iter(0).collect::<Vec<_>>();
+        let nodes_per_level = (0..=operator.connector_tree_depth)
+            .map(|level| nodes.iter().filter(|node| node.0 == level).count())
+            .collect::<Vec<_>>();
+        assert_eq!(nodes_per_level, vec![1, 2, 4, 8, 16]);
+        for (level, index, utxo, preimage, hash) in nodes {
+            assert_eq!(utxo, utxo_tree[level][index]);
+            assert_eq!(hash, hashes[0][level][index]);
+            assert_eq!(sha256_hash!(preimage), hash);
+        }
+        // Only the first period's utxos are set
+        assert_eq!(operator.connector_tree_iter(1).count(), 0);
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_deposit_merkle_tree_ordered() {
+        let mut rng = StdRng::from_seed([74u8; 32]);
+        let operator = create_test_operator(&mut rng);
+
+        // Two deposits in one block, and one in the next
+        let mut deposit_txids = Vec::new();
+        for blocks in [0, 1, 1] {
+            let utxo = operator
+                .rpc
+                .send_to_address(&operator.signer.address, BRIDGE_AMOUNT_SATS)
+                .unwrap();
+            deposit_txids.push(utxo.txid);
+            operator.rpc.mine_blocks(blocks).unwrap();
+        }
+        let mut chain_ordered_txids = deposit_txids.clone();
+        chain_ordered_txids.sort_by_key(|txid| operator.rpc.get_tx_chain_position(txid).unwrap());
+        assert_eq!(chain_ordered_txids[2], deposit_txids[2]);
+        let mut expected = MerkleTree::<WITHDRAWAL_MERKLE_TREE_DEPTH>::new();
+        for txid in chain_ordered_txids.iter() {
+            expected.add(txid.to_byte_array());
+        }
+
+        deposit_txids.reverse();
+        let deposit_merkle_tree = operator
+            .deposit_merkle_tree_ordered::<WITHDRAWAL_MERKLE_TREE_DEPTH>(&deposit_txids)
+            .unwrap();
+        assert_eq!(deposit_merkle_tree.root(), expected.root());
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_check_deposit_confirmations() {
+        let mut rng = StdRng::from_seed([75u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let deposit_utxo = operator
+            .rpc
+            .send_to_address(&operator.signer.address, BRIDGE_AMOUNT_SATS)
+            .unwrap();
+        operator.rpc.mine_blocks(3).unwrap();
+        // Only the deposit utxo is looked at, the move tx can be anything
+        let move_tx = create_key_spend_tx(
+            &operator,
+            deposit_utxo,
+            Amount::from_sat(BRIDGE_AMOUNT_SATS),
+            MIN_RELAY_FEE,
+        );
+        operator
+            .operator_db_connector
+            .add_move_tx((deposit_utxo, move_tx));
+
+        assert_eq!(operator.check_deposit_confirmations(3).unwrap(), vec![]);
+        assert_eq!(
+            operator.check_deposit_confirmations(4).unwrap(),
+            vec![deposit_utxo]
+        );
+
+        // Reorg out the block confirming the deposit
+        let deposit_block_hash = operator
+            .rpc
+            .get_raw_transaction_info(&deposit_utxo.txid, None)
+            .unwrap()
+            .blockhash
+            .unwrap();
+        operator
+            .rpc
+            .inner
+            .call::<()>("invalidateblock", &[serde_json::json!(deposit_block_hash)])
+            .unwrap();
+        assert_eq!(
+            operator.check_deposit_confirmations(1).unwrap(),
+            vec![deposit_utxo]
+        );
+        operator
+            .rpc
+            .inner
+            .call::<()>("reconsiderblock", &[serde_json::json!(deposit_block_hash)])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_order_move_signatures() {
+        let MoveTxFixture {
+            actors,
+            mut move_tx,
+            sigs,
+            ..
+        } = MoveTxFixture::new(
+            23,
+            OutPoint {
+                txid: Txid::from_byte_array([24u8; 32]),
+                vout: 0,
+            },
+            &[1u8; 20],
+        );
+        let all_xonly_pks = actors[..4]
+            .iter()
+            .map(|actor| actor.xonly_public_key)
+            .collect::<Vec<_>>();
+        let operator = Operator::new(
+            ExtendedRpc::new(),
+            all_xonly_pks.clone(),
+            actors[3].secret_key,
+            Vec::new(),
+            vec![[1u8; 20], [2u8; 20], [3u8; 20]],
+            CONNECTOR_TREE_DEPTH,
+        )
+        .unwrap();
+        let user = &actors[4];
+
+        // Verifier signatures arrive in a different order than the keys
+        let shuffled = vec![sigs[2], sigs[0], sigs[1]];
+        let ordered = operator
+            .order_move_signatures(&mut move_tx, &shuffled, sigs[3])
+            .unwrap();
+        assert_eq!(ordered, sigs[..4]);
+
+        // The keys of the move script appear in the same order as the signatures
+        let script_keys = move_tx.scripts[0]
+            .instructions()
+            .filter_map(|instruction| match instruction.unwrap() {
+                bitcoin::script::Instruction::PushBytes(bytes) => {
+                    Some(XOnlyPublicKey::from_slice(bytes.as_bytes()).unwrap())
+                }
+                bitcoin::script::Instruction::Op(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(script_keys[..ordered.len()], all_xonly_pks[..]);
+
+        // A signature that does not verify for any key is rejected
+        assert_eq!(
+            operator.order_move_signatures(&mut move_tx, &shuffled[1..], sigs[3]),
+            Err(BridgeError::MoveSignatureNotFound)
+        );
+        let user_sig = user
+            .sign_taproot_script_spend_tx_new(&mut move_tx, 0)
+            .unwrap();
+        assert_eq!(
+            operator.order_move_signatures(&mut move_tx, &[user_sig; 3], sigs[3]),
+            Err(BridgeError::MoveSignatureNotFound)
+        );
+    }
+
+    #[test]
+    fn test_new_deposit_before_connector_trees() {
+        let mut rng = StdRng::from_seed([17u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let secp = secp256k1::Secp256k1::new();
+        let (_, return_pk) = secp.generate_keypair(&mut rng);
+        let deposit_utxo = OutPoint {
+            txid: Txid::from_byte_array([18u8; 32]),
+            vout: 0,
+        };
+        let user_sig = operator.signer.sign_digest([19u8; 32]);
+
+        assert_eq!(
+            operator.new_deposit(&DepositRequest::new(
+                deposit_utxo,
+                XOnlyPublicKey::from(return_pk),
+                [1u8; 20],
+                user_sig
+            )),
+            Err(BridgeError::ConnectorTreeNotReady)
+        );
+    }
+
+    #[test]
+    fn test_check_move_tx_fee() {
+        let mut rng = StdRng::from_seed([52u8; 32]);
+        let operator = create_test_operator(&mut rng);
+        let user = Actor::new(secp256k1::Secp256k1::new().generate_keypair(&mut rng).0);
+        let request = DepositRequest::new(
+            OutPoint {
+                txid: Txid::from_byte_array([53u8; 32]),
+                vout: 0,
+            },
+            user.xonly_public_key,
+            [1u8; 20],
+            user.sign_digest([54u8; 32]),
+        );
+        operator.check_move_tx_fee(&request).unwrap();
+
+        // The deposit leaf takes the user's signature on top of the n-of-n ones, the estimate of
+        // the unsigned move tx is the size of the signed one
+        let create_move_tx = || {
+            operator
+                .transaction_builder
+                .create_move_tx(request.start_utxo, &[1u8; 20], &user.xonly_public_key)
+                .unwrap()
+        };
+        let unsigned_move_tx = create_move_tx();
+        let mut move_tx = create_move_tx();
+        let placeholder_sigs = vec![[0u8; 64]; script_signature_count(&move_tx.scripts[0])];
+        assert_eq!(placeholder_sigs.len(), operator.verifiers_pks.len() + 1);
+        handle_taproot_witness_new(&mut move_tx, &placeholder_sigs, 0).unwrap();
+        let vsize = move_tx.tx.vsize() as u64;
+        assert!(MIN_RELAY_FEE >= vsize * MIN_RELAY_FEERATE);
+        assert_eq!(
+            operator
+                .transaction_builder
+                .estimate_vsize(&unsigned_move_tx)
+                .unwrap() as u64,
+            vsize
+        );
+    }
+
+    #[test]
+    fn test_deposit_request_serde() {
+        let mut rng = StdRng::from_seed([46u8; 32]);
+        let secp = secp256k1::Secp256k1::new();
+        let user = Actor::new(secp.generate_keypair(&mut rng).0);
+        let start_utxo = OutPoint {
+            txid: Txid::from_byte_array([47u8; 32]),
+            vout: 3,
+        };
+        let user_sig = user.sign_digest([48u8; 32]);
+        let request = DepositRequest::new(start_utxo, user.xonly_public_key, [49u8; 20], user_sig);
+        assert_eq!(request.start_utxo(), start_utxo);
+        assert_eq!(request.return_address(), &user.xonly_public_key);
+        assert_eq!(request.evm_address(), &[49u8; 20]);
+        assert_eq!(request.user_sig(), user_sig);
+
+        let json = serde_json::to_string(&request).unwrap();
+        assert_eq!(
+            serde_json::from_str::<DepositRequest>(&json).unwrap(),
+            request
+        );
+    }
+
+    #[test]
+    fn test_withdrawal_proof() {
+        let mut rng = StdRng::from_seed([50u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        let secp = secp256k1::Secp256k1::new();
+        let user = Actor::new(secp.generate_keypair(&mut rng).0);
+        let other = Actor::new(secp.generate_keypair(&mut rng).0);
+        let leaf = WithdrawalProof::leaf(&user.address).unwrap();
+
+        let mut rollup_withdrawals = MerkleTree::<WITHDRAWAL_MERKLE_TREE_DEPTH>::new();
+        rollup_withdrawals.add(leaf);
+        rollup_withdrawals.add(WithdrawalProof::leaf(&other.address).unwrap());
+        let proof = WithdrawalProof {
+            index: 0,
+            path: rollup_withdrawals.path(0),
+        };
+
+        // Nothing can be proven before the operator trusts a root
+        assert_eq!(
+            operator.check_withdrawal_proof(leaf, &proof),
+            Err(BridgeError::InvalidWithdrawalProof)
+        );
+
+        operator.set_rollup_withdrawals_root(rollup_withdrawals.root());
+        assert_eq!(operator.check_withdrawal_proof(leaf, &proof), Ok(()));
+
+        // A forged path does not lead to the trusted root
+        let mut forged = proof.clone();
+        forged.path[0] = [51u8; 32];
+        assert_eq!(
+            operator.new_withdrawal(user.address.clone(), forged),
+            Err(BridgeError::InvalidWithdrawalProof)
+        );
+        // A withdrawal that is not in the rollup's tree
+        assert_eq!(
+            operator.new_withdrawal(
+                Actor::new(secp.generate_keypair(&mut rng).0).address,
+                proof.clone()
+            ),
+            Err(BridgeError::InvalidWithdrawalProof)
+        );
+        // A valid proof of a withdrawal that is not the next one to pay
+        let proof_1 = WithdrawalProof {
+            index: 1,
+            path: rollup_withdrawals.path(1),
+        };
+        assert_eq!(
+            operator.new_withdrawal(other.address.clone(), proof_1),
+            Err(BridgeError::InvalidWithdrawalProof)
+        );
+        assert_eq!(
+            operator
+                .operator_db_connector
+                .get_withdrawals_merkle_tree_index(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_dry_run_leaves_db_unchanged() {
+        let mut rng = StdRng::from_seed([87u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        setup_connector_trees(&mut operator, &mut rng);
+        let start_block_height = 100;
+        operator
+            .operator_db_connector
+            .set_start_block_height(start_block_height);
+        operator
+            .operator_db_connector
+            .set_period_relative_block_heights(
+                (0..NUM_ROUNDS as u32 + 1)
+                    .map(|i| PERIOD_BLOCK_COUNT * (i + 1))
+                    .collect(),
+            );
+        // The first period's preimages are inscribed, so its reveal needs no node
+        operator.operator_db_connector.add_to_inscription_txs((
+            OutPoint {
+                txid: Txid::from_byte_array([88u8; 32]),
+                vout: 0,
+            },
+            Txid::from_byte_array([89u8; 32]),
+        ));
+        let deposit_txid = Txid::from_byte_array([90u8; 32]);
+        operator
+            .operator_db_connector
+            .add_deposit_take_sigs(OperatorClaimSigs {
+                operator_claim_sigs: Vec::new(),
+                deposit_txid,
+                height: 0,
+            });
+
+        operator.dry_run = true;
+        let db_before = format!("{:?}", operator.operator_db_connector);
+        assert_eq!(operator.prune_stale_presigns(1000, 10), vec![deposit_txid]);
+        let reveal_start =
+            start_block_height + PERIOD_BLOCK_COUNT as u64 - MAX_BLOCK_HANDLE_OPS as u64;
+        assert_eq!(
+            operator.advance_period(reveal_start).unwrap(),
+            Period::PreimageReveal(0)
+        );
+        assert!(operator.bit_commitment_hashes(1).is_empty());
+        assert_eq!(format!("{:?}", operator.operator_db_connector), db_before);
+        assert!(operator.take_dry_run_txs().is_empty());
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_dry_run_leaves_chain_unchanged() {
+        let mut rng = StdRng::from_seed([92u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        setup_connector_trees(&mut operator, &mut rng);
+        let rpc = operator.rpc.clone();
+        let start_block_height = rpc.get_block_height().unwrap();
+        operator
+            .operator_db_connector
+            .set_start_block_height(start_block_height);
+        operator
+            .operator_db_connector
+            .set_period_relative_block_heights(
+                (0..NUM_ROUNDS as u32 + 1)
+                    .map(|i| PERIOD_BLOCK_COUNT * (i + 1))
+                    .collect(),
+            );
+        let secp = secp256k1::Secp256k1::new();
+        let user = Actor::new(secp.generate_keypair(&mut rng).0);
+        let mut rollup_withdrawals = MerkleTree::<WITHDRAWAL_MERKLE_TREE_DEPTH>::new();
+        rollup_withdrawals.add(WithdrawalProof::leaf(&user.address).unwrap());
+        operator.set_rollup_withdrawals_root(rollup_withdrawals.root());
+
+        operator.dry_run = true;
+        let balance = rpc.get_balance().unwrap();
+        let mempool = rpc.inner.get_raw_mempool().unwrap();
+        let db_before = format!("{:?}", operator.operator_db_connector);
+        let payment_txid = operator
+            .new_withdrawal(
+                user.address.clone(),
+                WithdrawalProof {
+                    index: 0,
+                    path: rollup_withdrawals.path(0),
+                },
+            )
+            .unwrap();
+        // Nothing is recorded, so every call at a reveal height builds the inscription again
+        let reveal_start =
+            start_block_height + PERIOD_BLOCK_COUNT as u64 - MAX_BLOCK_HANDLE_OPS as u64;
+        for _ in 0..2 {
+            assert_eq!(
+                operator.advance_period(reveal_start).unwrap(),
+                Period::PreimageReveal(0)
+            );
+        }
+        assert_eq!(rpc.get_balance().unwrap(), balance);
+        assert_eq!(rpc.inner.get_raw_mempool().unwrap(), mempool);
+        assert_eq!(rpc.get_block_height().unwrap(), start_block_height);
+        assert_eq!(format!("{:?}", operator.operator_db_connector), db_before);
+
+        let dry_run_txs = operator.take_dry_run_txs();
+        assert_eq!(dry_run_txs.len(), 5);
+        let payment_tx = &dry_run_txs[0];
+        assert_eq!(payment_tx.txid(), payment_txid);
+        assert!(payment_tx.output.contains(&TxOut {
+            value: Amount::from_sat(BRIDGE_AMOUNT_SATS),
+            script_pubkey: user.address.script_pubkey(),
+        }));
+        let (commit_tx, reveal_tx) = (&dry_run_txs[1], &dry_run_txs[2]);
+        assert_eq!(reveal_tx.input[0].previous_output.txid, commit_tx.txid());
+        // The inscription is only built, broadcasting it spends from the wallet
+        assert_eq!(operator.broadcast(commit_tx).unwrap(), commit_tx.txid());
+        assert_eq!(operator.broadcast(reveal_tx).unwrap(), reveal_tx.txid());
+        assert!(rpc.get_balance().unwrap() < balance);
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_new_deposit_batch() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([45u8; 32]);
+        let rpc = ExtendedRpc::new();
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        // Two operators with the same keys and verifier set, both in dry run mode so that
+        // the same deposits can be moved by each of them
+        let create_operator = || {
+            let verifiers = all_sks[..2]
+                .iter()
+                .map(|sk| {
+                    Box::new(Verifier::new(rpc.clone(), all_xonly_pks.clone(), *sk).unwrap())
+                        as Box<dyn VerifierConnector>
+                })
+                .collect();
+            let mut operator = Operator::new(
+                rpc.clone(),
+                all_xonly_pks.clone(),
+                all_sks[2],
+                verifiers,
+                vec![[1u8; 20], [2u8; 20]],
+                CONNECTOR_TREE_DEPTH,
+            )
+            .unwrap();
+            operator.dry_run = true;
+            operator
+        };
+        let mut batch_operator = create_operator();
+        let mut single_operator = create_operator();
+
+        let (first_source_utxo, start_blockheight, hashes, period_relative_block_heights, _) =
+            batch_operator.initial_setup(&mut rng).unwrap();
+        single_operator
+            .operator_db_connector
+            .set_connector_tree_utxos(
+                batch_operator
+                    .operator_db_connector
+                    .get_connector_tree_utxos(),
+            );
+        single_operator
+            .operator_db_connector
+            .set_connector_tree_hashes(hashes.clone());
+        for operator in [&mut batch_operator, &mut single_operator] {
+            for verifier in operator.verifier_connector.iter_mut() {
+                verifier
+                    .connector_roots_created(
+                        &hashes,
+                        &first_source_utxo,
+                        start_blockheight,
+                        period_relative_block_heights.clone(),
+                    )
+                    .unwrap();
+            }
+        }
+
+        let requests = (1..4u8)
+            .map(|i| {
+                let user = User::new(
+                    rpc.clone(),
+                    all_xonly_pks.clone(),
+                    secp.generate_keypair(&mut rng).0,
+                );
+                let (start_utxo, return_address, evm_address, user_sig) =
+                    user.deposit_tx([i; 20]).unwrap();
+                DepositRequest::new(start_utxo, return_address, evm_address, user_sig)
+            })
+            .collect::<Vec<_>>();
+        rpc.mine_blocks(CONFIRMATION_BLOCK_COUNT as u64).unwrap();
+
+        let batch_move_utxos = batch_operator.new_deposit_batch(&requests).unwrap();
+        let single_move_utxos = requests
+            .iter()
+            .map(|request| single_operator.new_deposit(request).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(batch_move_utxos, single_move_utxos);
+        // Schnorr signatures are randomized, so only the txids are compared
+        assert_eq!(
+            batch_operator
+                .take_dry_run_txs()
+                .iter()
+                .map(Transaction::txid)
+                .collect::<Vec<_>>(),
+            single_operator
+                .take_dry_run_txs()
+                .iter()
+                .map(Transaction::txid)
+                .collect::<Vec<_>>()
+        );
+        // Neither dry run recorded the deposits
+        assert_eq!(batch_operator.operator_db_connector.get_deposit_index(), 0);
+        assert!(batch_operator
+            .operator_db_connector
+            .get_move_txs()
+            .is_empty());
+
+        // A single bad deposit fails the whole batch before any verifier is asked
+        let mut bad_requests = requests.clone();
+        bad_requests[1] = DepositRequest::new(
+            requests[1].start_utxo(),
+            *requests[1].return_address(),
+            [0u8; 20],
+            requests[1].user_sig(),
+        );
+        assert_eq!(
+            batch_operator.new_deposit_batch(&bad_requests),
+            Err(BridgeError::InvalidEvmAddress)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_new_deposit_batch_partial_failure() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([46u8; 32]);
+        let rpc = ExtendedRpc::new();
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        let verifiers = all_sks[..2]
+            .iter()
+            .map(|sk| {
+                Box::new(Verifier::new(rpc.clone(), all_xonly_pks.clone(), *sk).unwrap())
+                    as Box<dyn VerifierConnector>
+            })
+            .collect();
+        let mut operator = Operator::new(
+            rpc.clone(),
+            all_xonly_pks.clone(),
+            all_sks[2],
+            verifiers,
+            vec![[1u8; 20], [2u8; 20]],
+            CONNECTOR_TREE_DEPTH,
+        )
+        .unwrap();
+        let (first_source_utxo, start_blockheight, hashes, period_relative_block_heights, _) =
+            operator.initial_setup(&mut rng).unwrap();
+        for verifier in operator.verifier_connector.iter_mut() {
+            verifier
+                .connector_roots_created(
+                    &hashes,
+                    &first_source_utxo,
+                    start_blockheight,
+                    period_relative_block_heights.clone(),
+                )
+                .unwrap();
+        }
+
+        let mut requests = (1..3u8)
+            .map(|i| {
+                let user = User::new(
+                    rpc.clone(),
+                    all_xonly_pks.clone(),
+                    secp.generate_keypair(&mut rng).0,
+                );
+                let (start_utxo, return_address, evm_address, user_sig) =
+                    user.deposit_tx([i; 20]).unwrap();
+                DepositRequest::new(start_utxo, return_address, evm_address, user_sig)
+            })
+            .collect::<Vec<_>>();
+        rpc.mine_blocks(CONFIRMATION_BLOCK_COUNT as u64).unwrap();
+        // The verifiers presign the second deposit, but its move tx fails with the first
+        // user's signature
+        requests[1] = DepositRequest::new(
+            requests[1].start_utxo(),
+            *requests[1].return_address(),
+            *requests[1].evm_address(),
+            requests[0].user_sig(),
+        );
+
+        let Err(BridgeError::PartialDepositBatch(move_utxos, error)) =
+            operator.new_deposit_batch(&requests)
+        else {
+            panic!("the second deposit should fail the batch");
+        };
+        assert!(matches!(*error, BridgeError::InvalidWitness(_)));
+        // The first deposit is moved and recorded
+        assert_eq!(move_utxos.len(), 1);
+        assert!(rpc.get_utxo(&move_utxos[0], true).unwrap().is_some());
+        assert_eq!(operator.operator_db_connector.get_deposit_index(), 1);
+        assert_eq!(
+            operator.operator_db_connector.get_move_txs(),
+            vec![(
+                requests[0].start_utxo(),
+                rpc.get_raw_transaction(&move_utxos[0].txid, None).unwrap()
+            )]
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_new_deposit_insufficient_start_utxo() {
+        let mut rng = StdRng::from_seed([50u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        // A stand-in connector tree, the deposit is rejected before it is used
+        operator
+            .operator_db_connector
+            .set_connector_tree_utxos(vec![vec![vec![OutPoint::null()]]]);
+        let user = Actor::new(secp256k1::Secp256k1::new().generate_keypair(&mut rng).0);
+        let (deposit_address, _) = operator
+            .transaction_builder
+            .generate_deposit_address(&user.xonly_public_key)
+            .unwrap();
+        let underfunded_utxo = operator
+            .rpc
+            .send_to_address(&deposit_address, BRIDGE_AMOUNT_SATS - 1)
+            .unwrap();
+        operator
+            .rpc
+            .mine_blocks(CONFIRMATION_BLOCK_COUNT as u64)
+            .unwrap();
+        let user_sig = user.sign_digest([51u8; 32]);
+
+        assert_eq!(
+            operator.new_deposit(&DepositRequest::new(
+                underfunded_utxo,
+                user.xonly_public_key,
+                [1u8; 20],
+                user_sig,
+            )),
+            Err(BridgeError::InsufficientStartUtxo)
+        );
+        // An outpoint that does not exist
+        assert_eq!(
+            operator.new_deposit(&DepositRequest::new(
+                OutPoint {
+                    txid: underfunded_utxo.txid,
+                    vout: 100,
+                },
+                user.xonly_public_key,
+                [1u8; 20],
+                user_sig,
+            )),
+            Err(BridgeError::InsufficientStartUtxo)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_broadcast_rejected() {
+        let mut rng = StdRng::from_seed([39u8; 32]);
+        let operator = create_test_operator(&mut rng);
+        let value = Amount::from_sat(DUST_VALUE * 10);
+        let utxo = operator
+            .rpc
+            .send_to_address(&operator.signer.address, value.to_sat())
+            .unwrap();
+
+        let underpaid_tx = create_key_spend_tx(&operator, utxo, value, 0);
+        match operator.broadcast(&underpaid_tx) {
+            Err(BridgeError::TxRejected(reason)) => {
+                assert!(reason.contains("min relay fee not met"), "{}", reason)
+            }
+            result => panic!("underpaid tx is not rejected: {:?}", result),
+        }
+        assert!(!operator.rpc.is_utxo_spent(&utxo).unwrap());
+
+        let tx = create_key_spend_tx(&operator, utxo, value, MIN_RELAY_FEE);
+        assert_eq!(operator.broadcast(&tx), Ok(tx.txid()));
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_inscribe_period_preimages_commit_rejected() {
+        let mut rng = StdRng::from_seed([40u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        setup_connector_trees(&mut operator, &mut rng);
+
+        // No wallet can fund a reveal at this feerate, so the commit is never broadcast
+        operator.inscription_feerate = 1_000_000_000_000;
+        assert!(matches!(
+            operator.inscribe_period_preimages(0),
+            Err(BridgeError::InsufficientInscriptionFunding(_))
+        ));
+        // Nothing is recorded, so the period can still be inscribed
+        assert_eq!(operator.operator_db_connector.get_inscription_txs_len(), 0);
+
+        operator.inscription_feerate = MIN_RELAY_FEERATE;
+        let (commit_txid, reveal_txid) = operator.inscribe_period_preimages(0).unwrap();
+        let reveal_tx = operator
+            .rpc
+            .get_raw_transaction(&reveal_txid, None)
+            .unwrap();
+        assert_eq!(reveal_tx.input[0].previous_output.txid, commit_txid);
+        assert_eq!(
+            operator.operator_db_connector.get_inscription_txs(),
+            vec![(
+                OutPoint {
+                    txid: commit_txid,
+                    vout: reveal_tx.input[0].previous_output.vout
+                },
+                reveal_txid
+            )]
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_inscribe_preimages() {
+        let mut rng = StdRng::from_seed([41u8; 32]);
+        let operator = create_test_operator(&mut rng);
+        let preimages = [[42u8; 32], [43u8; 32]];
+
+        let (commit_txid, reveal_txid) = operator.inscribe_preimages(&preimages).unwrap();
+        let reveal_tx = operator
+            .rpc
+            .get_raw_transaction(&reveal_txid, None)
+            .unwrap();
+        assert_eq!(reveal_tx.input[0].previous_output.txid, commit_txid);
+        assert_eq!(
+            TransactionBuilder::parse_inscription_preimages(&reveal_tx).unwrap(),
+            preimages
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_inscribe_preimages_funding() {
+        let mut rng = StdRng::from_seed([44u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        operator.inscription_feerate = 10;
+        let preimages = (0..MAX_INSCRIPTION_ELEMENTS)
+            .map(|i| [i as u8; 32])
+            .collect::<Vec<_>>();
+
+        // The reveal is test accepted before it is broadcast, see Operator::broadcast
+        let (commit_txid, reveal_txid) = operator.inscribe_preimages(&preimages).unwrap();
+        let reveal_tx = operator
+            .rpc
+            .get_raw_transaction(&reveal_txid, None)
+            .unwrap();
+        let commit_tx = operator
+            .rpc
+            .get_raw_transaction(&commit_txid, None)
+            .unwrap();
+        let commit_value = commit_tx.output[reveal_tx.input[0].previous_output.vout as usize].value;
+        let fee = commit_value
+            - reveal_tx
+                .output
+                .iter()
+                .map(|txout| txout.value)
+                .sum::<Amount>();
+        assert_eq!(fee.to_sat(), reveal_tx.vsize() as u64 * 10);
+        assert_eq!(
+            commit_value,
+            operator
+                .transaction_builder
+                .inscription_commit_value(&operator.signer.xonly_public_key, &preimages, 10)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_detect_reorged_moves() {
+        let mut rng = StdRng::from_seed([31u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        assert_eq!(
+            operator.rebroadcast_move(OutPoint::null()),
+            Err(BridgeError::MoveTxNotFound)
+        );
+
+        let deposit_value = Amount::from_sat(BRIDGE_AMOUNT_SATS);
+        let deposit_utxo = operator
+            .rpc
+            .send_to_address(&operator.signer.address, BRIDGE_AMOUNT_SATS)
+            .unwrap();
+        // Stand-in move txs that spend the deposit with a key path spend, the second one
+        // pays a higher fee and replaces the first
+        let move_tx = create_key_spend_tx(&operator, deposit_utxo, deposit_value, MIN_RELAY_FEE);
+        let conflicting_tx =
+            create_key_spend_tx(&operator, deposit_utxo, deposit_value, MIN_RELAY_FEE * 10);
+
+        // A confirmed move whose utxo is claimed, in the mempool and in a block
+        let claimed_deposit_utxo = operator
+            .rpc
+            .send_to_address(&operator.signer.address, BRIDGE_AMOUNT_SATS)
+            .unwrap();
+        let claimed_move_tx = create_key_spend_tx(
+            &operator,
+            claimed_deposit_utxo,
+            deposit_value,
+            MIN_RELAY_FEE,
+        );
+        operator.rpc.send_raw_transaction(&claimed_move_tx).unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+        operator
+            .operator_db_connector
+            .add_move_tx((claimed_deposit_utxo, claimed_move_tx.clone()));
+        let claim_tx = create_key_spend_tx(
+            &operator,
+            OutPoint {
+                txid: claimed_move_tx.txid(),
+                vout: 0,
+            },
+            claimed_move_tx.output[0].value,
+            MIN_RELAY_FEE,
+        );
+        operator.rpc.send_raw_transaction(&claim_tx).unwrap();
+        assert_eq!(operator.detect_reorged_moves().unwrap(), vec![]);
+        operator.rpc.mine_blocks(1).unwrap();
+        assert_eq!(operator.detect_reorged_moves().unwrap(), vec![]);
+
+        operator.rpc.send_raw_transaction(&move_tx).unwrap();
+        operator
+            .operator_db_connector
+            .add_move_tx((deposit_utxo, move_tx.clone()));
+        assert_eq!(operator.detect_reorged_moves().unwrap(), vec![]);
+
+        operator.rpc.send_raw_transaction(&conflicting_tx).unwrap();
+        operator.rpc.mine_blocks(1).unwrap();
+        assert_eq!(
+            operator.detect_reorged_moves().unwrap(),
+            vec![OutPoint {
+                txid: move_tx.txid(),
+                vout: 0
+            }]
+        );
+        // The deposit is spent by the conflicting tx, so the move tx can not be re-finalized
+        assert!(operator.rebroadcast_move(deposit_utxo).is_err());
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_total_locked() {
+        let mut rng = StdRng::from_seed([32u8; 32]);
+        let mut operator = create_test_operator(&mut rng);
+        assert_eq!(operator.total_locked().unwrap(), Amount::ZERO);
+
+        let deposit_value = Amount::from_sat(BRIDGE_AMOUNT_SATS);
+        let move_value = deposit_value - Amount::from_sat(MIN_RELAY_FEE);
+        // Stand-in move txs that pay the deposits back to the operator's address
+        let mut move_txs = Vec::new();
+        for _ in 0..2 {
+            let deposit_utxo = operator
+                .rpc
+                .send_to_address(&operator.signer.address, BRIDGE_AMOUNT_SATS)
+                .unwrap();
+            let move_tx =
+                create_key_spend_tx(&operator, deposit_utxo, deposit_value, MIN_RELAY_FEE);
+            operator.rpc.send_raw_transaction(&move_tx).unwrap();
+            operator
+                .operator_db_connector
+                .add_move_tx((deposit_utxo, move_tx.clone()));
+            move_txs.push(move_tx);
+        }
+        operator.rpc.mine_blocks(1).unwrap();
+        assert_eq!(operator.total_locked().unwrap(), move_value * 2);
+
+        // Claiming the first deposit spends its move utxo
+        let move_utxo = OutPoint {
+            txid: move_txs[0].txid(),
+            vout: 0,
+        };
+        let claim_tx = create_key_spend_tx(&operator, move_utxo, move_value, MIN_RELAY_FEE);
+        operator.rpc.send_raw_transaction(&claim_tx).unwrap();
+        assert_eq!(operator.total_locked().unwrap(), move_value);
+        operator.rpc.mine_blocks(1).unwrap();
+        assert_eq!(operator.total_locked().unwrap(), move_value);
+    }
+}
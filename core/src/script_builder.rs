@@ -1,12 +1,109 @@
 use bitcoin::{
     opcodes::{all::*, OP_FALSE, OP_TRUE},
-    script::Builder,
-    ScriptBuf, TxOut,
+    script::{Builder, PushBytes},
+    Amount, Script, ScriptBuf, Sequence, TxOut, Witness,
 };
 use secp256k1::XOnlyPublicKey;
 
+use crate::errors::BridgeError;
 use crate::EVMAddress;
 
+/// The hash a hashlock script commits to, the variant decides the opcode used to hash the preimage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashLock {
+    /// OP_SHA256, the preimage is 32 bytes
+    Sha256([u8; 32]),
+    /// OP_HASH160 (RIPEMD160 of SHA256), the hash is 20 bytes
+    Hash160([u8; 20]),
+    /// OP_HASH256 (double SHA256), the hash is 32 bytes
+    Hash256([u8; 32]),
+}
+
+/// Maximum number of data bytes in a standard OP_RETURN output
+pub const MAX_OP_RETURN_DATA_SIZE: usize = 80;
+
+/// Size limit of legacy and segwit v0 scripts in bytes. Tapscripts have no such limit, only
+/// the transaction weight bounds them, but inscriptions are kept below it so that every
+/// reveal tx stays small
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// Maximum number of 32 byte elements one inscription script can hold. The envelope
+/// takes 37 bytes (key push, OP_CHECKSIG, OP_FALSE, OP_IF, OP_ENDIF), every element 33.
+pub const MAX_INSCRIPTION_ELEMENTS: usize = (MAX_SCRIPT_SIZE - 37) / 33;
+
+/// A BIP68 relative timelock, either in blocks or in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativeTimelock {
+    Blocks(u16),
+    /// Has to be a multiple of 512 seconds, at most 65535 * 512 seconds
+    Seconds(u32),
+}
+
+impl RelativeTimelock {
+    /// Returns the sequence that both satisfies the timelock and is pushed to the OP_CSV script
+    pub fn to_sequence(self) -> Result<Sequence, BridgeError> {
+        match self {
+            RelativeTimelock::Blocks(block_count) => Ok(Sequence::from_height(block_count)),
+            RelativeTimelock::Seconds(seconds) => {
+                if seconds % 512 != 0 || seconds / 512 > u16::MAX as u32 {
+                    return Err(BridgeError::InvalidTimelock);
+                }
+                Ok(Sequence::from_512_second_intervals((seconds / 512) as u16))
+            }
+        }
+    }
+}
+
+/// The output a transaction carries so that anyone can bump its fee with a child
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AnchorKind {
+    /// P2WSH of OP_TRUE, spent by revealing the script
+    #[default]
+    P2wshOpTrue,
+    /// Pay to anchor, `OP_1 <0x4e73>`, spent with an empty witness. Relayed by Bitcoin Core 28
+    /// and later, older nodes treat it as non-standard.
+    P2aEphemeral,
+}
+
+impl AnchorKind {
+    pub fn script_pubkey(self) -> ScriptBuf {
+        match self {
+            AnchorKind::P2wshOpTrue => ScriptBuilder::anyone_can_spend_script().to_p2wsh(),
+            AnchorKind::P2aEphemeral => Builder::new()
+                .push_opcode(OP_PUSHNUM_1)
+                .push_slice([0x4e, 0x73])
+                .into_script(),
+        }
+    }
+
+    /// The anchor output, worth the dust limit of its script so that its parent does not
+    /// need to pay zero fee to be relayed
+    pub fn txout(self) -> TxOut {
+        let script_pubkey = self.script_pubkey();
+        let value = script_pubkey.dust_value();
+        TxOut {
+            script_pubkey,
+            value,
+        }
+    }
+
+    /// Witness that spends the anchor
+    pub fn spend_witness(self) -> Witness {
+        let mut witness = Witness::new();
+        if self == AnchorKind::P2wshOpTrue {
+            witness.push(ScriptBuilder::anyone_can_spend_script().as_bytes());
+        }
+        witness
+    }
+
+    /// The anchor kind paid to by `script_pubkey`, None if it is not an anchor
+    pub fn from_script_pubkey(script_pubkey: &Script) -> Option<Self> {
+        [AnchorKind::P2wshOpTrue, AnchorKind::P2aEphemeral]
+            .into_iter()
+            .find(|kind| kind.script_pubkey().as_script() == script_pubkey)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ScriptBuilder {
     pub verifiers_pks: Vec<XOnlyPublicKey>,
@@ -17,27 +114,39 @@ impl ScriptBuilder {
         Self { verifiers_pks }
     }
 
+    /// Witness script of anyone_can_spend_txout, spending it takes only this script as witness
+    pub fn anyone_can_spend_script() -> ScriptBuf {
+        Builder::new().push_opcode(OP_TRUE).into_script()
+    }
+
     pub fn anyone_can_spend_txout() -> TxOut {
-        let script = Builder::new().push_opcode(OP_TRUE).into_script();
-        let script_pubkey = script.to_p2wsh();
-        let value = script_pubkey.dust_value();
-        TxOut {
-            script_pubkey,
-            value,
-        }
+        AnchorKind::P2wshOpTrue.txout()
     }
 
     pub fn op_return_txout(evm_address: &EVMAddress) -> TxOut {
-        let script = Builder::new()
-            .push_opcode(OP_RETURN)
-            .push_slice(evm_address)
-            .into_script();
-        let script_pubkey = script.to_p2wsh();
-        let value = script_pubkey.dust_value();
-        TxOut {
-            script_pubkey,
-            value,
-        }
+        // An evm address always fits in a single push
+        ScriptBuilder::op_return_chunked(evm_address).remove(0)
+    }
+
+    /// Splits `data` into OP_RETURN outputs of at most MAX_OP_RETURN_DATA_SIZE bytes each, in
+    /// order. Bitcoin Core's standardness policy rejects OP_RETURN outputs carrying more than 80
+    /// bytes of data (an 83 byte script pubkey). Before v30 it also rejects every transaction with
+    /// more than one OP_RETURN output ("multi-op-return"), so a transaction carrying the outputs of
+    /// more than MAX_OP_RETURN_DATA_SIZE bytes of data is only relayed by v30 and later nodes, or
+    /// has to be mined directly. Larger data that has to reach older nodes belongs in a tapscript
+    /// inscription instead, see create_inscription_script_32_bytes.
+    pub fn op_return_chunked(data: &[u8]) -> Vec<TxOut> {
+        data.chunks(MAX_OP_RETURN_DATA_SIZE)
+            .map(|chunk| {
+                let push_bytes: &PushBytes = chunk
+                    .try_into()
+                    .expect("chunks are smaller than the push limit");
+                TxOut {
+                    script_pubkey: ScriptBuf::new_op_return(push_bytes),
+                    value: Amount::ZERO,
+                }
+            })
+            .collect()
     }
 
     pub fn generate_script_n_of_n(&self) -> ScriptBuf {
@@ -61,10 +170,28 @@ impl ScriptBuilder {
         builder.into_script()
     }
 
+    /// Tapscript leaves of a deposit address, in order: the n-of-n spend that also needs
+    /// the user's signature, used by the move tx, and the user's refund after `refund_blocks`
+    pub fn deposit_taproot_leaves(
+        &self,
+        user_pk: &XOnlyPublicKey,
+        refund_blocks: u32,
+    ) -> Vec<ScriptBuf> {
+        vec![
+            self.generate_script_n_of_n_with_user_pk(user_pk),
+            ScriptBuilder::generate_timelock_script(user_pk, refund_blocks),
+        ]
+    }
+
+    /// Inscribes `data` in a single script, fails with InscriptionTooLarge if it has more than
+    /// MAX_INSCRIPTION_ELEMENTS elements, see create_inscription_scripts_32_bytes
     pub fn create_inscription_script_32_bytes(
         public_key: &XOnlyPublicKey,
-        data: &Vec<[u8; 32]>,
-    ) -> ScriptBuf {
+        data: &[[u8; 32]],
+    ) -> Result<ScriptBuf, BridgeError> {
+        if data.len() > MAX_INSCRIPTION_ELEMENTS {
+            return Err(BridgeError::InscriptionTooLarge);
+        }
         let mut inscribe_preimage_script_builder = Builder::new()
             .push_x_only_key(public_key)
             .push_opcode(OP_CHECKSIG)
@@ -75,7 +202,21 @@ impl ScriptBuilder {
         }
         inscribe_preimage_script_builder = inscribe_preimage_script_builder.push_opcode(OP_ENDIF);
 
-        inscribe_preimage_script_builder.into_script()
+        Ok(inscribe_preimage_script_builder.into_script())
+    }
+
+    /// Inscribes `data` in as many scripts as needed to keep each one within MAX_SCRIPT_SIZE,
+    /// in order. Each script is meant for its own leaf or reveal tx.
+    pub fn create_inscription_scripts_32_bytes(
+        public_key: &XOnlyPublicKey,
+        data: &[[u8; 32]],
+    ) -> Vec<ScriptBuf> {
+        data.chunks(MAX_INSCRIPTION_ELEMENTS)
+            .map(|chunk| {
+                ScriptBuilder::create_inscription_script_32_bytes(public_key, chunk)
+                    .expect("chunks are within the element limit")
+            })
+            .collect()
     }
 
     // ATTENTION: If you want to spend a UTXO using timelock script, the condition is that
@@ -91,6 +232,40 @@ impl ScriptBuilder {
             .into_script()
     }
 
+    /// Requires both `block_count` blocks to have passed since the UTXO is confirmed and the
+    /// preimage of `hash`, the witness stack is [signature, preimage] with the preimage on top
+    pub fn generate_hashlock_timelock_script(
+        actor_pk: &XOnlyPublicKey,
+        block_count: u32,
+        hash: [u8; 32],
+    ) -> ScriptBuf {
+        Builder::new()
+            .push_int(block_count as i64)
+            .push_opcode(OP_CSV)
+            .push_opcode(OP_DROP)
+            .push_opcode(OP_SHA256)
+            .push_slice(hash)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_x_only_key(actor_pk)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Same as generate_timelock_script but the delay is measured in seconds, which sets the BIP68 type flag
+    pub fn generate_timelock_script_seconds(
+        actor_pk: &XOnlyPublicKey,
+        seconds: u32,
+    ) -> Result<ScriptBuf, BridgeError> {
+        let sequence = RelativeTimelock::Seconds(seconds).to_sequence()?;
+        Ok(Builder::new()
+            .push_int(sequence.to_consensus_u32() as i64)
+            .push_opcode(OP_CSV)
+            .push_opcode(OP_DROP)
+            .push_x_only_key(actor_pk)
+            .push_opcode(OP_CHECKSIG)
+            .into_script())
+    }
+
     pub fn generate_absolute_timelock_script(
         actor_pk: &XOnlyPublicKey,
         block_count: u32,
@@ -112,6 +287,30 @@ impl ScriptBuilder {
             .into_script()
     }
 
+    pub fn generate_hash160_script(hash: [u8; 20]) -> ScriptBuf {
+        Builder::new()
+            .push_opcode(OP_HASH160)
+            .push_slice(hash)
+            .push_opcode(OP_EQUAL)
+            .into_script()
+    }
+
+    pub fn generate_hash256_script(hash: [u8; 32]) -> ScriptBuf {
+        Builder::new()
+            .push_opcode(OP_HASH256)
+            .push_slice(hash)
+            .push_opcode(OP_EQUAL)
+            .into_script()
+    }
+
+    pub fn generate_hashlock_script(hash_lock: &HashLock) -> ScriptBuf {
+        match *hash_lock {
+            HashLock::Sha256(hash) => Self::generate_hash_script(hash),
+            HashLock::Hash160(hash) => Self::generate_hash160_script(hash),
+            HashLock::Hash256(hash) => Self::generate_hash256_script(hash),
+        }
+    }
+
     pub fn generate_dust_script(evm_address: &EVMAddress) -> ScriptBuf {
         Builder::new()
             .push_opcode(OP_RETURN)
@@ -119,3 +318,452 @@ impl ScriptBuilder {
             .into_script()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::Actor;
+    use crate::constants::{MIN_RELAY_FEE, USER_TAKES_AFTER};
+    use crate::extended_rpc::ExtendedRpc;
+    use crate::transaction_builder::{deposit_address, INTERNAL_KEY};
+    use crate::utils::{handle_taproot_witness, verify_taproot_witness};
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::opcodes::Opcode;
+    use bitcoin::script::Instruction;
+    use bitcoin::taproot::{TaprootBuilder, TaprootSpendInfo};
+    use bitcoin::transaction::Version;
+    use bitcoin::{Address, OutPoint, Transaction, TxIn, Witness};
+    use clementine_circuits::sha256_hash;
+    use secp256k1::rand::rngs::StdRng;
+    use secp256k1::rand::SeedableRng;
+
+    fn assert_hashlock_script(script: &ScriptBuf, opcode: Opcode, hash: &[u8]) {
+        let instructions = script
+            .instructions()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0], Instruction::Op(opcode));
+        assert_eq!(instructions[1].push_bytes().unwrap().as_bytes(), hash);
+        assert_eq!(instructions[2], Instruction::Op(OP_EQUAL));
+    }
+
+    #[test]
+    fn test_generate_hashlock_script() {
+        let hash32 = [3u8; 32];
+        let hash20 = [4u8; 20];
+
+        let sha256_script = ScriptBuilder::generate_hashlock_script(&HashLock::Sha256(hash32));
+        assert_eq!(sha256_script, ScriptBuilder::generate_hash_script(hash32));
+        assert_hashlock_script(&sha256_script, OP_SHA256, &hash32);
+
+        let hash160_script = ScriptBuilder::generate_hashlock_script(&HashLock::Hash160(hash20));
+        assert_eq!(
+            hash160_script,
+            ScriptBuilder::generate_hash160_script(hash20)
+        );
+        assert_hashlock_script(&hash160_script, OP_HASH160, &hash20);
+
+        let hash256_script = ScriptBuilder::generate_hashlock_script(&HashLock::Hash256(hash32));
+        assert_eq!(
+            hash256_script,
+            ScriptBuilder::generate_hash256_script(hash32)
+        );
+        assert_hashlock_script(&hash256_script, OP_HASH256, &hash32);
+    }
+
+    fn create_script_address(
+        secp: &secp256k1::Secp256k1<secp256k1::All>,
+        script: &ScriptBuf,
+    ) -> (Address, TaprootSpendInfo) {
+        let tree_info = TaprootBuilder::new()
+            .add_leaf(0, script.clone())
+            .unwrap()
+            .finalize(secp, *INTERNAL_KEY)
+            .unwrap();
+        let address = Address::p2tr(
+            secp,
+            *INTERNAL_KEY,
+            tree_info.merkle_root(),
+            bitcoin::Network::Regtest,
+        );
+        (address, tree_info)
+    }
+
+    /// Spends `prevout` at `utxo` back to the actor through `script`, the actor's signature
+    /// is at the bottom of the witness stack followed by `witness_elements`
+    fn create_script_spend_tx(
+        actor: &Actor,
+        utxo: OutPoint,
+        prevout: &TxOut,
+        sequence: Sequence,
+        script: &ScriptBuf,
+        tree_info: &TaprootSpendInfo,
+        witness_elements: &[&[u8]],
+    ) -> Transaction {
+        let mut tx = Transaction {
+            version: Version(2),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: utxo,
+                sequence,
+                script_sig: ScriptBuf::default(),
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: prevout.value - Amount::from_sat(MIN_RELAY_FEE),
+                script_pubkey: actor.address.script_pubkey(),
+            }],
+        };
+        let sig = actor
+            .sign_taproot_script_spend_tx(&mut tx, &vec![prevout.clone()], script, 0)
+            .unwrap();
+        let mut elements: Vec<&[u8]> = vec![sig.as_ref()];
+        elements.extend_from_slice(witness_elements);
+        handle_taproot_witness(&mut tx, 0, &elements, script, tree_info).unwrap();
+        tx
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_hashlock_timelock_script_spend() {
+        let rpc = ExtendedRpc::new();
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([11u8; 32]);
+        let (sk, _) = secp.generate_keypair(&mut rng);
+        let actor = Actor::new(sk);
+
+        let block_count = 2;
+        let preimage = [5u8; 32];
+        let script = ScriptBuilder::generate_hashlock_timelock_script(
+            &actor.xonly_public_key,
+            block_count,
+            sha256_hash!(preimage),
+        );
+        let (address, tree_info) = create_script_address(&secp, &script);
+        let prevout = TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: address.script_pubkey(),
+        };
+        let utxo = rpc
+            .send_to_address(&address, prevout.value.to_sat())
+            .unwrap();
+        rpc.mine_blocks(1).unwrap();
+
+        let create_spend_tx = |preimage: &[u8; 32]| {
+            create_script_spend_tx(
+                &actor,
+                utxo,
+                &prevout,
+                Sequence::from_height(block_count as u16),
+                &script,
+                &tree_info,
+                &[preimage],
+            )
+        };
+
+        // The timelock has not matured yet
+        assert!(rpc
+            .send_raw_transaction(&create_spend_tx(&preimage))
+            .is_err());
+        rpc.mine_blocks(1).unwrap();
+        // The timelock has matured but the preimage is wrong
+        assert!(rpc
+            .send_raw_transaction(&create_spend_tx(&[6u8; 32]))
+            .is_err());
+        let spending_txid = rpc
+            .send_raw_transaction(&create_spend_tx(&preimage))
+            .unwrap();
+        assert_eq!(
+            rpc.get_raw_transaction(&spending_txid, None).unwrap().input[0].previous_output,
+            utxo
+        );
+    }
+
+    #[test]
+    fn test_op_return_chunked() {
+        let data = (0..200).map(|i| i as u8).collect::<Vec<u8>>();
+        let txouts = ScriptBuilder::op_return_chunked(&data);
+        assert_eq!(txouts.len(), 3);
+
+        let mut payload = Vec::new();
+        for (txout, expected_len) in txouts.iter().zip([80, 80, 40]) {
+            assert_eq!(txout.value, Amount::ZERO);
+            assert!(txout.script_pubkey.is_op_return());
+            assert!(txout.script_pubkey.len() <= MAX_OP_RETURN_DATA_SIZE + 3);
+            let instructions = txout
+                .script_pubkey
+                .instructions()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(instructions.len(), 2);
+            assert_eq!(instructions[0], Instruction::Op(OP_RETURN));
+            let chunk = instructions[1].push_bytes().unwrap().as_bytes();
+            assert_eq!(chunk.len(), expected_len);
+            payload.extend_from_slice(chunk);
+        }
+        assert_eq!(payload, data);
+
+        let evm_address = [7u8; 20];
+        assert_eq!(
+            vec![ScriptBuilder::op_return_txout(&evm_address)],
+            ScriptBuilder::op_return_chunked(&evm_address)
+        );
+    }
+
+    #[test]
+    fn test_inscription_script_size_limit() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([58u8; 32]);
+        let public_key = XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1);
+
+        let data = (0..MAX_INSCRIPTION_ELEMENTS)
+            .map(|i| [i as u8; 32])
+            .collect::<Vec<_>>();
+        let script = ScriptBuilder::create_inscription_script_32_bytes(&public_key, &data).unwrap();
+        assert!(script.len() <= MAX_SCRIPT_SIZE);
+        assert!(script.len() + 33 > MAX_SCRIPT_SIZE);
+        assert_eq!(
+            ScriptBuilder::create_inscription_scripts_32_bytes(&public_key, &data),
+            vec![script]
+        );
+
+        // One more element does not fit, and is moved to a second script
+        let data = (0..MAX_INSCRIPTION_ELEMENTS + 1)
+            .map(|i| [i as u8; 32])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ScriptBuilder::create_inscription_script_32_bytes(&public_key, &data),
+            Err(BridgeError::InscriptionTooLarge)
+        );
+        let scripts = ScriptBuilder::create_inscription_scripts_32_bytes(&public_key, &data);
+        assert_eq!(scripts.len(), 2);
+        assert!(scripts.iter().all(|script| script.len() <= MAX_SCRIPT_SIZE));
+        let inscribed = scripts
+            .iter()
+            .flat_map(|script| {
+                script
+                    .instructions()
+                    .filter_map(|instruction| match instruction.unwrap() {
+                        Instruction::PushBytes(bytes) if bytes.len() == 32 => {
+                            Some(<[u8; 32]>::try_from(bytes.as_bytes()).unwrap())
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // Every script starts with the public key push
+        assert_eq!(inscribed.len(), data.len() + 2);
+        assert_eq!(
+            inscribed[1..=MAX_INSCRIPTION_ELEMENTS],
+            data[..MAX_INSCRIPTION_ELEMENTS]
+        );
+        assert_eq!(
+            inscribed[MAX_INSCRIPTION_ELEMENTS + 2..],
+            data[MAX_INSCRIPTION_ELEMENTS..]
+        );
+    }
+
+    #[test]
+    fn test_relative_timelock_to_sequence() {
+        assert_eq!(
+            RelativeTimelock::Blocks(6).to_sequence(),
+            Ok(Sequence::from_consensus(6))
+        );
+        assert_eq!(
+            RelativeTimelock::Seconds(1024).to_sequence(),
+            Ok(Sequence::from_consensus((1 << 22) | 2))
+        );
+        assert_eq!(
+            RelativeTimelock::Seconds(u16::MAX as u32 * 512).to_sequence(),
+            Ok(Sequence::from_consensus((1 << 22) | u16::MAX as u32))
+        );
+        assert_eq!(
+            RelativeTimelock::Seconds(1000).to_sequence(),
+            Err(BridgeError::InvalidTimelock)
+        );
+        assert_eq!(
+            RelativeTimelock::Seconds((u16::MAX as u32 + 1) * 512).to_sequence(),
+            Err(BridgeError::InvalidTimelock)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_timelock_script_seconds_spend() {
+        let rpc = ExtendedRpc::new();
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([12u8; 32]);
+        let (sk, _) = secp.generate_keypair(&mut rng);
+        let actor = Actor::new(sk);
+
+        let seconds = 1024;
+        let script =
+            ScriptBuilder::generate_timelock_script_seconds(&actor.xonly_public_key, seconds)
+                .unwrap();
+        let (address, tree_info) = create_script_address(&secp, &script);
+        let prevout = TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: address.script_pubkey(),
+        };
+        let utxo = rpc
+            .send_to_address(&address, prevout.value.to_sat())
+            .unwrap();
+        rpc.mine_blocks(1).unwrap();
+
+        let spend_tx = create_script_spend_tx(
+            &actor,
+            utxo,
+            &prevout,
+            RelativeTimelock::Seconds(seconds).to_sequence().unwrap(),
+            &script,
+            &tree_info,
+            &[],
+        );
+        assert!(rpc.send_raw_transaction(&spend_tx).is_err());
+
+        // Median time past is the median of the last 11 blocks, so mine 11 blocks past the timelock
+        let best_block_time = rpc
+            .get_block_header(&rpc.get_best_block_hash().unwrap())
+            .unwrap()
+            .time as u64;
+        rpc.set_mock_time(best_block_time + 2 * seconds as u64)
+            .unwrap();
+        rpc.mine_blocks(11).unwrap();
+        let spending_txid = rpc.send_raw_transaction(&spend_tx).unwrap();
+        rpc.set_mock_time(0).unwrap();
+        assert_eq!(
+            rpc.get_raw_transaction(&spending_txid, None).unwrap().input[0].previous_output,
+            utxo
+        );
+    }
+
+    #[test]
+    fn test_deposit_taproot_leaves() {
+        let secp = secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([58u8; 32]);
+        let actors = (0..4)
+            .map(|_| Actor::new(secp.generate_keypair(&mut rng).0))
+            .collect::<Vec<_>>();
+        let (user, signers) = actors.split_last().unwrap();
+        let verifiers_pks = signers
+            .iter()
+            .map(|actor| actor.xonly_public_key)
+            .collect::<Vec<_>>();
+        let script_builder = ScriptBuilder::new(verifiers_pks.clone());
+        let leaves =
+            script_builder.deposit_taproot_leaves(&user.xonly_public_key, USER_TAKES_AFTER);
+        assert_eq!(leaves.len(), 2);
+
+        // The leaves are the ones the deposit address commits to
+        let (address, tree_info) = deposit_address(
+            &secp,
+            &verifiers_pks,
+            &user.xonly_public_key,
+            bitcoin::Network::Regtest,
+        )
+        .unwrap();
+        let utxo = OutPoint {
+            txid: bitcoin::Txid::from_byte_array([59u8; 32]),
+            vout: 0,
+        };
+        let prevout = TxOut {
+            value: Amount::from_sat(100_000),
+            script_pubkey: address.script_pubkey(),
+        };
+
+        // Refund: the user alone after USER_TAKES_AFTER blocks
+        let refund_tx = create_script_spend_tx(
+            user,
+            utxo,
+            &prevout,
+            Sequence::from_height(USER_TAKES_AFTER as u16),
+            &leaves[1],
+            &tree_info,
+            &[],
+        );
+        assert_eq!(
+            verify_taproot_witness(&refund_tx, 0, &[prevout.clone()]),
+            Ok(())
+        );
+        let early_refund_tx = create_script_spend_tx(
+            user,
+            utxo,
+            &prevout,
+            Sequence::from_height(USER_TAKES_AFTER as u16 - 1),
+            &leaves[1],
+            &tree_info,
+            &[],
+        );
+        assert!(verify_taproot_witness(&early_refund_tx, 0, &[prevout.clone()]).is_err());
+
+        // Cooperative spend: every signer and the user, the first key's signature on top
+        let mut cooperative_tx = create_script_spend_tx(
+            user,
+            utxo,
+            &prevout,
+            Sequence::ENABLE_RBF_NO_LOCKTIME,
+            &leaves[0],
+            &tree_info,
+            &[],
+        );
+        cooperative_tx.input[0].witness = Witness::new();
+        let sigs = actors
+            .iter()
+            .rev()
+            .map(|actor| {
+                actor
+                    .sign_taproot_script_spend_tx(
+                        &mut cooperative_tx,
+                        &vec![prevout.clone()],
+                        &leaves[0],
+                        0,
+                    )
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let elements = sigs
+            .iter()
+            .map(|sig| sig.as_ref().as_slice())
+            .collect::<Vec<&[u8]>>();
+        handle_taproot_witness(&mut cooperative_tx, 0, &elements, &leaves[0], &tree_info).unwrap();
+        assert_eq!(
+            verify_taproot_witness(&cooperative_tx, 0, &[prevout]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_anchor_kind_script_pubkey() {
+        let p2wsh = AnchorKind::P2wshOpTrue.txout();
+        assert_eq!(p2wsh, ScriptBuilder::anyone_can_spend_txout());
+        assert!(p2wsh.script_pubkey.is_p2wsh());
+        assert_eq!(
+            p2wsh.script_pubkey,
+            ScriptBuf::new_p2wsh(&ScriptBuilder::anyone_can_spend_script().wscript_hash())
+        );
+        assert_eq!(
+            AnchorKind::P2wshOpTrue.spend_witness().to_vec(),
+            vec![vec![OP_TRUE.to_u8()]]
+        );
+
+        let p2a = AnchorKind::P2aEphemeral.txout();
+        assert_eq!(p2a.script_pubkey.as_bytes(), &[0x51, 0x02, 0x4e, 0x73]);
+        assert!(p2a.script_pubkey.is_witness_program());
+        assert_eq!(p2a.value, p2a.script_pubkey.dust_value());
+        assert!(AnchorKind::P2aEphemeral.spend_witness().is_empty());
+
+        for kind in [AnchorKind::P2wshOpTrue, AnchorKind::P2aEphemeral] {
+            assert_eq!(
+                AnchorKind::from_script_pubkey(&kind.script_pubkey()),
+                Some(kind)
+            );
+        }
+        assert_eq!(
+            AnchorKind::from_script_pubkey(&ScriptBuilder::anyone_can_spend_script()),
+            None
+        );
+    }
+}
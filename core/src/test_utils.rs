@@ -0,0 +1,98 @@
+//! Fixtures shared by the unit tests of several modules
+
+use bitcoin::OutPoint;
+use secp256k1::rand::rngs::StdRng;
+use secp256k1::rand::SeedableRng;
+use secp256k1::{schnorr, Secp256k1, XOnlyPublicKey};
+
+use crate::actor::Actor;
+use crate::transaction_builder::{CreateTxOutputs, TransactionBuilder};
+use crate::utils::handle_taproot_witness_new;
+use crate::EVMAddress;
+
+/// Three verifiers, the operator and a user, with the move tx of the user's deposit signed by
+/// every one of them
+pub(crate) struct MoveTxFixture {
+    /// The n-of-n actors, verifiers first and the operator last, followed by the user
+    pub actors: Vec<Actor>,
+    pub transaction_builder: TransactionBuilder,
+    /// The move tx without a witness
+    pub move_tx: CreateTxOutputs,
+    /// Signature of every actor over the move tx, in the order of `actors`
+    pub sigs: Vec<schnorr::Signature>,
+    deposit_utxo: OutPoint,
+    evm_address: EVMAddress,
+}
+
+impl MoveTxFixture {
+    pub fn new(seed: u8, deposit_utxo: OutPoint, evm_address: &EVMAddress) -> Self {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([seed; 32]);
+        let actors = (0..5)
+            .map(|_| Actor::new(secp.generate_keypair(&mut rng).0))
+            .collect::<Vec<_>>();
+        let transaction_builder = TransactionBuilder::new(
+            actors[..4]
+                .iter()
+                .map(|actor| actor.xonly_public_key)
+                .collect(),
+        );
+        let mut move_tx = transaction_builder
+            .create_move_tx(deposit_utxo, evm_address, &actors[4].xonly_public_key)
+            .unwrap();
+        let sigs = actors
+            .iter()
+            .map(|actor| {
+                actor
+                    .sign_taproot_script_spend_tx_new(&mut move_tx, 0)
+                    .unwrap()
+            })
+            .collect();
+        Self {
+            actors,
+            transaction_builder,
+            move_tx,
+            sigs,
+            deposit_utxo,
+            evm_address: *evm_address,
+        }
+    }
+
+    pub fn user(&self) -> &Actor {
+        &self.actors[4]
+    }
+
+    /// Keys of the n-of-n, verifiers first and the operator last
+    pub fn n_of_n_pks(&self) -> Vec<XOnlyPublicKey> {
+        self.transaction_builder.verifiers_pks.clone()
+    }
+
+    /// The signatures in the order the deposit script consumes them
+    pub fn witness_sigs(&self) -> Vec<schnorr::Signature> {
+        TransactionBuilder::build_n_of_n_witness(
+            &self.sigs[..3],
+            self.sigs[3],
+            Some(self.sigs[4]),
+            &self.move_tx.scripts[0],
+        )
+        .unwrap()
+    }
+
+    /// The move tx with `witness_sigs` followed by the deposit script and its control block
+    pub fn witnessed_move_tx(&self, witness_sigs: &[schnorr::Signature]) -> CreateTxOutputs {
+        let mut move_tx = self
+            .transaction_builder
+            .create_move_tx(
+                self.deposit_utxo,
+                &self.evm_address,
+                &self.user().xonly_public_key,
+            )
+            .unwrap();
+        let witness_elements = witness_sigs
+            .iter()
+            .map(|sig| sig.as_ref().to_vec())
+            .collect::<Vec<_>>();
+        handle_taproot_witness_new(&mut move_tx, &witness_elements, 0).unwrap();
+        move_tx
+    }
+}
@@ -1,11 +1,15 @@
 use crate::{
-    merkle::MerkleTree, operator::OperatorClaimSigs, ConnectorUTXOTree, InscriptionTxs,
-    WithdrawalPayment,
+    bit_commitment::BitCommitmentPreimages, merkle::MerkleTree, operator::OperatorClaimSigs,
+    ConnectorUTXOTree, DepositMoveTx, InscriptionTxs, WithdrawalPayment,
 };
+use bitcoin::Txid;
 use clementine_circuits::{constants::CLAIM_MERKLE_TREE_DEPTH, HashType, PreimageType};
 pub trait OperatorDBConnector: std::fmt::Debug {
     fn get_deposit_index(&self) -> usize;
     fn add_deposit_take_sigs(&mut self, deposit_take_sigs: OperatorClaimSigs);
+    fn get_deposit_leaf_index(&self, deposit_txid: Txid) -> Option<u32>;
+    fn get_deposit_take_sigs(&self) -> Vec<OperatorClaimSigs>;
+    fn set_deposit_take_sigs(&mut self, deposit_take_sigs: Vec<OperatorClaimSigs>);
     fn get_connector_tree_preimages_level(&self, period: usize, level: usize) -> Vec<PreimageType>;
     fn get_connector_tree_preimages(&self, period: usize, level: usize, idx: usize)
         -> PreimageType;
@@ -42,4 +46,10 @@ pub trait OperatorDBConnector: std::fmt::Debug {
 
     fn add_inscribed_preimages(&mut self, period: usize, preimages: Vec<PreimageType>);
     fn get_inscribed_preimages(&self, period: usize) -> Vec<PreimageType>;
+
+    fn set_bit_commitments(&mut self, period: usize, bit_commitments: Vec<BitCommitmentPreimages>);
+    fn get_bit_commitments(&self, period: usize) -> Vec<BitCommitmentPreimages>;
+
+    fn add_move_tx(&mut self, move_tx: DepositMoveTx);
+    fn get_move_txs(&self) -> Vec<DepositMoveTx>;
 }
@@ -1,20 +1,37 @@
 use bitcoin::{Address, OutPoint};
-use secp256k1::XOnlyPublicKey;
 
 use crate::{
-    constants::VerifierChallenge, errors::BridgeError, operator::DepositPresigns, EVMAddress,
+    constants::VerifierChallenge,
+    errors::BridgeError,
+    operator::{DepositPresigns, DepositRequest},
 };
 
 pub trait VerifierConnector: std::fmt::Debug {
     fn new_deposit(
         &self,
-        start_utxo: OutPoint,
-        return_address: &XOnlyPublicKey,
+        request: &DepositRequest,
         deposit_index: u32,
-        evm_address: &EVMAddress,
         operator_address: &Address,
     ) -> Result<DepositPresigns, BridgeError>;
 
+    /// Presigns every deposit of `requests` in one call, the deposit indices are consecutive
+    /// starting at `first_deposit_index`. Connectors over the network should override this to
+    /// send all the deposits in a single request.
+    fn new_deposit_batch(
+        &self,
+        requests: &[DepositRequest],
+        first_deposit_index: u32,
+        operator_address: &Address,
+    ) -> Result<Vec<DepositPresigns>, BridgeError> {
+        requests
+            .iter()
+            .zip(first_deposit_index..)
+            .map(|(request, deposit_index)| {
+                self.new_deposit(request, deposit_index, operator_address)
+            })
+            .collect()
+    }
+
     fn connector_roots_created(
         &mut self,
         connector_tree_hashes: &Vec<Vec<Vec<[u8; 32]>>>,
@@ -2,28 +2,35 @@ use std::str::FromStr;
 
 use crate::{
     constants::{
-        CONNECTOR_TREE_DEPTH, CONNECTOR_TREE_OPERATOR_TAKES_AFTER, DUST_VALUE, K_DEEP,
-        MAX_BITVM_CHALLENGE_RESPONSE_BLOCKS, MIN_RELAY_FEE, USER_TAKES_AFTER,
+        CONNECTOR_TREE_OPERATOR_TAKES_AFTER, DUST_VALUE, K_DEEP,
+        MAX_BITVM_CHALLENGE_RESPONSE_BLOCKS, MIN_RELAY_FEE, MIN_RELAY_FEERATE, USER_TAKES_AFTER,
     },
     merkle::MerkleTree,
-    utils::get_claim_proof_tree_leaf,
+    utils::{
+        claim_proof_merkle_tree, push_taproot_witness, verify_taproot_script_spend,
+        ControlBlockCache,
+    },
     ConnectorUTXOTree, EVMAddress, HashTree,
 };
 use bitcoin::{
     absolute,
-    opcodes::all::{OP_EQUAL, OP_SHA256},
-    script::Builder,
-    taproot::{TaprootBuilder, TaprootSpendInfo},
-    Address, Amount, OutPoint, ScriptBuf, TxIn, TxOut, Witness,
+    opcodes::all::{OP_CHECKSIG, OP_ENDIF, OP_EQUAL, OP_IF, OP_SHA256},
+    script::{Builder, Instruction},
+    taproot::{ControlBlock, TaprootBuilder, TaprootSpendInfo},
+    Address, Amount, OutPoint, ScriptBuf, TxIn, TxOut, Txid, Witness,
 };
 use clementine_circuits::{
     constants::{BRIDGE_AMOUNT_SATS, CLAIM_MERKLE_TREE_DEPTH, NUM_ROUNDS},
     sha256_hash, HashType, MerkleRoot, PreimageType,
 };
-use secp256k1::{Secp256k1, XOnlyPublicKey};
+use secp256k1::{schnorr, Secp256k1, XOnlyPublicKey};
 use sha2::{Digest, Sha256};
 
-use crate::{errors::BridgeError, script_builder::ScriptBuilder, utils::calculate_amount};
+use crate::{
+    errors::BridgeError,
+    script_builder::{AnchorKind, RelativeTimelock, ScriptBuilder},
+    utils::{calculate_amount_with_weights, script_signature_count},
+};
 use lazy_static::lazy_static;
 
 // This is an unspendable pubkey
@@ -45,11 +52,85 @@ pub struct CreateTxOutputs {
 
 pub type CreateAddressOutputs = (Address, TaprootSpendInfo);
 
+/// Predicts the txid of the move tx of `deposit_utxo` from public inputs alone. The txid does
+/// not depend on the witness, so it is the txid the operator broadcasts, as long as it uses
+/// the default anchor kind.
+pub fn predict_move_txid(
+    deposit_utxo: OutPoint,
+    evm_address: &EVMAddress,
+    bridge_amount: Amount,
+    verifiers_pks: &[XOnlyPublicKey],
+) -> Result<Txid, BridgeError> {
+    let (bridge_address, _) =
+        TransactionBuilder::new(verifiers_pks.to_vec()).generate_bridge_address()?;
+    Ok(TransactionBuilder::create_move_btc_tx(
+        deposit_utxo,
+        evm_address,
+        bridge_amount,
+        &bridge_address,
+    )
+    .txid())
+}
+
+/// Deposit address together with what is needed to spend it through the n-of-n leaf
+#[derive(Debug, Clone)]
+pub struct DepositSpendInfo {
+    pub address: Address,
+    pub tree_info: TaprootSpendInfo,
+    /// N-of-N with user pk script, the leaf the move tx spends
+    pub script_n_of_n_with_user_pk: ScriptBuf,
+    pub control_block: ControlBlock,
+}
+
+/// Derives the deposit address of `return_address` from the verifier set alone, so that
+/// wallets can compute it without an operator. Same as TransactionBuilder::generate_deposit_address.
+pub fn deposit_address(
+    secp: &Secp256k1<secp256k1::All>,
+    verifiers_pks: &[XOnlyPublicKey],
+    return_address: &XOnlyPublicKey,
+    network: bitcoin::Network,
+) -> Result<CreateAddressOutputs, BridgeError> {
+    deposit_address_with_refund_blocks(
+        secp,
+        verifiers_pks,
+        return_address,
+        USER_TAKES_AFTER,
+        network,
+    )
+}
+
+/// Same as deposit_address, but the user can take the deposit back after `refund_blocks`
+/// instead of USER_TAKES_AFTER
+pub fn deposit_address_with_refund_blocks(
+    secp: &Secp256k1<secp256k1::All>,
+    verifiers_pks: &[XOnlyPublicKey],
+    return_address: &XOnlyPublicKey,
+    refund_blocks: u32,
+    network: bitcoin::Network,
+) -> Result<CreateAddressOutputs, BridgeError> {
+    let taproot = ScriptBuilder::new(verifiers_pks.to_vec())
+        .deposit_taproot_leaves(return_address, refund_blocks)
+        .into_iter()
+        .try_fold(TaprootBuilder::new(), |taproot, leaf| {
+            taproot.add_leaf(1, leaf)
+        })?;
+    let tree_info = taproot.finalize(secp, *INTERNAL_KEY)?;
+    let address = Address::p2tr(secp, *INTERNAL_KEY, tree_info.merkle_root(), network);
+    Ok((address, tree_info))
+}
+
 #[derive(Debug, Clone)]
 pub struct TransactionBuilder {
     pub secp: Secp256k1<secp256k1::All>,
     pub verifiers_pks: Vec<XOnlyPublicKey>,
     pub script_builder: ScriptBuilder,
+    pub control_block_cache: ControlBlockCache,
+    /// Relative timelock in blocks after which the operator can spend connector tree nodes
+    pub connector_tree_operator_takes_after: u16,
+    /// Anchor output of the move and claim txs
+    pub anchor_kind: AnchorKind,
+    /// Feerate in sat/vB the connector tree txs and leaf spends are funded for
+    pub connector_tree_feerate: u64,
 }
 
 impl TransactionBuilder {
@@ -60,7 +141,131 @@ impl TransactionBuilder {
             secp,
             verifiers_pks,
             script_builder,
+            control_block_cache: ControlBlockCache::default(),
+            connector_tree_operator_takes_after: CONNECTOR_TREE_OPERATOR_TAKES_AFTER,
+            anchor_kind: AnchorKind::default(),
+            connector_tree_feerate: MIN_RELAY_FEERATE,
+        }
+    }
+
+    /// Sets the relative timelock, in blocks, after which the operator can spend connector
+    /// tree nodes, instead of CONNECTOR_TREE_OPERATOR_TAKES_AFTER
+    pub fn with_connector_tree_operator_takes_after(
+        mut self,
+        block_count: u32,
+    ) -> Result<Self, BridgeError> {
+        // OP_CSV only takes 16 bits of block based relative timelocks, and without any delay
+        // the verifiers have no time to burn a revealed branch
+        let block_count = u16::try_from(block_count).map_err(|_| BridgeError::InvalidTimelock)?;
+        if block_count == 0 {
+            return Err(BridgeError::InvalidTimelock);
         }
+        self.connector_tree_operator_takes_after = block_count;
+        Ok(self)
+    }
+
+    /// Sets the anchor output of the move and claim txs, instead of AnchorKind::default()
+    pub fn with_anchor_kind(mut self, anchor_kind: AnchorKind) -> Self {
+        self.anchor_kind = anchor_kind;
+        self
+    }
+
+    /// Sets the feerate the connector trees are funded for, instead of MIN_RELAY_FEERATE. Every
+    /// node amount depends on it, so the verifiers have to use the same feerate.
+    pub fn with_connector_tree_feerate(mut self, feerate_sat_vb: u64) -> Self {
+        self.connector_tree_feerate = feerate_sat_vb;
+        self
+    }
+
+    /// Funding a connector tree node with `depth` levels below it needs at `feerate_sat_vb`:
+    /// every node tx pays for its own size and every dust leaf for the size of its spend, both
+    /// timelock script spends sized with estimate_vsize
+    pub fn connector_tree_funding(
+        &self,
+        depth: usize,
+        feerate_sat_vb: u64,
+    ) -> Result<Amount, BridgeError> {
+        let operator_pk = self
+            .verifiers_pks
+            .last()
+            .ok_or(BridgeError::InvalidOperatorKey)?;
+        let (address, tree_info) = TransactionBuilder::create_connector_tree_node_address(
+            &self.secp,
+            operator_pk,
+            &[0u8; 32],
+            self.connector_tree_operator_takes_after,
+        )?;
+        let timelock_script = ScriptBuilder::generate_timelock_script(
+            operator_pk,
+            self.connector_tree_operator_takes_after as u32,
+        );
+        let spend_vsize = |tx: bitcoin::Transaction| {
+            self.estimate_vsize(&CreateTxOutputs {
+                tx,
+                prevouts: vec![],
+                scripts: vec![timelock_script.clone()],
+                taproot_spend_infos: vec![tree_info.clone()],
+            })
+            .map(|vsize| vsize as u64)
+        };
+
+        let node_tx = TransactionBuilder::create_connector_tree_tx(
+            &OutPoint::null(),
+            Amount::ZERO,
+            address.clone(),
+            address,
+            self.connector_tree_operator_takes_after,
+        )?;
+        let mut leaf_spend_tx = node_tx.clone();
+        leaf_spend_tx.output.truncate(1);
+        Ok(calculate_amount_with_weights(
+            depth,
+            Amount::from_sat(DUST_VALUE),
+            spend_vsize(leaf_spend_tx)?,
+            spend_vsize(node_tx)?,
+            feerate_sat_vb,
+        ))
+    }
+
+    /// Amount of a connector tree node with `depth` levels below it, see connector_tree_funding
+    pub fn connector_tree_node_amount(&self, depth: usize) -> Result<Amount, BridgeError> {
+        self.connector_tree_funding(depth, self.connector_tree_feerate)
+    }
+
+    /// Returns the control block for spending `script` from `tree_info`. It is only computed
+    /// the first time, spends of the same leaf reuse it afterwards.
+    pub fn control_block(
+        &self,
+        tree_info: &TaprootSpendInfo,
+        script: &ScriptBuf,
+    ) -> Result<ControlBlock, BridgeError> {
+        self.control_block_cache.get(tree_info, script)
+    }
+
+    /// Same as utils::handle_taproot_witness, with the control block from control_block
+    pub fn handle_taproot_witness<T: AsRef<[u8]>>(
+        &self,
+        tx: &mut bitcoin::Transaction,
+        index: usize,
+        witness_elements: &Vec<T>,
+        script: &ScriptBuf,
+        tree_info: &TaprootSpendInfo,
+    ) -> Result<(), BridgeError> {
+        let control_block = self.control_block(tree_info, script)?;
+        push_taproot_witness(tx, index, witness_elements, script, &control_block)
+    }
+
+    /// Same as utils::verify_taproot_script_spend, with the control block from control_block
+    pub fn verify_script_spend(
+        &self,
+        tx: &bitcoin::Transaction,
+        index: usize,
+        prevouts: &[TxOut],
+        script: &ScriptBuf,
+        tree_info: &TaprootSpendInfo,
+    ) -> Result<(), BridgeError> {
+        let control_block = self.control_block(tree_info, script)?;
+        verify_taproot_script_spend(tx, index, prevouts, script, &control_block)
     }
 
     /// This function generates a deposit address for the user. N-of-N or User takes after timelock script can be used to spend the funds.
@@ -68,21 +273,38 @@ impl TransactionBuilder {
         &self,
         user_pk: &XOnlyPublicKey,
     ) -> Result<CreateAddressOutputs, BridgeError> {
-        let script_n_of_n_with_user_pk = self
-            .script_builder
-            .generate_script_n_of_n_with_user_pk(user_pk);
-        let script_timelock = ScriptBuilder::generate_timelock_script(user_pk, USER_TAKES_AFTER);
-        let taproot = TaprootBuilder::new()
-            .add_leaf(1, script_n_of_n_with_user_pk.clone())?
-            .add_leaf(1, script_timelock.clone())?;
-        let tree_info = taproot.finalize(&self.secp, *INTERNAL_KEY)?;
-        let address = Address::p2tr(
+        deposit_address(
             &self.secp,
-            *INTERNAL_KEY,
-            tree_info.merkle_root(),
+            &self.verifiers_pks,
+            user_pk,
             bitcoin::Network::Regtest,
-        );
-        Ok((address, tree_info))
+        )
+    }
+
+    /// Same as generate_deposit_address, but also returns the n-of-n leaf script and its
+    /// control block, so that spenders do not have to derive them again
+    pub fn generate_deposit_spend_info(
+        &self,
+        user_pk: &XOnlyPublicKey,
+    ) -> Result<DepositSpendInfo, BridgeError> {
+        let (address, tree_info) = self.generate_deposit_address(user_pk)?;
+        let script_n_of_n_with_user_pk = self
+            .script_builder
+            .deposit_taproot_leaves(user_pk, USER_TAKES_AFTER)
+            .remove(0);
+        let control_block = self.control_block(&tree_info, &script_n_of_n_with_user_pk)?;
+        Ok(DepositSpendInfo {
+            address,
+            tree_info,
+            script_n_of_n_with_user_pk,
+            control_block,
+        })
+    }
+
+    /// SHA256 of the deposit address' output script, a network independent key for indexing deposits
+    pub fn deposit_script_hash(&self, user_pk: &XOnlyPublicKey) -> Result<[u8; 32], BridgeError> {
+        let (deposit_address, _) = self.generate_deposit_address(user_pk)?;
+        Ok(sha256_hash!(deposit_address.script_pubkey().as_bytes()))
     }
 
     // This function generates bridge address. N-of-N script can be used to spend the funds.
@@ -106,45 +328,313 @@ impl TransactionBuilder {
         evm_address: &EVMAddress,
         return_address: &XOnlyPublicKey,
     ) -> Result<CreateTxOutputs, BridgeError> {
-        let anyone_can_spend_txout = ScriptBuilder::anyone_can_spend_txout();
-        let evm_address_inscription_txout = ScriptBuilder::op_return_txout(evm_address);
-        // tracing::debug!(
-        //     "evm_address_inscription_txout: {:?}",
-        //     evm_address_inscription_txout
-        // );
+        self.create_move_tx_with_change(
+            deposit_utxo,
+            evm_address,
+            return_address,
+            None,
+            Amount::from_sat(MIN_RELAY_FEE),
+        )
+    }
 
+    /// Same as create_move_tx, but pays `fee` instead of MIN_RELAY_FEE and, if given, carves
+    /// a change output of the given amount out of the deposit, e.g. as a fee reserve. The
+    /// bridge output gets the deposit minus the fee, the anchor and the change.
+    pub fn create_move_tx_with_change(
+        &self,
+        deposit_utxo: OutPoint,
+        evm_address: &EVMAddress,
+        return_address: &XOnlyPublicKey,
+        change: Option<(&Address, Amount)>,
+        fee: Amount,
+    ) -> Result<CreateTxOutputs, BridgeError> {
         let (bridge_address, _) = self.generate_bridge_address()?;
-        let (deposit_address, deposit_taproot_spend_info) =
-            self.generate_deposit_address(return_address)?;
+        let deposit_spend_info = self.generate_deposit_spend_info(return_address)?;
 
-        let tx_ins = TransactionBuilder::create_tx_ins(vec![deposit_utxo]);
-        let bridge_txout = TxOut {
-            value: Amount::from_sat(BRIDGE_AMOUNT_SATS)
-                - Amount::from_sat(MIN_RELAY_FEE)
-                - anyone_can_spend_txout.value
-                - evm_address_inscription_txout.value,
-            script_pubkey: bridge_address.script_pubkey(),
-        };
-        let move_tx = TransactionBuilder::create_btc_tx(
-            tx_ins,
-            vec![
-                bridge_txout,
-                evm_address_inscription_txout,
-                anyone_can_spend_txout,
-            ],
-        );
+        let change_txout = change.map(|(change_address, value)| TxOut {
+            value,
+            script_pubkey: change_address.script_pubkey(),
+        });
+        let move_tx = TransactionBuilder::create_move_btc_tx_with_change(
+            deposit_utxo,
+            evm_address,
+            Amount::from_sat(BRIDGE_AMOUNT_SATS),
+            &bridge_address,
+            change_txout,
+            fee,
+            self.anchor_kind,
+        )?;
         let prevouts = vec![TxOut {
-            script_pubkey: deposit_address.script_pubkey(),
+            script_pubkey: deposit_spend_info.address.script_pubkey(),
             value: Amount::from_sat(BRIDGE_AMOUNT_SATS),
         }];
-        let script_n_of_n_with_user_pk = vec![self
-            .script_builder
-            .generate_script_n_of_n_with_user_pk(return_address)];
         Ok(CreateTxOutputs {
             tx: move_tx,
             prevouts,
-            scripts: script_n_of_n_with_user_pk,
-            taproot_spend_infos: vec![deposit_taproot_spend_info],
+            scripts: vec![deposit_spend_info.script_n_of_n_with_user_pk],
+            taproot_spend_infos: vec![deposit_spend_info.tree_info],
+        })
+    }
+
+    /// Spends a deposit of `deposit_amount` back to `refund_address` through the refund leaf,
+    /// only valid once the deposit has `refund_blocks` confirmations. The user's signature is
+    /// the whole witness besides the script and control block.
+    pub fn create_deposit_refund_tx(
+        &self,
+        deposit_utxo: OutPoint,
+        deposit_amount: Amount,
+        user_pk: &XOnlyPublicKey,
+        refund_address: &Address,
+        refund_blocks: u32,
+    ) -> Result<CreateTxOutputs, BridgeError> {
+        let block_count = u16::try_from(refund_blocks).map_err(|_| BridgeError::InvalidTimelock)?;
+        let (deposit_address, tree_info) = deposit_address_with_refund_blocks(
+            &self.secp,
+            &self.verifiers_pks,
+            user_pk,
+            refund_blocks,
+            bitcoin::Network::Regtest,
+        )?;
+        let refund_script = ScriptBuilder::generate_timelock_script(user_pk, refund_blocks);
+
+        let tx_ins = TransactionBuilder::create_tx_ins_with_sequence(
+            vec![deposit_utxo],
+            RelativeTimelock::Blocks(block_count),
+        )?;
+        let tx_outs = TransactionBuilder::create_tx_outs(vec![(
+            deposit_amount - Amount::from_sat(MIN_RELAY_FEE),
+            refund_address.script_pubkey(),
+        )]);
+        Ok(CreateTxOutputs {
+            tx: TransactionBuilder::create_btc_tx(tx_ins, tx_outs),
+            prevouts: vec![TxOut {
+                script_pubkey: deposit_address.script_pubkey(),
+                value: deposit_amount,
+            }],
+            scripts: vec![refund_script],
+            taproot_spend_infos: vec![tree_info],
+        })
+    }
+
+    /// Move tx without its witness: pays `bridge_amount` minus fees to the bridge address, and
+    /// inscribes the evm address the deposit is for
+    fn create_move_btc_tx(
+        deposit_utxo: OutPoint,
+        evm_address: &EVMAddress,
+        bridge_amount: Amount,
+        bridge_address: &Address,
+    ) -> bitcoin::Transaction {
+        TransactionBuilder::create_move_btc_tx_with_change(
+            deposit_utxo,
+            evm_address,
+            bridge_amount,
+            bridge_address,
+            None,
+            Amount::from_sat(MIN_RELAY_FEE),
+            AnchorKind::default(),
+        )
+        .expect("the bridge amount covers the minimum fee and the anchor")
+    }
+
+    /// Move tx without its witness, the bridge output gets what is left of `bridge_amount`
+    /// after `fee`, the anchor and `change`, which comes last if given
+    fn create_move_btc_tx_with_change(
+        deposit_utxo: OutPoint,
+        evm_address: &EVMAddress,
+        bridge_amount: Amount,
+        bridge_address: &Address,
+        change: Option<TxOut>,
+        fee: Amount,
+        anchor_kind: AnchorKind,
+    ) -> Result<bitcoin::Transaction, BridgeError> {
+        let anyone_can_spend_txout = anchor_kind.txout();
+        let evm_address_inscription_txout = ScriptBuilder::op_return_txout(evm_address);
+        let change_value = change.as_ref().map_or(Amount::ZERO, |txout| txout.value);
+        let bridge_value = bridge_amount
+            .checked_sub(fee)
+            .and_then(|value| value.checked_sub(anyone_can_spend_txout.value))
+            .and_then(|value| value.checked_sub(evm_address_inscription_txout.value))
+            .and_then(|value| value.checked_sub(change_value))
+            .ok_or(BridgeError::MoveTxValueMismatch)?;
+        let bridge_txout = TxOut {
+            value: bridge_value,
+            script_pubkey: bridge_address.script_pubkey(),
+        };
+        let mut tx_outs = vec![
+            bridge_txout,
+            evm_address_inscription_txout,
+            anyone_can_spend_txout,
+        ];
+        tx_outs.extend(change);
+        Ok(TransactionBuilder::create_btc_tx(
+            TransactionBuilder::create_tx_ins(vec![deposit_utxo]),
+            tx_outs,
+        ))
+    }
+
+    /// Orders the signatures of an n-of-n script spend of `script` for the witness. `verifier_sigs`
+    /// are in the order of `verifiers_pks` without the operator's key. The script checks its first key
+    /// against the top of the stack, so the witness is the user's signature for the deposit script (if
+    /// any), then the operator's signature, then the verifiers' signatures in reverse order.
+    pub fn build_n_of_n_witness(
+        verifier_sigs: &[schnorr::Signature],
+        operator_sig: schnorr::Signature,
+        user_sig: Option<schnorr::Signature>,
+        script: &ScriptBuf,
+    ) -> Result<Vec<schnorr::Signature>, BridgeError> {
+        let witness = user_sig
+            .into_iter()
+            .chain(std::iter::once(operator_sig))
+            .chain(verifier_sigs.iter().rev().copied())
+            .collect::<Vec<_>>();
+        let required = script_signature_count(script);
+        // The deposit script takes one more signature than the n-of-n keys, the user's
+        if user_sig.is_none() && required == witness.len() + 1 {
+            return Err(BridgeError::MissingUserSignature);
+        }
+        if required != witness.len() {
+            return Err(BridgeError::WitnessElementCountMismatch);
+        }
+        Ok(witness)
+    }
+
+    /// Virtual size of `tx` once it is signed. Inputs that already carry a witness are counted as
+    /// they are. The others spend the script at their index in `tx.scripts`, with a signature for
+    /// every OP_CHECKSIG and OP_CHECKSIGVERIFY in it and the control block of the script in their
+    /// `tx.taproot_spend_infos`, or are key path spends if they have no script.
+    pub fn estimate_vsize(&self, tx: &CreateTxOutputs) -> Result<usize, BridgeError> {
+        let mut sized_tx = tx.tx.clone();
+        for (index, input) in sized_tx.input.iter_mut().enumerate() {
+            if !input.witness.is_empty() {
+                continue;
+            }
+            match (tx.scripts.get(index), tx.taproot_spend_infos.get(index)) {
+                (Some(script), Some(tree_info)) => {
+                    let control_block = self.control_block(tree_info, script)?;
+                    for _ in 0..script_signature_count(script) {
+                        input.witness.push([0u8; 64]);
+                    }
+                    input.witness.push(script.as_bytes());
+                    input.witness.push(control_block.serialize());
+                }
+                _ => input.witness.push([0u8; 64]),
+            }
+        }
+        Ok(sized_tx.vsize())
+    }
+
+    /// Fee `tx` needs to pay at `feerate_sat_vb` satoshis per virtual byte, see estimate_vsize
+    pub fn estimate_fee(
+        &self,
+        tx: &CreateTxOutputs,
+        feerate_sat_vb: u64,
+    ) -> Result<Amount, BridgeError> {
+        Ok(Amount::from_sat(
+            self.estimate_vsize(tx)? as u64 * feerate_sat_vb,
+        ))
+    }
+
+    /// Checks that `tx` pays at least MIN_RELAY_FEERATE for its estimated size out of its prevouts
+    pub fn check_fee(&self, tx: &CreateTxOutputs) -> Result<(), BridgeError> {
+        let input_value = tx
+            .prevouts
+            .iter()
+            .map(|prevout| prevout.value)
+            .sum::<Amount>();
+        let output_value = tx
+            .tx
+            .output
+            .iter()
+            .map(|output| output.value)
+            .sum::<Amount>();
+        let required = self.estimate_fee(tx, MIN_RELAY_FEERATE)?;
+        match input_value.checked_sub(output_value) {
+            Some(fee) if fee >= required => Ok(()),
+            _ => Err(BridgeError::InsufficientFee),
+        }
+    }
+
+    /// Child tx that bumps `parent` through its anyone can spend output at `anchor_vout`, funded by
+    /// `resource_utxo` which pays to `resource_txout`. It pays what the parent lacks for the whole
+    /// package to reach `feerate_sat_vb` and returns the rest of the resource utxo to
+    /// `change_address`. A change below the dust limit is left as fee instead. The resource input
+    /// is spent with a key path signature, which is left to the caller.
+    pub fn create_child_pays_for_parent(
+        &self,
+        parent: &CreateTxOutputs,
+        anchor_vout: u32,
+        resource_utxo: OutPoint,
+        resource_txout: TxOut,
+        change_address: &Address,
+        feerate_sat_vb: u64,
+    ) -> Result<CreateTxOutputs, BridgeError> {
+        let anchor_txout = parent
+            .tx
+            .output
+            .get(anchor_vout as usize)
+            .cloned()
+            .ok_or(BridgeError::TxInputNotFound)?;
+        let parent_input_value = parent
+            .prevouts
+            .iter()
+            .map(|prevout| prevout.value)
+            .sum::<Amount>();
+        let parent_output_value = parent
+            .tx
+            .output
+            .iter()
+            .map(|output| output.value)
+            .sum::<Amount>();
+        let parent_fee = parent_input_value
+            .checked_sub(parent_output_value)
+            .ok_or(BridgeError::InsufficientFee)?;
+
+        let mut tx_ins = TransactionBuilder::create_tx_ins(vec![
+            OutPoint {
+                txid: parent.tx.txid(),
+                vout: anchor_vout,
+            },
+            resource_utxo,
+        ]);
+        tx_ins[0].witness = AnchorKind::from_script_pubkey(&anchor_txout.script_pubkey)
+            .ok_or(BridgeError::NotAnAnchor)?
+            .spend_witness();
+        let change_txout = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: change_address.script_pubkey(),
+        };
+        let mut child_tx = TransactionBuilder::create_btc_tx(tx_ins, vec![change_txout.clone()]);
+
+        // Size the child with a placeholder key path signature for the resource input
+        let mut sized_child_tx = child_tx.clone();
+        sized_child_tx.input[1].witness.push([0u8; 64]);
+        let child_vsize = sized_child_tx.vsize() as u64;
+        let package_vsize = self.estimate_vsize(parent)? as u64 + child_vsize;
+        let child_fee = Amount::from_sat(package_vsize * feerate_sat_vb)
+            .checked_sub(parent_fee)
+            .unwrap_or(Amount::ZERO)
+            .max(Amount::from_sat(child_vsize * MIN_RELAY_FEERATE));
+        let change_value = (anchor_txout.value + resource_txout.value)
+            .checked_sub(child_fee)
+            .ok_or(BridgeError::InsufficientFee)?;
+
+        child_tx.output = if change_value >= change_txout.script_pubkey.dust_value() {
+            vec![TxOut {
+                value: change_value,
+                ..change_txout
+            }]
+        } else {
+            vec![TxOut {
+                value: Amount::ZERO,
+                script_pubkey: ScriptBuf::new_op_return([]),
+            }]
+        };
+
+        Ok(CreateTxOutputs {
+            tx: child_tx,
+            prevouts: vec![anchor_txout, resource_txout],
+            scripts: vec![],
+            taproot_spend_infos: vec![],
         })
     }
 
@@ -161,13 +651,19 @@ impl TransactionBuilder {
                 &self.secp,
                 operator_xonly,
                 hash,
+                self.connector_tree_operator_takes_after,
             )?;
         let (bridge_address, bridge_taproot_spend_info) = self.generate_bridge_address()?;
 
-        let anyone_can_spend_txout: TxOut = ScriptBuilder::anyone_can_spend_txout();
+        let anyone_can_spend_txout: TxOut = self.anchor_kind.txout();
         let evm_address_inscription_txout: TxOut =
             ScriptBuilder::op_return_txout(&EVMAddress::default());
-        let tx_ins = TransactionBuilder::create_tx_ins(vec![bridge_utxo, connector_utxo]);
+        // The connector leaf is spent by the operator after its timelock
+        let mut tx_ins = TransactionBuilder::create_tx_ins(vec![bridge_utxo]);
+        tx_ins.extend(TransactionBuilder::create_tx_ins_with_sequence(
+            vec![connector_utxo],
+            RelativeTimelock::Blocks(self.connector_tree_operator_takes_after),
+        )?);
         let claim_txout = TxOut {
             value: Amount::from_sat(BRIDGE_AMOUNT_SATS)
                 - Amount::from_sat(MIN_RELAY_FEE * 2)
@@ -180,7 +676,13 @@ impl TransactionBuilder {
             TransactionBuilder::create_btc_tx(tx_ins, vec![claim_txout, anyone_can_spend_txout]);
         let prevouts =
             self.create_operator_claim_tx_prevouts(&bridge_address, &connector_tree_leaf_address)?;
-        let scripts = vec![self.script_builder.generate_script_n_of_n()];
+        let scripts = vec![
+            self.script_builder.generate_script_n_of_n(),
+            ScriptBuilder::generate_timelock_script(
+                operator_xonly,
+                self.connector_tree_operator_takes_after as u32,
+            ),
+        ];
 
         Ok(CreateTxOutputs {
             tx: claim_tx,
@@ -195,7 +697,7 @@ impl TransactionBuilder {
         bridge_address: &Address,
         connector_tree_leaf_address: &Address,
     ) -> Result<Vec<TxOut>, BridgeError> {
-        let anyone_can_spend_txout: TxOut = ScriptBuilder::anyone_can_spend_txout();
+        let anyone_can_spend_txout: TxOut = self.anchor_kind.txout();
         Ok(vec![
             TxOut {
                 value: Amount::from_sat(BRIDGE_AMOUNT_SATS)
@@ -229,12 +731,16 @@ impl TransactionBuilder {
         ),
         BridgeError,
     > {
-        let single_tree_amount = calculate_amount(
-            CONNECTOR_TREE_DEPTH,
-            Amount::from_sat(DUST_VALUE),
-            Amount::from_sat(MIN_RELAY_FEE),
-        );
-        let total_amount = Amount::from_sat((single_tree_amount.to_sat()) * NUM_ROUNDS as u64);
+        // Depth of the connector trees is determined by the hashes the operator committed to
+        let connector_tree_depth = connector_tree_hashes
+            .first()
+            .ok_or(BridgeError::InvalidConnectorTreeDepth)?
+            .len()
+            - 1;
+        // Every period's tx pays the relay fee and funds the root of the period's tree
+        let single_tree_amount = self.connector_tree_node_amount(connector_tree_depth)?;
+        let total_amount =
+            (single_tree_amount + Amount::from_sat(MIN_RELAY_FEE)) * NUM_ROUNDS as u64;
 
         let mut cur_connector_source_utxo = *first_source_utxo;
         let mut cur_amount = total_amount;
@@ -254,17 +760,8 @@ impl TransactionBuilder {
             //         CONNECTOR_TREE_DEPTH,
             //         &connector_tree_hashes[i],
             //     ));
-            let mut claim_proof_merkle_tree_i: MerkleTree<CLAIM_MERKLE_TREE_DEPTH> =
-                MerkleTree::new();
-            for j in 0..(2_usize.pow(CONNECTOR_TREE_DEPTH as u32)) {
-                let hash = get_claim_proof_tree_leaf(
-                    CLAIM_MERKLE_TREE_DEPTH,
-                    j,
-                    &connector_tree_hashes[i],
-                );
-                // tracing::debug!("hash: {:?}", hash);
-                claim_proof_merkle_tree_i.add(hash);
-            }
+            let claim_proof_merkle_tree_i =
+                claim_proof_merkle_tree(connector_tree_depth, &connector_tree_hashes[i])?;
             claim_proof_merkle_roots.push(claim_proof_merkle_tree_i.root());
             claim_proof_merkle_trees.push(claim_proof_merkle_tree_i);
 
@@ -279,17 +776,18 @@ impl TransactionBuilder {
                     &self.secp,
                     &self.verifiers_pks[self.verifiers_pks.len() - 1],
                     &connector_tree_hashes[i][0][0],
+                    self.connector_tree_operator_takes_after,
                 )?;
             let curr_root_and_next_source_tx_ins =
                 TransactionBuilder::create_tx_ins(vec![cur_connector_source_utxo]);
 
             let curr_root_and_next_source_tx_outs = TransactionBuilder::create_tx_outs(vec![
                 (
-                    cur_amount - single_tree_amount,
+                    cur_amount - single_tree_amount - Amount::from_sat(MIN_RELAY_FEE),
                     next_connector_source_address.script_pubkey(),
                 ),
                 (
-                    single_tree_amount - Amount::from_sat(MIN_RELAY_FEE),
+                    single_tree_amount,
                     connector_bt_root_address.script_pubkey(),
                 ),
             ]);
@@ -309,12 +807,12 @@ impl TransactionBuilder {
                 i,
                 &self.verifiers_pks[self.verifiers_pks.len() - 1],
                 &cur_connector_bt_root_utxo,
-                CONNECTOR_TREE_DEPTH,
+                connector_tree_depth,
                 connector_tree_hashes[i].clone(),
             )?;
             root_utxos.push(cur_connector_bt_root_utxo);
             utxo_trees.push(utxo_tree);
-            cur_amount = cur_amount - single_tree_amount;
+            cur_amount = cur_amount - single_tree_amount - Amount::from_sat(MIN_RELAY_FEE);
         }
 
         Ok((
@@ -347,19 +845,21 @@ impl TransactionBuilder {
         tx_ins
     }
 
-    fn create_tx_ins_with_sequence(utxos: Vec<OutPoint>) -> Vec<TxIn> {
+    fn create_tx_ins_with_sequence(
+        utxos: Vec<OutPoint>,
+        timelock: RelativeTimelock,
+    ) -> Result<Vec<TxIn>, BridgeError> {
+        let sequence = timelock.to_sequence()?;
         let mut tx_ins = Vec::new();
         for utxo in utxos {
             tx_ins.push(TxIn {
                 previous_output: utxo,
-                sequence: bitcoin::transaction::Sequence::from_height(
-                    CONNECTOR_TREE_OPERATOR_TAKES_AFTER,
-                ),
+                sequence,
                 script_sig: ScriptBuf::default(),
                 witness: Witness::new(),
             });
         }
-        tx_ins
+        Ok(tx_ins)
     }
 
     fn create_tx_outs(pairs: Vec<(Amount, ScriptBuf)>) -> Vec<TxOut> {
@@ -422,15 +922,16 @@ impl TransactionBuilder {
         Ok((address, tree_info))
     }
 
+    /// The operator can spend a connector tree node after `operator_takes_after` blocks,
+    /// anyone who knows the preimage of `hash` can spend it right away
     pub fn create_connector_tree_node_address(
         secp: &Secp256k1<secp256k1::All>,
         actor_pk: &XOnlyPublicKey,
         hash: &HashType,
+        operator_takes_after: u16,
     ) -> Result<CreateAddressOutputs, BridgeError> {
-        let timelock_script = ScriptBuilder::generate_timelock_script(
-            actor_pk,
-            CONNECTOR_TREE_OPERATOR_TAKES_AFTER as u32,
-        );
+        let timelock_script =
+            ScriptBuilder::generate_timelock_script(actor_pk, operator_takes_after as u32);
         let preimage_script = Builder::new()
             .push_opcode(OP_SHA256)
             .push_slice(hash)
@@ -449,7 +950,7 @@ impl TransactionBuilder {
         preimages_to_be_revealed: &Vec<PreimageType>,
     ) -> Result<(Address, TaprootSpendInfo, ScriptBuf), BridgeError> {
         let inscribe_preimage_script =
-            ScriptBuilder::create_inscription_script_32_bytes(actor_pk, preimages_to_be_revealed);
+            ScriptBuilder::create_inscription_script_32_bytes(actor_pk, preimages_to_be_revealed)?;
         let (address, taproot_info) = TransactionBuilder::create_taproot_address(
             &self.secp,
             vec![inscribe_preimage_script.clone()],
@@ -466,6 +967,23 @@ impl TransactionBuilder {
         commit_utxo: OutPoint,
         sender_xonly: &XOnlyPublicKey,
         preimages_to_be_revealed: &Vec<PreimageType>,
+    ) -> Result<CreateTxOutputs, BridgeError> {
+        self.create_inscription_reveal_tx_with_commit_value(
+            commit_utxo,
+            sender_xonly,
+            preimages_to_be_revealed,
+            Amount::from_sat(DUST_VALUE * 2),
+        )
+    }
+
+    /// Same as create_inscription_reveal_tx for a commit output worth `commit_value`, see
+    /// inscription_commit_value
+    pub fn create_inscription_reveal_tx_with_commit_value(
+        &self,
+        commit_utxo: OutPoint,
+        sender_xonly: &XOnlyPublicKey,
+        preimages_to_be_revealed: &Vec<PreimageType>,
+        commit_value: Amount,
     ) -> Result<CreateTxOutputs, BridgeError> {
         let (commit_address, commit_tree_info, inscribe_preimage_script) =
             self.create_inscription_commit_address(sender_xonly, preimages_to_be_revealed)?;
@@ -476,7 +994,7 @@ impl TransactionBuilder {
 
         let prevouts = vec![TxOut {
             script_pubkey: commit_address.script_pubkey(),
-            value: Amount::from_sat(DUST_VALUE * 2),
+            value: commit_value,
         }];
 
         Ok(CreateTxOutputs {
@@ -487,33 +1005,151 @@ impl TransactionBuilder {
         })
     }
 
+    /// Value the commit output of an inscription of `preimages_to_be_revealed` needs for the
+    /// reveal tx to pay `feerate_sat_vb` on its signed size, on top of its anchor output
+    pub fn inscription_commit_value(
+        &self,
+        sender_xonly: &XOnlyPublicKey,
+        preimages_to_be_revealed: &Vec<PreimageType>,
+        feerate_sat_vb: u64,
+    ) -> Result<Amount, BridgeError> {
+        let reveal_tx = self.create_inscription_reveal_tx(
+            OutPoint::null(),
+            sender_xonly,
+            preimages_to_be_revealed,
+        )?;
+        let output_value = reveal_tx
+            .tx
+            .output
+            .iter()
+            .map(|txout| txout.value)
+            .sum::<Amount>();
+        Ok(Amount::from_sat(
+            (self.estimate_vsize(&reveal_tx)? as u64)
+                .saturating_mul(feerate_sat_vb)
+                .saturating_add(output_value.to_sat()),
+        ))
+    }
+
+    /// Extracts the preimages a reveal tx inscribes, by walking the
+    /// `<pk> OP_CHECKSIG OP_FALSE OP_IF <preimage>... OP_ENDIF` script it spends
+    pub fn parse_inscription_preimages(
+        reveal_tx: &bitcoin::Transaction,
+    ) -> Result<Vec<PreimageType>, BridgeError> {
+        let witness = &reveal_tx
+            .input
+            .first()
+            .ok_or(BridgeError::InvalidInscription)?
+            .witness;
+        // The witness is the signature, the script and the control block
+        if witness.len() != 3 {
+            return Err(BridgeError::InvalidInscription);
+        }
+        let script = bitcoin::Script::from_bytes(witness.nth(1).expect("witness has 3 elements"));
+        let mut instructions = script.instructions();
+        let mut next = || {
+            instructions
+                .next()
+                .ok_or(BridgeError::InvalidInscription)?
+                .map_err(|_| BridgeError::InvalidInscription)
+        };
+        match next()? {
+            Instruction::PushBytes(pk) if XOnlyPublicKey::from_slice(pk.as_bytes()).is_ok() => {}
+            _ => return Err(BridgeError::InvalidInscription),
+        }
+        if next()? != Instruction::Op(OP_CHECKSIG) {
+            return Err(BridgeError::InvalidInscription);
+        }
+        match next()? {
+            Instruction::PushBytes(bytes) if bytes.is_empty() => {}
+            _ => return Err(BridgeError::InvalidInscription),
+        }
+        if next()? != Instruction::Op(OP_IF) {
+            return Err(BridgeError::InvalidInscription);
+        }
+        let mut preimages = Vec::new();
+        loop {
+            match next()? {
+                Instruction::PushBytes(bytes) => preimages.push(
+                    bytes
+                        .as_bytes()
+                        .try_into()
+                        .map_err(|_| BridgeError::InvalidInscription)?,
+                ),
+                Instruction::Op(OP_ENDIF) => break,
+                Instruction::Op(_) => return Err(BridgeError::InvalidInscription),
+            }
+        }
+        // Nothing can follow the envelope
+        if next().is_ok() {
+            return Err(BridgeError::InvalidInscription);
+        }
+        Ok(preimages)
+    }
+
+    /// Checks that `reveal` spends an output of `commit` through the inscription script it
+    /// reveals, i.e. that the revealed script and control block commit to that output's key
+    pub fn verify_commit_reveal(
+        &self,
+        commit: &bitcoin::Transaction,
+        reveal: &bitcoin::Transaction,
+    ) -> Result<(), BridgeError> {
+        let input = reveal
+            .input
+            .first()
+            .ok_or(BridgeError::CommitRevealMismatch)?;
+        if input.previous_output.txid != commit.txid() {
+            return Err(BridgeError::CommitRevealMismatch);
+        }
+        let commit_output = commit
+            .output
+            .get(input.previous_output.vout as usize)
+            .ok_or(BridgeError::CommitRevealMismatch)?;
+        if !commit_output.script_pubkey.is_p2tr() {
+            return Err(BridgeError::CommitRevealMismatch);
+        }
+        let output_key = XOnlyPublicKey::from_slice(&commit_output.script_pubkey.as_bytes()[2..])
+            .map_err(|_| BridgeError::CommitRevealMismatch)?;
+
+        TransactionBuilder::parse_inscription_preimages(reveal)
+            .map_err(|_| BridgeError::CommitRevealMismatch)?;
+        let script = bitcoin::Script::from_bytes(
+            input
+                .witness
+                .nth(1)
+                .expect("inscription witness has 3 elements"),
+        );
+        let control_block = ControlBlock::decode(
+            input
+                .witness
+                .nth(2)
+                .expect("inscription witness has 3 elements"),
+        )
+        .map_err(|_| BridgeError::CommitRevealMismatch)?;
+        if !control_block.verify_taproot_commitment(&self.secp, output_key, script) {
+            return Err(BridgeError::CommitRevealMismatch);
+        }
+        Ok(())
+    }
+
+    /// Spends a connector tree node into its two children, each worth `child_amount`, see
+    /// connector_tree_node_amount
     pub fn create_connector_tree_tx(
         utxo: &OutPoint,
-        depth: usize,
+        child_amount: Amount,
         first_address: Address,
         second_address: Address,
-    ) -> bitcoin::Transaction {
-        // UTXO value should be at least 2^depth * dust_value + (2^depth-1) * fee
-        let tx_ins = TransactionBuilder::create_tx_ins_with_sequence(vec![*utxo]);
+        operator_takes_after: u16,
+    ) -> Result<bitcoin::Transaction, BridgeError> {
+        let tx_ins = TransactionBuilder::create_tx_ins_with_sequence(
+            vec![*utxo],
+            RelativeTimelock::Blocks(operator_takes_after),
+        )?;
         let tx_outs = TransactionBuilder::create_tx_outs(vec![
-            (
-                calculate_amount(
-                    depth,
-                    Amount::from_sat(DUST_VALUE),
-                    Amount::from_sat(MIN_RELAY_FEE),
-                ),
-                first_address.script_pubkey(),
-            ),
-            (
-                calculate_amount(
-                    depth,
-                    Amount::from_sat(DUST_VALUE),
-                    Amount::from_sat(MIN_RELAY_FEE),
-                ),
-                second_address.script_pubkey(),
-            ),
+            (child_amount, first_address.script_pubkey()),
+            (child_amount, second_address.script_pubkey()),
         ]);
-        TransactionBuilder::create_btc_tx(tx_ins, tx_outs)
+        Ok(TransactionBuilder::create_btc_tx(tx_ins, tx_outs))
     }
 
     // This function creates the connector binary tree for operator to be able to claim the funds that they paid out of their pocket.
@@ -526,24 +1162,18 @@ impl TransactionBuilder {
         depth: usize,
         connector_tree_hashes: Vec<Vec<[u8; 32]>>,
     ) -> Result<ConnectorUTXOTree, BridgeError> {
-        // UTXO value should be at least 2^depth * dust_value + (2^depth-1) * fee
-        let _total_amount = calculate_amount(
-            depth,
-            Amount::from_sat(DUST_VALUE),
-            Amount::from_sat(MIN_RELAY_FEE),
-        );
-        // tracing::debug!("total_amount: {:?}", total_amount);
-
         let (_root_address, _) = TransactionBuilder::create_connector_tree_node_address(
             &self.secp,
             xonly_public_key,
             &connector_tree_hashes[0][0],
+            self.connector_tree_operator_takes_after,
         )?;
 
         let mut utxo_binary_tree: ConnectorUTXOTree = Vec::new();
         utxo_binary_tree.push(vec![*root_utxo]);
 
         for i in 0..depth {
+            let child_amount = self.connector_tree_node_amount(depth - i - 1)?;
             let mut utxo_tree_current_level: Vec<OutPoint> = Vec::new();
             let utxo_tree_previous_level = utxo_binary_tree.last().unwrap();
 
@@ -552,19 +1182,22 @@ impl TransactionBuilder {
                     &self.secp,
                     xonly_public_key,
                     &connector_tree_hashes[i + 1][2 * j],
+                    self.connector_tree_operator_takes_after,
                 )?;
                 let (second_address, _) = TransactionBuilder::create_connector_tree_node_address(
                     &self.secp,
                     xonly_public_key,
                     &connector_tree_hashes[i + 1][2 * j + 1],
+                    self.connector_tree_operator_takes_after,
                 )?;
 
                 let tx = TransactionBuilder::create_connector_tree_tx(
                     utxo,
-                    depth - i - 1,
+                    child_amount,
                     first_address.clone(),
                     second_address.clone(),
-                );
+                    self.connector_tree_operator_takes_after,
+                )?;
                 let txid = tx.txid();
                 utxo_tree_current_level.push(OutPoint { txid, vout: 0 });
                 utxo_tree_current_level.push(OutPoint { txid, vout: 1 });
@@ -574,3 +1207,977 @@ impl TransactionBuilder {
         Ok(utxo_binary_tree)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::Actor;
+    use crate::constants::CONNECTOR_TREE_DEPTH;
+    use crate::test_utils::MoveTxFixture;
+    use crate::utils::{
+        handle_taproot_witness, handle_taproot_witness_new, verify_taproot_witness,
+    };
+    use bitcoin::hashes::Hash;
+    use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+    use bitcoin::taproot::{LeafVersion, TapLeafHash};
+    use secp256k1::rand::rngs::StdRng;
+    use secp256k1::rand::SeedableRng;
+    use secp256k1::Message;
+
+    #[test]
+    fn test_build_n_of_n_witness() {
+        let secp = Secp256k1::new();
+        let MoveTxFixture {
+            actors,
+            transaction_builder,
+            mut move_tx,
+            sigs,
+            ..
+        } = MoveTxFixture::new(
+            20,
+            OutPoint {
+                txid: Txid::from_byte_array([21u8; 32]),
+                vout: 0,
+            },
+            &[1u8; 20],
+        );
+
+        let deposit_script = &move_tx.scripts[0];
+        let witness = TransactionBuilder::build_n_of_n_witness(
+            &sigs[..3],
+            sigs[3],
+            Some(sigs[4]),
+            deposit_script,
+        )
+        .unwrap();
+        assert_eq!(witness, vec![sigs[4], sigs[3], sigs[2], sigs[1], sigs[0]]);
+        let n_of_n_script = transaction_builder.script_builder.generate_script_n_of_n();
+        let n_of_n_witness =
+            TransactionBuilder::build_n_of_n_witness(&sigs[..3], sigs[3], None, &n_of_n_script)
+                .unwrap();
+        assert_eq!(n_of_n_witness, vec![sigs[3], sigs[2], sigs[1], sigs[0]]);
+
+        // The deposit script also takes the user's signature
+        assert_eq!(
+            TransactionBuilder::build_n_of_n_witness(&sigs[..3], sigs[3], None, deposit_script),
+            Err(BridgeError::MissingUserSignature)
+        );
+        assert_eq!(
+            TransactionBuilder::build_n_of_n_witness(&sigs[..2], sigs[3], None, deposit_script),
+            Err(BridgeError::WitnessElementCountMismatch)
+        );
+        assert_eq!(
+            TransactionBuilder::build_n_of_n_witness(
+                &sigs[..3],
+                sigs[3],
+                Some(sigs[4]),
+                &n_of_n_script
+            ),
+            Err(BridgeError::WitnessElementCountMismatch)
+        );
+
+        // The script consumes the top of the stack first, so the i-th key in the script
+        // has to verify the i-th signature from the end of the witness
+        let script_keys = move_tx.scripts[0]
+            .instructions()
+            .filter_map(|instruction| match instruction.unwrap() {
+                Instruction::PushBytes(bytes) => {
+                    Some(XOnlyPublicKey::from_slice(bytes.as_bytes()).unwrap())
+                }
+                Instruction::Op(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(script_keys.len(), witness.len());
+        let sighash = actors[0]
+            .sighash_taproot_script_spend(&mut move_tx, 0)
+            .unwrap();
+        let message = Message::from_digest_slice(sighash.as_byte_array()).unwrap();
+        for (key, sig) in script_keys.iter().zip(witness.iter().rev()) {
+            assert!(secp.verify_schnorr(sig, &message, key).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_deposit_script_hash() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([38u8; 32]);
+        let pks = (0..5)
+            .map(|_| XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1))
+            .collect::<Vec<_>>();
+        let transaction_builder = TransactionBuilder::new(pks[..3].to_vec());
+
+        let script_hash = transaction_builder.deposit_script_hash(&pks[3]).unwrap();
+        assert_eq!(
+            TransactionBuilder::new(pks[..3].to_vec())
+                .deposit_script_hash(&pks[3])
+                .unwrap(),
+            script_hash
+        );
+        let (deposit_address, _) = transaction_builder
+            .generate_deposit_address(&pks[3])
+            .unwrap();
+        assert_eq!(
+            script_hash,
+            sha256_hash!(deposit_address.script_pubkey().as_bytes())
+        );
+        assert_ne!(
+            transaction_builder.deposit_script_hash(&pks[4]).unwrap(),
+            script_hash
+        );
+    }
+
+    #[test]
+    fn test_spend_deposit_with_spend_info() {
+        let secp = Secp256k1::new();
+        let MoveTxFixture {
+            actors,
+            transaction_builder,
+            ..
+        } = MoveTxFixture::new(52, OutPoint::null(), &[1u8; 20]);
+        let spend_info = transaction_builder
+            .generate_deposit_spend_info(&actors[4].xonly_public_key)
+            .unwrap();
+
+        let prevouts = vec![TxOut {
+            script_pubkey: spend_info.address.script_pubkey(),
+            value: Amount::from_sat(BRIDGE_AMOUNT_SATS),
+        }];
+        let mut spend_tx = TransactionBuilder::create_btc_tx(
+            TransactionBuilder::create_tx_ins(vec![OutPoint {
+                txid: Txid::from_byte_array([53u8; 32]),
+                vout: 0,
+            }]),
+            vec![ScriptBuilder::anyone_can_spend_txout()],
+        );
+        let sigs = actors
+            .iter()
+            .map(|actor| {
+                actor
+                    .sign_taproot_script_spend_tx(
+                        &mut spend_tx,
+                        &prevouts,
+                        &spend_info.script_n_of_n_with_user_pk,
+                        0,
+                    )
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let witness_sigs = TransactionBuilder::build_n_of_n_witness(
+            &sigs[..3],
+            sigs[3],
+            Some(sigs[4]),
+            &spend_info.script_n_of_n_with_user_pk,
+        )
+        .unwrap();
+        for sig in witness_sigs.iter() {
+            spend_tx.input[0].witness.push(sig.as_ref());
+        }
+        spend_tx.input[0]
+            .witness
+            .push(spend_info.script_n_of_n_with_user_pk.clone());
+        spend_tx.input[0]
+            .witness
+            .push(spend_info.control_block.serialize());
+
+        // The leaf and the control block commit to the output key of the deposit address
+        let witness = &spend_tx.input[0].witness;
+        let script = ScriptBuf::from_bytes(witness.nth(witness.len() - 2).unwrap().to_vec());
+        let control_block = ControlBlock::decode(witness.last().unwrap()).unwrap();
+        assert!(control_block.verify_taproot_commitment(
+            &secp,
+            spend_info.tree_info.output_key().to_inner(),
+            &script
+        ));
+        assert_eq!(
+            spend_info.address,
+            Address::p2tr_tweaked(spend_info.tree_info.output_key(), bitcoin::Network::Regtest)
+        );
+
+        // Every key of the leaf signs the spend
+        let sighash = SighashCache::new(&spend_tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&prevouts),
+                TapLeafHash::from_script(&script, LeafVersion::TapScript),
+                TapSighashType::Default,
+            )
+            .unwrap();
+        let message = Message::from_digest_slice(sighash.as_byte_array()).unwrap();
+        let script_keys = script
+            .instructions()
+            .filter_map(|instruction| match instruction.unwrap() {
+                Instruction::PushBytes(bytes) => {
+                    Some(XOnlyPublicKey::from_slice(bytes.as_bytes()).unwrap())
+                }
+                Instruction::Op(_) => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(script_keys.len(), witness_sigs.len());
+        for (key, sig) in script_keys.iter().zip(witness_sigs.iter().rev()) {
+            assert!(secp.verify_schnorr(sig, &message, key).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_parse_inscription_preimages() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([56u8; 32]);
+        let actor = Actor::new(secp.generate_keypair(&mut rng).0);
+        let transaction_builder = TransactionBuilder::new(vec![actor.xonly_public_key]);
+        let commit_utxo = OutPoint {
+            txid: Txid::from_byte_array([57u8; 32]),
+            vout: 0,
+        };
+
+        for count in [1u8, 2, 7] {
+            let preimages = (0..count).map(|i| [i; 32]).collect::<Vec<_>>();
+            let mut reveal_tx = transaction_builder
+                .create_inscription_reveal_tx(commit_utxo, &actor.xonly_public_key, &preimages)
+                .unwrap();
+            let sig = actor
+                .sign_taproot_script_spend_tx_new(&mut reveal_tx, 0)
+                .unwrap();
+            handle_taproot_witness_new(&mut reveal_tx, &vec![sig.as_ref()], 0).unwrap();
+            assert_eq!(
+                TransactionBuilder::parse_inscription_preimages(&reveal_tx.tx).unwrap(),
+                preimages
+            );
+            // The envelope is skipped, only the signature is checked
+            assert_eq!(
+                verify_taproot_witness(&reveal_tx.tx, 0, &reveal_tx.prevouts),
+                Ok(())
+            );
+        }
+
+        let reveal_tx_with_script = |script: ScriptBuf| {
+            let mut tx = TransactionBuilder::create_btc_tx(
+                TransactionBuilder::create_tx_ins(vec![commit_utxo]),
+                vec![ScriptBuilder::anyone_can_spend_txout()],
+            );
+            tx.input[0].witness.push([0u8; 64]);
+            tx.input[0].witness.push(script);
+            tx.input[0].witness.push([0u8; 33]);
+            tx
+        };
+        let envelope = || {
+            Builder::new()
+                .push_x_only_key(&actor.xonly_public_key)
+                .push_opcode(OP_CHECKSIG)
+                .push_opcode(bitcoin::opcodes::OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice([1u8; 32])
+        };
+        let malformed_scripts = vec![
+            // Envelope is not closed
+            envelope().into_script(),
+            // Pushes that are not 32 bytes
+            envelope()
+                .push_slice([1u8; 31])
+                .push_opcode(OP_ENDIF)
+                .into_script(),
+            // Opcodes inside the envelope
+            envelope()
+                .push_opcode(OP_SHA256)
+                .push_opcode(OP_ENDIF)
+                .into_script(),
+            // Something after the envelope
+            envelope()
+                .push_opcode(OP_ENDIF)
+                .push_opcode(OP_EQUAL)
+                .into_script(),
+            // No signature check before the envelope
+            Builder::new()
+                .push_opcode(bitcoin::opcodes::OP_FALSE)
+                .push_opcode(OP_IF)
+                .push_slice([1u8; 32])
+                .push_opcode(OP_ENDIF)
+                .into_script(),
+        ];
+        for script in malformed_scripts {
+            assert_eq!(
+                TransactionBuilder::parse_inscription_preimages(&reveal_tx_with_script(script)),
+                Err(BridgeError::InvalidInscription)
+            );
+        }
+        assert_eq!(
+            TransactionBuilder::parse_inscription_preimages(&reveal_tx_with_script(
+                envelope().push_opcode(OP_ENDIF).into_script()
+            )),
+            Ok(vec![[1u8; 32]])
+        );
+
+        // Key path spend, there is no script
+        let mut key_spend_tx = reveal_tx_with_script(ScriptBuf::new());
+        key_spend_tx.input[0].witness = Witness::from_slice(&[[0u8; 64]]);
+        assert_eq!(
+            TransactionBuilder::parse_inscription_preimages(&key_spend_tx),
+            Err(BridgeError::InvalidInscription)
+        );
+    }
+
+    #[test]
+    fn test_verify_commit_reveal() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([59u8; 32]);
+        let actor = Actor::new(secp.generate_keypair(&mut rng).0);
+        let transaction_builder = TransactionBuilder::new(vec![actor.xonly_public_key]);
+        let preimages = vec![[60u8; 32], [61u8; 32]];
+
+        let (commit_address, _, _) = transaction_builder
+            .create_inscription_commit_address(&actor.xonly_public_key, &preimages)
+            .unwrap();
+        let commit_tx = TransactionBuilder::create_btc_tx(
+            TransactionBuilder::create_tx_ins(vec![OutPoint {
+                txid: Txid::from_byte_array([62u8; 32]),
+                vout: 0,
+            }]),
+            vec![
+                TxOut {
+                    value: Amount::from_sat(DUST_VALUE * 2),
+                    script_pubkey: commit_address.script_pubkey(),
+                },
+                ScriptBuilder::anyone_can_spend_txout(),
+            ],
+        );
+        let create_reveal_tx = |commit_utxo: OutPoint, preimages: &Vec<[u8; 32]>| {
+            let mut reveal_tx = transaction_builder
+                .create_inscription_reveal_tx(commit_utxo, &actor.xonly_public_key, preimages)
+                .unwrap();
+            let sig = actor
+                .sign_taproot_script_spend_tx_new(&mut reveal_tx, 0)
+                .unwrap();
+            handle_taproot_witness_new(&mut reveal_tx, &vec![sig.as_ref()], 0).unwrap();
+            reveal_tx.tx
+        };
+
+        let commit_utxo = OutPoint {
+            txid: commit_tx.txid(),
+            vout: 0,
+        };
+        let reveal_tx = create_reveal_tx(commit_utxo, &preimages);
+        assert_eq!(
+            transaction_builder.verify_commit_reveal(&commit_tx, &reveal_tx),
+            Ok(())
+        );
+
+        let mismatched_reveal_txs = vec![
+            // Spends another tx
+            create_reveal_tx(
+                OutPoint {
+                    txid: Txid::from_byte_array([63u8; 32]),
+                    vout: 0,
+                },
+                &preimages,
+            ),
+            // Spends another output of the commit tx
+            create_reveal_tx(
+                OutPoint {
+                    txid: commit_tx.txid(),
+                    vout: 1,
+                },
+                &preimages,
+            ),
+            // Spends an output that does not exist
+            create_reveal_tx(
+                OutPoint {
+                    txid: commit_tx.txid(),
+                    vout: 2,
+                },
+                &preimages,
+            ),
+            // Reveals other preimages than the commit address commits to
+            create_reveal_tx(commit_utxo, &vec![[60u8; 32]]),
+        ];
+        for mismatched_reveal_tx in mismatched_reveal_txs {
+            assert_eq!(
+                transaction_builder.verify_commit_reveal(&commit_tx, &mismatched_reveal_tx),
+                Err(BridgeError::CommitRevealMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn test_predict_move_txid() {
+        let deposit_utxo = OutPoint {
+            txid: Txid::from_byte_array([71u8; 32]),
+            vout: 1,
+        };
+        let evm_address = [72u8; 20];
+        let fixture = MoveTxFixture::new(70, deposit_utxo, &evm_address);
+        let n_of_n_pks = fixture.n_of_n_pks();
+
+        let predicted_txid = predict_move_txid(
+            deposit_utxo,
+            &evm_address,
+            Amount::from_sat(BRIDGE_AMOUNT_SATS),
+            &n_of_n_pks,
+        )
+        .unwrap();
+        assert_eq!(predicted_txid, fixture.move_tx.tx.txid());
+
+        // Signing it the way the operator does does not change the txid
+        let signed_move_tx = fixture.witnessed_move_tx(&fixture.witness_sigs());
+        assert_eq!(predicted_txid, signed_move_tx.tx.txid());
+
+        // Every public input is committed to
+        assert_ne!(
+            predict_move_txid(
+                deposit_utxo,
+                &[73u8; 20],
+                Amount::from_sat(BRIDGE_AMOUNT_SATS),
+                &n_of_n_pks
+            )
+            .unwrap(),
+            predicted_txid
+        );
+        assert_ne!(
+            predict_move_txid(
+                deposit_utxo,
+                &evm_address,
+                Amount::from_sat(BRIDGE_AMOUNT_SATS * 2),
+                &n_of_n_pks
+            )
+            .unwrap(),
+            predicted_txid
+        );
+        assert_ne!(
+            predict_move_txid(
+                deposit_utxo,
+                &evm_address,
+                Amount::from_sat(BRIDGE_AMOUNT_SATS),
+                &n_of_n_pks[1..]
+            )
+            .unwrap(),
+            predicted_txid
+        );
+    }
+
+    #[test]
+    fn test_deposit_address() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([51u8; 32]);
+        let pks = (0..4)
+            .map(|_| XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1))
+            .collect::<Vec<_>>();
+        let transaction_builder = TransactionBuilder::new(pks[..3].to_vec());
+
+        let (address, tree_info) = transaction_builder
+            .generate_deposit_address(&pks[3])
+            .unwrap();
+        let (wallet_address, wallet_tree_info) =
+            deposit_address(&secp, &pks[..3], &pks[3], bitcoin::Network::Regtest).unwrap();
+        assert_eq!(wallet_address, address);
+        assert_eq!(
+            wallet_address.script_pubkey().as_bytes(),
+            address.script_pubkey().as_bytes()
+        );
+        assert_eq!(wallet_tree_info, tree_info);
+
+        // Only the address encoding depends on the network
+        let (testnet_address, _) =
+            deposit_address(&secp, &pks[..3], &pks[3], bitcoin::Network::Testnet).unwrap();
+        assert_eq!(testnet_address.script_pubkey(), address.script_pubkey());
+    }
+
+    #[test]
+    fn test_control_block_cache() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([28u8; 32]);
+        let actor_pk = XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1);
+        let transaction_builder = TransactionBuilder::new(vec![actor_pk]);
+        let timelock_script = ScriptBuilder::generate_timelock_script(
+            &actor_pk,
+            CONNECTOR_TREE_OPERATOR_TAKES_AFTER as u32,
+        );
+
+        // Sibling nodes share the timelock script but commit to different hashes
+        for hash in [[29u8; 32], [30u8; 32]] {
+            let (_, tree_info) = TransactionBuilder::create_connector_tree_node_address(
+                &secp,
+                &actor_pk,
+                &hash,
+                CONNECTOR_TREE_OPERATOR_TAKES_AFTER,
+            )
+            .unwrap();
+            let fresh = tree_info
+                .control_block(&(timelock_script.clone(), LeafVersion::TapScript))
+                .unwrap();
+            assert_eq!(
+                transaction_builder
+                    .control_block(&tree_info, &timelock_script)
+                    .unwrap(),
+                fresh
+            );
+            assert_eq!(
+                transaction_builder
+                    .control_block(&tree_info, &timelock_script)
+                    .unwrap(),
+                fresh
+            );
+        }
+
+        let (_, tree_info) = TransactionBuilder::create_connector_tree_node_address(
+            &secp,
+            &actor_pk,
+            &[29u8; 32],
+            CONNECTOR_TREE_OPERATOR_TAKES_AFTER,
+        )
+        .unwrap();
+        assert_eq!(
+            transaction_builder.control_block(&tree_info, &ScriptBuf::new()),
+            Err(BridgeError::ControlBlockError)
+        );
+
+        // The cached witness is the one handle_taproot_witness builds
+        let mut tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![],
+        };
+        let mut fresh_tx = tx.clone();
+        transaction_builder
+            .handle_taproot_witness(&mut tx, 0, &vec![[0u8; 64]], &timelock_script, &tree_info)
+            .unwrap();
+        handle_taproot_witness(
+            &mut fresh_tx,
+            0,
+            &vec![[0u8; 64]],
+            &timelock_script,
+            &tree_info,
+        )
+        .unwrap();
+        assert_eq!(tx, fresh_tx);
+
+        // The cache starts over instead of outgrowing every node of a connector tree
+        let capacity = 2usize.pow(CONNECTOR_TREE_DEPTH as u32 + 1);
+        for i in 0..capacity as u32 + 1 {
+            let (_, tree_info) = TransactionBuilder::create_connector_tree_node_address(
+                &secp,
+                &actor_pk,
+                &sha256_hash!(i.to_be_bytes()),
+                CONNECTOR_TREE_OPERATOR_TAKES_AFTER,
+            )
+            .unwrap();
+            transaction_builder
+                .control_block(&tree_info, &timelock_script)
+                .unwrap();
+            assert!(transaction_builder.control_block_cache.len() <= capacity);
+        }
+    }
+
+    #[test]
+    fn test_connector_tree_node_amount() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([62u8; 32]);
+        let operator_pk = XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1);
+        let transaction_builder = TransactionBuilder::new(vec![operator_pk])
+            .with_connector_tree_operator_takes_after(3)
+            .unwrap()
+            .with_connector_tree_feerate(25);
+        let timelock_script = ScriptBuilder::generate_timelock_script(&operator_pk, 3);
+        let (address, tree_info) = TransactionBuilder::create_connector_tree_node_address(
+            &secp,
+            &operator_pk,
+            &[63u8; 32],
+            3,
+        )
+        .unwrap();
+        let signed_vsize = |mut tx: bitcoin::Transaction| {
+            transaction_builder
+                .handle_taproot_witness(&mut tx, 0, &vec![[0u8; 64]], &timelock_script, &tree_info)
+                .unwrap();
+            tx.vsize() as u64
+        };
+
+        // Every node tx pays the feerate for its signed size out of its parent
+        for depth in 1..=CONNECTOR_TREE_DEPTH {
+            let node_amount = transaction_builder
+                .connector_tree_node_amount(depth)
+                .unwrap();
+            let child_amount = transaction_builder
+                .connector_tree_node_amount(depth - 1)
+                .unwrap();
+            let node_tx = TransactionBuilder::create_connector_tree_tx(
+                &OutPoint::null(),
+                child_amount,
+                address.clone(),
+                address.clone(),
+                3,
+            )
+            .unwrap();
+            assert_eq!(node_tx.output[0].value, child_amount);
+            let fee = node_amount - child_amount * 2;
+            assert_eq!(fee.to_sat(), signed_vsize(node_tx) * 25);
+        }
+        // And every leaf the dust limit and the fee of its own spend
+        let mut leaf_spend_tx = TransactionBuilder::create_connector_tree_tx(
+            &OutPoint::null(),
+            Amount::from_sat(DUST_VALUE),
+            address.clone(),
+            address,
+            3,
+        )
+        .unwrap();
+        leaf_spend_tx.output.truncate(1);
+        assert_eq!(
+            transaction_builder.connector_tree_node_amount(0).unwrap(),
+            Amount::from_sat(DUST_VALUE + signed_vsize(leaf_spend_tx) * 25)
+        );
+        assert_eq!(
+            transaction_builder
+                .connector_tree_funding(CONNECTOR_TREE_DEPTH, 25)
+                .unwrap(),
+            transaction_builder
+                .connector_tree_node_amount(CONNECTOR_TREE_DEPTH)
+                .unwrap()
+        );
+    }
+
+    /// Claim tx of `transaction_builder`, unsigned and with both inputs witnessed by placeholder
+    /// signatures
+    fn create_dummy_signed_claim_tx(
+        transaction_builder: &TransactionBuilder,
+        operator: &Actor,
+    ) -> (CreateTxOutputs, CreateTxOutputs) {
+        let create_claim_tx = || {
+            transaction_builder
+                .create_operator_claim_tx(
+                    OutPoint::null(),
+                    OutPoint::null(),
+                    &operator.address,
+                    &operator.xonly_public_key,
+                    &[0u8; 32],
+                )
+                .unwrap()
+        };
+        let unsigned_tx = create_claim_tx();
+        let mut claim_tx = create_claim_tx();
+        let dummy_sig = [0u8; 64];
+        let n_of_n_sigs = vec![dummy_sig; transaction_builder.verifiers_pks.len()];
+        handle_taproot_witness_new(&mut claim_tx, &n_of_n_sigs, 0).unwrap();
+        handle_taproot_witness_new(&mut claim_tx, &vec![dummy_sig], 1).unwrap();
+        (unsigned_tx, claim_tx)
+    }
+
+    #[test]
+    fn test_estimate_vsize() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([52u8; 32]);
+        let pks = (0..5)
+            .map(|_| XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1))
+            .collect::<Vec<_>>();
+        let operator = Actor::new(secp.generate_keypair(&mut rng).0);
+        let transaction_builder = TransactionBuilder::new(pks[..4].to_vec());
+
+        let (unsigned_tx, claim_tx) = create_dummy_signed_claim_tx(&transaction_builder, &operator);
+        // Witnessed inputs are counted exactly
+        assert_eq!(
+            transaction_builder.estimate_vsize(&claim_tx).unwrap(),
+            claim_tx.tx.vsize()
+        );
+        // The n-of-n input and the single signature connector leaf input are each sized from
+        // their own script
+        let unsigned_vsize = transaction_builder.estimate_vsize(&unsigned_tx).unwrap();
+        assert_eq!(unsigned_vsize, claim_tx.tx.vsize());
+        assert_eq!(
+            transaction_builder.estimate_fee(&unsigned_tx, 3).unwrap(),
+            Amount::from_sat(unsigned_vsize as u64 * 3)
+        );
+        let bigger_transaction_builder = TransactionBuilder::new(pks.clone());
+        let (bigger_unsigned_tx, bigger_claim_tx) =
+            create_dummy_signed_claim_tx(&bigger_transaction_builder, &operator);
+        assert!(bigger_claim_tx.tx.vsize() > claim_tx.tx.vsize());
+        assert_eq!(
+            bigger_transaction_builder
+                .estimate_vsize(&bigger_unsigned_tx)
+                .unwrap(),
+            bigger_claim_tx.tx.vsize()
+        );
+
+        // Inputs without a script are key path spends
+        let key_path_tx = CreateTxOutputs {
+            scripts: vec![],
+            taproot_spend_infos: vec![],
+            ..create_dummy_signed_claim_tx(&transaction_builder, &operator).0
+        };
+        let mut key_path_signed_tx = key_path_tx.tx.clone();
+        for input in key_path_signed_tx.input.iter_mut() {
+            input.witness.push([0u8; 64]);
+        }
+        assert_eq!(
+            transaction_builder.estimate_vsize(&key_path_tx).unwrap(),
+            key_path_signed_tx.vsize()
+        );
+    }
+
+    #[test]
+    fn test_check_fee() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([53u8; 32]);
+        let pks = (0..4)
+            .map(|_| XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1))
+            .collect::<Vec<_>>();
+        let operator = Actor::new(secp.generate_keypair(&mut rng).0);
+        let transaction_builder = TransactionBuilder::new(pks);
+
+        let (_, mut claim_tx) = create_dummy_signed_claim_tx(&transaction_builder, &operator);
+        transaction_builder.check_fee(&claim_tx).unwrap();
+
+        let fee = claim_tx
+            .prevouts
+            .iter()
+            .map(|prevout| prevout.value)
+            .sum::<Amount>()
+            - claim_tx
+                .tx
+                .output
+                .iter()
+                .map(|output| output.value)
+                .sum::<Amount>();
+        let required = transaction_builder
+            .estimate_fee(&claim_tx, MIN_RELAY_FEERATE)
+            .unwrap();
+        claim_tx.tx.output[0].value += fee - required;
+        transaction_builder.check_fee(&claim_tx).unwrap();
+        claim_tx.tx.output[0].value += Amount::from_sat(1);
+        assert!(matches!(
+            transaction_builder.check_fee(&claim_tx),
+            Err(BridgeError::InsufficientFee)
+        ));
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_estimate_vsize_matches_bitcoind() {
+        let rpc = crate::extended_rpc::ExtendedRpc::new();
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([54u8; 32]);
+        let pks = (0..4)
+            .map(|_| XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1))
+            .collect::<Vec<_>>();
+        let operator = Actor::new(secp.generate_keypair(&mut rng).0);
+        let transaction_builder = TransactionBuilder::new(pks);
+
+        // The estimate of the unsigned tx is the size bitcoind gives the signed one
+        let (unsigned_tx, claim_tx) = create_dummy_signed_claim_tx(&transaction_builder, &operator);
+        let decoded =
+            bitcoincore_rpc::RpcApi::decode_raw_transaction(&rpc.inner, &claim_tx.tx, Some(true))
+                .unwrap();
+        assert_eq!(
+            transaction_builder.estimate_vsize(&unsigned_tx).unwrap(),
+            decoded.vsize as usize
+        );
+    }
+
+    #[test]
+    fn test_create_child_pays_for_parent() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([55u8; 32]);
+        let pks = (0..4)
+            .map(|_| XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1))
+            .collect::<Vec<_>>();
+        let operator = Actor::new(secp.generate_keypair(&mut rng).0);
+        let transaction_builder = TransactionBuilder::new(pks);
+        let (_, parent) = create_dummy_signed_claim_tx(&transaction_builder, &operator);
+        let anchor_value = parent.tx.output[1].value;
+        let resource_utxo = OutPoint {
+            txid: Txid::from_byte_array([56u8; 32]),
+            vout: 0,
+        };
+        let create_child = |resource_value: Amount| {
+            transaction_builder.create_child_pays_for_parent(
+                &parent,
+                1,
+                resource_utxo,
+                TxOut {
+                    value: resource_value,
+                    script_pubkey: operator.address.script_pubkey(),
+                },
+                &operator.address,
+                10,
+            )
+        };
+
+        // An oversized resource utxo gets its excess back as change
+        let resource_value = Amount::from_sat(1_000_000);
+        let child = create_child(resource_value).unwrap();
+        assert_eq!(child.tx.input[0].previous_output.txid, parent.tx.txid());
+        assert_eq!(child.tx.output.len(), 1);
+        assert_eq!(
+            child.tx.output[0].script_pubkey,
+            operator.address.script_pubkey()
+        );
+        let child_fee = anchor_value + resource_value - child.tx.output[0].value;
+        let mut signed_child_tx = child.tx.clone();
+        signed_child_tx.input[1].witness.push([0u8; 64]);
+        let parent_fee = Amount::from_sat(MIN_RELAY_FEE);
+        let package_vsize = (parent.tx.vsize() + signed_child_tx.vsize()) as u64;
+        assert!((parent_fee + child_fee).to_sat() >= package_vsize * 10);
+        assert!((parent_fee + child_fee).to_sat() < (package_vsize + 1) * 10);
+
+        // A change below the dust limit is left as fee
+        let dust_resource_value = child_fee - anchor_value + Amount::from_sat(100);
+        let child = create_child(dust_resource_value).unwrap();
+        assert_eq!(child.tx.output.len(), 1);
+        assert_eq!(child.tx.output[0].value, Amount::ZERO);
+        assert!(child.tx.output[0].script_pubkey.is_op_return());
+
+        assert!(matches!(
+            create_child(child_fee - anchor_value - Amount::from_sat(1)),
+            Err(BridgeError::InsufficientFee)
+        ));
+        // The claim output is not an anchor
+        assert!(matches!(
+            transaction_builder.create_child_pays_for_parent(
+                &parent,
+                0,
+                resource_utxo,
+                TxOut {
+                    value: resource_value,
+                    script_pubkey: operator.address.script_pubkey(),
+                },
+                &operator.address,
+                10,
+            ),
+            Err(BridgeError::NotAnAnchor)
+        ));
+    }
+
+    #[test]
+    fn test_anchor_kind() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([68u8; 32]);
+        let actors = (0..4)
+            .map(|_| Actor::new(secp.generate_keypair(&mut rng).0))
+            .collect::<Vec<_>>();
+        let mut transaction_builder = TransactionBuilder::new(
+            actors[..3]
+                .iter()
+                .map(|actor| actor.xonly_public_key)
+                .collect(),
+        );
+        let deposit_utxo = OutPoint {
+            txid: Txid::from_byte_array([69u8; 32]),
+            vout: 0,
+        };
+        assert_eq!(transaction_builder.anchor_kind, AnchorKind::P2wshOpTrue);
+
+        for anchor_kind in [AnchorKind::P2wshOpTrue, AnchorKind::P2aEphemeral] {
+            transaction_builder.anchor_kind = anchor_kind;
+            let move_tx = transaction_builder
+                .create_move_tx(deposit_utxo, &[1u8; 20], &actors[3].xonly_public_key)
+                .unwrap();
+            assert_eq!(move_tx.tx.output[2], anchor_kind.txout());
+            assert_eq!(
+                move_tx
+                    .tx
+                    .output
+                    .iter()
+                    .map(|txout| txout.value)
+                    .sum::<Amount>()
+                    + Amount::from_sat(MIN_RELAY_FEE),
+                Amount::from_sat(BRIDGE_AMOUNT_SATS)
+            );
+
+            let child = transaction_builder
+                .create_child_pays_for_parent(
+                    &move_tx,
+                    2,
+                    OutPoint {
+                        txid: Txid::from_byte_array([70u8; 32]),
+                        vout: 0,
+                    },
+                    TxOut {
+                        value: Amount::from_sat(1_000_000),
+                        script_pubkey: actors[3].address.script_pubkey(),
+                    },
+                    &actors[3].address,
+                    10,
+                )
+                .unwrap();
+            assert_eq!(child.tx.input[0].witness, anchor_kind.spend_witness());
+        }
+    }
+
+    #[test]
+    fn test_create_move_tx_with_change() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([66u8; 32]);
+        let actors = (0..4)
+            .map(|_| Actor::new(secp.generate_keypair(&mut rng).0))
+            .collect::<Vec<_>>();
+        let transaction_builder = TransactionBuilder::new(
+            actors[..3]
+                .iter()
+                .map(|actor| actor.xonly_public_key)
+                .collect(),
+        );
+        let user_pk = actors[3].xonly_public_key;
+        let deposit_utxo = OutPoint {
+            txid: Txid::from_byte_array([67u8; 32]),
+            vout: 0,
+        };
+        let output_total =
+            |tx: &bitcoin::Transaction| tx.output.iter().map(|txout| txout.value).sum::<Amount>();
+
+        // Without change it is the plain move tx
+        let move_tx = transaction_builder
+            .create_move_tx_with_change(
+                deposit_utxo,
+                &[1u8; 20],
+                &user_pk,
+                None,
+                Amount::from_sat(MIN_RELAY_FEE),
+            )
+            .unwrap();
+        assert_eq!(
+            move_tx.tx,
+            transaction_builder
+                .create_move_tx(deposit_utxo, &[1u8; 20], &user_pk)
+                .unwrap()
+                .tx
+        );
+
+        let fee = Amount::from_sat(2_000);
+        let change = Amount::from_sat(50_000);
+        let move_tx = transaction_builder
+            .create_move_tx_with_change(
+                deposit_utxo,
+                &[1u8; 20],
+                &user_pk,
+                Some((&actors[2].address, change)),
+                fee,
+            )
+            .unwrap();
+        assert_eq!(move_tx.tx.output.len(), 4);
+        assert_eq!(move_tx.tx.output[3].value, change);
+        assert_eq!(
+            move_tx.tx.output[3].script_pubkey,
+            actors[2].address.script_pubkey()
+        );
+        assert_eq!(
+            move_tx.tx.output[0].value,
+            Amount::from_sat(BRIDGE_AMOUNT_SATS)
+                - fee
+                - change
+                - ScriptBuilder::anyone_can_spend_txout().value
+        );
+        assert_eq!(
+            output_total(&move_tx.tx) + fee,
+            Amount::from_sat(BRIDGE_AMOUNT_SATS)
+        );
+
+        // The change can not take more than the deposit
+        assert!(matches!(
+            transaction_builder.create_move_tx_with_change(
+                deposit_utxo,
+                &[1u8; 20],
+                &user_pk,
+                Some((&actors[2].address, Amount::from_sat(BRIDGE_AMOUNT_SATS))),
+                fee,
+            ),
+            Err(BridgeError::MoveTxValueMismatch)
+        ));
+    }
+}
@@ -1,12 +1,14 @@
-use crate::actor::Actor;
+use crate::actor::{verify_evm_signature, Actor};
 use crate::errors::BridgeError;
 use crate::extended_rpc::ExtendedRpc;
 use crate::transaction_builder::TransactionBuilder;
-use crate::EVMAddress;
+use crate::utils::handle_taproot_witness_new;
+use crate::{EVMAddress, EVMSignature};
 use bitcoin::secp256k1::Secp256k1;
-use bitcoin::OutPoint;
 use bitcoin::XOnlyPublicKey;
+use bitcoin::{OutPoint, Txid};
 use clementine_circuits::constants::BRIDGE_AMOUNT_SATS;
+use clementine_circuits::HashType;
 use secp256k1::schnorr::Signature;
 use secp256k1::SecretKey;
 
@@ -55,4 +57,191 @@ impl User {
 
         Ok((deposit_utxo, self.signer.xonly_public_key, evm_address, sig))
     }
+
+    /// Checks that `sigs` authorize minting the deposit of `deposit_txid` to `evm_address` on
+    /// the rollup: one valid signature from each of `expected_signers`, the evm addresses of
+    /// the verifiers and the operator, and from no one else
+    pub fn verify_deposit_signatures(
+        &self,
+        sigs: &[EVMSignature],
+        deposit_txid: Txid,
+        evm_address: &EVMAddress,
+        hash: &HashType,
+        expected_signers: &[EVMAddress],
+    ) -> Result<(), BridgeError> {
+        let mut signers = sigs
+            .iter()
+            .map(|sig| verify_evm_signature(sig, deposit_txid, evm_address, hash))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut expected_signers = expected_signers.to_vec();
+        signers.sort_unstable();
+        expected_signers.sort_unstable();
+        if signers != expected_signers {
+            return Err(BridgeError::InvalidDepositSignatures);
+        }
+        Ok(())
+    }
+
+    /// Takes back a deposit the bridge never moved, through the refund leaf of the deposit
+    /// address. Only possible once the deposit has `refund_blocks` confirmations, which has to
+    /// be the delay the deposit address commits to, USER_TAKES_AFTER for deposit_tx.
+    pub fn reclaim_deposit(
+        &self,
+        deposit_utxo: OutPoint,
+        refund_blocks: u32,
+    ) -> Result<Txid, BridgeError> {
+        let deposit_txout = self
+            .rpc
+            .get_utxo(&deposit_utxo, true)?
+            .ok_or(BridgeError::UTXOSpent)?;
+
+        let mut refund_tx = self.transaction_builder.create_deposit_refund_tx(
+            deposit_utxo,
+            deposit_txout.value,
+            &self.signer.xonly_public_key,
+            &self.signer.address,
+            refund_blocks,
+        )?;
+        // A different delay derives a different address, the refund leaf would not be in it
+        if refund_tx.prevouts[0].script_pubkey != deposit_txout.script_pubkey {
+            return Err(BridgeError::InvalidDepositUTXO);
+        }
+
+        let sig = self
+            .signer
+            .sign_taproot_script_spend_tx_new(&mut refund_tx, 0)?;
+        handle_taproot_witness_new(&mut refund_tx, &vec![sig.as_ref()], 0)?;
+
+        Ok(self.rpc.send_raw_transaction(&refund_tx.tx)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::evm_address_from_public_key;
+    use crate::constants::USER_TAKES_AFTER;
+    use bitcoin::hashes::Hash;
+    use secp256k1::rand::rngs::StdRng;
+    use secp256k1::rand::SeedableRng;
+
+    #[test]
+    fn test_verify_deposit_signatures() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([61u8; 32]);
+        // 2 verifiers, the operator and the user
+        let actors = (0..4)
+            .map(|_| Actor::new(secp.generate_keypair(&mut rng).0))
+            .collect::<Vec<_>>();
+        let user = User::new(
+            ExtendedRpc::new(),
+            actors[..3]
+                .iter()
+                .map(|actor| actor.xonly_public_key)
+                .collect(),
+            actors[3].secret_key,
+        );
+        let expected_signers = actors[..3]
+            .iter()
+            .map(|actor| evm_address_from_public_key(&actor.public_key))
+            .collect::<Vec<_>>();
+        let deposit_txid = Txid::from_byte_array([62u8; 32]);
+        let evm_address = [63u8; 20];
+        let hash = [64u8; 32];
+        let sigs = actors[..3]
+            .iter()
+            .rev()
+            .map(|actor| actor.sign_deposit(deposit_txid, &evm_address, &hash))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            user.verify_deposit_signatures(
+                &sigs,
+                deposit_txid,
+                &evm_address,
+                &hash,
+                &expected_signers
+            ),
+            Ok(())
+        );
+        // The operator's signature is missing
+        assert_eq!(
+            user.verify_deposit_signatures(
+                &sigs[1..],
+                deposit_txid,
+                &evm_address,
+                &hash,
+                &expected_signers
+            ),
+            Err(BridgeError::InvalidDepositSignatures)
+        );
+        // A verifier signed twice instead of the operator
+        let duplicate_sigs = [sigs[1], sigs[1], sigs[2]];
+        assert_eq!(
+            user.verify_deposit_signatures(
+                &duplicate_sigs,
+                deposit_txid,
+                &evm_address,
+                &hash,
+                &expected_signers
+            ),
+            Err(BridgeError::InvalidDepositSignatures)
+        );
+        // The signatures are for another evm address
+        assert_eq!(
+            user.verify_deposit_signatures(
+                &sigs,
+                deposit_txid,
+                &[65u8; 20],
+                &hash,
+                &expected_signers
+            ),
+            Err(BridgeError::InvalidDepositSignatures)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_reclaim_deposit() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([60u8; 32]);
+        let rpc = ExtendedRpc::new();
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..4)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        let user = User::new(rpc.clone(), all_xonly_pks[..3].to_vec(), all_sks[3]);
+
+        let (deposit_utxo, _, _, _) = user.deposit_tx([1u8; 20]).unwrap();
+        rpc.mine_blocks(1).unwrap();
+
+        // The deposit address does not commit to any other delay
+        assert_eq!(
+            user.reclaim_deposit(deposit_utxo, USER_TAKES_AFTER + 1),
+            Err(BridgeError::InvalidDepositUTXO)
+        );
+
+        // The deposit has 1 confirmation, the refund needs USER_TAKES_AFTER
+        assert!(user
+            .reclaim_deposit(deposit_utxo, USER_TAKES_AFTER)
+            .is_err());
+        rpc.mine_blocks(USER_TAKES_AFTER as u64 - 2).unwrap();
+        assert!(user
+            .reclaim_deposit(deposit_utxo, USER_TAKES_AFTER)
+            .is_err());
+        rpc.mine_blocks(1).unwrap();
+
+        let refund_txid = user
+            .reclaim_deposit(deposit_utxo, USER_TAKES_AFTER)
+            .unwrap();
+        rpc.mine_blocks(1).unwrap();
+        assert_eq!(rpc.get_utxo(&deposit_utxo, true).unwrap(), None);
+        let refund_tx = rpc.get_raw_transaction(&refund_txid, None).unwrap();
+        assert_eq!(
+            refund_tx.output[0].script_pubkey,
+            user.signer.address.script_pubkey()
+        );
+    }
 }
@@ -1,7 +1,20 @@
 use std::borrow::BorrowMut;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-use bitcoin::sighash::SighashCache;
-use bitcoin::{self, OutPoint, XOnlyPublicKey};
+use bitcoin::hashes::Hash;
+use bitcoin::hashes::{hash160, sha256d};
+use bitcoin::opcodes::all::{
+    OP_ADD, OP_BOOLOR, OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CLTV, OP_CSV, OP_DROP, OP_DUP, OP_ELSE,
+    OP_ENDIF, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160, OP_HASH256, OP_IF, OP_NOTIF, OP_PUSHNUM_1,
+    OP_PUSHNUM_16, OP_PUSHNUM_NEG1, OP_RETURN, OP_SHA256, OP_SWAP, OP_VERIFY,
+};
+use bitcoin::opcodes::{Class, ClassifyContext};
+use bitcoin::script::Instruction;
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::sighash::{Annex, Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{TapLeafHash, TAPROOT_ANNEX_PREFIX};
+use bitcoin::{self, OutPoint, TxOut, XOnlyPublicKey};
 
 use bitcoin::consensus::Decodable;
 
@@ -12,23 +25,37 @@ use bitcoin::taproot::TaprootSpendInfo;
 
 use bitcoin::Amount;
 
+use bitcoin::TapNodeHash;
+
 use bitcoin::ScriptBuf;
 
 use hex;
 
 use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+use clementine_circuits::constants::CLAIM_MERKLE_TREE_DEPTH;
 
-use crate::constants::CONFIRMATION_BLOCK_COUNT;
+use crate::constants::{CONFIRMATION_BLOCK_COUNT, CONNECTOR_TREE_DEPTH};
 use crate::errors::BridgeError;
 use crate::extended_rpc::ExtendedRpc;
+use crate::merkle::MerkleTree;
 use crate::transaction_builder::{CreateTxOutputs, TransactionBuilder};
-use crate::HashTree;
+use crate::{ConnectorTreeLeaf, EVMAddress, HashTree};
 
 pub fn parse_hex_to_btc_tx(
     tx_hex: &str,
 ) -> Result<bitcoin::blockdata::transaction::Transaction, bitcoin::consensus::encode::Error> {
-    if let Ok(reader) = hex::decode(tx_hex) {
-        bitcoin::blockdata::transaction::Transaction::consensus_decode(&mut &reader[..])
+    if let Ok(bytes) = hex::decode(tx_hex) {
+        let mut reader = &bytes[..];
+        let tx = bitcoin::blockdata::transaction::Transaction::consensus_decode(&mut reader)?;
+        // Trailing bytes mean the input is not a single transaction
+        if !reader.is_empty() {
+            return Err(bitcoin::consensus::encode::Error::ParseFailed(
+                "Trailing bytes after transaction",
+            ));
+        }
+        Ok(tx)
     } else {
         Err(bitcoin::consensus::encode::Error::ParseFailed(
             "Could not decode hex",
@@ -36,12 +63,67 @@ pub fn parse_hex_to_btc_tx(
     }
 }
 
+/// Encodes a transaction to consensus hex, the inverse of parse_hex_to_btc_tx
+pub fn btc_tx_to_hex(tx: &bitcoin::blockdata::transaction::Transaction) -> String {
+    hex::encode(bitcoin::consensus::serialize(tx))
+}
+
 pub fn create_control_block(tree_info: TaprootSpendInfo, script: &ScriptBuf) -> ControlBlock {
     tree_info
         .control_block(&(script.clone(), LeafVersion::TapScript))
         .expect("Cannot create control block")
 }
 
+/// Internal key, merkle root and spent script of a control block
+type ControlBlockKey = (XOnlyPublicKey, Option<TapNodeHash>, ScriptBuf);
+
+/// Entries a ControlBlockCache holds before it starts over, one per node of a connector tree
+const CONTROL_BLOCK_CACHE_CAPACITY: usize = 1 << (CONNECTOR_TREE_DEPTH + 1);
+
+/// Memoizes control blocks by the taproot output they belong to and the spent script,
+/// so that spending many leaves of the same trees does not recompute them. It is emptied
+/// once it holds CONTROL_BLOCK_CACHE_CAPACITY entries, so it does not grow with every period.
+#[derive(Debug, Clone, Default)]
+pub struct ControlBlockCache(Arc<Mutex<HashMap<ControlBlockKey, ControlBlock>>>);
+
+impl ControlBlockCache {
+    pub fn get(
+        &self,
+        tree_info: &TaprootSpendInfo,
+        script: &ScriptBuf,
+    ) -> Result<ControlBlock, BridgeError> {
+        let key = (
+            tree_info.internal_key(),
+            tree_info.merkle_root(),
+            script.clone(),
+        );
+        let mut control_blocks = self.0.lock().expect("control block cache is poisoned");
+        if let Some(control_block) = control_blocks.get(&key) {
+            return Ok(control_block.clone());
+        }
+        let control_block = tree_info
+            .control_block(&(script.clone(), LeafVersion::TapScript))
+            .ok_or(BridgeError::ControlBlockError)?;
+        if control_blocks.len() >= CONTROL_BLOCK_CACHE_CAPACITY {
+            control_blocks.clear();
+        }
+        control_blocks.insert(key, control_block.clone());
+        Ok(control_block)
+    }
+
+    /// Number of control blocks currently cached
+    pub fn len(&self) -> usize {
+        self.0
+            .lock()
+            .expect("control block cache is poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 pub fn check_deposit_utxo(
     rpc: &ExtendedRpc,
     tx_builder: &TransactionBuilder,
@@ -55,11 +137,17 @@ pub fn check_deposit_utxo(
 
     let (deposit_address, _) = tx_builder.generate_deposit_address(return_address)?;
 
-    if !rpc.check_utxo_address_and_amount(
-        outpoint,
-        &deposit_address.script_pubkey(),
-        amount_sats,
-    )? {
+    let deposit_tx = rpc.get_raw_transaction(&outpoint.txid, None)?;
+    let deposit_txout = deposit_tx
+        .output
+        .get(outpoint.vout as usize)
+        .ok_or(BridgeError::InvalidDepositUTXO)?;
+    // The address is recomputed from our own verifier set, so nobody can get presigns
+    // for a deposit that is spendable by someone else
+    if deposit_txout.script_pubkey != deposit_address.script_pubkey() {
+        return Err(BridgeError::DepositAddressMismatch);
+    }
+    if deposit_txout.value != Amount::from_sat(amount_sats) {
         return Err(BridgeError::InvalidDepositUTXO);
     }
 
@@ -69,16 +157,112 @@ pub fn check_deposit_utxo(
     Ok(())
 }
 
+/// Checks that the EVM address is not the zero address, since minting to it would burn the deposit
+pub fn check_evm_address(evm_address: &EVMAddress) -> Result<(), BridgeError> {
+    if evm_address.iter().all(|&b| b == 0) {
+        return Err(BridgeError::InvalidEvmAddress);
+    }
+    Ok(())
+}
+
+/// Parses a hex EVM address, with or without the `0x` prefix. Mixed case addresses have to
+/// carry a valid EIP-55 checksum, all lower or upper case ones are taken as they are.
+pub fn evm_address_from_hex(s: &str) -> Result<EVMAddress, BridgeError> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    let mut evm_address: EVMAddress = [0u8; 20];
+    hex::decode_to_slice(digits, &mut evm_address).map_err(|_| BridgeError::InvalidEvmAddress)?;
+    let is_mixed_case = digits.chars().any(|c| c.is_ascii_lowercase())
+        && digits.chars().any(|c| c.is_ascii_uppercase());
+    if is_mixed_case && evm_address_to_hex(&evm_address)[2..] != *digits {
+        return Err(BridgeError::InvalidEvmAddress);
+    }
+    Ok(evm_address)
+}
+
+/// Formats an EVM address as `0x` prefixed hex with the EIP-55 checksum casing
+pub fn evm_address_to_hex(evm_address: &EVMAddress) -> String {
+    let digits = hex::encode(evm_address);
+    let hash: [u8; 32] = Keccak256::digest(digits.as_bytes()).into();
+    let checksummed = digits
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (4 * (1 - i % 2))) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    format!("0x{}", checksummed)
+}
+
 pub fn calculate_amount(depth: usize, value: Amount, fee: Amount) -> Amount {
     (value + fee) * (2u64.pow(depth as u32))
 }
 
+/// Funding needed by the root of a connector tree of `depth` at `feerate` sat/vB: every level
+/// pays for its `node_vbytes` sized transactions, and every leaf holds `dust` plus the fee
+/// of its `leaf_vbytes` sized spend
+pub fn calculate_amount_with_weights(
+    depth: usize,
+    dust: Amount,
+    leaf_vbytes: u64,
+    node_vbytes: u64,
+    feerate: u64,
+) -> Amount {
+    let node_fee = Amount::from_sat(node_vbytes * feerate);
+    let leaf_fee = Amount::from_sat(leaf_vbytes * feerate);
+    let nodes_fee = (0..depth)
+        .map(|level| node_fee * 2u64.pow(level as u32))
+        .sum::<Amount>();
+    nodes_fee + (dust + leaf_fee) * 2u64.pow(depth as u32)
+}
+
+/// Number of OP_CHECKSIG and OP_CHECKSIGVERIFY in `script`, i.e. the signatures spending it takes
+pub fn script_signature_count(script: &ScriptBuf) -> usize {
+    script
+        .instructions()
+        .flatten()
+        .filter(|instruction| {
+            matches!(instruction, Instruction::Op(op) if *op == OP_CHECKSIG || *op == OP_CHECKSIGVERIFY)
+        })
+        .count()
+}
+
+/// Checks that a witness consisting only of signatures has one element for every
+/// OP_CHECKSIG and OP_CHECKSIGVERIFY in the script it spends
+pub fn check_witness_signature_count<T: AsRef<[u8]>>(
+    witness_elements: &[T],
+    script: &ScriptBuf,
+) -> Result<(), BridgeError> {
+    if witness_elements.len() != script_signature_count(script) {
+        return Err(BridgeError::WitnessElementCountMismatch);
+    }
+    Ok(())
+}
+
 pub fn handle_taproot_witness<T: AsRef<[u8]>>(
     tx: &mut bitcoin::Transaction,
     index: usize,
     witness_elements: &Vec<T>,
     script: &ScriptBuf,
     tree_info: &TaprootSpendInfo,
+) -> Result<(), BridgeError> {
+    let spend_control_block = tree_info
+        .control_block(&(script.clone(), LeafVersion::TapScript))
+        .ok_or(BridgeError::ControlBlockError)?;
+    push_taproot_witness(tx, index, witness_elements, script, &spend_control_block)
+}
+
+/// Fills the witness of input `index` with `witness_elements`, `script` and `control_block`
+pub fn push_taproot_witness<T: AsRef<[u8]>>(
+    tx: &mut bitcoin::Transaction,
+    index: usize,
+    witness_elements: &Vec<T>,
+    script: &ScriptBuf,
+    control_block: &ControlBlock,
 ) -> Result<(), BridgeError> {
     let mut sighash_cache = SighashCache::new(tx.borrow_mut());
     let witness = sighash_cache
@@ -87,11 +271,8 @@ pub fn handle_taproot_witness<T: AsRef<[u8]>>(
     for elem in witness_elements {
         witness.push(elem);
     }
-    let spend_control_block = tree_info
-        .control_block(&(script.clone(), LeafVersion::TapScript))
-        .ok_or(BridgeError::ControlBlockError)?;
     witness.push(script);
-    witness.push(&spend_control_block.serialize());
+    witness.push(control_block.serialize());
     Ok(())
 }
 
@@ -115,69 +296,468 @@ pub fn handle_taproot_witness_new<T: AsRef<[u8]>>(
     Ok(())
 }
 
-pub fn get_claim_reveal_indices(depth: usize, count: u32) -> Vec<(usize, usize)> {
-    assert!(count <= 2u32.pow(depth as u32));
+/// Decodes a script number of at most `max_size` bytes, 4 for arithmetic and 5 for the
+/// OP_CLTV and OP_CSV arguments
+fn read_script_num(bytes: &[u8], max_size: usize) -> Option<i64> {
+    if bytes.len() > max_size {
+        return None;
+    }
+    let Some((last, _)) = bytes.split_last() else {
+        return Some(0);
+    };
+    let magnitude = bytes
+        .iter()
+        .enumerate()
+        .fold(0i64, |acc, (i, byte)| acc | (*byte as i64) << (8 * i))
+        & !(0x80i64 << (8 * (bytes.len() - 1)));
+    Some(if last & 0x80 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    })
+}
+
+/// Whether a stack element is true, i.e. not a (negative) zero
+fn is_true(element: &[u8]) -> bool {
+    element
+        .iter()
+        .enumerate()
+        .any(|(i, byte)| *byte != 0 && !(i == element.len() - 1 && *byte == 0x80))
+}
+
+/// Largest element a tapscript can push or leave on the stack, BIP342
+const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Largest number of elements on the stack of a tapscript, BIP342
+const MAX_STACK_SIZE: usize = 1000;
+/// Validation weight every signature check with a non-empty signature uses, BIP342
+const VALIDATION_WEIGHT_PER_SIGOP: i64 = 50;
+
+/// Runs the taproot script path spend of input `index` against the output it spends with the
+/// BIP341/BIP342 rules: an annex is dropped from the witness and committed to by the
+/// signatures, the control block has to commit the spent script to the output key, unknown
+/// leaf versions and scripts with an OP_SUCCESSx opcode succeed, and the script has to leave a
+/// single true element with the rest of the witness as its initial stack. Returns InvalidWitness
+/// with the reason the spend fails.
+///
+/// Only the opcodes the bridge scripts use are interpreted, a script with any other one is
+/// neither accepted nor rejected but fails with UnsupportedScript. A full interpreter is not
+/// available: the libbitcoinconsensus the bitcoin crate binds with its `bitcoinconsensus`
+/// feature is the one of Bitcoin Core 0.20, which predates taproot and takes every witness v1
+/// spend as valid.
+pub fn verify_taproot_witness(
+    tx: &bitcoin::Transaction,
+    index: usize,
+    prevouts: &[TxOut],
+) -> Result<(), BridgeError> {
+    let invalid = |reason: &str| BridgeError::InvalidWitness(reason.to_string());
+    let input = tx.input.get(index).ok_or(BridgeError::TxInputNotFound)?;
+    let prevout = prevouts.get(index).ok_or(BridgeError::TxInputNotFound)?;
+    if !prevout.script_pubkey.is_p2tr() {
+        return Err(invalid("spent output is not a taproot output"));
+    }
+    let output_key = XOnlyPublicKey::from_slice(&prevout.script_pubkey.as_bytes()[2..])
+        .map_err(|_| invalid("invalid taproot output key"))?;
+
+    let mut stack = input
+        .witness
+        .iter()
+        .map(|element| element.to_vec())
+        .collect::<Vec<_>>();
+    let annex = match stack.last() {
+        Some(last) if stack.len() >= 2 && last.first() == Some(&TAPROOT_ANNEX_PREFIX) => {
+            stack.pop()
+        }
+        _ => None,
+    };
+    let (Some(control_block), Some(script)) = (stack.pop(), stack.pop()) else {
+        return Err(invalid("witness has no script and control block"));
+    };
+    let control_block =
+        ControlBlock::decode(&control_block).map_err(|_| invalid("invalid control block"))?;
+    let script = ScriptBuf::from(script);
+    let secp = Secp256k1::verification_only();
+    if !control_block.verify_taproot_commitment(&secp, output_key, &script) {
+        return Err(invalid("control block does not commit to the script"));
+    }
+    // Spends of leaf versions other than tapscript are left to future soft forks
+    if control_block.leaf_version != LeafVersion::TapScript {
+        return Ok(());
+    }
+    for instruction in script.instructions() {
+        if let Instruction::Op(op) = instruction.map_err(|_| invalid("script can not be parsed"))? {
+            if op.classify(ClassifyContext::TapScript) == Class::SuccessOp {
+                return Ok(());
+            }
+        }
+    }
+    if stack.len() > MAX_STACK_SIZE {
+        return Err(invalid("stack size limit exceeded"));
+    }
+    if stack
+        .iter()
+        .any(|element| element.len() > MAX_SCRIPT_ELEMENT_SIZE)
+    {
+        return Err(invalid("witness element size limit exceeded"));
+    }
+    let annex = annex
+        .as_deref()
+        .map(Annex::new)
+        .transpose()
+        .map_err(|_| invalid("invalid annex"))?;
+    let leaf_hash = TapLeafHash::from_script(&script, control_block.leaf_version);
+    let mut validation_weight_left = VALIDATION_WEIGHT_PER_SIGOP + input.witness.size() as i64;
+    // Whether each enclosing OP_IF / OP_NOTIF branch is taken, ops only run inside taken ones
+    let mut conditions: Vec<bool> = Vec::new();
+
+    for instruction in script.instructions() {
+        let executing = conditions.iter().all(|condition| *condition);
+        let op = match instruction.map_err(|_| invalid("script can not be parsed"))? {
+            Instruction::PushBytes(bytes) => {
+                if bytes.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                    return Err(invalid("push size limit exceeded"));
+                }
+                if executing {
+                    stack.push(bytes.as_bytes().to_vec());
+                }
+                if stack.len() > MAX_STACK_SIZE {
+                    return Err(invalid("stack size limit exceeded"));
+                }
+                continue;
+            }
+            Instruction::Op(op) => op,
+        };
+        if !executing && !matches!(op, OP_IF | OP_NOTIF | OP_ELSE | OP_ENDIF) {
+            continue;
+        }
+        let mut pop = || stack.pop().ok_or_else(|| invalid("stack underflow"));
+        match op {
+            OP_IF | OP_NOTIF => {
+                let condition = if executing {
+                    // BIP342: the argument has to be empty or exactly 0x01
+                    let taken = match pop()?.as_slice() {
+                        [] => false,
+                        [1] => true,
+                        _ => return Err(invalid("OP_IF argument is not minimal")),
+                    };
+                    taken != (op == OP_NOTIF)
+                } else {
+                    false
+                };
+                conditions.push(condition);
+            }
+            OP_ELSE => {
+                let condition = conditions
+                    .last_mut()
+                    .ok_or_else(|| invalid("unbalanced conditional"))?;
+                *condition = !*condition;
+            }
+            OP_ENDIF => {
+                conditions
+                    .pop()
+                    .ok_or_else(|| invalid("unbalanced conditional"))?;
+            }
+            OP_VERIFY => {
+                if !is_true(&pop()?) {
+                    return Err(invalid("OP_VERIFY failed"));
+                }
+            }
+            OP_RETURN => return Err(invalid("OP_RETURN")),
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                let pk = pop()?;
+                let sig = pop()?;
+                // An empty signature fails the check, any other invalid one fails the script
+                let valid = !sig.is_empty();
+                if valid {
+                    validation_weight_left -= VALIDATION_WEIGHT_PER_SIGOP;
+                    if validation_weight_left < 0 {
+                        return Err(invalid("validation weight limit exceeded"));
+                    }
+                }
+                if pk.is_empty() {
+                    return Err(invalid("empty public key"));
+                }
+                // Public keys of other sizes are unknown types, their checks succeed
+                if valid && pk.len() == 32 {
+                    let pk = XOnlyPublicKey::from_slice(&pk)
+                        .map_err(|_| invalid("invalid public key"))?;
+                    // BIP341: the default sighash type is only encoded by leaving it out
+                    if sig.len() == 65 && sig[64] == TapSighashType::Default as u8 {
+                        return Err(invalid("invalid signature"));
+                    }
+                    let sig = bitcoin::taproot::Signature::from_slice(&sig)
+                        .map_err(|_| invalid("invalid signature"))?;
+                    let sighash = SighashCache::new(tx).taproot_signature_hash(
+                        index,
+                        &Prevouts::All(prevouts),
+                        annex.clone(),
+                        Some((leaf_hash, u32::MAX)),
+                        sig.hash_ty,
+                    )?;
+                    let message = Message::from_digest_slice(sighash.as_byte_array())
+                        .expect("should be hash");
+                    secp.verify_schnorr(&sig.sig, &message, &pk)
+                        .map_err(|_| invalid("signature verification failed"))?;
+                }
+                if op == OP_CHECKSIGVERIFY {
+                    if !valid {
+                        return Err(invalid("OP_CHECKSIGVERIFY failed"));
+                    }
+                } else {
+                    stack.push(if valid { vec![1] } else { vec![] });
+                }
+            }
+            OP_CLTV => {
+                let locktime = stack
+                    .last()
+                    .and_then(|element| read_script_num(element, 5))
+                    .ok_or_else(|| invalid("invalid OP_CLTV argument"))?;
+                if locktime < 0 {
+                    return Err(invalid("negative OP_CLTV argument"));
+                }
+                // BIP65: the tx lock time has to be of the same kind, at least the argument and
+                // not disabled by the sequence
+                let threshold = bitcoin::absolute::LOCK_TIME_THRESHOLD as i64;
+                let tx_locktime = tx.lock_time.to_consensus_u32() as i64;
+                if (locktime < threshold) != (tx_locktime < threshold)
+                    || locktime > tx_locktime
+                    || input.sequence == bitcoin::Sequence::MAX
+                {
+                    return Err(invalid("OP_CLTV lock time is not satisfied"));
+                }
+            }
+            OP_CSV => {
+                let locktime = stack
+                    .last()
+                    .and_then(|element| read_script_num(element, 5))
+                    .ok_or_else(|| invalid("invalid OP_CSV argument"))?;
+                if locktime < 0 {
+                    return Err(invalid("negative OP_CSV argument"));
+                }
+                // BIP112: the type flag and the 16 bit value have to be satisfied by the sequence
+                const DISABLE_FLAG: i64 = 1 << 31;
+                const MASK: u32 = (1 << 22) | 0xffff;
+                if locktime & DISABLE_FLAG == 0 {
+                    let sequence = input.sequence.to_consensus_u32();
+                    let locktime = locktime as u32 & MASK;
+                    if tx.version.0 < 2
+                        || sequence & (1 << 31) != 0
+                        || locktime & (1 << 22) != sequence & (1 << 22)
+                        || locktime > sequence & MASK
+                    {
+                        return Err(invalid("OP_CSV relative timelock is not satisfied"));
+                    }
+                }
+            }
+            OP_DROP => {
+                pop()?;
+            }
+            OP_DUP => {
+                let element = pop()?;
+                stack.push(element.clone());
+                stack.push(element);
+            }
+            OP_SWAP => {
+                let a = pop()?;
+                let b = pop()?;
+                stack.push(a);
+                stack.push(b);
+            }
+            OP_BOOLOR => {
+                let a = is_true(&pop()?);
+                let b = is_true(&pop()?);
+                stack.push(if a || b { vec![1] } else { vec![] });
+            }
+            OP_ADD => {
+                let mut add = || {
+                    pop().and_then(|element| {
+                        read_script_num(&element, 4)
+                            .ok_or_else(|| invalid("invalid OP_ADD argument"))
+                    })
+                };
+                let sum = add()? + add()?;
+                let mut encoded = [0u8; 8];
+                let size = bitcoin::script::write_scriptint(&mut encoded, sum);
+                stack.push(encoded[..size].to_vec());
+            }
+            OP_SHA256 => {
+                let element = pop()?;
+                stack.push(Sha256::digest(element).to_vec());
+            }
+            OP_HASH160 => {
+                let element = pop()?;
+                stack.push(hash160::Hash::hash(&element).to_byte_array().to_vec());
+            }
+            OP_HASH256 => {
+                let element = pop()?;
+                stack.push(sha256d::Hash::hash(&element).to_byte_array().to_vec());
+            }
+            OP_EQUAL | OP_EQUALVERIFY => {
+                let equal = pop()? == pop()?;
+                if op == OP_EQUALVERIFY {
+                    if !equal {
+                        return Err(invalid("OP_EQUALVERIFY failed"));
+                    }
+                } else {
+                    stack.push(if equal { vec![1] } else { vec![] });
+                }
+            }
+            OP_PUSHNUM_NEG1 => stack.push(vec![0x81]),
+            op if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&op.to_u8()) => {
+                stack.push(vec![op.to_u8() - OP_PUSHNUM_1.to_u8() + 1]);
+            }
+            op => return Err(BridgeError::UnsupportedScript(op.to_string())),
+        }
+        if stack.len() > MAX_STACK_SIZE {
+            return Err(invalid("stack size limit exceeded"));
+        }
+    }
+    if !conditions.is_empty() {
+        return Err(invalid("unbalanced conditional"));
+    }
+
+    match stack.as_slice() {
+        [top] if is_true(top) => Ok(()),
+        [_] => Err(invalid("script evaluated to false")),
+        _ => Err(invalid(
+            "script does not leave a single element on the stack",
+        )),
+    }
+}
+
+/// Checks the spend of input `input_index` through `script` and `control_block` before it is
+/// broadcast: the input's witness has to end with them, and has to pass
+/// verify_taproot_witness. A script verify_taproot_witness does not support is only logged and
+/// left to bitcoind.
+pub fn verify_taproot_script_spend(
+    tx: &bitcoin::Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+    script: &ScriptBuf,
+    control_block: &ControlBlock,
+) -> Result<(), BridgeError> {
+    let witness = &tx
+        .input
+        .get(input_index)
+        .ok_or(BridgeError::TxInputNotFound)?
+        .witness;
+    if witness.len() < 2
+        || witness.nth(witness.len() - 2) != Some(script.as_bytes())
+        || witness.last() != Some(control_block.serialize().as_slice())
+    {
+        return Err(BridgeError::InvalidWitness(
+            "witness does not spend the script".to_string(),
+        ));
+    }
+    match verify_taproot_witness(tx, input_index, prevouts) {
+        Err(BridgeError::UnsupportedScript(op)) => {
+            tracing::warn!(
+                txid = %tx.txid(),
+                input_index,
+                "Script spend not verified, {} is not supported",
+                op
+            );
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+pub fn get_claim_reveal_indices(
+    depth: usize,
+    count: u32,
+) -> Result<Vec<ConnectorTreeLeaf>, BridgeError> {
+    if count > 2u32.pow(depth as u32) {
+        return Err(BridgeError::InvalidClaimCount);
+    }
 
     if count == 0 {
-        return vec![(0, 0)];
+        return Ok(vec![ConnectorTreeLeaf { level: 0, index: 0 }]);
     }
 
-    let mut indices: Vec<(usize, usize)> = Vec::new();
+    let mut indices: Vec<ConnectorTreeLeaf> = Vec::new();
     if count == 2u32.pow(depth as u32) {
-        return indices;
+        return Ok(indices);
     }
 
     if count % 2 == 1 {
-        indices.push((depth, count as usize));
-        indices.extend(get_claim_reveal_indices(depth - 1, (count + 1) / 2));
+        indices.push(ConnectorTreeLeaf {
+            level: depth,
+            index: count as usize,
+        });
+        indices.extend(get_claim_reveal_indices(depth - 1, count.div_ceil(2))?);
     } else {
-        indices.extend(get_claim_reveal_indices(depth - 1, count / 2));
+        indices.extend(get_claim_reveal_indices(depth - 1, count / 2)?);
     }
 
-    indices
+    Ok(indices)
+}
+
+/// Returns whether the leaf `index` is below one of the nodes whose preimages are revealed
+/// for `count` withdrawals, i.e. whether anyone can burn it after the reveal
+pub fn is_connector_leaf_revealed(
+    depth: usize,
+    count: u32,
+    index: usize,
+) -> Result<bool, BridgeError> {
+    Ok(get_claim_reveal_indices(depth, count)?
+        .iter()
+        .any(|leaf| index >> (depth - leaf.level) == leaf.index))
 }
 
 pub fn get_claim_proof_tree_leaf(
     depth: usize,
     num_claims: usize,
     connector_tree_hashes: &HashTree,
-) -> [u8; 32] {
-    let indices = get_claim_reveal_indices(depth, num_claims as u32);
+) -> Result<[u8; 32], BridgeError> {
+    let indices = get_claim_reveal_indices(depth, num_claims as u32)?;
     let mut hasher = Sha256::new();
-    indices.iter().for_each(|(level, index)| {
-        hasher.update(connector_tree_hashes[*level][*index]);
+    indices.iter().for_each(|leaf| {
+        hasher.update(connector_tree_hashes[leaf.level][leaf.index]);
     });
-    hasher.finalize().into()
+    Ok(hasher.finalize().into())
 }
-pub fn calculate_claim_proof_root(
+/// Claim proof merkle tree of a connector tree of `depth`, its leaf `i` commits to the preimages
+/// revealed after `i` claims. The circuit checks claim proofs against a tree of
+/// CLAIM_MERKLE_TREE_DEPTH, so the leaves a shallower connector tree does not fill stay empty,
+/// and no preimages open them.
+pub fn claim_proof_merkle_tree(
     depth: usize,
-    connector_tree_hashes: &Vec<Vec<[u8; 32]>>,
-) -> [u8; 32] {
-    let mut hashes: Vec<[u8; 32]> = Vec::new();
-    for i in 0..2u32.pow(depth as u32) {
-        let hash = get_claim_proof_tree_leaf(depth, i as usize, connector_tree_hashes);
-        hashes.push(hash);
+    connector_tree_hashes: &HashTree,
+) -> Result<MerkleTree<CLAIM_MERKLE_TREE_DEPTH>, BridgeError> {
+    if depth > CLAIM_MERKLE_TREE_DEPTH {
+        return Err(BridgeError::InvalidConnectorTreeDepth);
     }
-    let mut level = 0;
-    while level < depth {
-        let mut level_hashes: Vec<[u8; 32]> = Vec::new();
-        for i in 0..2u32.pow(depth as u32 - level as u32 - 1) {
-            let mut hasher = Sha256::new();
-            hasher.update(hashes[i as usize * 2]);
-            hasher.update(hashes[i as usize * 2 + 1]);
-            let hash = hasher.finalize().into();
-            level_hashes.push(hash);
-        }
-        hashes = level_hashes.clone();
-        level += 1;
+    let mut claim_proof_merkle_tree = MerkleTree::new();
+    for i in 0..2usize.pow(depth as u32) {
+        claim_proof_merkle_tree.add(get_claim_proof_tree_leaf(depth, i, connector_tree_hashes)?);
     }
-    hashes[0]
+    Ok(claim_proof_merkle_tree)
+}
+
+/// Root of the claim_proof_merkle_tree of a connector tree of `depth`
+pub fn calculate_claim_proof_root(
+    depth: usize,
+    connector_tree_hashes: &HashTree,
+) -> Result<[u8; 32], BridgeError> {
+    Ok(claim_proof_merkle_tree(depth, connector_tree_hashes)?.root())
 }
 
 // tests
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::actor::Actor;
+    use crate::script_builder::ScriptBuilder;
+    use crate::test_utils::MoveTxFixture;
+    use crate::transaction_builder::INTERNAL_KEY;
+    use bitcoin::opcodes::all::{OP_PUSHBYTES_0, OP_RESERVED, OP_SIZE};
+    use bitcoin::opcodes::Opcode;
+    use bitcoin::script::{Builder, PushBytesBuf};
+    use bitcoin::secp256k1::rand::rngs::StdRng;
+    use bitcoin::secp256k1::rand::SeedableRng;
+    use bitcoin::taproot::TaprootBuilder;
+    use bitcoin::Address;
+    use clementine_circuits::sha256_hash;
 
     #[test]
     fn test_get_indices() {
@@ -204,7 +784,11 @@ mod tests {
         ];
 
         for ((depth, index), expected) in test_cases {
-            let indices = get_claim_reveal_indices(depth, index);
+            let indices = get_claim_reveal_indices(depth, index).unwrap();
+            let expected = expected
+                .into_iter()
+                .map(|(level, index)| ConnectorTreeLeaf { level, index })
+                .collect::<Vec<_>>();
             assert_eq!(
                 indices, expected,
                 "Failed at get_indices({}, {})",
@@ -212,4 +796,730 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_calculate_amount_with_weights() {
+        let dust = Amount::from_sat(1000);
+        // A single leaf, nothing to split
+        assert_eq!(
+            calculate_amount_with_weights(0, dust, 150, 200, 3),
+            Amount::from_sat(1000 + 150 * 3)
+        );
+        // 1 + 2 node txs of 200 vB, 4 leaves of 1000 sats spent with 150 vB, at 3 sat/vB
+        assert_eq!(
+            calculate_amount_with_weights(2, dust, 150, 200, 3),
+            Amount::from_sat(3 * 200 * 3 + 4 * (1000 + 150 * 3))
+        );
+        // Without node txs to pay for, it is the flat per leaf amount
+        assert_eq!(
+            calculate_amount_with_weights(3, dust, 150, 0, 3),
+            calculate_amount(3, dust, Amount::from_sat(450))
+        );
+    }
+
+    #[test]
+    fn test_is_connector_leaf_revealed() {
+        // Nothing is claimed, the root is revealed
+        assert!((0..8).all(|index| is_connector_leaf_revealed(3, 0, index).unwrap()));
+        // The first three leaves are claimed, the nodes above the rest are revealed
+        assert!((0..3).all(|index| !is_connector_leaf_revealed(3, 3, index).unwrap()));
+        assert!((3..8).all(|index| is_connector_leaf_revealed(3, 3, index).unwrap()));
+        // Everything is claimed, nothing is revealed
+        assert!((0..8).all(|index| !is_connector_leaf_revealed(3, 8, index).unwrap()));
+        assert_eq!(
+            is_connector_leaf_revealed(3, 9, 0),
+            Err(BridgeError::InvalidClaimCount)
+        );
+    }
+
+    #[test]
+    fn test_get_indices_claim_count_bounds() {
+        // A zero count reveals the root, on every depth
+        for depth in 0..4 {
+            assert_eq!(
+                get_claim_reveal_indices(depth, 0),
+                Ok(vec![ConnectorTreeLeaf { level: 0, index: 0 }])
+            );
+        }
+        // A count above the number of leaves is rejected instead of panicking
+        assert_eq!(
+            get_claim_reveal_indices(0, 2),
+            Err(BridgeError::InvalidClaimCount)
+        );
+        assert_eq!(
+            get_claim_reveal_indices(3, 9),
+            Err(BridgeError::InvalidClaimCount)
+        );
+        assert_eq!(
+            get_claim_reveal_indices(3, u32::MAX),
+            Err(BridgeError::InvalidClaimCount)
+        );
+    }
+
+    #[test]
+    fn test_check_witness_signature_count() {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([26u8; 32]);
+        let pks = (0..4)
+            .map(|_| XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1))
+            .collect::<Vec<_>>();
+        let sig = [0u8; 64];
+
+        let n_of_n_script =
+            ScriptBuilder::new(pks[..3].to_vec()).generate_script_n_of_n_with_user_pk(&pks[3]);
+        assert_eq!(
+            check_witness_signature_count(&[sig; 4], &n_of_n_script),
+            Ok(())
+        );
+        assert_eq!(
+            check_witness_signature_count(&[sig; 3], &n_of_n_script),
+            Err(BridgeError::WitnessElementCountMismatch)
+        );
+
+        let timelock_script = ScriptBuilder::generate_timelock_script(&pks[0], 10);
+        assert_eq!(
+            check_witness_signature_count(&[sig], &timelock_script),
+            Ok(())
+        );
+        assert_eq!(
+            check_witness_signature_count::<[u8; 64]>(&[], &timelock_script),
+            Err(BridgeError::WitnessElementCountMismatch)
+        );
+    }
+
+    #[test]
+    fn test_verify_taproot_witness() {
+        let fixture = MoveTxFixture::new(54, OutPoint::null(), &[1u8; 20]);
+        let witness_sigs = fixture.witness_sigs();
+        let signed_tx = fixture.witnessed_move_tx(&witness_sigs);
+        assert_eq!(
+            verify_taproot_witness(&signed_tx.tx, 0, &signed_tx.prevouts),
+            Ok(())
+        );
+
+        // Two verifiers' signatures are swapped
+        let mut misordered_sigs = witness_sigs.clone();
+        misordered_sigs.swap(2, 3);
+        let misordered_tx = fixture.witnessed_move_tx(&misordered_sigs);
+        assert_eq!(
+            verify_taproot_witness(&misordered_tx.tx, 0, &misordered_tx.prevouts),
+            Err(BridgeError::InvalidWitness(
+                "signature verification failed".to_string()
+            ))
+        );
+
+        // A signature is missing
+        let missing_sig_tx = fixture.witnessed_move_tx(&witness_sigs[1..]);
+        assert_eq!(
+            verify_taproot_witness(&missing_sig_tx.tx, 0, &missing_sig_tx.prevouts),
+            Err(BridgeError::InvalidWitness("stack underflow".to_string()))
+        );
+    }
+
+    /// A version 2 tx spending the single leaf taproot output of `script`, without a witness
+    fn script_spend_tx(
+        script: &ScriptBuf,
+        leaf_version: LeafVersion,
+    ) -> (bitcoin::Transaction, Vec<TxOut>, ControlBlock) {
+        let secp = Secp256k1::new();
+        let tree_info = TaprootBuilder::new()
+            .add_leaf_with_ver(0, script.clone(), leaf_version)
+            .unwrap()
+            .finalize(&secp, *INTERNAL_KEY)
+            .unwrap();
+        let control_block = tree_info
+            .control_block(&(script.clone(), leaf_version))
+            .unwrap();
+        let address = Address::p2tr(
+            &secp,
+            *INTERNAL_KEY,
+            tree_info.merkle_root(),
+            bitcoin::Network::Regtest,
+        );
+        let prevouts = vec![TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: address.script_pubkey(),
+        }];
+        let tx = bitcoin::Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::new(),
+                sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: bitcoin::Witness::new(),
+            }],
+            output: vec![ScriptBuilder::anyone_can_spend_txout()],
+        };
+        (tx, prevouts, control_block)
+    }
+
+    /// Spends `script` with `stack` as its initial stack and `annex` at the end of the witness
+    fn run_script_with_annex(
+        mut tx: bitcoin::Transaction,
+        prevouts: &[TxOut],
+        script: &ScriptBuf,
+        control_block: &ControlBlock,
+        stack: &[Vec<u8>],
+        annex: Option<&[u8]>,
+    ) -> Result<(), BridgeError> {
+        for element in stack {
+            tx.input[0].witness.push(element);
+        }
+        tx.input[0].witness.push(script.as_bytes());
+        tx.input[0].witness.push(control_block.serialize());
+        if let Some(annex) = annex {
+            tx.input[0].witness.push(annex);
+        }
+        verify_taproot_witness(&tx, 0, prevouts)
+    }
+
+    fn run_script(script: &ScriptBuf, stack: &[Vec<u8>]) -> Result<(), BridgeError> {
+        let (tx, prevouts, control_block) = script_spend_tx(script, LeafVersion::TapScript);
+        run_script_with_annex(tx, &prevouts, script, &control_block, stack, None)
+    }
+
+    fn invalid(reason: &str) -> Result<(), BridgeError> {
+        Err(BridgeError::InvalidWitness(reason.to_string()))
+    }
+
+    fn ops_script(ops: &[Opcode]) -> ScriptBuf {
+        ops.iter()
+            .fold(Builder::new(), |builder, op| builder.push_opcode(*op))
+            .into_script()
+    }
+
+    #[test]
+    fn test_verify_taproot_witness_opcodes() {
+        let pushnum_script = Builder::new()
+            .push_opcode(OP_PUSHNUM_16)
+            .push_slice([16u8])
+            .push_opcode(OP_EQUAL)
+            .into_script();
+        assert_eq!(run_script(&pushnum_script, &[]), Ok(()));
+
+        assert_eq!(
+            run_script(&ops_script(&[OP_DROP]), &[vec![1], vec![]]),
+            Ok(())
+        );
+        assert_eq!(
+            run_script(&ops_script(&[OP_DROP]), &[]),
+            invalid("stack underflow")
+        );
+        assert_eq!(
+            run_script(&ops_script(&[OP_DUP, OP_EQUAL]), &[vec![7]]),
+            Ok(())
+        );
+
+        // The true element is only left on the stack if it is swapped below the dropped one
+        let swap_script = ops_script(&[OP_SWAP, OP_DROP]);
+        assert_eq!(run_script(&swap_script, &[vec![], vec![1]]), Ok(()));
+        assert_eq!(
+            run_script(&ops_script(&[OP_DROP]), &[vec![], vec![1]]),
+            invalid("script evaluated to false")
+        );
+
+        let boolor_script = ops_script(&[OP_BOOLOR]);
+        assert_eq!(run_script(&boolor_script, &[vec![], vec![1]]), Ok(()));
+        assert_eq!(run_script(&boolor_script, &[vec![2, 0], vec![]]), Ok(()));
+        // Negative zero is false
+        assert_eq!(
+            run_script(&boolor_script, &[vec![0, 0x80], vec![]]),
+            invalid("script evaluated to false")
+        );
+
+        let preimage = [3u8; 32];
+        let hash_script = Builder::new()
+            .push_opcode(OP_SHA256)
+            .push_slice(sha256_hash!(preimage))
+            .push_opcode(OP_EQUALVERIFY)
+            .push_opcode(OP_PUSHNUM_1)
+            .into_script();
+        assert_eq!(run_script(&hash_script, &[preimage.to_vec()]), Ok(()));
+        assert_eq!(
+            run_script(&hash_script, &[vec![4u8; 32]]),
+            invalid("OP_EQUALVERIFY failed")
+        );
+        assert_eq!(
+            run_script(&ops_script(&[OP_EQUAL]), &[vec![1], vec![2]]),
+            invalid("script evaluated to false")
+        );
+
+        assert_eq!(
+            run_script(&ops_script(&[OP_PUSHNUM_1]), &[vec![1]]),
+            invalid("script does not leave a single element on the stack")
+        );
+        let add_script = Builder::new()
+            .push_opcode(OP_ADD)
+            .push_int(3)
+            .push_opcode(OP_EQUAL)
+            .into_script();
+        assert_eq!(run_script(&add_script, &[vec![1], vec![2]]), Ok(()));
+        assert_eq!(
+            run_script(&add_script, &[vec![1], vec![1; 5]]),
+            invalid("invalid OP_ADD argument")
+        );
+        assert_eq!(
+            run_script(&ops_script(&[OP_VERIFY, OP_PUSHNUM_1]), &[vec![]]),
+            invalid("OP_VERIFY failed")
+        );
+
+        let hash160_script = Builder::new()
+            .push_opcode(OP_HASH160)
+            .push_slice(hash160::Hash::hash(&preimage).to_byte_array())
+            .push_opcode(OP_EQUAL)
+            .into_script();
+        assert_eq!(run_script(&hash160_script, &[preimage.to_vec()]), Ok(()));
+        let hash256_script = Builder::new()
+            .push_opcode(OP_HASH256)
+            .push_slice(sha256d::Hash::hash(&preimage).to_byte_array())
+            .push_opcode(OP_EQUAL)
+            .into_script();
+        assert_eq!(run_script(&hash256_script, &[preimage.to_vec()]), Ok(()));
+        assert_eq!(
+            run_script(&hash256_script, &[vec![4u8; 32]]),
+            invalid("script evaluated to false")
+        );
+
+        // A script with an opcode that is not interpreted is neither valid nor invalid
+        assert_eq!(
+            run_script(&ops_script(&[OP_SIZE]), &[vec![1]]),
+            Err(BridgeError::UnsupportedScript(OP_SIZE.to_string()))
+        );
+        // An OP_SUCCESSx opcode anywhere in the script makes it succeed
+        assert_eq!(run_script(&ops_script(&[OP_ADD, OP_RESERVED]), &[]), Ok(()));
+        // As does a leaf version tapscript does not define
+        let unknown_version = LeafVersion::from_consensus(0xc2).unwrap();
+        let script = ops_script(&[OP_ADD]);
+        let (tx, prevouts, control_block) = script_spend_tx(&script, unknown_version);
+        assert_eq!(
+            run_script_with_annex(tx, &prevouts, &script, &control_block, &[], None),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_verify_taproot_witness_conditionals() {
+        let if_script = ops_script(&[OP_IF, OP_PUSHNUM_1, OP_ELSE, OP_RETURN, OP_ENDIF]);
+        assert_eq!(run_script(&if_script, &[vec![1]]), Ok(()));
+        assert_eq!(run_script(&if_script, &[vec![]]), invalid("OP_RETURN"));
+        // Only an empty element and 0x01 are valid conditions
+        assert_eq!(
+            run_script(&if_script, &[vec![2]]),
+            invalid("OP_IF argument is not minimal")
+        );
+        let notif_script = ops_script(&[OP_NOTIF, OP_RETURN, OP_ENDIF, OP_PUSHNUM_1]);
+        assert_eq!(run_script(&notif_script, &[vec![1]]), Ok(()));
+        assert_eq!(run_script(&notif_script, &[vec![]]), invalid("OP_RETURN"));
+
+        // Ops of branches that are not taken do not run, like the contents of an inscription
+        let envelope_script = Builder::new()
+            .push_opcode(OP_PUSHNUM_1)
+            .push_opcode(OP_PUSHBYTES_0)
+            .push_opcode(OP_IF)
+            .push_slice([5u8; 32])
+            .push_opcode(OP_SIZE)
+            .push_opcode(OP_IF)
+            .push_opcode(OP_RETURN)
+            .push_opcode(OP_ELSE)
+            .push_opcode(OP_RETURN)
+            .push_opcode(OP_ENDIF)
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        assert_eq!(run_script(&envelope_script, &[]), Ok(()));
+
+        assert_eq!(
+            run_script(&ops_script(&[OP_PUSHNUM_1, OP_PUSHNUM_1, OP_IF]), &[]),
+            invalid("unbalanced conditional")
+        );
+        assert_eq!(
+            run_script(&ops_script(&[OP_PUSHNUM_1, OP_ENDIF]), &[]),
+            invalid("unbalanced conditional")
+        );
+        assert_eq!(
+            run_script(&ops_script(&[OP_PUSHNUM_1, OP_ELSE]), &[]),
+            invalid("unbalanced conditional")
+        );
+    }
+
+    #[test]
+    fn test_verify_taproot_witness_cltv() {
+        let run_cltv = |locktime: i64, tx_locktime: u32, sequence: u32| {
+            let script = Builder::new()
+                .push_int(locktime)
+                .push_opcode(OP_CLTV)
+                .push_opcode(OP_DROP)
+                .push_opcode(OP_PUSHNUM_1)
+                .into_script();
+            let (mut tx, prevouts, control_block) =
+                script_spend_tx(&script, LeafVersion::TapScript);
+            tx.lock_time = bitcoin::absolute::LockTime::from_consensus(tx_locktime);
+            tx.input[0].sequence = bitcoin::Sequence(sequence);
+            run_script_with_annex(tx, &prevouts, &script, &control_block, &[], None)
+        };
+        let not_satisfied = invalid("OP_CLTV lock time is not satisfied");
+
+        assert_eq!(run_cltv(10, 10, 0xfffffffd), Ok(()));
+        assert_eq!(run_cltv(10, 11, 0xfffffffd), Ok(()));
+        assert_eq!(run_cltv(10, 9, 0xfffffffd), not_satisfied);
+        // A final sequence disables the tx lock time
+        assert_eq!(run_cltv(10, 10, 0xffffffff), not_satisfied);
+        // The tx lock time is a timestamp, the argument a height
+        assert_eq!(run_cltv(10, 500_000_001, 0xfffffffd), not_satisfied);
+        assert_eq!(
+            run_cltv(-1, 10, 0xfffffffd),
+            invalid("negative OP_CLTV argument")
+        );
+    }
+
+    #[test]
+    fn test_verify_taproot_witness_csv() {
+        let run_csv = |locktime: i64, version: i32, sequence: u32| {
+            let script = Builder::new()
+                .push_int(locktime)
+                .push_opcode(OP_CSV)
+                .push_opcode(OP_DROP)
+                .push_opcode(OP_PUSHNUM_1)
+                .into_script();
+            let (mut tx, prevouts, control_block) =
+                script_spend_tx(&script, LeafVersion::TapScript);
+            tx.version = bitcoin::transaction::Version(version);
+            tx.input[0].sequence = bitcoin::Sequence(sequence);
+            run_script_with_annex(tx, &prevouts, &script, &control_block, &[], None)
+        };
+        let not_satisfied = invalid("OP_CSV relative timelock is not satisfied");
+
+        assert_eq!(run_csv(2, 2, 2), Ok(()));
+        assert_eq!(run_csv(2, 2, 3), Ok(()));
+        assert_eq!(run_csv(2, 2, 1), not_satisfied);
+        assert_eq!(run_csv(2, 1, 2), not_satisfied);
+        // The sequence disables relative timelocks
+        assert_eq!(run_csv(2, 2, (1 << 31) | 2), not_satisfied);
+        // The sequence is in units of time, the argument in blocks
+        assert_eq!(run_csv(2, 2, (1 << 22) | 2), not_satisfied);
+        // The argument disables the check
+        assert_eq!(run_csv(1 << 31, 1, 0), Ok(()));
+        assert_eq!(run_csv(-2, 2, 2), invalid("negative OP_CSV argument"));
+
+        let script = Builder::new()
+            .push_slice([1u8; 6])
+            .push_opcode(OP_CSV)
+            .into_script();
+        assert_eq!(run_script(&script, &[]), invalid("invalid OP_CSV argument"));
+    }
+
+    #[test]
+    fn test_verify_taproot_witness_checksig() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([57u8; 32]);
+        let actor = Actor::new(secp.generate_keypair(&mut rng).0);
+        let checksig_script = Builder::new()
+            .push_x_only_key(&actor.xonly_public_key)
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let (tx, prevouts, control_block) =
+            script_spend_tx(&checksig_script, LeafVersion::TapScript);
+        let leaf_hash = TapLeafHash::from_script(&checksig_script, LeafVersion::TapScript);
+        let sign = |annex: Option<&[u8]>, sighash_type: TapSighashType| {
+            let sighash = SighashCache::new(&tx)
+                .taproot_signature_hash(
+                    0,
+                    &Prevouts::All(&prevouts),
+                    annex.map(|annex| Annex::new(annex).unwrap()),
+                    Some((leaf_hash, u32::MAX)),
+                    sighash_type,
+                )
+                .unwrap();
+            actor.sign(sighash).as_ref().to_vec()
+        };
+        let run = |stack: &[Vec<u8>], annex: Option<&[u8]>| {
+            run_script_with_annex(
+                tx.clone(),
+                &prevouts,
+                &checksig_script,
+                &control_block,
+                stack,
+                annex,
+            )
+        };
+
+        let sig = sign(None, TapSighashType::Default);
+        assert_eq!(run(&[sig.clone()], None), Ok(()));
+        // An empty signature only fails the check
+        assert_eq!(run(&[vec![]], None), invalid("script evaluated to false"));
+        assert_eq!(
+            run(&[actor.sign_digest([0u8; 32]).as_ref().to_vec()], None),
+            invalid("signature verification failed")
+        );
+        assert_eq!(
+            run(&[sig[..63].to_vec()], None),
+            invalid("invalid signature")
+        );
+        // The default sighash type can not be given explicitly
+        let mut explicit_default_sig = sig.clone();
+        explicit_default_sig.push(TapSighashType::Default as u8);
+        assert_eq!(
+            run(&[explicit_default_sig], None),
+            invalid("invalid signature")
+        );
+        let mut sighash_all_sig = sign(None, TapSighashType::All);
+        sighash_all_sig.push(TapSighashType::All as u8);
+        assert_eq!(run(&[sighash_all_sig], None), Ok(()));
+
+        // The annex is left out of the stack and committed to by the signature
+        let annex = [TAPROOT_ANNEX_PREFIX, 1];
+        assert_eq!(
+            run(&[sign(Some(&annex), TapSighashType::Default)], Some(&annex)),
+            Ok(())
+        );
+        assert_eq!(
+            run(&[sig], Some(&annex)),
+            invalid("signature verification failed")
+        );
+
+        let checksigverify_script = Builder::new()
+            .push_x_only_key(&actor.xonly_public_key)
+            .push_opcode(OP_CHECKSIGVERIFY)
+            .push_opcode(OP_PUSHNUM_1)
+            .into_script();
+        assert_eq!(
+            run_script(&checksigverify_script, &[vec![]]),
+            invalid("OP_CHECKSIGVERIFY failed")
+        );
+
+        let empty_pk_script = ops_script(&[OP_PUSHBYTES_0, OP_CHECKSIG]);
+        assert_eq!(
+            run_script(&empty_pk_script, &[vec![1]]),
+            invalid("empty public key")
+        );
+        assert_eq!(
+            run_script(&empty_pk_script, &[vec![]]),
+            invalid("empty public key")
+        );
+        // Keys of unknown types accept any non-empty signature
+        let unknown_pk_script = Builder::new()
+            .push_slice([2u8; 33])
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        assert_eq!(run_script(&unknown_pk_script, &[vec![1]]), Ok(()));
+        assert_eq!(
+            run_script(&unknown_pk_script, &[vec![]]),
+            invalid("script evaluated to false")
+        );
+    }
+
+    #[test]
+    fn test_verify_taproot_witness_limits() {
+        let drop_script = ops_script(&[OP_DROP, OP_PUSHNUM_1]);
+        assert_eq!(run_script(&drop_script, &[vec![0u8; 520]]), Ok(()));
+        assert_eq!(
+            run_script(&drop_script, &[vec![0u8; 521]]),
+            invalid("witness element size limit exceeded")
+        );
+        let push_script = Builder::new()
+            .push_slice(PushBytesBuf::try_from(vec![0u8; 521]).unwrap())
+            .push_opcode(OP_DROP)
+            .push_opcode(OP_PUSHNUM_1)
+            .into_script();
+        assert_eq!(
+            run_script(&push_script, &[]),
+            invalid("push size limit exceeded")
+        );
+
+        assert_eq!(
+            run_script(&ops_script(&[OP_DROP; 999]), &vec![vec![1]; 1000]),
+            Ok(())
+        );
+        assert_eq!(
+            run_script(&ops_script(&[OP_DROP; 1000]), &vec![vec![1]; 1001]),
+            invalid("stack size limit exceeded")
+        );
+        assert_eq!(
+            run_script(&ops_script(&[OP_DUP, OP_DUP]), &vec![vec![1]; 999]),
+            invalid("stack size limit exceeded")
+        );
+
+        // Signature checks in the script are not paid for by a small witness
+        let many_checks_script = (0..20)
+            .fold(Builder::new(), |builder, _| {
+                builder
+                    .push_slice([1u8])
+                    .push_slice([2u8; 33])
+                    .push_opcode(OP_CHECKSIGVERIFY)
+            })
+            .push_opcode(OP_PUSHNUM_1)
+            .into_script();
+        assert_eq!(
+            run_script(&many_checks_script, &[]),
+            invalid("validation weight limit exceeded")
+        );
+    }
+
+    #[test]
+    fn test_verify_taproot_script_spend() {
+        let secp = bitcoin::secp256k1::Secp256k1::new();
+        let mut rng = StdRng::from_seed([55u8; 32]);
+        let actor = Actor::new(secp.generate_keypair(&mut rng).0);
+        let preimage = [56u8; 32];
+        let (address, tree_info) = TransactionBuilder::create_connector_tree_node_address(
+            &secp,
+            &actor.xonly_public_key,
+            &sha256_hash!(preimage),
+            1,
+        )
+        .unwrap();
+        let hash_script = ScriptBuilder::generate_hash_script(sha256_hash!(preimage));
+        let timelock_script = ScriptBuilder::generate_timelock_script(&actor.xonly_public_key, 1);
+        let prevouts = vec![TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: address.script_pubkey(),
+        }];
+        let spend_tx = |script: &ScriptBuf, control_block: &ControlBlock| {
+            let mut tx = bitcoin::Transaction {
+                version: bitcoin::transaction::Version(2),
+                lock_time: bitcoin::absolute::LockTime::ZERO,
+                input: vec![bitcoin::TxIn {
+                    previous_output: OutPoint::null(),
+                    script_sig: ScriptBuf::new(),
+                    sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+                    witness: bitcoin::Witness::new(),
+                }],
+                output: vec![ScriptBuilder::anyone_can_spend_txout()],
+            };
+            tx.input[0].witness.push(preimage);
+            tx.input[0].witness.push(script.as_bytes());
+            tx.input[0].witness.push(control_block.serialize());
+            tx
+        };
+
+        let control_block = create_control_block(tree_info.clone(), &hash_script);
+        let tx = spend_tx(&hash_script, &control_block);
+        assert_eq!(
+            verify_taproot_script_spend(&tx, 0, &prevouts, &hash_script, &control_block),
+            Ok(())
+        );
+
+        // The control block of the other leaf
+        let wrong_control_block = create_control_block(tree_info, &timelock_script);
+        let tx = spend_tx(&hash_script, &wrong_control_block);
+        assert_eq!(
+            verify_taproot_script_spend(&tx, 0, &prevouts, &hash_script, &wrong_control_block),
+            Err(BridgeError::InvalidWitness(
+                "control block does not commit to the script".to_string()
+            ))
+        );
+        // A witness spending another script than the one checked
+        let tx = spend_tx(&timelock_script, &wrong_control_block);
+        assert_eq!(
+            verify_taproot_script_spend(&tx, 0, &prevouts, &hash_script, &control_block),
+            Err(BridgeError::InvalidWitness(
+                "witness does not spend the script".to_string()
+            ))
+        );
+
+        // A script the interpreter does not support is left to bitcoind
+        let size_script = ops_script(&[OP_SIZE]);
+        let (mut tx, prevouts, control_block) =
+            script_spend_tx(&size_script, LeafVersion::TapScript);
+        tx.input[0].witness.push([1u8]);
+        tx.input[0].witness.push(size_script.as_bytes());
+        tx.input[0].witness.push(control_block.serialize());
+        assert_eq!(
+            verify_taproot_witness(&tx, 0, &prevouts),
+            Err(BridgeError::UnsupportedScript(OP_SIZE.to_string()))
+        );
+        assert_eq!(
+            verify_taproot_script_spend(&tx, 0, &prevouts, &size_script, &control_block),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_check_evm_address() {
+        assert_eq!(
+            check_evm_address(&[0u8; 20]),
+            Err(BridgeError::InvalidEvmAddress)
+        );
+        let mut evm_address = [0u8; 20];
+        evm_address[19] = 1;
+        assert_eq!(check_evm_address(&evm_address), Ok(()));
+        assert_eq!(check_evm_address(&[0xab; 20]), Ok(()));
+    }
+
+    #[test]
+    fn test_evm_address_hex() {
+        // Test vectors of EIP-55
+        for checksummed in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            let evm_address = evm_address_from_hex(checksummed).unwrap();
+            assert_eq!(evm_address_to_hex(&evm_address), checksummed);
+            assert_eq!(
+                evm_address_from_hex(&checksummed.to_lowercase()),
+                Ok(evm_address)
+            );
+            assert_eq!(
+                evm_address_from_hex(&checksummed[2..].to_uppercase()),
+                Ok(evm_address)
+            );
+        }
+        assert_eq!(
+            evm_address_from_hex("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"),
+            Ok(hex::decode("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed")
+                .unwrap()
+                .try_into()
+                .unwrap())
+        );
+    }
+
+    #[test]
+    fn test_evm_address_from_invalid_hex() {
+        // Wrong length
+        for invalid in [
+            "",
+            "0x",
+            "0x5aaeb6053f3e94c9b9a09f33669435e7ef1bea",
+            "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed00",
+        ] {
+            assert_eq!(
+                evm_address_from_hex(invalid),
+                Err(BridgeError::InvalidEvmAddress)
+            );
+        }
+        // Not hex
+        for invalid in [
+            "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaeg",
+            "0x 5aeb6053f3e94c9b9a09f33669435e7ef1beaed",
+            "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed0x",
+        ] {
+            assert_eq!(
+                evm_address_from_hex(invalid),
+                Err(BridgeError::InvalidEvmAddress)
+            );
+        }
+        // Mixed case with a wrong checksum
+        assert_eq!(
+            evm_address_from_hex("0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"),
+            Err(BridgeError::InvalidEvmAddress)
+        );
+    }
+
+    #[test]
+    fn test_btc_tx_to_hex() {
+        let fixture = MoveTxFixture::new(57, OutPoint::null(), &[1u8; 20]);
+        let move_tx = fixture.witnessed_move_tx(&fixture.witness_sigs());
+
+        let tx_hex = btc_tx_to_hex(&move_tx.tx);
+        let parsed = parse_hex_to_btc_tx(&tx_hex).unwrap();
+        assert_eq!(parsed, move_tx.tx);
+        assert_eq!(btc_tx_to_hex(&parsed), tx_hex);
+
+        assert!(matches!(
+            parse_hex_to_btc_tx(&format!("{}00ff", tx_hex)),
+            Err(bitcoin::consensus::encode::Error::ParseFailed(_))
+        ));
+    }
 }
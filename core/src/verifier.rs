@@ -1,21 +1,89 @@
-use crate::constants::{VerifierChallenge, CONNECTOR_TREE_DEPTH};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use crate::constants::VerifierChallenge;
 use crate::errors::BridgeError;
 
+use crate::fraud_proof::FraudProof;
 use crate::merkle::MerkleTree;
+use crate::script_builder::AnchorKind;
 use crate::traits::verifier::VerifierConnector;
-use crate::utils::check_deposit_utxo;
-use crate::{ConnectorUTXOTree, EVMAddress, HashTree};
+use crate::utils::{check_deposit_utxo, check_evm_address, get_claim_reveal_indices};
+use crate::{ConnectorUTXOTree, HashTree};
 use bitcoin::Address;
-use bitcoin::{secp256k1, secp256k1::Secp256k1, OutPoint};
+use bitcoin::{secp256k1, secp256k1::schnorr, secp256k1::Secp256k1, OutPoint, Txid};
 
 use clementine_circuits::constants::{BRIDGE_AMOUNT_SATS, CLAIM_MERKLE_TREE_DEPTH, NUM_ROUNDS};
+use clementine_circuits::{sha256_hash, PreimageType};
+use crypto_bigint::{Encoding, U256};
 use secp256k1::SecretKey;
 use secp256k1::XOnlyPublicKey;
 
-use crate::extended_rpc::ExtendedRpc;
+use crate::extended_rpc::{ExtendedRpc, RPC_INVALID_ADDRESS_OR_KEY};
 use crate::transaction_builder::TransactionBuilder;
 
-use crate::{actor::Actor, operator::DepositPresigns};
+use crate::actor::Actor;
+use crate::operator::{DepositPresigns, DepositRequest};
+
+/// What a verifier newly observed about a connector tree, see Verifier::watch_connector_tree.
+/// The caller keeps the observed state and applies the update to it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectorTreeUpdate {
+    /// Inscribed preimages of connector tree hashes, in the order they were inscribed
+    pub newly_revealed_preimages: Vec<PreimageType>,
+    /// Spent connector tree utxos with their (level, index) in the tree
+    pub newly_spent_utxos: Vec<(OutPoint, (u32, u32))>,
+}
+
+impl ConnectorTreeUpdate {
+    /// Compares what is observed on chain, the `spent` utxos of `utxo_tree` and the
+    /// `inscribed` preimages, to what is already known
+    pub fn from_observations(
+        utxo_tree: &ConnectorUTXOTree,
+        hash_tree: &HashTree,
+        spent: &HashSet<OutPoint>,
+        inscribed: &[PreimageType],
+        revealed_preimages: &HashSet<PreimageType>,
+        spent_utxos: &HashMap<OutPoint, (u32, u32)>,
+    ) -> Self {
+        let newly_spent_utxos = utxo_tree
+            .iter()
+            .enumerate()
+            .flat_map(|(level, utxos)| {
+                utxos
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, utxo)| (*utxo, (level as u32, index as u32)))
+            })
+            .filter(|(utxo, _)| spent.contains(utxo) && !spent_utxos.contains_key(utxo))
+            .collect();
+
+        let hashes = hash_tree.iter().flatten().collect::<HashSet<_>>();
+        let mut newly_revealed_preimages = Vec::new();
+        for preimage in inscribed {
+            if hashes.contains(&sha256_hash!(preimage))
+                && !revealed_preimages.contains(preimage)
+                && !newly_revealed_preimages.contains(preimage)
+            {
+                newly_revealed_preimages.push(*preimage);
+            }
+        }
+
+        ConnectorTreeUpdate {
+            newly_revealed_preimages,
+            newly_spent_utxos,
+        }
+    }
+
+    pub fn apply(
+        self,
+        revealed_preimages: &mut HashSet<PreimageType>,
+        spent_utxos: &mut HashMap<OutPoint, (u32, u32)>,
+    ) {
+        revealed_preimages.extend(self.newly_revealed_preimages);
+        spent_utxos.extend(self.newly_spent_utxos);
+    }
+}
 
 #[derive(Debug)]
 pub struct Verifier {
@@ -30,6 +98,8 @@ pub struct Verifier {
     pub operator_pk: XOnlyPublicKey,
     pub start_block_height: u64,
     pub period_relative_block_heights: Vec<u32>,
+    /// Operator claim signatures given for each deposit utxo, one per period
+    claim_authorizations: Mutex<HashMap<OutPoint, Vec<schnorr::Signature>>>,
 }
 
 // impl VerifierConnector
@@ -40,13 +110,16 @@ impl VerifierConnector for Verifier {
     /// 3. Give move signature and operator claim signatures
     fn new_deposit(
         &self,
-        start_utxo: OutPoint,
-        return_address: &XOnlyPublicKey,
+        request: &DepositRequest,
         deposit_index: u32,
-        evm_address: &EVMAddress,
         operator_address: &Address,
     ) -> Result<DepositPresigns, BridgeError> {
         // 1. Check if there is any previous pending deposit
+        let start_utxo = request.start_utxo();
+        let return_address = request.return_address();
+        let evm_address = request.evm_address();
+
+        check_evm_address(evm_address)?;
 
         check_deposit_utxo(
             &self.rpc,
@@ -73,10 +146,11 @@ impl VerifierConnector for Verifier {
         let mut op_claim_sigs = Vec::new();
 
         for i in 0..NUM_ROUNDS {
+            let connector_tree_depth = self.connector_tree_hashes[i].len() - 1;
             let connector_utxo =
-                self.connector_tree_utxos[i][CONNECTOR_TREE_DEPTH][deposit_index as usize];
+                self.connector_tree_utxos[i][connector_tree_depth][deposit_index as usize];
             let connector_hash =
-                self.connector_tree_hashes[i][CONNECTOR_TREE_DEPTH][deposit_index as usize];
+                self.connector_tree_hashes[i][connector_tree_depth][deposit_index as usize];
 
             let mut operator_claim_tx = self.transaction_builder.create_operator_claim_tx(
                 move_utxo,
@@ -92,6 +166,11 @@ impl VerifierConnector for Verifier {
             op_claim_sigs.push(op_claim_sig);
         }
 
+        self.claim_authorizations
+            .lock()
+            .expect("claim authorizations are poisoned")
+            .insert(start_utxo, op_claim_sigs.clone());
+
         Ok(DepositPresigns {
             move_sign: move_sig,
             operator_claim_sign: op_claim_sigs,
@@ -174,6 +253,647 @@ impl Verifier {
             claim_proof_merkle_trees,
             start_block_height: 0,
             period_relative_block_heights: Vec::new(),
+            claim_authorizations: Mutex::new(HashMap::new()),
         })
     }
+
+    /// Sets the delay after which the operator can spend connector tree nodes. It has to be the
+    /// operator's, see Operator::with_connector_tree_operator_takes_after, or the connector tree
+    /// addresses and the claim txs this verifier signs differ from the operator's.
+    pub fn with_connector_tree_operator_takes_after(
+        mut self,
+        block_count: u32,
+    ) -> Result<Self, BridgeError> {
+        self.transaction_builder = self
+            .transaction_builder
+            .with_connector_tree_operator_takes_after(block_count)?;
+        Ok(self)
+    }
+
+    /// Sets the feerate the connector trees are funded for, it has to be the operator's, see
+    /// Operator::with_connector_tree_feerate
+    pub fn with_connector_tree_feerate(mut self, feerate_sat_vb: u64) -> Self {
+        self.transaction_builder = self
+            .transaction_builder
+            .with_connector_tree_feerate(feerate_sat_vb);
+        self
+    }
+
+    /// Sets the anchor output of the move and claim txs this verifier signs, it has to be the
+    /// operator's, see Operator::with_anchor_kind
+    pub fn with_anchor_kind(mut self, anchor_kind: AnchorKind) -> Self {
+        self.transaction_builder = self.transaction_builder.with_anchor_kind(anchor_kind);
+        self
+    }
+
+    /// Returns the signature this verifier gave over the operator's claim of `deposit_utxo` in `period`
+    pub fn claim_authorization(
+        &self,
+        deposit_utxo: OutPoint,
+        period: usize,
+    ) -> Option<schnorr::Signature> {
+        self.claim_authorizations
+            .lock()
+            .expect("claim authorizations are poisoned")
+            .get(&deposit_utxo)
+            .and_then(|sigs| sigs.get(period))
+            .copied()
+    }
+
+    /// Fetches the reveal tx `reveal_txid` and returns the preimages it inscribes, in order
+    pub fn extract_revealed_preimages(
+        &self,
+        reveal_txid: Txid,
+    ) -> Result<Vec<PreimageType>, BridgeError> {
+        let reveal_tx = self.rpc.get_raw_transaction(&reveal_txid, None)?;
+        TransactionBuilder::parse_inscription_preimages(&reveal_tx)
+    }
+
+    /// Checks that `root_utxo` pays to the connector tree root address committing to `hashes`,
+    /// the connector tree hashes the operator gave for its period, before the tree is trusted
+    pub fn verify_connector_root(
+        &self,
+        root_utxo: &OutPoint,
+        hashes: &HashTree,
+    ) -> Result<(), BridgeError> {
+        let root_hash = hashes
+            .first()
+            .and_then(|level| level.first())
+            .ok_or(BridgeError::InvalidConnectorTreeDepth)?;
+        let (root_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &self.secp,
+            &self.operator_pk,
+            root_hash,
+            self.transaction_builder.connector_tree_operator_takes_after,
+        )?;
+        let root_tx = self.rpc.get_raw_transaction(&root_utxo.txid, None)?;
+        let root_txout = root_tx
+            .output
+            .get(root_utxo.vout as usize)
+            .ok_or(BridgeError::ConnectorRootMismatch)?;
+        if root_txout.script_pubkey != root_address.script_pubkey() {
+            return Err(BridgeError::ConnectorRootMismatch);
+        }
+        Ok(())
+    }
+
+    /// Checks the preimages revealed for `period` against the withdrawals the operator actually
+    /// paid in it. Every node get_claim_reveal_indices returns for `actual_claims` has to be
+    /// revealed, otherwise leaves past the paid withdrawals stay claimable and the missing
+    /// nodes are returned as evidence. None if the reveals match. An unknown period, or more
+    /// claims than the connector tree has leaves, is an error.
+    pub fn detect_overclaim(
+        &self,
+        period: usize,
+        revealed: &HashSet<PreimageType>,
+        actual_claims: u32,
+    ) -> Result<Option<FraudProof>, BridgeError> {
+        let (Some(hash_tree), Some(claim_tree)) = (
+            self.connector_tree_hashes.get(period),
+            self.claim_proof_merkle_trees.get(period),
+        ) else {
+            return Err(BridgeError::InvalidPeriod);
+        };
+        let depth = hash_tree
+            .len()
+            .checked_sub(1)
+            .ok_or(BridgeError::InvalidPeriod)?;
+        get_claim_reveal_indices(depth, actual_claims)?;
+        Ok(FraudProof::new(
+            period,
+            hash_tree,
+            claim_tree,
+            revealed,
+            actual_claims,
+        ))
+    }
+
+    /// Looks for connector tree utxos of `period` spent since `spent_utxos` was last updated,
+    /// and for preimages of its connector tree hashes inscribed by `reveal_txids` that are not
+    /// in `revealed_preimages` yet. Neither is modified, see ConnectorTreeUpdate::apply.
+    pub fn watch_connector_tree(
+        &self,
+        period: usize,
+        reveal_txids: &[Txid],
+        revealed_preimages: &HashSet<PreimageType>,
+        spent_utxos: &HashMap<OutPoint, (u32, u32)>,
+    ) -> Result<ConnectorTreeUpdate, BridgeError> {
+        let utxo_tree = self
+            .connector_tree_utxos
+            .get(period)
+            .ok_or(BridgeError::InvalidPeriod)?;
+        let hash_tree = self
+            .connector_tree_hashes
+            .get(period)
+            .ok_or(BridgeError::InvalidPeriod)?;
+
+        let mut spent = HashSet::new();
+        for utxo in utxo_tree.iter().flatten() {
+            if !spent_utxos.contains_key(utxo) && self.is_connector_tree_utxo_spent(utxo)? {
+                spent.insert(*utxo);
+            }
+        }
+        let mut inscribed = Vec::new();
+        for reveal_txid in reveal_txids {
+            inscribed.extend(self.extract_revealed_preimages(*reveal_txid)?);
+        }
+
+        Ok(ConnectorTreeUpdate::from_observations(
+            utxo_tree,
+            hash_tree,
+            &spent,
+            &inscribed,
+            revealed_preimages,
+            spent_utxos,
+        ))
+    }
+
+    /// Confirmation count of the connector tree root utxo of `period`, None while the tx
+    /// creating it is unknown or still in the mempool. Watching the tree should wait until
+    /// the root is confirmed deep enough not to be reorged away.
+    pub fn did_connector_tree_process_start(
+        &self,
+        period: usize,
+    ) -> Result<Option<u32>, BridgeError> {
+        let root_utxo = self
+            .connector_tree_utxos
+            .get(period)
+            .and_then(|utxo_tree| utxo_tree.first())
+            .and_then(|level| level.first())
+            .ok_or(BridgeError::InvalidPeriod)?;
+        match self.rpc.get_raw_transaction_info(&root_utxo.txid, None) {
+            Ok(tx_info) => Ok(tx_info
+                .confirmations
+                .filter(|confirmations| *confirmations > 0)),
+            Err(bitcoincore_rpc::Error::JsonRpc(bitcoincore_rpc::jsonrpc::Error::Rpc(
+                ref rpc_error,
+            ))) if rpc_error.code == RPC_INVALID_ADDRESS_OR_KEY => Ok(None),
+            Err(e) => {
+                tracing::error!("Failed to get connector tree root tx: {}", e);
+                Err(BridgeError::RpcError)
+            }
+        }
+    }
+
+    /// The connector tree txs are broadcast level by level, so a utxo missing from the utxo
+    /// set is only spent if the tx creating it is known
+    fn is_connector_tree_utxo_spent(&self, utxo: &OutPoint) -> Result<bool, BridgeError> {
+        if self.rpc.get_utxo(utxo, true)?.is_some() {
+            return Ok(false);
+        }
+        Ok(self.rpc.get_raw_transaction(&utxo.txid, None).is_ok())
+    }
+
+    /// Serializes a challenge the way read_and_verify_verifiers_challenge_proof reads it: the
+    /// proof, the last finalized blockhash, the total work in little endian and the period.
+    /// The challenge proof itself is not verified by the circuit yet, so an empty one is used
+    pub fn build_challenge_proof(
+        &self,
+        last_finalized_bh: [u8; 32],
+        period: u8,
+        work: U256,
+    ) -> Vec<u8> {
+        let mut challenge_proof = vec![0u8; 4 * 32];
+        challenge_proof.extend_from_slice(&last_finalized_bh);
+        challenge_proof.extend_from_slice(&work.to_le_bytes());
+        challenge_proof.extend_from_slice(&(period as u32).to_le_bytes());
+        challenge_proof
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{CONFIRMATION_BLOCK_COUNT, DUST_VALUE};
+    use crate::operator::{create_connector_tree_preimages_and_hashes, Operator};
+    use crate::utils::{
+        get_claim_proof_tree_leaf, get_claim_reveal_indices, handle_taproot_witness_new,
+    };
+    use crate::ConnectorTreeLeaf;
+    use bitcoin::hashes::Hash;
+    use secp256k1::rand::rngs::StdRng;
+    use secp256k1::rand::SeedableRng;
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_new_deposit_tampered_deposit_address() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([36u8; 32]);
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        let verifier =
+            Verifier::new(ExtendedRpc::new(), all_xonly_pks.clone(), all_sks[0]).unwrap();
+        let return_address = XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1);
+
+        // The operator swapped a verifier for a key it controls
+        let mut tampered_pks = all_xonly_pks.clone();
+        tampered_pks[1] = XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1);
+        let (tampered_address, _) = TransactionBuilder::new(tampered_pks)
+            .generate_deposit_address(&return_address)
+            .unwrap();
+        let deposit_utxo = verifier
+            .rpc
+            .send_to_address(&tampered_address, BRIDGE_AMOUNT_SATS)
+            .unwrap();
+        verifier
+            .rpc
+            .mine_blocks(CONFIRMATION_BLOCK_COUNT as u64)
+            .unwrap();
+
+        assert_eq!(
+            verifier
+                .new_deposit(
+                    &DepositRequest::new(
+                        deposit_utxo,
+                        return_address,
+                        [1u8; 20],
+                        verifier.signer.sign_digest([37u8; 32]),
+                    ),
+                    0,
+                    &verifier.signer.address,
+                )
+                .unwrap_err(),
+            BridgeError::DepositAddressMismatch
+        );
+    }
+
+    #[test]
+    fn test_signs_with_operator_config() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([58u8; 32]);
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        let operator = Operator::builder(all_xonly_pks.clone(), all_sks[2])
+            .verifier_evm_addresses(vec![[1u8; 20], [2u8; 20]])
+            .connector_tree_operator_takes_after(7)
+            .anchor_kind(AnchorKind::P2aEphemeral)
+            .build()
+            .unwrap();
+        let verifier = || Verifier::new(ExtendedRpc::new(), all_xonly_pks.clone(), all_sks[0]);
+        let configured_verifier = verifier()
+            .unwrap()
+            .with_connector_tree_operator_takes_after(7)
+            .unwrap()
+            .with_anchor_kind(AnchorKind::P2aEphemeral);
+        let user_pk = XOnlyPublicKey::from(secp.generate_keypair(&mut rng).1);
+        let deposit_utxo = OutPoint {
+            txid: Txid::from_byte_array([59u8; 32]),
+            vout: 0,
+        };
+        let connector_utxo = OutPoint {
+            txid: Txid::from_byte_array([60u8; 32]),
+            vout: 0,
+        };
+
+        // The move and claim txs of the deposit, as `transaction_builder` builds them
+        let txs_of = |transaction_builder: &TransactionBuilder| {
+            let move_tx = transaction_builder
+                .create_move_tx(deposit_utxo, &[1u8; 20], &user_pk)
+                .unwrap();
+            let claim_tx = transaction_builder
+                .create_operator_claim_tx(
+                    OutPoint {
+                        txid: move_tx.tx.txid(),
+                        vout: 0,
+                    },
+                    connector_utxo,
+                    &operator.signer.address,
+                    &operator.signer.xonly_public_key,
+                    &[61u8; 32],
+                )
+                .unwrap();
+            [move_tx, claim_tx]
+        };
+        // Whether the signatures of `verifier` are valid for the txs the operator finalizes
+        let signs_operator_txs = |verifier: &Verifier| {
+            let mut operator_txs = txs_of(&operator.transaction_builder);
+            let mut verifier_txs = txs_of(&verifier.transaction_builder);
+            verifier_txs.iter_mut().zip(operator_txs.iter_mut()).all(
+                |(verifier_tx, operator_tx)| {
+                    let sig = verifier
+                        .signer
+                        .sign_taproot_script_spend_tx_new(verifier_tx, 0)
+                        .unwrap();
+                    let sighash = operator
+                        .signer
+                        .sighash_taproot_script_spend(operator_tx, 0)
+                        .unwrap();
+                    let message =
+                        secp256k1::Message::from_digest_slice(sighash.as_byte_array()).unwrap();
+                    secp.verify_schnorr(&sig, &message, &verifier.signer.xonly_public_key)
+                        .is_ok()
+                },
+            )
+        };
+        assert!(signs_operator_txs(&configured_verifier));
+        assert!(!signs_operator_txs(&verifier().unwrap()));
+        assert!(!signs_operator_txs(
+            &verifier()
+                .unwrap()
+                .with_anchor_kind(AnchorKind::P2aEphemeral)
+        ));
+
+        assert_eq!(
+            verifier()
+                .unwrap()
+                .with_connector_tree_operator_takes_after(0)
+                .unwrap_err(),
+            BridgeError::InvalidTimelock
+        );
+    }
+
+    #[test]
+    fn test_connector_tree_update() {
+        // A depth 1 tree: the root and its two children
+        let preimages = vec![vec![[42u8; 32]], vec![[43u8; 32], [44u8; 32]]];
+        let hash_tree = preimages
+            .iter()
+            .map(|level| {
+                level
+                    .iter()
+                    .map(|preimage| sha256_hash!(preimage))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<HashTree>();
+        let utxo = |byte: u8, vout: u32| OutPoint {
+            txid: Txid::from_byte_array([byte; 32]),
+            vout,
+        };
+        let utxo_tree = vec![vec![utxo(45, 0)], vec![utxo(46, 0), utxo(46, 1)]];
+        let mut revealed_preimages = HashSet::new();
+        let mut spent_utxos = HashMap::new();
+
+        // The root is spent, creating the first level, and both its preimages are inscribed
+        // together with one that is not in the tree
+        let spent = HashSet::from([utxo(45, 0)]);
+        let inscribed = vec![preimages[1][0], [47u8; 32], preimages[1][1]];
+        let update = ConnectorTreeUpdate::from_observations(
+            &utxo_tree,
+            &hash_tree,
+            &spent,
+            &inscribed,
+            &revealed_preimages,
+            &spent_utxos,
+        );
+        assert_eq!(
+            update,
+            ConnectorTreeUpdate {
+                newly_revealed_preimages: vec![preimages[1][0], preimages[1][1]],
+                newly_spent_utxos: vec![(utxo(45, 0), (0, 0))],
+            }
+        );
+        update.apply(&mut revealed_preimages, &mut spent_utxos);
+
+        // The first level is spent too, only its utxos are new
+        let spent = HashSet::from([utxo(45, 0), utxo(46, 0), utxo(46, 1)]);
+        let update = ConnectorTreeUpdate::from_observations(
+            &utxo_tree,
+            &hash_tree,
+            &spent,
+            &inscribed,
+            &revealed_preimages,
+            &spent_utxos,
+        );
+        assert_eq!(
+            update,
+            ConnectorTreeUpdate {
+                newly_revealed_preimages: vec![],
+                newly_spent_utxos: vec![(utxo(46, 0), (1, 0)), (utxo(46, 1), (1, 1))],
+            }
+        );
+        update.apply(&mut revealed_preimages, &mut spent_utxos);
+        assert_eq!(spent_utxos.len(), 3);
+        assert_eq!(revealed_preimages.len(), 2);
+    }
+
+    #[test]
+    fn test_detect_overclaim() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([48u8; 32]);
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        let mut verifier =
+            Verifier::new(ExtendedRpc::new(), all_xonly_pks.clone(), all_sks[0]).unwrap();
+        // A depth 2 tree of the first period, every node has its own preimage
+        let preimages = (0..3u8)
+            .map(|level| {
+                (0..1u8 << level)
+                    .map(|index| [level * 4 + index + 1; 32])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        verifier.connector_tree_hashes = vec![preimages
+            .iter()
+            .map(|level| {
+                level
+                    .iter()
+                    .map(|preimage| sha256_hash!(preimage))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<HashTree>()];
+        let mut claim_tree = MerkleTree::new();
+        for claims in 0..4 {
+            claim_tree.add(
+                get_claim_proof_tree_leaf(2, claims, &verifier.connector_tree_hashes[0]).unwrap(),
+            );
+        }
+        verifier.claim_proof_merkle_trees = vec![claim_tree.clone()];
+        let reveal = |claims: u32| {
+            get_claim_reveal_indices(2, claims)
+                .unwrap()
+                .iter()
+                .map(|node| preimages[node.level][node.index])
+                .collect::<HashSet<_>>()
+        };
+
+        // The operator revealed what 1 claim requires
+        assert_eq!(verifier.detect_overclaim(0, &reveal(1), 1), Ok(None));
+        // Revealing more than needed only burns more leaves
+        let mut revealed = reveal(1);
+        revealed.extend(reveal(0));
+        assert_eq!(verifier.detect_overclaim(0, &revealed, 1), Ok(None));
+
+        // The operator revealed for 3 claims but paid a single withdrawal, leaves 1 and 2
+        // can not be burned
+        let proof = verifier
+            .detect_overclaim(0, &reveal(3), 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(proof.period, 0);
+        assert_eq!(proof.actual_claims, 1);
+        assert_eq!(proof.claim_root, claim_tree.root());
+        assert_eq!(
+            proof.unrevealed_nodes,
+            vec![
+                ConnectorTreeLeaf { level: 2, index: 1 },
+                ConnectorTreeLeaf { level: 1, index: 1 },
+            ]
+        );
+        assert_eq!(proof.validate(2), Ok(()));
+        // Nothing revealed at all
+        assert_eq!(
+            verifier
+                .detect_overclaim(0, &HashSet::new(), 0)
+                .unwrap()
+                .unwrap()
+                .unrevealed_nodes,
+            vec![ConnectorTreeLeaf { level: 0, index: 0 }]
+        );
+        assert_eq!(
+            verifier.detect_overclaim(1, &reveal(1), 1),
+            Err(BridgeError::InvalidPeriod)
+        );
+        // A depth 2 tree has 4 leaves to claim
+        assert_eq!(
+            verifier.detect_overclaim(0, &reveal(1), 5),
+            Err(BridgeError::InvalidClaimCount)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_did_connector_tree_process_start() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([50u8; 32]);
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        let mut verifier =
+            Verifier::new(ExtendedRpc::new(), all_xonly_pks.clone(), all_sks[0]).unwrap();
+        assert_eq!(
+            verifier.did_connector_tree_process_start(0),
+            Err(BridgeError::InvalidPeriod)
+        );
+
+        // The root tx is not known yet
+        let root_utxo = OutPoint {
+            txid: Txid::from_byte_array([51u8; 32]),
+            vout: 0,
+        };
+        verifier.connector_tree_utxos = vec![vec![vec![root_utxo]]];
+        assert_eq!(verifier.did_connector_tree_process_start(0), Ok(None));
+
+        let root_utxo = verifier
+            .rpc
+            .send_to_address(&verifier.signer.address, DUST_VALUE * 4)
+            .unwrap();
+        verifier.connector_tree_utxos = vec![vec![vec![root_utxo]]];
+        // In the mempool only
+        assert_eq!(verifier.did_connector_tree_process_start(0), Ok(None));
+        verifier.rpc.mine_blocks(1).unwrap();
+        assert_eq!(verifier.did_connector_tree_process_start(0), Ok(Some(1)));
+        verifier
+            .rpc
+            .mine_blocks(CONFIRMATION_BLOCK_COUNT as u64)
+            .unwrap();
+        assert_eq!(
+            verifier.did_connector_tree_process_start(0),
+            Ok(Some(1 + CONFIRMATION_BLOCK_COUNT))
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_verify_connector_root() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([49u8; 32]);
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        let verifier =
+            Verifier::new(ExtendedRpc::new(), all_xonly_pks.clone(), all_sks[0]).unwrap();
+        let (_, hashes) = create_connector_tree_preimages_and_hashes(2, &mut rng);
+
+        let (root_address, _) = TransactionBuilder::create_connector_tree_node_address(
+            &secp,
+            &all_xonly_pks[2],
+            &hashes[0][0],
+            verifier
+                .transaction_builder
+                .connector_tree_operator_takes_after,
+        )
+        .unwrap();
+        let root_utxo = verifier
+            .rpc
+            .send_to_address(&root_address, DUST_VALUE * 4)
+            .unwrap();
+        verifier.rpc.mine_blocks(1).unwrap();
+        assert_eq!(verifier.verify_connector_root(&root_utxo, &hashes), Ok(()));
+
+        // Hashes of another tree do not match the root on chain
+        let (_, tampered_hashes) = create_connector_tree_preimages_and_hashes(2, &mut rng);
+        assert_eq!(
+            verifier.verify_connector_root(&root_utxo, &tampered_hashes),
+            Err(BridgeError::ConnectorRootMismatch)
+        );
+        assert_eq!(
+            verifier.verify_connector_root(&root_utxo, &Vec::new()),
+            Err(BridgeError::InvalidConnectorTreeDepth)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a running bitcoind regtest node"]
+    fn test_extract_revealed_preimages() {
+        let secp = Secp256k1::new();
+        let mut rng = StdRng::from_seed([38u8; 32]);
+        let (all_sks, all_xonly_pks): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| {
+                let (sk, pk) = secp.generate_keypair(&mut rng);
+                (sk, XOnlyPublicKey::from(pk))
+            })
+            .unzip();
+        let verifier =
+            Verifier::new(ExtendedRpc::new(), all_xonly_pks.clone(), all_sks[0]).unwrap();
+        let operator = Actor::new(all_sks[2]);
+
+        // The operator inscribes three preimages
+        let preimages = vec![[39u8; 32], [40u8; 32], [41u8; 32]];
+        let (commit_address, _, _) = verifier
+            .transaction_builder
+            .create_inscription_commit_address(&operator.xonly_public_key, &preimages)
+            .unwrap();
+        let commit_utxo = verifier
+            .rpc
+            .send_to_address(&commit_address, DUST_VALUE * 2)
+            .unwrap();
+        let mut reveal_tx = verifier
+            .transaction_builder
+            .create_inscription_reveal_tx(commit_utxo, &operator.xonly_public_key, &preimages)
+            .unwrap();
+        let sig = operator
+            .sign_taproot_script_spend_tx_new(&mut reveal_tx, 0)
+            .unwrap();
+        handle_taproot_witness_new(&mut reveal_tx, &vec![sig.as_ref()], 0).unwrap();
+        let reveal_txid = verifier.rpc.send_raw_transaction(&reveal_tx.tx).unwrap();
+        verifier.rpc.mine_blocks(1).unwrap();
+
+        assert_eq!(
+            verifier.extract_revealed_preimages(reveal_txid).unwrap(),
+            preimages
+        );
+        // The commit tx inscribes nothing
+        assert_eq!(
+            verifier.extract_revealed_preimages(commit_utxo.txid),
+            Err(BridgeError::InvalidInscription)
+        );
+    }
 }